@@ -0,0 +1,206 @@
+//! The shape of the player's body, and checks of it against the blocks around it. Kept free of ECS
+//! types, so the checks work on any block lookup.
+
+use crate::camera::voxel_collision::Aabb;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+/// Radius of the player's collision capsule.
+pub const PLAYER_RADIUS: f32 = 0.2;
+/// Distance from the player's origin (where the view is) down to the bottom of its feet.
+pub const FEET_OFFSET: f32 = 1.2;
+/// Distance from the player's origin up to the top of its head while standing.
+pub const STANDING_HEAD_OFFSET: f32 = 2.2;
+/// Distance from the player's origin up to the top of its head while crouching.
+pub const CROUCHING_HEAD_OFFSET: f32 = 1.6;
+/// How far the view moves down while crouching.
+pub const CROUCH_VIEW_DROP: f32 = 0.3;
+
+/// How many heights between the feet and the head are checked for liquid, to work out how
+/// submerged the player is.
+const SUBMERSION_SAMPLES: usize = 4;
+
+/// How far below the feet to look for ground when checking for edges.
+const GROUND_CHECK_DEPTH: f32 = 0.1;
+
+pub fn player_collider(crouching: bool) -> Collider {
+    let head_offset = if crouching {
+        CROUCHING_HEAD_OFFSET
+    } else {
+        STANDING_HEAD_OFFSET
+    };
+
+    Collider::capsule(
+        Vec3::new(0.0, -FEET_OFFSET + PLAYER_RADIUS, 0.0),
+        Vec3::new(0.0, head_offset - PLAYER_RADIUS, 0.0),
+        PLAYER_RADIUS,
+    )
+}
+
+/// The player's bounding box for voxel collision, matching the size of its capsule.
+pub fn player_aabb(origin: Vec3, crouching: bool) -> Aabb {
+    let head_offset = if crouching {
+        CROUCHING_HEAD_OFFSET
+    } else {
+        STANDING_HEAD_OFFSET
+    };
+
+    Aabb {
+        min: origin + Vec3::new(-PLAYER_RADIUS, -FEET_OFFSET, -PLAYER_RADIUS),
+        max: origin + Vec3::new(PLAYER_RADIUS, head_offset, PLAYER_RADIUS),
+    }
+}
+
+/// Every block column the player's footprint overlaps, if their feet are at `feet`.
+fn footprint(feet: Vec3) -> impl Iterator<Item = (i32, i32)> {
+    let min_x = (feet.x - PLAYER_RADIUS).floor() as i32;
+    let max_x = (feet.x + PLAYER_RADIUS).floor() as i32;
+    let min_z = (feet.z - PLAYER_RADIUS).floor() as i32;
+    let max_z = (feet.z + PLAYER_RADIUS).floor() as i32;
+
+    (min_x..=max_x).flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+}
+
+/// Whether there's a solid block directly below any part of the player's feet.
+pub fn has_ground_below(feet: Vec3, is_solid: &impl Fn(IVec3) -> bool) -> bool {
+    let y = (feet.y - GROUND_CHECK_DEPTH).floor() as i32;
+
+    footprint(feet).any(|(x, z)| is_solid(IVec3::new(x, y, z)))
+}
+
+/// Limits a horizontal displacement so that the player doesn't walk off the edge of the blocks
+/// they're standing on. Each axis is checked separately, so the player can still slide along the
+/// edge.
+pub fn prevent_edge_fall(
+    feet: Vec3,
+    displacement: Vec3,
+    is_solid: &impl Fn(IVec3) -> bool,
+) -> Vec3 {
+    // Not standing on anything to begin with, e.g. halfway through a jump
+    if !has_ground_below(feet, is_solid) {
+        return displacement;
+    }
+
+    let mut limited = displacement;
+
+    if !has_ground_below(feet + Vec3::new(limited.x, 0.0, 0.0), is_solid) {
+        limited.x = 0.0;
+    }
+    if !has_ground_below(feet + Vec3::new(limited.x, 0.0, limited.z), is_solid) {
+        limited.z = 0.0;
+    }
+
+    limited
+}
+
+/// Whether any solid block overlaps the player's body between `bottom` and `top`, which are
+/// heights relative to the player's origin.
+pub fn is_obstructed(
+    origin: Vec3,
+    bottom: f32,
+    top: f32,
+    is_solid: &impl Fn(IVec3) -> bool,
+) -> bool {
+    let min_y = (origin.y + bottom).floor() as i32;
+    let max_y = (origin.y + top).floor() as i32;
+
+    footprint(origin).any(|(x, z)| (min_y..=max_y).any(|y| is_solid(IVec3::new(x, y, z))))
+}
+
+/// How much of the player's body is in liquid, from 0 (not at all) to 1 (fully submerged).
+pub fn submersion(origin: Vec3, crouching: bool, is_liquid: &impl Fn(IVec3) -> bool) -> f32 {
+    let aabb = player_aabb(origin, crouching);
+
+    let submerged = (0..SUBMERSION_SAMPLES)
+        .filter(|i| {
+            // Sample the middle of each slice of the body
+            let t = (*i as f32 + 0.5) / SUBMERSION_SAMPLES as f32;
+            let y = aabb.min.y + (aabb.max.y - aabb.min.y) * t;
+
+            is_liquid(Vec3::new(origin.x, y, origin.z).floor().as_ivec3())
+        })
+        .count();
+
+    submerged as f32 / SUBMERSION_SAMPLES as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feet resting on top of blocks at y = -1.
+    fn feet(x: f32, z: f32) -> Vec3 {
+        Vec3::new(x, 0.0, z)
+    }
+
+    #[test]
+    fn stops_at_a_ledge_along_x() {
+        let is_solid = |pos: IVec3| pos.y == -1 && pos.x <= 0;
+
+        let limited = prevent_edge_fall(feet(0.9, 0.5), Vec3::new(0.4, 0.0, 0.3), &is_solid);
+
+        assert_eq!(limited, Vec3::new(0.0, 0.0, 0.3));
+    }
+
+    #[test]
+    fn stops_at_a_ledge_along_z() {
+        let is_solid = |pos: IVec3| pos.y == -1 && pos.z >= 0;
+
+        let limited = prevent_edge_fall(feet(0.5, 0.1), Vec3::new(-0.3, 0.0, -0.4), &is_solid);
+
+        assert_eq!(limited, Vec3::new(-0.3, 0.0, 0.0));
+    }
+
+    #[test]
+    fn walks_right_up_to_the_edge() {
+        let is_solid = |pos: IVec3| pos.y == -1 && pos.x <= 0;
+
+        // The footprint still overlaps the last column of blocks
+        let displacement = Vec3::new(0.15, 0.0, 0.0);
+
+        assert_eq!(
+            prevent_edge_fall(feet(0.9, 0.5), displacement, &is_solid),
+            displacement
+        );
+    }
+
+    #[test]
+    fn stops_on_both_axes_at_an_outside_corner() {
+        let is_solid = |pos: IVec3| pos.y == -1 && pos.x <= 0 && pos.z <= 0;
+
+        let limited = prevent_edge_fall(feet(0.9, 0.9), Vec3::new(0.4, 0.0, 0.4), &is_solid);
+
+        assert_eq!(limited, Vec3::ZERO);
+    }
+
+    #[test]
+    fn slides_along_the_edge_next_to_an_outside_corner() {
+        let is_solid = |pos: IVec3| pos.y == -1 && pos.x <= 0 && pos.z <= 0;
+
+        let limited = prevent_edge_fall(feet(0.9, -3.0), Vec3::new(0.4, 0.0, 0.4), &is_solid);
+
+        assert_eq!(limited, Vec3::new(0.0, 0.0, 0.4));
+    }
+
+    #[test]
+    fn stops_diagonally_into_an_inside_corner() {
+        // Everything is solid except for the quadrant past the corner
+        let is_solid = |pos: IVec3| pos.y == -1 && (pos.x <= 0 || pos.z <= 0);
+
+        // Either axis on its own still has ground, but not both together
+        let limited = prevent_edge_fall(feet(0.9, 0.9), Vec3::new(0.4, 0.0, 0.4), &is_solid);
+
+        assert_eq!(limited, Vec3::new(0.4, 0.0, 0.0));
+    }
+
+    #[test]
+    fn does_nothing_in_midair() {
+        let is_solid = |_: IVec3| false;
+        let displacement = Vec3::new(0.4, -0.1, 0.4);
+
+        assert_eq!(
+            prevent_edge_fall(feet(0.9, 0.9), displacement, &is_solid),
+            displacement
+        );
+    }
+}
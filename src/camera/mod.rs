@@ -0,0 +1,1075 @@
+pub mod body;
+pub mod voxel_collision;
+
+use crate::camera::body::{
+    is_obstructed, player_aabb, player_collider, prevent_edge_fall, submersion,
+    CROUCHING_HEAD_OFFSET, CROUCH_VIEW_DROP, FEET_OFFSET, STANDING_HEAD_OFFSET,
+};
+use crate::camera::voxel_collision::{move_aabb, Aabb};
+use crate::input::{Action, ActionState};
+use crate::inventory::dropped_item::DROPPED_ITEM_GROUP;
+use crate::inventory::{Inventory, PLAYER_INVENTORY_SIZE};
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::get_block;
+use crate::worldgen::chunk::{ChunkLoader, GeneratedChunks};
+use crate::worldgen::model::BlockModels;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_rapier3d::prelude::*;
+
+/// How long one physics step of the player movement is, in seconds.
+pub const PHYSICS_TIMESTEP: f32 = 1.0 / 60.0;
+
+/// Downwards acceleration of the player, in metres per second squared.
+pub const GRAVITY: f32 = 32.0;
+/// The upwards velocity the player gets from jumping, in metres per second. Enough to jump up one
+/// block.
+pub const JUMP_SPEED: f32 = 9.0;
+/// The speed the player walks at when the movement keys are held, in metres per second.
+pub const WALK_SPEED: f32 = 4.5;
+/// How quickly horizontal velocity decays, per second. Higher values make the player speed up and
+/// stop faster.
+pub const FRICTION: f32 = 12.0;
+/// Terminal velocity, in metres per second.
+pub const MAX_SPEED: f32 = 60.0;
+/// Multiplies the walking speed while sprinting.
+pub const SPRINT_MULTIPLIER: f32 = 1.4;
+/// Multiplies the walking speed while crouching.
+pub const CROUCH_MULTIPLIER: f32 = 0.35;
+/// Multiplies the field of view while sprinting.
+pub const SPRINT_FOV_MULTIPLIER: f32 = 1.15;
+/// How quickly the field of view changes, per second.
+pub const FOV_CHANGE_RATE: f32 = 10.0;
+/// Stamina used up per second of sprinting.
+pub const STAMINA_DRAIN: f32 = 10.0;
+/// Stamina regained per second while not sprinting.
+pub const STAMINA_REGEN: f32 = 5.0;
+/// The speed the player flies at in creative flight and noclip, in metres per second.
+pub const FLY_SPEED: f32 = 10.0;
+/// Upwards acceleration from being fully submerged in water, in metres per second squared. A bit
+/// less than gravity, so the player slowly sinks.
+pub const WATER_BUOYANCY: f32 = 28.0;
+/// How quickly velocity decays in water, per second, when fully submerged.
+pub const WATER_DRAG: f32 = 4.0;
+/// Upwards acceleration from holding jump in water, in metres per second squared.
+pub const SWIM_ACCELERATION: f32 = 12.0;
+/// Multiplies the walking speed when fully submerged.
+pub const SWIM_SPEED_MULTIPLIER: f32 = 0.5;
+/// How far the player can see underwater, in metres.
+pub const UNDERWATER_VIEW_DISTANCE: f32 = 16.0;
+/// The highest ledge the player walks up onto without jumping, with voxel collision.
+pub const STEP_HEIGHT: f32 = 1.0;
+/// The longest time between two presses of the jump key that still counts as a double tap, in
+/// seconds.
+pub const DOUBLE_TAP_TIME: f32 = 0.3;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FixedTime::new_from_secs(PHYSICS_TIMESTEP))
+            .init_resource::<PlayerCollision>()
+            .add_systems(Startup, spawn_camera)
+            .add_systems(
+                Update,
+                (
+                    handle_input_movement,
+                    handle_input_jump,
+                    handle_input_rotation,
+                    handle_input_movement_mode,
+                    handle_input_stance,
+                    update_view_fov,
+                    update_underwater_fog,
+                ),
+            )
+            .add_systems(FixedUpdate, tick_player_movement)
+            .add_systems(
+                PostUpdate,
+                interpolate_camera
+                    .after(PhysicsSet::Writeback)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Marker for whether an entity is the player camera. There should only be one.
+///
+/// This entity is the physics body of the player; the actual camera is its `PlayerView` child.
+#[derive(Component)]
+pub struct PlayerCamera;
+
+/// How the player collides with the world.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PlayerCollision {
+    /// Rapier's character controller, against trimesh colliders built for every loaded chunk.
+    #[default]
+    Rapier,
+    /// A swept AABB resolved directly against the block data. Chunks don't get colliders at all.
+    Voxel,
+}
+
+/// Marker for the camera that renders what the player sees.
+#[derive(Component)]
+pub struct PlayerView;
+
+/// Controls the physics of the player camera. The position is just the player transform's translation.
+#[derive(Component)]
+pub struct PlayerCameraMovement {
+    /// In metres per second.
+    pub velocity: Vec3,
+    /// In metres per second squared.
+    pub acceleration: Vec3,
+    /// The horizontal direction the player wants to walk in, with a length of at most 1.
+    pub input: Vec3,
+    /// Whether the player wants to jump on the next physics step.
+    pub jump: bool,
+    pub grounded: bool,
+    pub sprinting: bool,
+    /// Crouching also makes the player sneak, which stops them from walking off the edges of blocks.
+    pub crouching: bool,
+    /// Whether the player is holding jump, which swims upwards in water.
+    pub swim_up: bool,
+    /// How much of the player is in water, from 0 to 1.
+    pub submersion: f32,
+}
+
+impl PlayerCameraMovement {
+    pub fn speed_multiplier(&self) -> f32 {
+        let stance = if self.crouching {
+            CROUCH_MULTIPLIER
+        } else if self.sprinting {
+            SPRINT_MULTIPLIER
+        } else {
+            1.0
+        };
+
+        stance * (1.0 + (SWIM_SPEED_MULTIPLIER - 1.0) * self.submersion)
+    }
+}
+
+/// Used up by sprinting. Anything that should tire the player out, like hunger, can hook into this.
+#[derive(Component)]
+pub struct Stamina {
+    pub current: f32,
+    pub max: f32,
+}
+
+/// How the player moves. Switched between with the input systems.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MovementMode {
+    /// Affected by gravity, and collides with the world.
+    #[default]
+    Walking,
+    /// Creative flight; no gravity, moves up and down with the jump and sneak keys.
+    Flying,
+    /// Like flying, but without any collision.
+    Noclip,
+}
+
+/// Keeps track of how the last physics step moved the player, so the view can be smoothly
+/// interpolated between steps.
+#[derive(Component, Default)]
+pub struct CameraInterpolation {
+    /// How far the player moved during the last physics step.
+    pub last_step: Vec3,
+}
+
+fn spawn_camera(mut commands: Commands, collision: Res<PlayerCollision>) {
+    let transform = Transform::from_xyz(0.0, 20.0, 0.0);
+
+    let mut player = commands.spawn(SpatialBundle::from_transform(transform));
+
+    if *collision == PlayerCollision::Rapier {
+        player
+            .insert(RigidBody::KinematicPositionBased)
+            .insert(LockedAxes::ROTATION_LOCKED)
+            .insert(player_collider(false));
+    }
+
+    player
+        .insert(PlayerCamera)
+        .insert(ChunkLoader)
+        .insert(PlayerCameraMovement {
+            velocity: Vec3::ZERO,
+            acceleration: Vec3::new(0.0, -GRAVITY, 0.0),
+            input: Vec3::ZERO,
+            jump: false,
+            grounded: false,
+            sprinting: false,
+            crouching: false,
+            swim_up: false,
+            submersion: 0.0,
+        })
+        .insert(Stamina {
+            current: 100.0,
+            max: 100.0,
+        })
+        .insert(MovementMode::default())
+        .insert(Inventory::new(PLAYER_INVENTORY_SIZE))
+        .insert(CameraInterpolation::default())
+        .with_children(|parent| {
+            parent.spawn(Camera3dBundle::default()).insert(PlayerView);
+        });
+}
+
+/// Advances the movement by one physics step of `delta_seconds`, and returns how far the player
+/// should move during it.
+///
+/// This only depends on its inputs, so the same input gives the same result no matter the frame rate.
+pub fn step_movement(
+    movement: &mut PlayerCameraMovement,
+    mode: MovementMode,
+    delta_seconds: f32,
+) -> Vec3 {
+    let damping = f32::exp(-FRICTION * delta_seconds);
+
+    match mode {
+        MovementMode::Walking => {
+            if movement.grounded {
+                movement.velocity.y = movement.velocity.y.max(0.0);
+
+                if movement.jump {
+                    movement.velocity.y = JUMP_SPEED;
+                }
+            }
+
+            let input = Vec3::new(movement.input.x, 0.0, movement.input.z);
+            let speed = WALK_SPEED * movement.speed_multiplier();
+            let mut accel = movement.acceleration + input * speed * FRICTION;
+
+            if movement.submersion > 0.0 {
+                accel.y += WATER_BUOYANCY * movement.submersion;
+
+                if movement.swim_up {
+                    accel.y += SWIM_ACCELERATION;
+                }
+            }
+
+            movement.velocity += accel * delta_seconds;
+
+            movement.velocity.x *= damping;
+            movement.velocity.z *= damping;
+
+            movement.velocity *= f32::exp(-WATER_DRAG * movement.submersion * delta_seconds);
+        }
+        MovementMode::Flying | MovementMode::Noclip => {
+            movement.velocity += movement.input * FLY_SPEED * FRICTION * delta_seconds;
+            movement.velocity *= damping;
+        }
+    }
+    movement.jump = false;
+
+    movement.velocity = movement.velocity.clamp_length_max(MAX_SPEED);
+
+    movement.velocity * delta_seconds
+}
+
+/// Steps the PlayerCameraMovement component, and moves the player by the resulting displacement.
+/// Runs on a fixed timestep, possibly several times per frame, and every step collides with the
+/// world on its own, so the movement doesn't depend on the frame rate.
+///
+/// With Rapier, the player's collider is swept through the world with Rapier's character
+/// controller. With voxel collision, its bounding box is swept against the block data. In noclip,
+/// the player is moved directly.
+#[allow(clippy::type_complexity)]
+fn tick_player_movement(
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut PlayerCameraMovement,
+            &mut CameraInterpolation,
+            &mut Stamina,
+            &MovementMode,
+            Option<&Collider>,
+        ),
+        With<PlayerCamera>,
+    >,
+    generated_chunks: Res<GeneratedChunks>,
+    models: Res<BlockModels>,
+    collision: Res<PlayerCollision>,
+    mut rapier_context: ResMut<RapierContext>,
+    fixed_time: Res<FixedTime>,
+) {
+    let Ok((entity, mut transform, mut movement, mut interpolation, mut stamina, mode, collider)) =
+        query.get_single_mut()
+    else {
+        return;
+    };
+
+    let delta_seconds = fixed_time.period.as_secs_f32();
+
+    let map = generated_chunks.map.lock().unwrap();
+
+    // Chunks that haven't been generated yet count as solid, so the player doesn't fall out of the
+    // world before they are
+    let is_solid = |pos| get_block(&map, pos).is_none_or(Block::is_solid);
+    let boxes_at = |pos| match get_block(&map, pos) {
+        Some(block) => models.collision_boxes(block, pos),
+        None => vec![Aabb::block(pos)],
+    };
+
+    movement.submersion = submersion(transform.translation, movement.crouching, &|pos| {
+        get_block(&map, pos).is_some_and(Block::is_liquid)
+    });
+
+    let mut displacement = step_movement(&mut movement, *mode, delta_seconds);
+
+    if *mode == MovementMode::Walking && movement.crouching && movement.grounded {
+        let feet = transform.translation - Vec3::new(0.0, FEET_OFFSET, 0.0);
+        let limited = prevent_edge_fall(feet, displacement, &is_solid);
+
+        if limited.x != displacement.x {
+            movement.velocity.x = 0.0;
+        }
+        if limited.z != displacement.z {
+            movement.velocity.z = 0.0;
+        }
+
+        displacement = limited;
+    }
+
+    if movement.sprinting {
+        stamina.current = (stamina.current - STAMINA_DRAIN * delta_seconds).max(0.0);
+    } else {
+        stamina.current = (stamina.current + STAMINA_REGEN * delta_seconds).min(stamina.max);
+    }
+
+    let moved = match (*mode, *collision, collider) {
+        (MovementMode::Noclip, ..) => {
+            movement.grounded = false;
+            displacement
+        }
+        (_, PlayerCollision::Rapier, Some(collider)) => {
+            let mut hit_normals = Vec::new();
+
+            let output = rapier_context.move_shape(
+                displacement,
+                collider,
+                transform.translation,
+                // The body stays upright no matter where the player looks
+                Quat::IDENTITY,
+                0.0,
+                &MoveShapeOptions::default(),
+                QueryFilter::new()
+                    // Walk through dropped items instead of climbing on top of them
+                    .groups(CollisionGroups::new(Group::ALL, !DROPPED_ITEM_GROUP))
+                    .exclude_rigid_body(entity),
+                |collision| hit_normals.push(collision.toi.normal1),
+            );
+
+            // Stop moving into whatever the player ran into, but keep sliding along it
+            for normal in hit_normals {
+                let into_surface = movement.velocity.dot(normal).min(0.0);
+                movement.velocity -= normal * into_surface;
+            }
+
+            movement.grounded = output.grounded;
+            output.effective_translation
+        }
+        _ => {
+            let aabb = player_aabb(transform.translation, movement.crouching);
+            let result = move_aabb(aabb, displacement, STEP_HEIGHT, &boxes_at);
+
+            // Stop moving into whatever the player ran into
+            for axis in 0..3 {
+                if result.displacement[axis] != displacement[axis] {
+                    movement.velocity[axis] = 0.0;
+                }
+            }
+
+            movement.grounded = result.grounded;
+            result.displacement
+        }
+    };
+
+    transform.translation += moved;
+    interpolation.last_step = moved;
+}
+
+/// Offsets the view back towards where the player was before the last physics step, by however
+/// much of the next step has already elapsed, so the view moves smoothly even when the frame rate
+/// doesn't match the physics rate. Also lowers the view while crouching.
+fn interpolate_camera(
+    player_query: Query<
+        (&Transform, &CameraInterpolation, &PlayerCameraMovement),
+        With<PlayerCamera>,
+    >,
+    mut view_query: Query<&mut Transform, (With<PlayerView>, Without<PlayerCamera>)>,
+    fixed_time: Res<FixedTime>,
+) {
+    let Ok((transform, interpolation, movement)) = player_query.get_single() else {
+        return;
+    };
+
+    let alpha = fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32();
+    let mut offset = -interpolation.last_step * (1.0 - alpha.clamp(0.0, 1.0));
+
+    if movement.crouching {
+        offset.y -= CROUCH_VIEW_DROP;
+    }
+
+    for mut view_transform in view_query.iter_mut() {
+        // The view is a child of the player, so the offset has to be in the player's local space
+        view_transform.translation = transform.rotation.inverse() * offset;
+    }
+}
+
+/// Updates the input direction of the PlayerCameraMovement depending on user input.
+fn handle_input_movement(
+    mut query: Query<(&Transform, &mut PlayerCameraMovement, &MovementMode), With<PlayerCamera>>,
+    actions: Res<ActionState>,
+) {
+    let (transform, mut player_movement, mode) = query.get_single_mut().unwrap();
+
+    let mut movement = Vec3::ZERO;
+
+    let forward = {
+        let forward = transform.forward();
+        Vec3::new(forward.x, 0.0, forward.z).normalize()
+    };
+
+    let right = {
+        let right = transform.right();
+        Vec3::new(right.x, 0.0, right.z).normalize()
+    };
+
+    // Analog values, so a gamepad stick pushed halfway walks at half speed
+    movement +=
+        forward * (actions.value(Action::MoveForward) - actions.value(Action::MoveBackward));
+    movement += right * (actions.value(Action::MoveRight) - actions.value(Action::MoveLeft));
+
+    if *mode != MovementMode::Walking {
+        movement.y = actions.value(Action::Jump) - actions.value(Action::Crouch);
+    }
+
+    player_movement.input = movement.clamp_length_max(1.0);
+}
+
+/// Jumps are only consumed by the next physics step, which may not happen in the same frame.
+/// Holding jump swims upwards.
+fn handle_input_jump(
+    mut query: Query<(&mut PlayerCameraMovement, &MovementMode), With<PlayerCamera>>,
+    actions: Res<ActionState>,
+) {
+    if let Ok((mut movement, mode)) = query.get_single_mut() {
+        if *mode == MovementMode::Walking && actions.just_pressed(Action::Jump) {
+            movement.jump = true;
+        }
+
+        movement.swim_up = *mode == MovementMode::Walking && actions.pressed(Action::Jump);
+    }
+}
+
+/// Tints the view blue and limits how far the player can see while their head is underwater.
+fn update_underwater_fog(
+    mut commands: Commands,
+    player_query: Query<&PlayerCameraMovement, With<PlayerCamera>>,
+    view_query: Query<(Entity, &GlobalTransform, Option<&FogSettings>), With<PlayerView>>,
+    generated_chunks: Res<GeneratedChunks>,
+) {
+    let Ok(movement) = player_query.get_single() else {
+        return;
+    };
+
+    // Nothing can be underwater if the player isn't in water at all
+    if movement.submersion == 0.0 && view_query.iter().all(|(_, _, fog)| fog.is_none()) {
+        return;
+    }
+
+    let map = generated_chunks.map.lock().unwrap();
+
+    for (entity, transform, fog) in view_query.iter() {
+        let eye = transform.translation().floor().as_ivec3();
+        let underwater = get_block(&map, eye).is_some_and(Block::is_liquid);
+
+        if underwater && fog.is_none() {
+            commands.entity(entity).insert(FogSettings {
+                color: Color::rgba(0.1, 0.25, 0.5, 1.0),
+                falloff: FogFalloff::Linear {
+                    start: 0.0,
+                    end: UNDERWATER_VIEW_DISTANCE,
+                },
+                ..default()
+            });
+        } else if !underwater && fog.is_some() {
+            commands.entity(entity).remove::<FogSettings>();
+        }
+    }
+}
+
+/// Crouching while holding the crouch action, and sprinting while holding the sprint action and
+/// moving forwards. The player stays crouched while there's no room to stand up.
+fn handle_input_stance(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut PlayerCameraMovement,
+            &Stamina,
+            &MovementMode,
+        ),
+        With<PlayerCamera>,
+    >,
+    generated_chunks: Res<GeneratedChunks>,
+    actions: Res<ActionState>,
+    collision: Res<PlayerCollision>,
+) {
+    let Ok((entity, transform, mut movement, stamina, mode)) = query.get_single_mut() else {
+        return;
+    };
+
+    // While flying, the crouch action moves down instead
+    let wants_crouch = *mode == MovementMode::Walking && actions.pressed(Action::Crouch);
+
+    if wants_crouch && !movement.crouching {
+        movement.crouching = true;
+
+        if *collision == PlayerCollision::Rapier {
+            commands.entity(entity).insert(player_collider(true));
+        }
+    } else if !wants_crouch && movement.crouching {
+        let map = generated_chunks.map.lock().unwrap();
+        let is_solid = |pos| get_block(&map, pos).is_some_and(Block::is_solid);
+
+        if !is_obstructed(
+            transform.translation,
+            CROUCHING_HEAD_OFFSET,
+            STANDING_HEAD_OFFSET,
+            &is_solid,
+        ) {
+            movement.crouching = false;
+
+            if *collision == PlayerCollision::Rapier {
+                commands.entity(entity).insert(player_collider(false));
+            }
+        }
+    }
+
+    movement.sprinting = *mode == MovementMode::Walking
+        && !movement.crouching
+        && actions.pressed(Action::Sprint)
+        && actions.value(Action::MoveForward) > 0.0
+        && stamina.current > 0.0;
+}
+
+/// Widens the field of view while sprinting.
+fn update_view_fov(
+    player_query: Query<&PlayerCameraMovement, With<PlayerCamera>>,
+    mut view_query: Query<&mut Projection, With<PlayerView>>,
+    time: Res<Time>,
+) {
+    let Ok(movement) = player_query.get_single() else {
+        return;
+    };
+
+    let base_fov = PerspectiveProjection::default().fov;
+    let target_fov = if movement.sprinting {
+        base_fov * SPRINT_FOV_MULTIPLIER
+    } else {
+        base_fov
+    };
+
+    for mut projection in view_query.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            let t = 1.0 - f32::exp(-FOV_CHANGE_RATE * time.delta_seconds());
+            perspective.fov += (target_fov - perspective.fov) * t;
+        }
+    }
+}
+
+/// Double tapping jump toggles creative flight, and the noclip action toggles noclip. Collision is
+/// disabled while in noclip.
+fn handle_input_movement_mode(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut MovementMode, &mut PlayerCameraMovement), With<PlayerCamera>>,
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    mut last_jump_press: Local<Option<f32>>,
+) {
+    let Ok((entity, mut mode, mut movement)) = query.get_single_mut() else {
+        return;
+    };
+
+    let previous_mode = *mode;
+
+    if actions.just_pressed(Action::Jump) {
+        let now = time.elapsed_seconds();
+
+        match *last_jump_press {
+            Some(last) if now - last < DOUBLE_TAP_TIME => {
+                *mode = match *mode {
+                    MovementMode::Walking => MovementMode::Flying,
+                    MovementMode::Flying => MovementMode::Walking,
+                    MovementMode::Noclip => MovementMode::Noclip,
+                };
+
+                // A triple tap shouldn't toggle twice
+                *last_jump_press = None;
+            }
+            _ => *last_jump_press = Some(now),
+        }
+    }
+
+    if actions.just_pressed(Action::ToggleNoclip) {
+        *mode = match *mode {
+            MovementMode::Noclip => MovementMode::Walking,
+            _ => MovementMode::Noclip,
+        };
+    }
+
+    if *mode == previous_mode {
+        return;
+    }
+
+    // Don't keep falling or flying upwards after switching
+    movement.velocity = Vec3::ZERO;
+    movement.jump = false;
+
+    if *mode == MovementMode::Noclip {
+        commands.entity(entity).insert(ColliderDisabled);
+    } else if previous_mode == MovementMode::Noclip {
+        commands.entity(entity).remove::<ColliderDisabled>();
+    }
+}
+
+/// The same system as above, but for rotation.
+fn handle_input_rotation(
+    mut transform_query: Query<&mut Transform, With<PlayerCamera>>,
+    actions: Res<ActionState>,
+) {
+    let mut transform = transform_query.get_single_mut().unwrap();
+
+    let pitch = actions.look.y.clamp(-89.0, 89.0);
+    let yaw = -actions.look.x;
+
+    let right = transform.right();
+
+    transform.rotate_axis(right, f32::sin(pitch.to_radians()));
+    transform.rotate_y(yaw.to_radians());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::body::PLAYER_RADIUS;
+    use crate::worldgen::chunk::{Chunk, ChunkMap};
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+    use bevy_rapier3d::rapier::geometry::ColliderBuilder;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// How many physics steps each run simulates.
+    const STEPS: u128 = 180;
+    /// The x coordinate of the wall the player walks into.
+    const WALL_X: i32 = 10;
+
+    fn standing_still() -> PlayerCameraMovement {
+        PlayerCameraMovement {
+            velocity: Vec3::ZERO,
+            acceleration: Vec3::new(0.0, -GRAVITY, 0.0),
+            input: Vec3::ZERO,
+            jump: false,
+            grounded: false,
+            sprinting: false,
+            crouching: false,
+            swim_up: false,
+            submersion: 0.0,
+        }
+    }
+
+    /// A floor at y = 0 with a wall across it, as blocks.
+    fn walled_floor() -> ChunkMap {
+        let mut chunk = Chunk::empty(IVec3::ZERO);
+
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set(UVec3::new(x, 0, z), Block::Stone);
+            }
+        }
+        for y in 1..5 {
+            for z in 0..16 {
+                chunk.set(UVec3::new(WALL_X as u32, y, z), Block::Stone);
+            }
+        }
+
+        ChunkMap::from([((0, 0, 0), chunk)])
+    }
+
+    /// The same floor and wall as `walled_floor`, as Rapier colliders.
+    fn walled_floor_colliders() -> RapierContext {
+        let mut context = RapierContext::default();
+
+        context.colliders.insert(
+            ColliderBuilder::cuboid(8.0, 0.5, 8.0)
+                .translation(Vec3::new(8.0, 0.5, 8.0).into())
+                .build(),
+        );
+        context.colliders.insert(
+            ColliderBuilder::cuboid(0.5, 2.0, 8.0)
+                .translation(Vec3::new(WALL_X as f32 + 0.5, 3.0, 8.0).into())
+                .build(),
+        );
+        context.update_query_pipeline();
+
+        context
+    }
+
+    /// Drops the player onto the floor and walks them diagonally into the wall for `STEPS` physics
+    /// steps, rendering at `fps`. Returns where the player ended up, and how fast they were moving.
+    fn walk_into_wall(collision: PlayerCollision, fps: u32) -> (Vec3, Vec3, bool) {
+        move_towards_wall(collision, MovementMode::Walking, fps)
+    }
+
+    /// Like `walk_into_wall`, but moving in any mode.
+    fn move_towards_wall(
+        collision: PlayerCollision,
+        mode: MovementMode,
+        fps: u32,
+    ) -> (Vec3, Vec3, bool) {
+        let period = Duration::from_secs_f32(PHYSICS_TIMESTEP);
+        // Rounded up, so the frames never add up to less than `STEPS` physics steps. At 30 FPS
+        // every frame is exactly two steps long.
+        let frame_nanos = (period.as_nanos() * 60).div_ceil(fps as u128);
+        let frames = (period.as_nanos() * STEPS).div_ceil(frame_nanos);
+
+        let mut app = App::new();
+
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_nanos(
+                frame_nanos as u64,
+            )))
+            .insert_resource(FixedTime::new(period))
+            .insert_resource(collision)
+            .insert_resource(GeneratedChunks {
+                map: Arc::new(Mutex::new(walled_floor())),
+            })
+            .insert_resource(walled_floor_colliders())
+            .init_resource::<BlockModels>()
+            .add_systems(FixedUpdate, tick_player_movement);
+
+        let player = app
+            .world
+            .spawn((
+                Transform::from_xyz(3.0, 3.0, 3.0),
+                PlayerCamera,
+                PlayerCameraMovement {
+                    input: Vec3::new(1.0, 0.0, 0.5).normalize(),
+                    ..standing_still()
+                },
+                Stamina {
+                    current: 100.0,
+                    max: 100.0,
+                },
+                mode,
+                CameraInterpolation::default(),
+                player_collider(false),
+            ))
+            .id();
+
+        // The first update only starts the clock
+        for _ in 0..=frames {
+            app.update();
+        }
+
+        let position = app.world.get::<Transform>(player).unwrap().translation;
+        let movement = app.world.get::<PlayerCameraMovement>(player).unwrap();
+
+        (position, movement.velocity, movement.grounded)
+    }
+
+    fn assert_frame_rate_independent(collision: PlayerCollision) {
+        let (slow_position, slow_velocity, slow_grounded) = walk_into_wall(collision, 30);
+        let (fast_position, fast_velocity, fast_grounded) = walk_into_wall(collision, 144);
+
+        assert!(
+            slow_position.distance(fast_position) < 1e-3,
+            "{slow_position} at 30 FPS, {fast_position} at 144 FPS"
+        );
+        assert!(
+            slow_velocity.distance(fast_velocity) < 1e-3,
+            "{slow_velocity} at 30 FPS, {fast_velocity} at 144 FPS"
+        );
+        assert!(slow_grounded && fast_grounded);
+
+        // Standing on the floor, pressed against the wall and sliding along it
+        assert!((slow_position.y - 1.0 - FEET_OFFSET).abs() < 0.05);
+        assert!((slow_position.x - (WALL_X as f32 - PLAYER_RADIUS)).abs() < 0.05);
+        assert!(slow_velocity.x.abs() < 1e-3);
+        assert!(slow_velocity.y.abs() < 1e-3);
+        assert!(slow_velocity.z > 0.0);
+    }
+
+    #[test]
+    fn rapier_movement_does_not_depend_on_the_frame_rate() {
+        assert_frame_rate_independent(PlayerCollision::Rapier);
+    }
+
+    #[test]
+    fn voxel_movement_does_not_depend_on_the_frame_rate() {
+        assert_frame_rate_independent(PlayerCollision::Voxel);
+    }
+
+    /// Runs `seconds` worth of physics steps in open water, whose surface is at y = 0, and returns
+    /// where the player ended up.
+    fn swim(movement: &mut PlayerCameraMovement, mut position: Vec3, seconds: f32) -> Vec3 {
+        let is_liquid = |pos: IVec3| pos.y < 0;
+
+        for _ in 0..(seconds / PHYSICS_TIMESTEP).round() as usize {
+            movement.submersion = submersion(position, movement.crouching, &is_liquid);
+            position += step_movement(movement, MovementMode::Walking, PHYSICS_TIMESTEP);
+        }
+
+        position
+    }
+
+    /// The vertical speed the player settles at in deep water, with a constant `acceleration` on
+    /// top of drag.
+    fn terminal_speed(acceleration: f32) -> f32 {
+        // Each step adds the acceleration, then drag takes away a fraction of the velocity
+        let damping = f32::exp(-WATER_DRAG * PHYSICS_TIMESTEP);
+
+        acceleration * PHYSICS_TIMESTEP * damping / (1.0 - damping)
+    }
+
+    #[test]
+    fn sinks_slowly_in_deep_water() {
+        let mut movement = standing_still();
+
+        let position = swim(&mut movement, Vec3::new(0.0, -20.0, 0.0), 3.0);
+
+        // Buoyancy almost cancels out gravity, and drag limits the rest
+        let sinking_speed = terminal_speed(WATER_BUOYANCY - GRAVITY);
+        assert!(sinking_speed < 0.0 && sinking_speed > -1.5);
+        assert!(
+            (movement.velocity.y - sinking_speed).abs() < 0.01,
+            "sinking at {}",
+            movement.velocity.y
+        );
+        assert!(position.y < -20.0 && position.y > -24.0);
+    }
+
+    #[test]
+    fn swims_up_when_holding_jump() {
+        let mut movement = PlayerCameraMovement {
+            swim_up: true,
+            ..standing_still()
+        };
+
+        swim(&mut movement, Vec3::new(0.0, -20.0, 0.0), 3.0);
+
+        let surfacing_speed = terminal_speed(WATER_BUOYANCY + SWIM_ACCELERATION - GRAVITY);
+        assert!(surfacing_speed > 1.0);
+        assert!(
+            (movement.velocity.y - surfacing_speed).abs() < 0.01,
+            "surfacing at {}",
+            movement.velocity.y
+        );
+    }
+
+    #[test]
+    fn floats_at_the_surface_when_holding_jump() {
+        let mut movement = PlayerCameraMovement {
+            swim_up: true,
+            ..standing_still()
+        };
+
+        let position = swim(&mut movement, Vec3::new(0.0, -5.0, 0.0), 20.0);
+
+        // Bobbing with the head out of the water and the feet still in it
+        assert!(
+            position.y + STANDING_HEAD_OFFSET > 0.0,
+            "head at {}",
+            position.y
+        );
+        assert!(position.y - FEET_OFFSET < 0.0, "feet at {}", position.y);
+        assert!(movement.velocity.y.abs() < 1.0);
+    }
+
+    #[test]
+    fn falls_faster_in_air_than_in_water() {
+        let mut in_air = standing_still();
+        let mut in_water = standing_still();
+
+        let air_position = swim(&mut in_air, Vec3::new(0.0, 100.0, 0.0), 1.0);
+        let water_position = swim(&mut in_water, Vec3::new(0.0, -100.0, 0.0), 1.0);
+
+        assert!(100.0 - air_position.y > 10.0 * (-100.0 - water_position.y));
+    }
+
+    #[test]
+    fn flying_has_no_gravity() {
+        let mut movement = standing_still();
+        let mut position = Vec3::ZERO;
+
+        for _ in 0..(1.0 / PHYSICS_TIMESTEP) as usize {
+            position += step_movement(&mut movement, MovementMode::Flying, PHYSICS_TIMESTEP);
+        }
+
+        assert_eq!(position, Vec3::ZERO);
+        assert_eq!(movement.velocity, Vec3::ZERO);
+    }
+
+    #[test]
+    fn flying_moves_up_and_down_at_the_fly_speed() {
+        let mut movement = PlayerCameraMovement {
+            input: Vec3::Y,
+            ..standing_still()
+        };
+
+        for _ in 0..(3.0 / PHYSICS_TIMESTEP) as usize {
+            step_movement(&mut movement, MovementMode::Noclip, PHYSICS_TIMESTEP);
+        }
+
+        // Friction holds the speed at about the fly speed, rather than exactly, with whole steps
+        let damping = f32::exp(-FRICTION * PHYSICS_TIMESTEP);
+        let top_speed = FLY_SPEED * FRICTION * PHYSICS_TIMESTEP * damping / (1.0 - damping);
+        assert!((top_speed - FLY_SPEED).abs() < FLY_SPEED * 0.1);
+        assert!(
+            (movement.velocity.y - top_speed).abs() < 0.01,
+            "flying up at {}",
+            movement.velocity.y
+        );
+        assert_eq!(movement.velocity.x, 0.0);
+    }
+
+    #[test]
+    fn noclip_moves_through_solid_blocks() {
+        for collision in [PlayerCollision::Rapier, PlayerCollision::Voxel] {
+            let (position, velocity, grounded) =
+                move_towards_wall(collision, MovementMode::Noclip, 60);
+
+            // Straight through the wall, without falling through the floor
+            assert!(position.x > WALL_X as f32 + 1.0, "stopped at {position}");
+            assert_eq!(position.y, 3.0);
+            assert!(velocity.x > 0.0);
+            assert!(!grounded);
+        }
+    }
+
+    /// Runs `handle_input_movement_mode` once a frame, `FRAME_TIME` apart.
+    struct ModeSwitcher {
+        app: App,
+        player: Entity,
+    }
+
+    impl ModeSwitcher {
+        const FRAME_TIME: Duration = Duration::from_millis(50);
+
+        fn new() -> Self {
+            let mut app = App::new();
+            app.add_plugins(TimePlugin)
+                .insert_resource(TimeUpdateStrategy::ManualDuration(Self::FRAME_TIME))
+                .init_resource::<ActionState>()
+                .add_systems(Update, handle_input_movement_mode);
+
+            let player = app
+                .world
+                .spawn((
+                    PlayerCamera,
+                    MovementMode::Walking,
+                    PlayerCameraMovement {
+                        velocity: Vec3::new(0.0, -5.0, 0.0),
+                        ..standing_still()
+                    },
+                ))
+                .id();
+
+            // The first update only starts the clock
+            app.update();
+
+            Self { app, player }
+        }
+
+        /// Runs a frame, pressing the actions during it.
+        fn frame(&mut self, actions: &[Action]) -> MovementMode {
+            let mut action_state = ActionState::default();
+            for action in actions {
+                action_state.press(*action);
+            }
+            self.app.insert_resource(action_state);
+
+            self.app.update();
+
+            *self.app.world.get::<MovementMode>(self.player).unwrap()
+        }
+
+        /// Runs frames without pressing anything, for at least the time.
+        fn wait(&mut self, seconds: f32) {
+            let frames = (seconds / Self::FRAME_TIME.as_secs_f32()).ceil() as usize;
+
+            for _ in 0..frames {
+                self.frame(&[]);
+            }
+        }
+
+        fn collider_disabled(&self) -> bool {
+            self.app
+                .world
+                .get::<ColliderDisabled>(self.player)
+                .is_some()
+        }
+    }
+
+    #[test]
+    fn double_tapping_jump_toggles_flight() {
+        let mut switcher = ModeSwitcher::new();
+
+        assert_eq!(switcher.frame(&[Action::Jump]), MovementMode::Walking);
+        switcher.wait(DOUBLE_TAP_TIME / 2.0);
+        assert_eq!(switcher.frame(&[Action::Jump]), MovementMode::Flying);
+
+        // Stops falling when starting to fly
+        let movement = switcher
+            .app
+            .world
+            .get::<PlayerCameraMovement>(switcher.player);
+        assert_eq!(movement.unwrap().velocity, Vec3::ZERO);
+
+        switcher.frame(&[Action::Jump]);
+        assert_eq!(switcher.frame(&[Action::Jump]), MovementMode::Walking);
+    }
+
+    #[test]
+    fn slow_taps_do_not_toggle_flight() {
+        let mut switcher = ModeSwitcher::new();
+
+        switcher.frame(&[Action::Jump]);
+        switcher.wait(DOUBLE_TAP_TIME);
+        assert_eq!(switcher.frame(&[Action::Jump]), MovementMode::Walking);
+
+        // That tap can start a new double tap, though
+        assert_eq!(switcher.frame(&[Action::Jump]), MovementMode::Flying);
+    }
+
+    #[test]
+    fn a_triple_tap_only_toggles_once() {
+        let mut switcher = ModeSwitcher::new();
+
+        switcher.frame(&[Action::Jump]);
+        switcher.frame(&[Action::Jump]);
+        assert_eq!(switcher.frame(&[Action::Jump]), MovementMode::Flying);
+    }
+
+    #[test]
+    fn noclip_toggles_and_disables_the_collider() {
+        let mut switcher = ModeSwitcher::new();
+
+        assert_eq!(
+            switcher.frame(&[Action::ToggleNoclip]),
+            MovementMode::Noclip
+        );
+        assert!(switcher.collider_disabled());
+
+        // Double tapping jump doesn't leave noclip
+        switcher.frame(&[Action::Jump]);
+        assert_eq!(switcher.frame(&[Action::Jump]), MovementMode::Noclip);
+
+        assert_eq!(
+            switcher.frame(&[Action::ToggleNoclip]),
+            MovementMode::Walking
+        );
+        assert!(!switcher.collider_disabled());
+    }
+}
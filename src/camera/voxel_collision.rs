@@ -0,0 +1,264 @@
+//! Swept AABB collision directly against block data, as an alternative to Rapier's character
+//! controller that doesn't need any chunk colliders. Blocks are collided with as the boxes of
+//! their models, so the box can stand on slabs and walk up stairs.
+
+use bevy::prelude::*;
+
+/// Keeps faces that are exactly touching from counting as overlapping.
+const EPSILON: f32 = 1e-4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// The box filling the whole block.
+    pub fn block(pos: IVec3) -> Self {
+        Self {
+            min: pos.as_vec3(),
+            max: (pos + IVec3::ONE).as_vec3(),
+        }
+    }
+
+    /// Whether the boxes overlap, not counting touching faces.
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.overlaps_on_axis(other, axis))
+    }
+
+    fn overlaps_on_axis(&self, other: &Aabb, axis: usize) -> bool {
+        self.min[axis] + EPSILON < other.max[axis] && self.max[axis] - EPSILON > other.min[axis]
+    }
+
+    fn translated(self, offset: Vec3) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MoveResult {
+    /// How far the box actually moved.
+    pub displacement: Vec3,
+    /// Whether the box landed on something.
+    pub grounded: bool,
+}
+
+/// Range of blocks overlapped by the box along an axis, not counting touching faces.
+fn block_range(aabb: &Aabb, axis: usize) -> std::ops::RangeInclusive<i32> {
+    (aabb.min[axis] + EPSILON).floor() as i32..=(aabb.max[axis] - EPSILON).floor() as i32
+}
+
+/// Moves the box along one axis, stopping at the first of the blocks' boxes in the way.
+/// `boxes_at` gives the boxes of the block at a position, in world space. Boxes the box already
+/// overlaps are ignored, so it can always move out of them.
+fn sweep_axis(aabb: &Aabb, axis: usize, delta: f32, boxes_at: &impl Fn(IVec3) -> Vec<Aabb>) -> f32 {
+    if delta == 0.0 {
+        return 0.0;
+    }
+
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+
+    // Every block the box passes through on the way
+    let mut swept = *aabb;
+    swept.min[axis] = aabb.min[axis].min(aabb.min[axis] + delta);
+    swept.max[axis] = aabb.max[axis].max(aabb.max[axis] + delta);
+
+    let mut allowed = delta;
+
+    for i in block_range(&swept, axis) {
+        for j in block_range(&swept, a) {
+            for k in block_range(&swept, b) {
+                let mut pos = IVec3::ZERO;
+                pos[axis] = i;
+                pos[a] = j;
+                pos[b] = k;
+
+                for other in boxes_at(pos) {
+                    if !aabb.overlaps_on_axis(&other, a) || !aabb.overlaps_on_axis(&other, b) {
+                        continue;
+                    }
+
+                    if delta > 0.0 && other.min[axis] >= aabb.max[axis] - EPSILON {
+                        allowed = allowed.min((other.min[axis] - aabb.max[axis]).max(0.0));
+                    } else if delta < 0.0 && other.max[axis] <= aabb.min[axis] + EPSILON {
+                        allowed = allowed.max((other.max[axis] - aabb.min[axis]).min(0.0));
+                    }
+                }
+            }
+        }
+    }
+
+    allowed
+}
+
+/// Moves the box one axis at a time; vertically first, then along x and z.
+fn move_per_axis(aabb: Aabb, displacement: Vec3, boxes_at: &impl Fn(IVec3) -> Vec<Aabb>) -> Vec3 {
+    let mut aabb = aabb;
+    let mut moved = Vec3::ZERO;
+
+    for axis in [1, 0, 2] {
+        moved[axis] = sweep_axis(&aabb, axis, displacement[axis], boxes_at);
+
+        let mut offset = Vec3::ZERO;
+        offset[axis] = moved[axis];
+        aabb = aabb.translated(offset);
+    }
+
+    moved
+}
+
+/// Moves the box by `displacement`, sliding along any blocks it runs into. If it's on the ground
+/// and a wall stops it horizontally, it tries stepping up onto the wall, by up to `step_height`.
+pub fn move_aabb(
+    aabb: Aabb,
+    displacement: Vec3,
+    step_height: f32,
+    boxes_at: &impl Fn(IVec3) -> Vec<Aabb>,
+) -> MoveResult {
+    let moved = move_per_axis(aabb, displacement, boxes_at);
+
+    let blocked_horizontally = moved.x != displacement.x || moved.z != displacement.z;
+    let on_ground = displacement.y <= 0.0 && sweep_axis(&aabb, 1, -EPSILON * 2.0, boxes_at) == 0.0;
+
+    if blocked_horizontally && on_ground && step_height > 0.0 {
+        // Step up, move horizontally, and then back down onto whatever is there
+        let up = sweep_axis(&aabb, 1, step_height, boxes_at);
+        let raised = aabb.translated(Vec3::new(0.0, up, 0.0));
+
+        let horizontal = move_per_axis(
+            raised,
+            Vec3::new(displacement.x, 0.0, displacement.z),
+            boxes_at,
+        );
+        let moved_over = raised.translated(horizontal);
+
+        let down = sweep_axis(&moved_over, 1, -up + displacement.y.min(0.0), boxes_at);
+
+        let stepped = Vec3::new(horizontal.x, up + down, horizontal.z);
+
+        if stepped.x.abs() + stepped.z.abs() > moved.x.abs() + moved.z.abs() {
+            return MoveResult {
+                displacement: stepped,
+                grounded: true,
+            };
+        }
+    }
+
+    MoveResult {
+        displacement: moved,
+        grounded: displacement.y < 0.0 && moved.y > displacement.y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A box about the size of the player, standing on the ground at y = 0 with its right side at
+    /// x = 0.9.
+    fn player_box() -> Aabb {
+        Aabb {
+            min: Vec3::new(0.5, 0.0, 0.3),
+            max: Vec3::new(0.9, 1.8, 0.7),
+        }
+    }
+
+    /// Full blocks below y = 0, plus whatever `extra` adds.
+    fn ground_with(extra: impl Fn(IVec3) -> Vec<Aabb>) -> impl Fn(IVec3) -> Vec<Aabb> {
+        move |pos| {
+            if pos.y < 0 {
+                vec![Aabb::block(pos)]
+            } else {
+                extra(pos)
+            }
+        }
+    }
+
+    fn assert_moved(result: MoveResult, expected: Vec3) {
+        assert!(
+            result.displacement.distance(expected) < 1e-3,
+            "moved {}, expected {expected}",
+            result.displacement
+        );
+    }
+
+    #[test]
+    fn falls_onto_the_ground() {
+        let boxes_at = ground_with(|_| vec![]);
+        let falling = player_box().translated(Vec3::new(0.0, 0.3, 0.0));
+
+        let result = move_aabb(falling, Vec3::new(0.1, -1.0, 0.0), 0.5, &boxes_at);
+
+        assert_moved(result, Vec3::new(0.1, -0.3, 0.0));
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn is_not_grounded_in_midair() {
+        let boxes_at = ground_with(|_| vec![]);
+        let falling = player_box().translated(Vec3::new(0.0, 3.0, 0.0));
+
+        let result = move_aabb(falling, Vec3::new(0.0, -1.0, 0.0), 0.5, &boxes_at);
+
+        assert_moved(result, Vec3::new(0.0, -1.0, 0.0));
+        assert!(!result.grounded);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let boxes_at = ground_with(|pos| match pos.x {
+            1 => vec![Aabb::block(pos)],
+            _ => vec![],
+        });
+
+        let result = move_aabb(player_box(), Vec3::new(0.3, -0.01, 0.2), 0.5, &boxes_at);
+
+        assert_moved(result, Vec3::new(0.1, 0.0, 0.2));
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn steps_up_a_half_block() {
+        let boxes_at = ground_with(|pos| match (pos.x, pos.y) {
+            (1, 0) => vec![Aabb {
+                min: pos.as_vec3(),
+                max: pos.as_vec3() + Vec3::new(1.0, 0.5, 1.0),
+            }],
+            _ => vec![],
+        });
+
+        let result = move_aabb(player_box(), Vec3::new(0.3, -0.01, 0.0), 0.5, &boxes_at);
+
+        assert_moved(result, Vec3::new(0.3, 0.5, 0.0));
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn refuses_a_full_block_step_higher_than_the_step_height() {
+        let boxes_at = ground_with(|pos| match (pos.x, pos.y) {
+            (1, 0) => vec![Aabb::block(pos)],
+            _ => vec![],
+        });
+
+        let result = move_aabb(player_box(), Vec3::new(0.3, -0.01, 0.0), 0.5, &boxes_at);
+
+        assert_moved(result, Vec3::new(0.1, 0.0, 0.0));
+    }
+
+    #[test]
+    fn refuses_a_step_without_headroom() {
+        // A single block to step onto, but with a ceiling right above it
+        let boxes_at = ground_with(|pos| match (pos.x, pos.y) {
+            (1, 0) | (_, 2) => vec![Aabb::block(pos)],
+            _ => vec![],
+        });
+
+        let result = move_aabb(player_box(), Vec3::new(0.3, -0.01, 0.0), 1.0, &boxes_at);
+
+        assert_moved(result, Vec3::new(0.1, 0.0, 0.0));
+    }
+}
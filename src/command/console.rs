@@ -0,0 +1,292 @@
+//! A text box for typing commands into. Output stays on screen for a while after it's closed.
+
+use crate::command::history::CommandHistory;
+use crate::command::registry::CommandRegistry;
+use crate::command::{CommandOutput, RunCommand};
+use crate::input::{Action, ActionState, TextInputFocus};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::mem;
+
+/// How many lines of output are kept.
+const MAX_LOG_LINES: usize = 50;
+/// How many lines of output are shown while the console is open.
+const OPEN_LOG_LINES: usize = 12;
+/// How long output stays on screen once the console is closed, in seconds.
+const LOG_LINGER_TIME: f32 = 8.0;
+const CONSOLE_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+const OUTPUT_COLOR: Color = Color::WHITE;
+const ERROR_COLOR: Color = Color::rgb(1.0, 0.4, 0.4);
+const SUGGESTION_COLOR: Color = Color::GRAY;
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    /// The ways the word being typed could be completed, after tab completion found more than one.
+    pub suggestions: Vec<String>,
+    pub history: CommandHistory,
+    /// Newest last.
+    log: VecDeque<LogLine>,
+}
+
+struct LogLine {
+    text: String,
+    is_error: bool,
+    /// When the line was added, in seconds since startup.
+    time: f32,
+}
+
+impl Console {
+    fn close(&mut self, focus: &mut TextInputFocus) {
+        self.open = false;
+        self.input.clear();
+        self.suggestions.clear();
+        self.history.stop_browsing();
+        focus.0 = false;
+    }
+}
+
+#[derive(Component)]
+pub struct ConsoleLog;
+
+#[derive(Component)]
+pub struct ConsoleInput;
+
+pub fn spawn_console(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                // Above the hotbar
+                bottom: Val::Px(96.0),
+                left: Val::Px(8.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexStart,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle {
+                    text: Text::from_section("", console_text_style()),
+                    background_color: CONSOLE_BACKGROUND.into(),
+                    ..default()
+                })
+                .insert(ConsoleLog);
+
+            parent
+                .spawn(TextBundle {
+                    text: Text::from_section("", console_text_style()),
+                    style: Style {
+                        padding: UiRect::all(Val::Px(4.0)),
+                        ..default()
+                    },
+                    background_color: CONSOLE_BACKGROUND.into(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                })
+                .insert(ConsoleInput);
+        });
+}
+
+/// Opens the console, and while it's open, types into it. Enter runs the line, Tab completes the
+/// word being typed, the arrow keys go through the history, and Escape or `~` close it again.
+pub fn handle_console_input(
+    action_state: Res<ActionState>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut console: ResMut<Console>,
+    mut focus: ResMut<TextInputFocus>,
+    mut requests: EventWriter<RunCommand>,
+    registry: Res<CommandRegistry>,
+) {
+    let console = &mut *console;
+
+    if !console.open {
+        // The key that opened the console shouldn't be typed into it
+        characters.clear();
+
+        if action_state.just_pressed(Action::OpenConsole) {
+            console.open = true;
+            console.input = "/".to_string();
+            focus.0 = true;
+        }
+
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Escape) || keys.just_pressed(KeyCode::Grave) {
+        // Otherwise closing the console would close the game too
+        keys.reset(KeyCode::Escape);
+        characters.clear();
+        console.close(&mut focus);
+
+        return;
+    }
+
+    let mut edited = false;
+
+    for character in characters.iter() {
+        if !character.char.is_control() {
+            console.input.push(character.char);
+            edited = true;
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        console.input.pop();
+        edited = true;
+    }
+
+    if edited {
+        console.suggestions.clear();
+        console.history.stop_browsing();
+    }
+
+    if keys.just_pressed(KeyCode::Tab) {
+        let completion = registry.complete(&console.input);
+
+        console.input = completion.apply(&console.input);
+        console.suggestions = if completion.candidates.len() > 1 {
+            completion.candidates
+        } else {
+            Vec::new()
+        };
+    }
+
+    let browsed = if keys.just_pressed(KeyCode::Up) {
+        console.history.older()
+    } else if keys.just_pressed(KeyCode::Down) {
+        console.history.newer()
+    } else {
+        None
+    };
+
+    if let Some(line) = browsed {
+        console.input = line.to_string();
+        console.suggestions.clear();
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        let line = mem::take(&mut console.input);
+
+        console.history.push(&line);
+        requests.send(RunCommand(line));
+        console.close(&mut focus);
+    }
+}
+
+pub fn record_command_output(
+    mut outputs: EventReader<CommandOutput>,
+    mut console: ResMut<Console>,
+    time: Res<Time>,
+) {
+    for output in outputs.iter() {
+        for line in output.message.lines() {
+            console.log.push_back(LogLine {
+                text: line.to_string(),
+                is_error: output.is_error,
+                time: time.elapsed_seconds(),
+            });
+        }
+    }
+
+    while console.log.len() > MAX_LOG_LINES {
+        console.log.pop_front();
+    }
+}
+
+/// Shows the latest output, the line being typed, and the suggestions for completing it.
+pub fn update_console_text(
+    console: Res<Console>,
+    time: Res<Time>,
+    mut log_query: Query<&mut Text, (With<ConsoleLog>, Without<ConsoleInput>)>,
+    mut input_query: Query<(&mut Text, &mut Visibility), With<ConsoleInput>>,
+) {
+    let now = time.elapsed_seconds();
+
+    let mut shown: Vec<&LogLine> = console
+        .log
+        .iter()
+        .rev()
+        .take_while(|line| console.open || now - line.time < LOG_LINGER_TIME)
+        .take(OPEN_LOG_LINES)
+        .collect();
+    shown.reverse();
+
+    let log_sections: Vec<TextSection> = shown
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let separator = if index == 0 { "" } else { "\n" };
+            let color = if line.is_error {
+                ERROR_COLOR
+            } else {
+                OUTPUT_COLOR
+            };
+
+            section(format!("{}{}", separator, line.text), color)
+        })
+        .collect();
+
+    for mut text in log_query.iter_mut() {
+        set_sections(&mut text, &log_sections);
+    }
+
+    let mut input_sections = vec![section(format!("{}_", console.input), OUTPUT_COLOR)];
+
+    if !console.suggestions.is_empty() {
+        input_sections.push(section(
+            format!("\n{}", console.suggestions.join("  ")),
+            SUGGESTION_COLOR,
+        ));
+    }
+
+    let input_visibility = if console.open {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    for (mut text, mut visibility) in input_query.iter_mut() {
+        set_sections(&mut text, &input_sections);
+
+        if *visibility != input_visibility {
+            *visibility = input_visibility;
+        }
+    }
+}
+
+fn section(value: String, color: Color) -> TextSection {
+    TextSection {
+        value,
+        style: TextStyle {
+            color,
+            ..console_text_style()
+        },
+    }
+}
+
+fn console_text_style() -> TextStyle {
+    TextStyle {
+        font_size: 16.0,
+        color: OUTPUT_COLOR,
+        ..default()
+    }
+}
+
+/// Only touches the text if it's different, so that it isn't laid out again every frame.
+fn set_sections(text: &mut Mut<Text>, sections: &[TextSection]) {
+    let unchanged = text.sections.len() == sections.len()
+        && text
+            .sections
+            .iter()
+            .zip(sections.iter())
+            .all(|(old, new)| old.value == new.value && old.style.color == new.style.color);
+
+    if !unchanged {
+        text.sections = sections.to_vec();
+    }
+}
@@ -0,0 +1,532 @@
+use crate::camera::{CameraInterpolation, PlayerCamera, PlayerCameraMovement};
+use crate::command::parser::{block_name, Position};
+use crate::command::registry::{Command, CommandRegistry};
+use crate::command::{CommandOutput, RunCommand};
+use crate::crafting::{RecipeKind, RecipeRegistry};
+use crate::interaction::TargetedBlock;
+use crate::inventory::item::{Item, ItemStack};
+use crate::inventory::Inventory;
+use crate::sky::TimeOfDay;
+use crate::worldedit::clipboard::Clipboard;
+use crate::worldedit::history::EditRecord;
+use crate::worldedit::region::Region;
+use crate::worldedit::{operations, schematic, Corner, WorldEdit, MAX_EDIT_VOLUME};
+use crate::worldgen::block::Axis;
+use crate::worldgen::chunk::access::{get_block, world_to_chunk_pos};
+use crate::worldgen::chunk::{ChunkMap, GeneratedChunks, ViewDistance, CHUNK_SIZE};
+use crate::worldgen::edit::{BlockEdit, BlockEntityEdit};
+use crate::worldgen::gen::{self, WorldSeed};
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+/// Parses and runs every requested command, and reports how it went. Positions are relative to
+/// the player, or to the origin if there isn't one, like on a server.
+#[allow(clippy::too_many_arguments)]
+pub fn run_commands(
+    mut requests: EventReader<RunCommand>,
+    mut outputs: EventWriter<CommandOutput>,
+    mut edits: EventWriter<BlockEdit>,
+    mut block_entity_edits: EventWriter<BlockEntityEdit>,
+    registry: Res<CommandRegistry>,
+    recipes: Res<RecipeRegistry>,
+    generated_chunks: Res<GeneratedChunks>,
+    mut view_distance: ResMut<ViewDistance>,
+    seed: Option<Res<WorldSeed>>,
+    mut time_of_day: Option<ResMut<TimeOfDay>>,
+    mut world_edit: ResMut<WorldEdit>,
+    targeted_block: Option<Res<TargetedBlock>>,
+    mut player_query: Query<
+        (
+            &mut Transform,
+            &mut Inventory,
+            &mut PlayerCameraMovement,
+            &mut CameraInterpolation,
+        ),
+        With<PlayerCamera>,
+    >,
+) {
+    for request in requests.iter() {
+        let origin = player_query
+            .get_single()
+            .map_or(Vec3::ZERO, |(transform, ..)| transform.translation);
+
+        let command = match registry.parse(&request.0) {
+            Ok(command) => command,
+            Err(err) => {
+                outputs.send(CommandOutput::error(err.to_string()));
+                continue;
+            }
+        };
+
+        // Selection commands without a position use the targeted block, or the player's block
+        let target = targeted_block
+            .as_ref()
+            .and_then(|targeted_block| targeted_block.0)
+            .map_or_else(|| origin.floor().as_ivec3(), |hit| hit.pos);
+
+        let world_edit = &mut *world_edit;
+
+        let result = match command {
+            Command::Help(name) => help(&registry, name.as_deref()),
+            Command::Teleport(pos) => {
+                let target = pos.resolve(origin);
+
+                player_query
+                    .get_single_mut()
+                    .map(|(mut transform, _, mut movement, mut interpolation)| {
+                        transform.translation = target;
+                        movement.velocity = Vec3::ZERO;
+                        // Don't smooth the view over the whole distance
+                        interpolation.last_step = Vec3::ZERO;
+
+                        format!(
+                            "Teleported to {:.1} {:.1} {:.1}",
+                            target.x, target.y, target.z
+                        )
+                    })
+                    .map_err(|_| "There's no player to teleport".to_string())
+            }
+            Command::SetBlock { pos, block } => {
+                let pos = pos.resolve_block(origin);
+                let map = generated_chunks.map.lock().unwrap();
+
+                if get_block(&map, pos).is_some() {
+                    let record = operations::fill(&map, Region::new(pos, pos), block);
+                    apply(record, world_edit, &mut edits, &mut block_entity_edits);
+
+                    Ok(format!(
+                        "Set the block at {} {} {} to {}",
+                        pos.x,
+                        pos.y,
+                        pos.z,
+                        block_name(block)
+                    ))
+                } else {
+                    Err("That block hasn't been generated yet".to_string())
+                }
+            }
+            Command::Fill { from, to, block } => {
+                let region = Region::new(from.resolve_block(origin), to.resolve_block(origin));
+
+                check_volume(region).map(|_| {
+                    let map = generated_chunks.map.lock().unwrap();
+                    let record = operations::fill(&map, region, block);
+
+                    format!(
+                        "Changed {} blocks to {}",
+                        apply(record, world_edit, &mut edits, &mut block_entity_edits),
+                        block_name(block)
+                    )
+                })
+            }
+            Command::Seed => seed
+                .as_ref()
+                .map(|seed| format!("Seed: {}", seed.0))
+                .ok_or_else(|| "The seed is only known to the server".to_string()),
+            Command::ViewDistance(None) => {
+                Ok(format!("The view distance is {} chunks", view_distance.0.x))
+            }
+            Command::ViewDistance(Some(distance)) => {
+                view_distance.0 = IVec3::splat(distance);
+
+                Ok(format!("Set the view distance to {} chunks", distance))
+            }
+            Command::Give { item, count } => player_query
+                .get_single_mut()
+                .map_err(|_| "There's no player to give items to".to_string())
+                .and_then(|(_, mut inventory, ..)| give(&mut inventory, item, count)),
+            Command::Craft { recipe, count } => player_query
+                .get_single_mut()
+                .map_err(|_| "There's no player to craft for".to_string())
+                .and_then(|(_, mut inventory, ..)| match recipe {
+                    Some(name) => craft(&mut inventory, &recipes, &name, count),
+                    None => Ok(list_craftable(&inventory, &recipes)),
+                }),
+            Command::MoveStack { from, to } => player_query
+                .get_single_mut()
+                .map_err(|_| "There's no player with an inventory".to_string())
+                .and_then(|(_, mut inventory, ..)| move_stack(&mut inventory, from, to)),
+            Command::SplitStack(slot) => player_query
+                .get_single_mut()
+                .map_err(|_| "There's no player with an inventory".to_string())
+                .and_then(|(_, mut inventory, ..)| split_stack(&mut inventory, slot)),
+            Command::SetTime(ticks) => time_of_day
+                .as_mut()
+                .map(|time_of_day| {
+                    **time_of_day = TimeOfDay::from_ticks(ticks);
+
+                    format!("Set the time to {}", ticks)
+                })
+                .ok_or_else(|| "There's no day cycle to set the time of".to_string()),
+            Command::RegenChunk(pos) => match seed.as_ref() {
+                Some(seed) => {
+                    let map = generated_chunks.map.lock().unwrap();
+                    regen_chunk(&map, pos, origin, **seed).map(|(chunk_pos, record)| {
+                        format!(
+                            "Regenerated chunk {} {} {}, changing {} blocks",
+                            chunk_pos.0,
+                            chunk_pos.1,
+                            chunk_pos.2,
+                            apply(record, world_edit, &mut edits, &mut block_entity_edits)
+                        )
+                    })
+                }
+                None => Err("Only the server can generate chunks".to_string()),
+            },
+            Command::SetCorner(corner, pos) => {
+                let pos = pos.map_or(target, |pos| pos.resolve_block(origin));
+                world_edit.selection.set(corner, pos);
+
+                let name = match corner {
+                    Corner::First => "first",
+                    Corner::Second => "second",
+                };
+                let selected = world_edit
+                    .selection
+                    .region()
+                    .map_or_else(String::new, |region| {
+                        format!(" ({} blocks selected)", region.volume())
+                    });
+
+                Ok(format!(
+                    "Set the {} corner to {} {} {}{}",
+                    name, pos.x, pos.y, pos.z, selected
+                ))
+            }
+            Command::Set(block) => selected_region(world_edit).map(|region| {
+                let map = generated_chunks.map.lock().unwrap();
+                let record = operations::fill(&map, region, block);
+
+                format!(
+                    "Changed {} blocks to {}",
+                    apply(record, world_edit, &mut edits, &mut block_entity_edits),
+                    block_name(block)
+                )
+            }),
+            Command::Replace { from, to } => selected_region(world_edit).map(|region| {
+                let map = generated_chunks.map.lock().unwrap();
+                let record = operations::replace(&map, region, from, to);
+
+                format!(
+                    "Replaced {} blocks of {} with {}",
+                    apply(record, world_edit, &mut edits, &mut block_entity_edits),
+                    block_name(from),
+                    block_name(to)
+                )
+            }),
+            Command::Hollow(block) => selected_region(world_edit).map(|region| {
+                let map = generated_chunks.map.lock().unwrap();
+                let record = operations::hollow(&map, region, block);
+
+                format!(
+                    "Made the selection a hollow box of {}, changing {} blocks",
+                    block_name(block),
+                    apply(record, world_edit, &mut edits, &mut block_entity_edits)
+                )
+            }),
+            Command::Copy => selected_region(world_edit).map(|region| {
+                let map = generated_chunks.map.lock().unwrap();
+                world_edit.clipboard = Some(Clipboard::copy(&map, region));
+
+                format!("Copied {} blocks", region.volume())
+            }),
+            Command::Paste(pos) => match world_edit.clipboard.as_ref() {
+                Some(clipboard) => {
+                    let min =
+                        pos.map_or(origin.floor().as_ivec3(), |pos| pos.resolve_block(origin));
+                    let map = generated_chunks.map.lock().unwrap();
+                    let record = clipboard.paste(&map, min);
+
+                    Ok(format!(
+                        "Pasted at {} {} {}, changing {} blocks",
+                        min.x,
+                        min.y,
+                        min.z,
+                        apply(record, world_edit, &mut edits, &mut block_entity_edits)
+                    ))
+                }
+                None => Err("Nothing has been copied yet".to_string()),
+            },
+            Command::Rotate(quarter_turns) => match world_edit.clipboard.as_mut() {
+                Some(clipboard) => {
+                    *clipboard = clipboard.rotated(quarter_turns);
+
+                    Ok(format!(
+                        "Turned the clipboard {} degrees clockwise",
+                        quarter_turns as u32 * 90
+                    ))
+                }
+                None => Err("Nothing has been copied yet".to_string()),
+            },
+            Command::Mirror(axis) => match world_edit.clipboard.as_mut() {
+                Some(clipboard) => {
+                    *clipboard = clipboard.mirrored(axis);
+
+                    let name = match axis {
+                        Axis::X => "X",
+                        Axis::Y => "Y",
+                        Axis::Z => "Z",
+                    };
+
+                    Ok(format!("Mirrored the clipboard across {}", name))
+                }
+                None => Err("Nothing has been copied yet".to_string()),
+            },
+            Command::Undo => match world_edit.history.undo() {
+                Some(record) => {
+                    record.apply(&mut edits, &mut block_entity_edits);
+
+                    Ok(format!("Undid an edit of {} blocks", record.len()))
+                }
+                None => Err("There's nothing to undo".to_string()),
+            },
+            Command::Redo => match world_edit.history.redo() {
+                Some(record) => {
+                    record.apply(&mut edits, &mut block_entity_edits);
+
+                    Ok(format!("Redid an edit of {} blocks", record.len()))
+                }
+                None => Err("There's nothing to redo".to_string()),
+            },
+            Command::SaveSchematic(name) => selected_region(world_edit).and_then(|region| {
+                let path = schematic_path(&name)?;
+                let map = generated_chunks.map.lock().unwrap();
+
+                schematic::save(&Clipboard::copy(&map, region), &path)
+                    .map(|_| format!("Saved {} blocks to {}", region.volume(), path.display()))
+                    .map_err(|err| format!("Couldn't save {}: {}", path.display(), err))
+            }),
+            Command::LoadSchematic(name) => schematic_path(&name).and_then(|path| {
+                let clipboard = schematic::load(&path)
+                    .map_err(|err| format!("Couldn't load {}: {}", path.display(), err))?;
+                let size = clipboard.size;
+                world_edit.clipboard = Some(clipboard);
+
+                Ok(format!(
+                    "Loaded {}, {}x{}x{} blocks, into the clipboard",
+                    path.display(),
+                    size.x,
+                    size.y,
+                    size.z
+                ))
+            }),
+        };
+
+        outputs.send(match result {
+            Ok(message) => CommandOutput::info(message),
+            Err(message) => CommandOutput::error(message),
+        });
+    }
+}
+
+fn help(registry: &CommandRegistry, name: Option<&str>) -> Result<String, String> {
+    match name {
+        Some(name) => {
+            let name = name.strip_prefix('/').unwrap_or(name);
+
+            registry
+                .get(name)
+                .map(|spec| format!("{}: {}", spec.usage(), spec.description))
+                .ok_or_else(|| format!("Unknown command '{}'", name))
+        }
+        None => Ok(registry
+            .iter()
+            .map(|spec| format!("{}: {}", spec.usage(), spec.description))
+            .collect::<Vec<_>>()
+            .join("\n")),
+    }
+}
+
+/// Fails if the region is too big to edit at once.
+fn check_volume(region: Region) -> Result<(), String> {
+    if region.volume() > MAX_EDIT_VOLUME {
+        Err(format!(
+            "That's {} blocks, but at most {} can be edited at once",
+            region.volume(),
+            MAX_EDIT_VOLUME
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The selection, as long as both its corners are set and it isn't too big.
+fn selected_region(world_edit: &WorldEdit) -> Result<Region, String> {
+    let region = world_edit.selection.region().ok_or_else(|| {
+        "Set both corners of the selection with /pos1 and /pos2 first".to_string()
+    })?;
+    check_volume(region)?;
+
+    Ok(region)
+}
+
+fn schematic_path(name: &str) -> Result<PathBuf, String> {
+    schematic::schematic_path(name)
+        .ok_or_else(|| format!("'{}' should be the name of a file, not a path", name))
+}
+
+/// Makes the edit and records it so it can be undone. Returns how many blocks it changed.
+fn apply(
+    record: EditRecord,
+    world_edit: &mut WorldEdit,
+    edits: &mut EventWriter<BlockEdit>,
+    block_entity_edits: &mut EventWriter<BlockEntityEdit>,
+) -> usize {
+    let changed = record.len();
+
+    record.apply(edits, block_entity_edits);
+    world_edit.history.push(record);
+
+    changed
+}
+
+fn give(inventory: &mut Inventory, item: Item, count: u32) -> Result<String, String> {
+    let left_over = inventory
+        .insert(ItemStack::new(item, count))
+        .map_or(0, |stack| stack.count);
+    let given = count - left_over;
+
+    match (given, left_over) {
+        (0, _) => Err("There's no room in the inventory".to_string()),
+        (_, 0) => Ok(format!("Gave {} {}", given, item.name())),
+        _ => Ok(format!(
+            "Gave {} {}, but {} didn't fit",
+            given,
+            item.name(),
+            left_over
+        )),
+    }
+}
+
+/// Lets recipe names be tab completed.
+pub fn register_recipe_names(mut registry: ResMut<CommandRegistry>, recipes: Res<RecipeRegistry>) {
+    registry.set_recipe_names(
+        recipes
+            .recipes()
+            .iter()
+            .filter(|recipe| !matches!(recipe.kind, RecipeKind::Processing { .. }))
+            .map(|recipe| recipe.name.clone()),
+    );
+}
+
+fn list_craftable(inventory: &Inventory, recipes: &RecipeRegistry) -> String {
+    let names: Vec<&str> = recipes
+        .craftable(inventory)
+        .map(|recipe| recipe.name.as_str())
+        .collect();
+
+    if names.is_empty() {
+        "Nothing can be crafted with what's in the inventory".to_string()
+    } else {
+        format!("Can craft: {}", names.join(", "))
+    }
+}
+
+/// Crafts the recipe up to `count` times, for as long as there are enough ingredients and room
+/// for the output.
+fn craft(
+    inventory: &mut Inventory,
+    recipes: &RecipeRegistry,
+    name: &str,
+    count: u32,
+) -> Result<String, String> {
+    let recipe = recipes
+        .get(name)
+        .ok_or_else(|| format!("There's no recipe called '{}'", name))?;
+
+    if let RecipeKind::Processing { machine, .. } = recipe.kind {
+        return Err(format!("{} is made in a {:?}, not by hand", name, machine));
+    }
+
+    let mut crafted = 0;
+
+    while crafted < count {
+        let missing = recipe.missing_ingredients(inventory);
+
+        if !missing.is_empty() {
+            if crafted > 0 {
+                break;
+            }
+
+            let missing: Vec<String> = missing
+                .iter()
+                .map(|stack| format!("{} {}", stack.count, stack.item.name()))
+                .collect();
+            return Err(format!("Missing {}", missing.join(", ")));
+        }
+
+        if inventory.space_for(recipe.output.item) < recipe.output.count {
+            if crafted > 0 {
+                break;
+            }
+
+            return Err("There's no room in the inventory".to_string());
+        }
+
+        for ingredient in recipe.ingredients() {
+            inventory.remove_item(ingredient.item, ingredient.count);
+        }
+        inventory.insert(recipe.output);
+
+        crafted += 1;
+    }
+
+    Ok(format!(
+        "Crafted {} {}",
+        crafted * recipe.output.count,
+        recipe.output.item.name()
+    ))
+}
+
+fn move_stack(inventory: &mut Inventory, from: usize, to: usize) -> Result<String, String> {
+    if inventory.get(from).is_none() {
+        return Err(format!("Slot {} is empty", from + 1));
+    }
+
+    inventory.move_stack(from, to);
+
+    Ok(format!("Moved slot {} to slot {}", from + 1, to + 1))
+}
+
+/// Moves half of the stack in the slot, rounded up, into the first empty slot.
+fn split_stack(inventory: &mut Inventory, slot: usize) -> Result<String, String> {
+    if inventory.get(slot).is_none() {
+        return Err(format!("Slot {} is empty", slot + 1));
+    }
+    let Some(empty) = inventory.split_into_empty(slot) else {
+        return Err("There's no empty slot to split the stack into".to_string());
+    };
+    let half = inventory.get(empty).unwrap();
+
+    Ok(format!(
+        "Moved {} {} into slot {}",
+        half.count,
+        half.item.name(),
+        empty + 1
+    ))
+}
+
+/// Works out how to change every block in the chunk that's different to how it was generated
+/// back.
+fn regen_chunk(
+    map: &ChunkMap,
+    pos: Position,
+    origin: Vec3,
+    seed: WorldSeed,
+) -> Result<((i32, i32, i32), EditRecord), String> {
+    let (chunk_pos, _) = world_to_chunk_pos(pos.resolve_block(origin));
+
+    let Some(chunk) = map.get(&chunk_pos) else {
+        return Err("That chunk hasn't been generated yet".to_string());
+    };
+
+    let region = Region::new(chunk.pos, chunk.pos + CHUNK_SIZE as i32 - 1);
+    let record = EditRecord::plan(
+        map,
+        region
+            .positions()
+            .map(|pos| (pos, gen::at_pos(pos.as_vec3().into(), seed))),
+    );
+
+    Ok((chunk_pos, record))
+}
@@ -0,0 +1,152 @@
+/// How many commands are remembered.
+pub const MAX_HISTORY: usize = 100;
+
+/// Lines that were run before, to be brought back with the arrow keys.
+#[derive(Default)]
+pub struct CommandHistory {
+    /// Oldest first.
+    entries: Vec<String>,
+    /// The entry being shown while going back through the history, or `None` while typing a new
+    /// line.
+    browsing: Option<usize>,
+}
+
+impl CommandHistory {
+    /// Remembers a line that was run, and stops browsing. Running the same line twice in a row
+    /// only remembers it once.
+    pub fn push(&mut self, line: &str) {
+        self.browsing = None;
+
+        if line.trim().is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return;
+        }
+
+        self.entries.push(line.to_string());
+
+        if self.entries.len() > MAX_HISTORY {
+            self.entries.remove(0);
+        }
+    }
+
+    /// Goes back one line. Returns `None` if there's nothing further back.
+    pub fn older(&mut self) -> Option<&str> {
+        let index = match self.browsing {
+            Some(0) => return None,
+            Some(index) => index - 1,
+            None => self.entries.len().checked_sub(1)?,
+        };
+
+        self.browsing = Some(index);
+        Some(&self.entries[index])
+    }
+
+    /// Goes forward one line. Going past the newest line gives an empty line to type a new one
+    /// into, and returns `None` if it wasn't browsing at all.
+    pub fn newer(&mut self) -> Option<&str> {
+        let index = self.browsing? + 1;
+
+        if index < self.entries.len() {
+            self.browsing = Some(index);
+            Some(&self.entries[index])
+        } else {
+            self.browsing = None;
+            Some("")
+        }
+    }
+
+    /// Stops browsing, e.g. once the line being shown gets edited.
+    pub fn stop_browsing(&mut self) {
+        self.browsing = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(lines: &[&str]) -> CommandHistory {
+        let mut history = CommandHistory::default();
+
+        for line in lines {
+            history.push(line);
+        }
+
+        history
+    }
+
+    #[test]
+    fn going_back_starts_at_the_newest_line() {
+        let mut history = history(&["seed", "time set day", "tp 0 64 0"]);
+
+        assert_eq!(history.older(), Some("tp 0 64 0"));
+        assert_eq!(history.older(), Some("time set day"));
+        assert_eq!(history.older(), Some("seed"));
+        assert_eq!(history.older(), None);
+    }
+
+    #[test]
+    fn going_forward_ends_on_an_empty_line() {
+        let mut history = history(&["seed", "undo"]);
+
+        assert_eq!(history.newer(), None);
+
+        history.older();
+        history.older();
+
+        assert_eq!(history.newer(), Some("undo"));
+        assert_eq!(history.newer(), Some(""));
+        assert_eq!(history.newer(), None);
+        assert_eq!(history.older(), Some("undo"));
+    }
+
+    #[test]
+    fn running_a_line_stops_browsing() {
+        let mut history = history(&["seed", "undo"]);
+
+        history.older();
+        history.older();
+        history.push("redo");
+
+        assert_eq!(history.older(), Some("redo"));
+    }
+
+    #[test]
+    fn stopping_browsing_goes_back_to_the_newest_line() {
+        let mut history = history(&["seed", "undo"]);
+
+        history.older();
+        history.older();
+        history.stop_browsing();
+
+        assert_eq!(history.older(), Some("undo"));
+    }
+
+    #[test]
+    fn empty_and_repeated_lines_are_not_remembered() {
+        let mut history = history(&["seed", "seed", "  ", "undo", "seed"]);
+
+        assert_eq!(history.older(), Some("seed"));
+        assert_eq!(history.older(), Some("undo"));
+        assert_eq!(history.older(), Some("seed"));
+        assert_eq!(history.older(), None);
+    }
+
+    #[test]
+    fn only_the_newest_lines_are_kept() {
+        let lines: Vec<String> = (0..MAX_HISTORY + 5)
+            .map(|i| format!("tp {} 64 0", i))
+            .collect();
+        let mut history = CommandHistory::default();
+
+        for line in lines.iter() {
+            history.push(line);
+        }
+
+        let mut oldest = None;
+        while let Some(line) = history.older() {
+            oldest = Some(line.to_string());
+        }
+
+        assert_eq!(oldest.as_deref(), Some("tp 5 64 0"));
+    }
+}
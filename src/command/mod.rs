@@ -0,0 +1,96 @@
+//! Commands like `/tp` and `/fill`, typed into the in-game console or a server's terminal.
+//!
+//! Parsing doesn't depend on the ECS, so `parser`, `registry` and `history` can be used without an
+//! app; the systems that run parsed commands against the world are in `execute`.
+
+pub mod console;
+pub mod execute;
+pub mod history;
+pub mod parser;
+pub mod registry;
+pub mod stdin;
+
+use crate::worldedit::WorldEdit;
+use crate::worldgen::edit;
+use bevy::prelude::*;
+
+/// Asks for a line like `/tp ~ ~10 ~` to be parsed and run.
+#[derive(Event)]
+pub struct RunCommand(pub String);
+
+/// What a command had to say once it ran, or why it couldn't.
+#[derive(Event, Clone)]
+pub struct CommandOutput {
+    pub message: String,
+    pub is_error: bool,
+}
+
+impl CommandOutput {
+    pub fn info(message: String) -> Self {
+        Self {
+            message,
+            is_error: false,
+        }
+    }
+
+    pub fn error(message: String) -> Self {
+        Self {
+            message,
+            is_error: true,
+        }
+    }
+}
+
+/// Runs commands sent as `RunCommand` events. Used by both the game and a headless server.
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<registry::CommandRegistry>()
+            .init_resource::<WorldEdit>()
+            .add_event::<RunCommand>()
+            .add_event::<CommandOutput>()
+            .add_systems(Startup, execute::register_recipe_names)
+            .add_systems(
+                Update,
+                execute::run_commands.before(edit::apply_block_edits),
+            );
+    }
+}
+
+/// The in-game console, toggled with `Action::OpenConsole`.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<console::Console>()
+            .add_systems(Startup, console::spawn_console)
+            .add_systems(
+                Update,
+                (
+                    console::handle_console_input
+                        .before(execute::run_commands)
+                        .before(bevy::window::close_on_esc),
+                    (console::record_command_output, console::update_console_text)
+                        .chain()
+                        .after(execute::run_commands),
+                ),
+            );
+    }
+}
+
+/// Runs commands typed into the terminal, for headless servers.
+pub struct StdinCommandPlugin;
+
+impl Plugin for StdinCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(stdin::StdinLines::spawn_reader())
+            .add_systems(
+                Update,
+                (
+                    stdin::read_stdin_commands.before(execute::run_commands),
+                    stdin::log_command_output.after(execute::run_commands),
+                ),
+            );
+    }
+}
@@ -0,0 +1,480 @@
+//! Splits a command line into words, and reads them one at a time as arguments, checking each is
+//! the kind it should be.
+
+use crate::inventory::item::Item;
+use crate::worldgen::block::Block;
+use bevy::math::{IVec3, Vec3};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Blocks that can't be held as items, but can still be placed with commands.
+const NON_ITEM_BLOCKS: [(&str, Block); 2] = [("air", Block::Air), ("water", Block::Water)];
+
+/// The names blocks can be given by in commands.
+pub fn block_names() -> impl Iterator<Item = &'static str> {
+    NON_ITEM_BLOCKS.into_iter().map(|(name, _)| name).chain(
+        Item::ALL
+            .into_iter()
+            .filter(|item| item.as_block().is_some())
+            .map(Item::name),
+    )
+}
+
+/// The name a block is given by in commands, ignoring its state.
+pub fn block_name(block: Block) -> &'static str {
+    NON_ITEM_BLOCKS
+        .into_iter()
+        .find(|(_, non_item_block)| *non_item_block == block)
+        .map_or_else(
+            || Item::Block(block.with_default_state()).name(),
+            |(name, _)| name,
+        )
+}
+
+pub fn item_names() -> impl Iterator<Item = &'static str> {
+    Item::ALL.into_iter().map(Item::name)
+}
+
+/// A word of a command line, with its quotes and escapes taken out.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Token {
+    pub text: String,
+    /// The byte in the line where the word starts.
+    pub start: usize,
+    /// The quote the word starts with, if it does.
+    pub quote: Option<char>,
+}
+
+/// Why a line couldn't be split into words.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SyntaxError {
+    UnclosedQuote(char),
+    /// A backslash at the very end of the line, with nothing to escape.
+    TrailingEscape,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxError::UnclosedQuote(quote) => write!(f, "Missing closing {}", quote),
+            SyntaxError::TrailingEscape => write!(f, "Nothing to escape after the last \\"),
+        }
+    }
+}
+
+/// Where the scanner is in the line.
+#[derive(Clone, Copy, PartialEq)]
+enum ScanState {
+    Between,
+    Word,
+    Quoted(char),
+    /// Just after a backslash, inside the quote if there is one.
+    Escaped(Option<char>),
+}
+
+/// Splits a line into words at whitespace. Whitespace inside single or double quotes, or after a
+/// backslash, is part of the word. A backslash escapes any character, except inside single quotes
+/// where everything is taken as is.
+pub fn tokenize(line: &str) -> Result<Vec<Token>, SyntaxError> {
+    let (tokens, state) = scan(line);
+
+    match state {
+        ScanState::Quoted(quote) => Err(SyntaxError::UnclosedQuote(quote)),
+        ScanState::Escaped(_) => Err(SyntaxError::TrailingEscape),
+        ScanState::Between | ScanState::Word => Ok(tokens),
+    }
+}
+
+/// Splits a line that's still being typed into words, like [`tokenize`] but leaving quotes open.
+/// Also returns whether the last word is still being typed, rather than finished by whitespace.
+pub fn tokenize_partial(line: &str) -> (Vec<Token>, bool) {
+    let (tokens, state) = scan(line);
+
+    (tokens, state != ScanState::Between)
+}
+
+fn scan(line: &str) -> (Vec<Token>, ScanState) {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut state = ScanState::Between;
+
+    for (index, c) in line.char_indices() {
+        if state == ScanState::Between {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            tokens.push(Token {
+                text: String::new(),
+                start: index,
+                quote: Some(c).filter(|c| matches!(c, '"' | '\'')),
+            });
+        }
+
+        // A word is always started above before anything is added to it
+        let text = &mut tokens.last_mut().unwrap().text;
+
+        state = match state {
+            ScanState::Escaped(quote) => {
+                text.push(c);
+                quote.map_or(ScanState::Word, ScanState::Quoted)
+            }
+            ScanState::Quoted(quote) if c == quote => ScanState::Word,
+            ScanState::Quoted('"') if c == '\\' => ScanState::Escaped(Some('"')),
+            ScanState::Quoted(quote) => {
+                text.push(c);
+                ScanState::Quoted(quote)
+            }
+            ScanState::Between | ScanState::Word => match c {
+                c if c.is_whitespace() => ScanState::Between,
+                '"' | '\'' => ScanState::Quoted(c),
+                '\\' => ScanState::Escaped(None),
+                c => {
+                    text.push(c);
+                    ScanState::Word
+                }
+            },
+        };
+    }
+
+    (tokens, state)
+}
+
+/// How a word has to be written to come back out of [`tokenize`] as is, starting with the quote if
+/// one is given. Words that need quoting but weren't given one are put in double quotes.
+pub fn quote_word(word: &str, quote: Option<char>) -> String {
+    let needs_quoting = word.is_empty()
+        || word.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\'));
+
+    match quote.or(needs_quoting.then_some('"')) {
+        None => word.to_string(),
+        Some('\'') if !word.contains('\'') => format!("'{}'", word),
+        Some(_) => format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\"")),
+    }
+}
+
+/// One coordinate of a position. Relative coordinates are written with a `~` in front, and are
+/// relative to wherever the command is run from; a lone `~` is the same as `~0`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Coordinate {
+    Absolute(f32),
+    Relative(f32),
+}
+
+impl Coordinate {
+    pub fn parse(token: &str) -> Option<Self> {
+        let number = |token: &str| token.parse().ok().filter(|value: &f32| value.is_finite());
+
+        match token.strip_prefix('~') {
+            Some("") => Some(Coordinate::Relative(0.0)),
+            Some(offset) => number(offset).map(Coordinate::Relative),
+            None => number(token).map(Coordinate::Absolute),
+        }
+    }
+
+    pub fn resolve(self, origin: f32) -> f32 {
+        match self {
+            Coordinate::Absolute(value) => value,
+            Coordinate::Relative(offset) => origin + offset,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Position {
+    pub x: Coordinate,
+    pub y: Coordinate,
+    pub z: Coordinate,
+}
+
+impl Position {
+    /// `~ ~ ~`, wherever the command is run from.
+    pub const HERE: Self = Self {
+        x: Coordinate::Relative(0.0),
+        y: Coordinate::Relative(0.0),
+        z: Coordinate::Relative(0.0),
+    };
+
+    pub fn resolve(self, origin: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.resolve(origin.x),
+            self.y.resolve(origin.y),
+            self.z.resolve(origin.z),
+        )
+    }
+
+    /// The block the position is in. Relative coordinates count from the block the origin is in.
+    pub fn resolve_block(self, origin: Vec3) -> IVec3 {
+        self.resolve(origin.floor()).floor().as_ivec3()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ArgumentError {
+    Missing(&'static str),
+    /// The first argument that wasn't expected.
+    TooMany(String),
+    InvalidNumber {
+        name: &'static str,
+        found: String,
+    },
+    OutOfRange {
+        name: &'static str,
+        range: RangeInclusive<i64>,
+    },
+    InvalidCoordinate(String),
+    InvalidChoice {
+        name: &'static str,
+        found: String,
+        choices: &'static [&'static str],
+    },
+    UnknownBlock(String),
+    UnknownItem(String),
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgumentError::Missing(name) => write!(f, "Missing {}", name),
+            ArgumentError::TooMany(found) => write!(f, "Unexpected argument '{}'", found),
+            ArgumentError::InvalidNumber { name, found } => {
+                write!(f, "{} should be a whole number, not '{}'", name, found)
+            }
+            ArgumentError::OutOfRange { name, range } => write!(
+                f,
+                "{} should be from {} to {}",
+                name,
+                range.start(),
+                range.end()
+            ),
+            ArgumentError::InvalidCoordinate(found) => write!(f, "Invalid coordinate '{}'", found),
+            ArgumentError::InvalidChoice {
+                found,
+                choices: [choice],
+                ..
+            } => write!(f, "Expected '{}', not '{}'", choice, found),
+            ArgumentError::InvalidChoice {
+                name,
+                found,
+                choices,
+            } => write!(
+                f,
+                "{} should be one of {}, not '{}'",
+                name,
+                choices.join(", "),
+                found
+            ),
+            ArgumentError::UnknownBlock(name) => write!(f, "Unknown block '{}'", name),
+            ArgumentError::UnknownItem(name) => write!(f, "Unknown item '{}'", name),
+        }
+    }
+}
+
+/// The arguments following a command's name, taken one at a time.
+pub struct Arguments<'a> {
+    tokens: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Arguments<'a> {
+    pub fn new(tokens: Vec<&'a str>) -> Self {
+        Self { tokens, next: 0 }
+    }
+
+    /// Whether every argument has been taken.
+    pub fn is_empty(&self) -> bool {
+        self.next >= self.tokens.len()
+    }
+
+    /// Fails if there are arguments left over, once the command has taken all it needs.
+    pub fn finish(&self) -> Result<(), ArgumentError> {
+        match self.tokens.get(self.next) {
+            Some(token) => Err(ArgumentError::TooMany(token.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// The next argument, without taking it.
+    pub fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next).copied()
+    }
+
+    pub fn word(&mut self, name: &'static str) -> Result<&'a str, ArgumentError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .ok_or(ArgumentError::Missing(name))?;
+        self.next += 1;
+
+        Ok(token)
+    }
+
+    /// A word that has to be one of a fixed set.
+    pub fn choice(
+        &mut self,
+        name: &'static str,
+        choices: &'static [&'static str],
+    ) -> Result<&'static str, ArgumentError> {
+        let token = self.word(name)?;
+
+        choices
+            .iter()
+            .find(|choice| **choice == token)
+            .copied()
+            .ok_or_else(|| ArgumentError::InvalidChoice {
+                name,
+                found: token.to_string(),
+                choices,
+            })
+    }
+
+    pub fn integer(
+        &mut self,
+        name: &'static str,
+        range: RangeInclusive<i64>,
+    ) -> Result<i64, ArgumentError> {
+        let token = self.word(name)?;
+        let value: i64 = token.parse().map_err(|_| ArgumentError::InvalidNumber {
+            name,
+            found: token.to_string(),
+        })?;
+
+        if range.contains(&value) {
+            Ok(value)
+        } else {
+            Err(ArgumentError::OutOfRange { name, range })
+        }
+    }
+
+    pub fn coordinate(&mut self, name: &'static str) -> Result<Coordinate, ArgumentError> {
+        let token = self.word(name)?;
+
+        Coordinate::parse(token).ok_or_else(|| ArgumentError::InvalidCoordinate(token.to_string()))
+    }
+
+    /// Three coordinates in a row.
+    pub fn position(&mut self, name: &'static str) -> Result<Position, ArgumentError> {
+        Ok(Position {
+            x: self.coordinate(name)?,
+            y: self.coordinate(name)?,
+            z: self.coordinate(name)?,
+        })
+    }
+
+    pub fn block(&mut self, name: &'static str) -> Result<Block, ArgumentError> {
+        let token = self.word(name)?;
+
+        NON_ITEM_BLOCKS
+            .into_iter()
+            .find(|(block_name, _)| *block_name == token)
+            .map(|(_, block)| block)
+            .or_else(|| Item::from_name(token).and_then(Item::as_block))
+            .ok_or_else(|| ArgumentError::UnknownBlock(token.to_string()))
+    }
+
+    pub fn item(&mut self, name: &'static str) -> Result<Item, ArgumentError> {
+        let token = self.word(name)?;
+
+        Item::from_name(token).ok_or_else(|| ArgumentError::UnknownItem(token.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        tokenize(line)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.text)
+            .collect()
+    }
+
+    #[test]
+    fn words_are_split_at_any_whitespace() {
+        assert_eq!(words("  give\tstone   4 "), ["give", "stone", "4"]);
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn quotes_keep_whitespace_in_a_word() {
+        assert_eq!(
+            words(r#"schematic save "my house" 'big  tower'"#),
+            ["schematic", "save", "my house", "big  tower"]
+        );
+        assert_eq!(words(r#"a"b c"d"#), ["ab cd"]);
+        assert_eq!(words(r#""" x"#), ["", "x"]);
+    }
+
+    #[test]
+    fn backslashes_escape_the_next_character() {
+        assert_eq!(words(r"my\ house"), ["my house"]);
+        assert_eq!(words(r#""say \"hi\" \\ bye""#), [r#"say "hi" \ bye"#]);
+        assert_eq!(words(r#"\'"#), ["'"]);
+    }
+
+    #[test]
+    fn single_quotes_take_backslashes_as_is() {
+        assert_eq!(words(r"'C:\schematics'"), [r"C:\schematics"]);
+    }
+
+    #[test]
+    fn tokens_know_where_they_start_and_how_they_are_quoted() {
+        let tokens = tokenize(r#"load  "my house""#).unwrap();
+
+        assert_eq!(tokens[0].start, 0);
+        assert_eq!(tokens[0].quote, None);
+        assert_eq!(tokens[1].start, 6);
+        assert_eq!(tokens[1].quote, Some('"'));
+    }
+
+    #[test]
+    fn unfinished_quotes_and_escapes_are_errors() {
+        assert_eq!(
+            tokenize(r#"save "my house"#),
+            Err(SyntaxError::UnclosedQuote('"'))
+        );
+        assert_eq!(tokenize("save 'x"), Err(SyntaxError::UnclosedQuote('\'')));
+        assert_eq!(tokenize(r"save x\"), Err(SyntaxError::TrailingEscape));
+    }
+
+    #[test]
+    fn partial_lines_say_whether_a_word_is_being_typed() {
+        let (tokens, typing) = tokenize_partial(r#"load "my ho"#);
+        assert!(typing);
+        assert_eq!(tokens[1].text, "my ho");
+
+        let (tokens, typing) = tokenize_partial("give stone ");
+        assert!(!typing);
+        assert_eq!(tokens.len(), 2);
+    }
+
+    #[test]
+    fn quoted_words_come_back_out_as_is() {
+        for word in [
+            "stone",
+            "my house",
+            r#"say "hi""#,
+            r"back\slash",
+            "it's",
+            "",
+        ] {
+            for quote in [None, Some('"'), Some('\'')] {
+                assert_eq!(words(&quote_word(word, quote)), [word]);
+            }
+        }
+
+        assert_eq!(quote_word("stone", None), "stone");
+        assert_eq!(quote_word("my house", None), r#""my house""#);
+        assert_eq!(quote_word("my house", Some('\'')), "'my house'");
+    }
+
+    #[test]
+    fn coordinates_are_absolute_or_relative() {
+        assert_eq!(Coordinate::parse("12.5"), Some(Coordinate::Absolute(12.5)));
+        assert_eq!(Coordinate::parse("~"), Some(Coordinate::Relative(0.0)));
+        assert_eq!(Coordinate::parse("~-3"), Some(Coordinate::Relative(-3.0)));
+        assert_eq!(Coordinate::parse("~x"), None);
+        assert_eq!(Coordinate::parse("inf"), None);
+    }
+}
@@ -0,0 +1,872 @@
+//! The commands there are, what arguments they take, and parsing and tab completing lines of text
+//! into them.
+
+use crate::command::parser::{
+    block_names, item_names, quote_word, tokenize, tokenize_partial, ArgumentError, Arguments,
+    Position, SyntaxError,
+};
+use crate::inventory::item::{Item, DEFAULT_MAX_STACK_SIZE};
+use crate::inventory::PLAYER_INVENTORY_SIZE;
+use crate::sky::TICKS_PER_DAY;
+use crate::worldedit::{schematic, Corner};
+use crate::worldgen::block::{Axis, Block};
+use crate::worldgen::chunk::MAX_VIEW_DISTANCE;
+use bevy::prelude::*;
+use std::fmt;
+
+/// Times of day that can be set by name instead of in ticks.
+const NAMED_TIMES: [(&str, u32); 4] = [
+    ("day", 1000),
+    ("noon", 6000),
+    ("night", 13000),
+    ("midnight", 18000),
+];
+
+/// Enough to fill the player's whole inventory.
+const MAX_GIVE_COUNT: i64 = PLAYER_INVENTORY_SIZE as i64 * DEFAULT_MAX_STACK_SIZE as i64;
+
+/// A parsed command, ready to be run.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    /// Lists every command, or explains one.
+    Help(Option<String>),
+    Teleport(Position),
+    SetBlock {
+        pos: Position,
+        block: Block,
+    },
+    /// Sets every block in the box between the two corners, including both corners.
+    Fill {
+        from: Position,
+        to: Position,
+        block: Block,
+    },
+    Seed,
+    /// Shows the view distance, or sets it.
+    ViewDistance(Option<i32>),
+    Give {
+        item: Item,
+        count: u32,
+    },
+    /// Crafts a recipe by hand out of the player's inventory, or lists what can be crafted.
+    Craft {
+        recipe: Option<String>,
+        count: u32,
+    },
+    /// Moves a stack onto another slot of the player's inventory, merging or swapping them.
+    MoveStack {
+        from: usize,
+        to: usize,
+    },
+    /// Moves half of a stack in the player's inventory into an empty slot.
+    SplitStack(usize),
+    /// Sets the time of day, in ticks since sunrise.
+    SetTime(u32),
+    /// Generates the chunk containing the position again, undoing every change made to it.
+    RegenChunk(Position),
+    /// Sets a corner of the selection, to the targeted block if no position is given.
+    SetCorner(Corner, Option<Position>),
+    /// Sets every block in the selection.
+    Set(Block),
+    Replace {
+        from: Block,
+        to: Block,
+    },
+    /// Sets the faces of the selection to the block, and empties the inside.
+    Hollow(Block),
+    Copy,
+    /// Pastes the clipboard with its minimum corner at the position, or at the player.
+    Paste(Option<Position>),
+    /// Turns the clipboard clockwise by some quarter turns.
+    Rotate(u8),
+    Mirror(Axis),
+    Undo,
+    Redo,
+    /// Saves the selection to a file in the schematics folder.
+    SaveSchematic(String),
+    /// Loads a file from the schematics folder into the clipboard.
+    LoadSchematic(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArgKind {
+    Coordinate,
+    Block,
+    Item,
+    Integer,
+    /// A time of day, either by name or in ticks.
+    Time,
+    /// The name of another command.
+    Command,
+    /// The name of a file in the schematics folder.
+    Schematic,
+    /// The name of a recipe that can be crafted by hand.
+    Recipe,
+    /// One of a fixed set of words.
+    Choice(&'static [&'static str]),
+}
+
+/// Describes an argument, for usage messages and tab completion. Parsing it is up to the command.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+impl Arg {
+    pub const fn required(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub const fn optional(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+
+    /// A word that has to be written as is, like the `set` in `/time set`.
+    pub const fn literal(choices: &'static [&'static str]) -> Self {
+        Self::required(choices[0], ArgKind::Choice(choices))
+    }
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub args: Vec<Arg>,
+    /// Parses the arguments following the name. Arguments it leaves over are an error.
+    pub parse: fn(&mut Arguments) -> Result<Command, ArgumentError>,
+}
+
+impl CommandSpec {
+    /// How the command is written, like `/give <item> [count]`.
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+
+        for arg in self.args.iter() {
+            let written = match arg.kind {
+                ArgKind::Choice([choice]) => choice.to_string(),
+                _ if arg.optional => format!("[{}]", arg.name),
+                _ => format!("<{}>", arg.name),
+            };
+
+            usage.push(' ');
+            usage.push_str(&written);
+        }
+
+        usage
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CommandError {
+    Empty,
+    Syntax(SyntaxError),
+    Unknown(String),
+    InvalidArguments { usage: String, error: ArgumentError },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "No command given"),
+            CommandError::Syntax(error) => write!(f, "{}", error),
+            CommandError::Unknown(name) => {
+                write!(f, "Unknown command '{}', see /help for a list", name)
+            }
+            CommandError::InvalidArguments { usage, error } => {
+                write!(f, "{}. Usage: {}", error, usage)
+            }
+        }
+    }
+}
+
+/// The ways the word being typed could be completed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Completion {
+    /// The byte in the line where the word being completed starts.
+    pub start: usize,
+    /// Every word that starts with what's been typed so far, sorted, and quoted the way the word
+    /// being completed is, or however they need to be.
+    pub candidates: Vec<String>,
+}
+
+impl Completion {
+    /// Completes the line as far as the candidates agree. A single candidate is completed fully,
+    /// followed by a space for the next argument.
+    pub fn apply(&self, line: &str) -> String {
+        let Some(first) = self.candidates.first() else {
+            return line.to_string();
+        };
+
+        if self.candidates.len() == 1 {
+            return format!("{}{} ", &line[..self.start], first);
+        }
+
+        let mut common = first.as_str();
+
+        for candidate in self.candidates.iter().skip(1) {
+            let shared = common
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(common.len().min(candidate.len()), |((index, _), _)| index);
+            common = &common[..shared];
+        }
+
+        // Never throw away what's already been typed
+        if common.len() <= line.len() - self.start {
+            return line.to_string();
+        }
+
+        format!("{}{}", &line[..self.start], common)
+    }
+}
+
+/// Every command that can be run, by name.
+#[derive(Resource)]
+pub struct CommandRegistry {
+    commands: Vec<CommandSpec>,
+    /// Recipes aren't known until they're loaded, so they're filled in afterwards.
+    recipe_names: Vec<String>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            commands: Vec::new(),
+            recipe_names: Vec::new(),
+        };
+
+        for spec in built_in_commands() {
+            registry.register(spec);
+        }
+
+        registry
+    }
+}
+
+impl CommandRegistry {
+    /// Adds a command, replacing any with the same name.
+    pub fn register(&mut self, spec: CommandSpec) {
+        self.commands.retain(|existing| existing.name != spec.name);
+        self.commands.push(spec);
+        self.commands.sort_by_key(|spec| spec.name);
+    }
+
+    /// Sets the recipes that are suggested when completing a recipe argument.
+    pub fn set_recipe_names(&mut self, names: impl IntoIterator<Item = String>) {
+        self.recipe_names = names.into_iter().collect();
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands.iter().find(|spec| spec.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CommandSpec> {
+        self.commands.iter()
+    }
+
+    /// Parses a line like `/tp ~ ~10 ~`. The leading slash is optional, and arguments can be quoted
+    /// or escaped to have spaces in them.
+    pub fn parse(&self, line: &str) -> Result<Command, CommandError> {
+        let tokens = tokenize(line).map_err(CommandError::Syntax)?;
+        let mut tokens = tokens.iter().map(|token| token.text.as_str());
+        let name = tokens.next().ok_or(CommandError::Empty)?;
+        let name = name.strip_prefix('/').unwrap_or(name);
+
+        if name.is_empty() {
+            return Err(CommandError::Empty);
+        }
+
+        let spec = self
+            .get(name)
+            .ok_or_else(|| CommandError::Unknown(name.to_string()))?;
+
+        let mut args = Arguments::new(tokens.collect());
+
+        (spec.parse)(&mut args)
+            .and_then(|command| args.finish().map(|_| command))
+            .map_err(|error| CommandError::InvalidArguments {
+                usage: spec.usage(),
+                error,
+            })
+    }
+
+    /// The ways the last word of the line could be completed: a command name for the first word,
+    /// and whatever kind of argument comes next after that.
+    pub fn complete(&self, line: &str) -> Completion {
+        let (mut tokens, typing) = tokenize_partial(line);
+        let typing = if typing { tokens.pop() } else { None };
+
+        let start = typing.as_ref().map_or(line.len(), |token| token.start);
+        let typed = typing.as_ref().map_or("", |token| token.text.as_str());
+        let quote = typing.as_ref().and_then(|token| token.quote);
+        let previous: Vec<&str> = tokens.iter().map(|token| token.text.as_str()).collect();
+
+        let mut candidates: Vec<String> = match previous.first() {
+            None => {
+                let slash = if typed.starts_with('/') { "/" } else { "" };
+
+                self.commands
+                    .iter()
+                    .map(|spec| format!("{}{}", slash, spec.name))
+                    .collect()
+            }
+            Some(name) => {
+                let name = name.strip_prefix('/').unwrap_or(name);
+
+                self.get(name)
+                    .and_then(|spec| spec.args.get(previous.len() - 1))
+                    .map_or_else(Vec::new, |arg| self.arg_candidates(arg.kind))
+            }
+        };
+
+        candidates.retain(|candidate| candidate.starts_with(typed));
+        candidates.sort();
+        candidates.dedup();
+
+        let candidates = candidates
+            .iter()
+            .map(|candidate| quote_word(candidate, quote))
+            .collect();
+
+        Completion { start, candidates }
+    }
+
+    fn arg_candidates(&self, kind: ArgKind) -> Vec<String> {
+        match kind {
+            ArgKind::Coordinate => vec!["~".to_string()],
+            ArgKind::Block => block_names().map(str::to_string).collect(),
+            ArgKind::Item => item_names().map(str::to_string).collect(),
+            ArgKind::Integer => Vec::new(),
+            ArgKind::Time => NAMED_TIMES
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            ArgKind::Command => self
+                .commands
+                .iter()
+                .map(|spec| spec.name.to_string())
+                .collect(),
+            ArgKind::Schematic => schematic::saved_schematics(),
+            ArgKind::Recipe => self.recipe_names.clone(),
+            ArgKind::Choice(choices) => choices.iter().map(|choice| choice.to_string()).collect(),
+        }
+    }
+}
+
+fn built_in_commands() -> Vec<CommandSpec> {
+    let position =
+        |names: [&'static str; 3]| names.map(|name| Arg::required(name, ArgKind::Coordinate));
+    let optional_position = ["x", "y", "z"].map(|name| Arg::optional(name, ArgKind::Coordinate));
+
+    vec![
+        CommandSpec {
+            name: "help",
+            description: "Lists every command, or shows how to use one",
+            args: vec![Arg::optional("command", ArgKind::Command)],
+            parse: |args| {
+                let name = (!args.is_empty())
+                    .then(|| args.word("command"))
+                    .transpose()?;
+
+                Ok(Command::Help(name.map(str::to_string)))
+            },
+        },
+        CommandSpec {
+            name: "tp",
+            description: "Teleports the player",
+            args: position(["x", "y", "z"]).to_vec(),
+            parse: |args| Ok(Command::Teleport(args.position("position")?)),
+        },
+        CommandSpec {
+            name: "setblock",
+            description: "Sets one block",
+            args: [
+                position(["x", "y", "z"]).as_slice(),
+                &[Arg::required("block", ArgKind::Block)],
+            ]
+            .concat(),
+            parse: |args| {
+                Ok(Command::SetBlock {
+                    pos: args.position("position")?,
+                    block: args.block("block")?,
+                })
+            },
+        },
+        CommandSpec {
+            name: "fill",
+            description: "Sets every block in a box",
+            args: [
+                position(["x1", "y1", "z1"]).as_slice(),
+                &position(["x2", "y2", "z2"]),
+                &[Arg::required("block", ArgKind::Block)],
+            ]
+            .concat(),
+            parse: |args| {
+                Ok(Command::Fill {
+                    from: args.position("first corner")?,
+                    to: args.position("second corner")?,
+                    block: args.block("block")?,
+                })
+            },
+        },
+        CommandSpec {
+            name: "seed",
+            description: "Shows the seed the world is generated with",
+            args: Vec::new(),
+            parse: |_| Ok(Command::Seed),
+        },
+        CommandSpec {
+            name: "viewdistance",
+            description: "Shows or sets how many chunks around the player are loaded",
+            args: vec![Arg::optional("chunks", ArgKind::Integer)],
+            parse: |args| {
+                let distance = (!args.is_empty())
+                    .then(|| args.integer("chunks", 1..=MAX_VIEW_DISTANCE as i64))
+                    .transpose()?;
+
+                Ok(Command::ViewDistance(
+                    distance.map(|distance| distance as i32),
+                ))
+            },
+        },
+        CommandSpec {
+            name: "give",
+            description: "Puts items in the player's inventory",
+            args: vec![
+                Arg::required("item", ArgKind::Item),
+                Arg::optional("count", ArgKind::Integer),
+            ],
+            parse: |args| {
+                let item = args.item("item")?;
+                let count = if args.is_empty() {
+                    1
+                } else {
+                    args.integer("count", 1..=MAX_GIVE_COUNT)? as u32
+                };
+
+                Ok(Command::Give { item, count })
+            },
+        },
+        CommandSpec {
+            name: "craft",
+            description: "Crafts a recipe out of the inventory, or lists what can be crafted",
+            args: vec![
+                Arg::optional("recipe", ArgKind::Recipe),
+                Arg::optional("count", ArgKind::Integer),
+            ],
+            parse: |args| {
+                let recipe = (!args.is_empty())
+                    .then(|| args.word("recipe").map(str::to_string))
+                    .transpose()?;
+                let count = if args.is_empty() {
+                    1
+                } else {
+                    args.integer("count", 1..=MAX_GIVE_COUNT)? as u32
+                };
+
+                Ok(Command::Craft { recipe, count })
+            },
+        },
+        CommandSpec {
+            name: "inventory",
+            description: "Moves or splits stacks in the inventory, by slot number starting at 1",
+            args: vec![
+                Arg::required("action", ArgKind::Choice(&["move", "split"])),
+                Arg::required("slot", ArgKind::Integer),
+                Arg::optional("to", ArgKind::Integer),
+            ],
+            parse: |args| {
+                let action = args.choice("action", &["move", "split"])?;
+                let slot = |args: &mut Arguments, name| {
+                    args.integer(name, 1..=PLAYER_INVENTORY_SIZE as i64)
+                        .map(|slot| slot as usize - 1)
+                };
+
+                Ok(match action {
+                    "move" => Command::MoveStack {
+                        from: slot(args, "slot")?,
+                        to: slot(args, "to")?,
+                    },
+                    _ => Command::SplitStack(slot(args, "slot")?),
+                })
+            },
+        },
+        CommandSpec {
+            name: "time",
+            description: "Sets the time of day",
+            args: vec![Arg::literal(&["set"]), Arg::required("time", ArgKind::Time)],
+            parse: |args| {
+                args.choice("set", &["set"])?;
+
+                let named = NAMED_TIMES
+                    .iter()
+                    .find(|(name, _)| args.peek() == Some(*name));
+
+                let ticks = match named {
+                    Some((_, ticks)) => {
+                        args.word("time")?;
+                        *ticks
+                    }
+                    None => args.integer("time", 0..=TICKS_PER_DAY as i64 - 1)? as u32,
+                };
+
+                Ok(Command::SetTime(ticks))
+            },
+        },
+        CommandSpec {
+            name: "regen",
+            description: "Generates a chunk again, undoing every change to it",
+            args: [&[Arg::literal(&["chunk"])], optional_position.as_slice()].concat(),
+            parse: |args| {
+                args.choice("chunk", &["chunk"])?;
+
+                Ok(Command::RegenChunk(
+                    maybe_position(args)?.unwrap_or(Position::HERE),
+                ))
+            },
+        },
+        CommandSpec {
+            name: "pos1",
+            description: "Sets the first corner of the selection",
+            args: optional_position.to_vec(),
+            parse: |args| Ok(Command::SetCorner(Corner::First, maybe_position(args)?)),
+        },
+        CommandSpec {
+            name: "pos2",
+            description: "Sets the second corner of the selection",
+            args: optional_position.to_vec(),
+            parse: |args| Ok(Command::SetCorner(Corner::Second, maybe_position(args)?)),
+        },
+        CommandSpec {
+            name: "set",
+            description: "Sets every block in the selection",
+            args: vec![Arg::required("block", ArgKind::Block)],
+            parse: |args| Ok(Command::Set(args.block("block")?)),
+        },
+        CommandSpec {
+            name: "replace",
+            description: "Replaces one block with another in the selection",
+            args: vec![
+                Arg::required("from", ArgKind::Block),
+                Arg::required("to", ArgKind::Block),
+            ],
+            parse: |args| {
+                Ok(Command::Replace {
+                    from: args.block("from")?,
+                    to: args.block("to")?,
+                })
+            },
+        },
+        CommandSpec {
+            name: "hollow",
+            description: "Makes the selection a hollow box of a block",
+            args: vec![Arg::required("block", ArgKind::Block)],
+            parse: |args| Ok(Command::Hollow(args.block("block")?)),
+        },
+        CommandSpec {
+            name: "copy",
+            description: "Copies the selection to the clipboard",
+            args: Vec::new(),
+            parse: |_| Ok(Command::Copy),
+        },
+        CommandSpec {
+            name: "paste",
+            description: "Pastes the clipboard, with its lowest corner at the player",
+            args: optional_position.to_vec(),
+            parse: |args| Ok(Command::Paste(maybe_position(args)?)),
+        },
+        CommandSpec {
+            name: "rotate",
+            description: "Turns the clipboard clockwise by some quarter turns",
+            args: vec![Arg::optional("turns", ArgKind::Integer)],
+            parse: |args| {
+                let turns = if args.is_empty() {
+                    1
+                } else {
+                    args.integer("turns", 1..=3)? as u8
+                };
+
+                Ok(Command::Rotate(turns))
+            },
+        },
+        CommandSpec {
+            name: "mirror",
+            description: "Mirrors the clipboard across an axis",
+            args: vec![Arg::required("axis", ArgKind::Choice(&["x", "y", "z"]))],
+            parse: |args| {
+                let axis = match args.choice("axis", &["x", "y", "z"])? {
+                    "x" => Axis::X,
+                    "y" => Axis::Y,
+                    _ => Axis::Z,
+                };
+
+                Ok(Command::Mirror(axis))
+            },
+        },
+        CommandSpec {
+            name: "undo",
+            description: "Undoes the last edit",
+            args: Vec::new(),
+            parse: |_| Ok(Command::Undo),
+        },
+        CommandSpec {
+            name: "redo",
+            description: "Makes the last undone edit again",
+            args: Vec::new(),
+            parse: |_| Ok(Command::Redo),
+        },
+        CommandSpec {
+            name: "schematic",
+            description: "Saves the selection to a file, or loads one into the clipboard",
+            args: vec![
+                Arg::required("action", ArgKind::Choice(&["save", "load"])),
+                Arg::required("file", ArgKind::Schematic),
+            ],
+            parse: |args| {
+                let action = args.choice("action", &["save", "load"])?;
+                let file = args.word("file")?.to_string();
+
+                Ok(match action {
+                    "save" => Command::SaveSchematic(file),
+                    _ => Command::LoadSchematic(file),
+                })
+            },
+        },
+    ]
+}
+
+/// A position that can be left out, as long as nothing follows it.
+fn maybe_position(args: &mut Arguments) -> Result<Option<Position>, ArgumentError> {
+    (!args.is_empty())
+        .then(|| args.position("position"))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::parser::Coordinate;
+
+    fn invalid_arguments(line: &str) -> ArgumentError {
+        match CommandRegistry::default().parse(line) {
+            Err(CommandError::InvalidArguments { error, .. }) => error,
+            other => panic!(
+                "expected invalid arguments from '{}', got {:?}",
+                line, other
+            ),
+        }
+    }
+
+    fn completion(registry: &CommandRegistry, line: &str) -> (String, Vec<String>) {
+        let completion = registry.complete(line);
+
+        (completion.apply(line), completion.candidates)
+    }
+
+    #[test]
+    fn commands_parse_with_or_without_a_slash() {
+        let registry = CommandRegistry::default();
+        let give = Command::Give {
+            item: Item::Block(Block::Stone),
+            count: 5,
+        };
+
+        assert_eq!(registry.parse("/give stone 5"), Ok(give.clone()));
+        assert_eq!(registry.parse("give stone 5"), Ok(give));
+        assert_eq!(
+            registry.parse("tp ~ ~10 3"),
+            Ok(Command::Teleport(Position {
+                x: Coordinate::Relative(0.0),
+                y: Coordinate::Relative(10.0),
+                z: Coordinate::Absolute(3.0),
+            }))
+        );
+    }
+
+    #[test]
+    fn quoted_and_escaped_arguments_keep_their_spaces() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(
+            registry.parse(r#"schematic save "my house""#),
+            Ok(Command::SaveSchematic("my house".to_string()))
+        );
+        assert_eq!(
+            registry.parse(r"schematic load my\ house"),
+            Ok(Command::LoadSchematic("my house".to_string()))
+        );
+        assert_eq!(
+            registry.parse("'give' \"stone\" '2'"),
+            Ok(Command::Give {
+                item: Item::Block(Block::Stone),
+                count: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn unfinished_quotes_are_syntax_errors() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(
+            registry.parse(r#"schematic save "my house"#),
+            Err(CommandError::Syntax(SyntaxError::UnclosedQuote('"')))
+        );
+        assert_eq!(
+            registry.parse(r"schematic save house\"),
+            Err(CommandError::Syntax(SyntaxError::TrailingEscape))
+        );
+    }
+
+    #[test]
+    fn empty_and_unknown_commands_are_rejected() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(registry.parse(""), Err(CommandError::Empty));
+        assert_eq!(registry.parse("  / "), Err(CommandError::Empty));
+        assert_eq!(
+            registry.parse("/explode 5"),
+            Err(CommandError::Unknown("explode".to_string()))
+        );
+        assert_eq!(
+            registry.parse("\"give stone\""),
+            Err(CommandError::Unknown("give stone".to_string()))
+        );
+    }
+
+    #[test]
+    fn arguments_of_the_wrong_type_are_rejected() {
+        assert_eq!(
+            invalid_arguments("give stone lots"),
+            ArgumentError::InvalidNumber {
+                name: "count",
+                found: "lots".to_string(),
+            }
+        );
+        assert_eq!(
+            invalid_arguments("give stone 0"),
+            ArgumentError::OutOfRange {
+                name: "count",
+                range: 1..=MAX_GIVE_COUNT,
+            }
+        );
+        assert_eq!(
+            invalid_arguments("give diamond"),
+            ArgumentError::UnknownItem("diamond".to_string())
+        );
+        assert_eq!(
+            invalid_arguments("setblock 0 0 0 lava"),
+            ArgumentError::UnknownBlock("lava".to_string())
+        );
+        assert_eq!(
+            invalid_arguments("tp 0 up 0"),
+            ArgumentError::InvalidCoordinate("up".to_string())
+        );
+        assert_eq!(
+            invalid_arguments("mirror w"),
+            ArgumentError::InvalidChoice {
+                name: "axis",
+                found: "w".to_string(),
+                choices: &["x", "y", "z"],
+            }
+        );
+    }
+
+    #[test]
+    fn missing_and_extra_arguments_are_rejected() {
+        assert_eq!(invalid_arguments("give"), ArgumentError::Missing("item"));
+        assert_eq!(
+            invalid_arguments("tp 0 64"),
+            ArgumentError::Missing("position")
+        );
+        assert_eq!(
+            invalid_arguments("seed please"),
+            ArgumentError::TooMany("please".to_string())
+        );
+    }
+
+    #[test]
+    fn errors_show_how_the_command_is_used() {
+        let error = CommandRegistry::default().parse("give").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Missing item. Usage: /give <item> [count]"
+        );
+    }
+
+    #[test]
+    fn the_first_word_completes_to_a_command() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(
+            completion(&registry, "/gi"),
+            ("/give ".to_string(), vec!["/give".to_string()])
+        );
+        assert_eq!(completion(&registry, "re").1, ["redo", "regen", "replace"]);
+        assert_eq!(completion(&registry, "re").0, "re");
+        assert_eq!(completion(&registry, "").1.len(), registry.iter().count());
+    }
+
+    #[test]
+    fn arguments_complete_by_their_kind() {
+        let registry = CommandRegistry::default();
+
+        assert_eq!(completion(&registry, "give furn").0, "give furnace ");
+        assert_eq!(
+            completion(&registry, "time set mid").0,
+            "time set midnight "
+        );
+        assert_eq!(completion(&registry, "mirror ").1, ["x", "y", "z"]);
+        assert_eq!(completion(&registry, "help und").0, "help undo ");
+        assert!(completion(&registry, "explode ").1.is_empty());
+        assert!(completion(&registry, "seed ").1.is_empty());
+    }
+
+    #[test]
+    fn candidates_agree_as_far_as_they_can() {
+        let registry = CommandRegistry::default();
+
+        let (line, candidates) = completion(&registry, "setblock ~ ~ ~ stone_s");
+
+        assert_eq!(candidates, ["stone_slab", "stone_stairs"]);
+        assert_eq!(line, "setblock ~ ~ ~ stone_s");
+
+        let (line, _) = completion(&registry, "setblock ~ ~ ~ ston");
+        assert_eq!(line, "setblock ~ ~ ~ stone");
+    }
+
+    #[test]
+    fn completions_are_quoted_when_they_need_to_be() {
+        let mut registry = CommandRegistry::default();
+        registry.set_recipe_names(["iron plate".to_string(), "iron gear".to_string()]);
+
+        assert_eq!(
+            completion(&registry, "craft iron\\ p").0,
+            "craft \"iron plate\" "
+        );
+        assert_eq!(
+            completion(&registry, "craft 'iron p").0,
+            "craft 'iron plate' "
+        );
+        assert_eq!(
+            completion(&registry, "craft \"iron").1,
+            ["\"iron gear\"", "\"iron plate\""]
+        );
+        assert_eq!(completion(&registry, "craft \"iron").0, "craft \"iron ");
+        assert_eq!(
+            completion(&registry, "craft \"iron plate\" ").0,
+            "craft \"iron plate\" "
+        );
+    }
+}
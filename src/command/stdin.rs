@@ -0,0 +1,48 @@
+use crate::command::{CommandOutput, RunCommand};
+use bevy::prelude::*;
+use crossbeam::channel::{unbounded, Receiver};
+use std::io::{self, BufRead};
+use std::thread;
+
+/// Lines typed into the terminal, read on a separate thread since reading blocks.
+#[derive(Resource)]
+pub struct StdinLines(Receiver<String>);
+
+impl StdinLines {
+    pub fn spawn_reader() -> Self {
+        let (sender, receiver) = unbounded();
+
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                // The app has exited
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self(receiver)
+    }
+}
+
+pub fn read_stdin_commands(stdin: Res<StdinLines>, mut requests: EventWriter<RunCommand>) {
+    for line in stdin.0.try_iter() {
+        if !line.trim().is_empty() {
+            requests.send(RunCommand(line));
+        }
+    }
+}
+
+pub fn log_command_output(mut outputs: EventReader<CommandOutput>) {
+    for output in outputs.iter() {
+        if output.is_error {
+            warn!("{}", output.message);
+        } else {
+            info!("{}", output.message);
+        }
+    }
+}
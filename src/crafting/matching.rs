@@ -0,0 +1,397 @@
+//! Checking which recipes can be crafted, either from what's laid out in a crafting grid or from
+//! what's anywhere in an inventory.
+
+use crate::crafting::{Recipe, RecipeKind, RecipeRegistry, CRAFTING_GRID_SIZE};
+use crate::inventory::item::{Item, ItemStack};
+use crate::inventory::Inventory;
+use crate::machine::ProcessingMachine;
+
+/// Cuts off the empty rows and columns around the items in a grid, which is stored row by row.
+/// Returns the width, height and contents of what's left, or `None` if the grid is empty.
+pub fn trim(grid: &[Option<Item>], width: usize) -> Option<(usize, usize, Vec<Option<Item>>)> {
+    if width == 0 {
+        return None;
+    }
+
+    let filled = || {
+        grid.iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_some())
+            .map(|(index, _)| (index % width, index / width))
+    };
+
+    let min_x = filled().map(|(x, _)| x).min()?;
+    let max_x = filled().map(|(x, _)| x).max()?;
+    let min_y = filled().map(|(_, y)| y).min()?;
+    let max_y = filled().map(|(_, y)| y).max()?;
+
+    let trimmed = (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| grid[y * width + x]))
+        .collect();
+
+    Some((max_x - min_x + 1, max_y - min_y + 1, trimmed))
+}
+
+/// Adds up how many of each item there are, in the order they first appear.
+fn count_items(items: impl IntoIterator<Item = Item>) -> Vec<ItemStack> {
+    let mut stacks: Vec<ItemStack> = Vec::new();
+
+    for item in items {
+        match stacks.iter_mut().find(|stack| stack.item == item) {
+            Some(stack) => stack.count += 1,
+            None => stacks.push(ItemStack::new(item, 1)),
+        }
+    }
+
+    stacks
+}
+
+impl Recipe {
+    /// Whether the items laid out in a crafting grid, stored row by row, make this recipe.
+    /// Processing recipes can't be crafted in a grid.
+    pub fn matches_grid(&self, grid: &[Option<Item>], grid_width: usize) -> bool {
+        match &self.kind {
+            RecipeKind::Shaped {
+                width,
+                height,
+                pattern,
+            } => {
+                let Some((grid_width, grid_height, grid)) = trim(grid, grid_width) else {
+                    return false;
+                };
+
+                if grid_width != *width || grid_height != *height {
+                    return false;
+                }
+
+                let mirrored = (0..*height)
+                    .flat_map(|y| (0..*width).rev().map(move |x| pattern[y * width + x]));
+
+                grid == *pattern || grid.iter().copied().eq(mirrored)
+            }
+            RecipeKind::Shapeless { ingredients } => {
+                let mut needed = count_items(ingredients.iter().copied());
+                let mut laid_out = count_items(grid.iter().flatten().copied());
+
+                // The order items first appear in doesn't matter
+                needed.sort_by_key(|stack| stack.item.name());
+                laid_out.sort_by_key(|stack| stack.item.name());
+
+                needed == laid_out
+            }
+            RecipeKind::Processing { .. } => false,
+        }
+    }
+
+    /// The ingredients laid out in the top left corner of the crafting grid, row by row, in a way
+    /// that makes the recipe. Processing recipes can't be laid out.
+    pub fn grid_layout(&self) -> Option<Vec<Option<Item>>> {
+        let mut grid = vec![None; CRAFTING_GRID_SIZE * CRAFTING_GRID_SIZE];
+
+        match &self.kind {
+            RecipeKind::Shaped {
+                width,
+                height,
+                pattern,
+            } => {
+                for y in 0..*height {
+                    for x in 0..*width {
+                        grid[y * CRAFTING_GRID_SIZE + x] = pattern[y * width + x];
+                    }
+                }
+            }
+            RecipeKind::Shapeless { ingredients } => {
+                for (slot, ingredient) in grid.iter_mut().zip(ingredients) {
+                    *slot = Some(*ingredient);
+                }
+            }
+            RecipeKind::Processing { .. } => return None,
+        }
+
+        Some(grid)
+    }
+
+    /// Everything used up by crafting or processing the recipe once.
+    pub fn ingredients(&self) -> Vec<ItemStack> {
+        match &self.kind {
+            RecipeKind::Shaped { pattern, .. } => count_items(pattern.iter().flatten().copied()),
+            RecipeKind::Shapeless { ingredients } => count_items(ingredients.iter().copied()),
+            RecipeKind::Processing { input, .. } => vec![*input],
+        }
+    }
+
+    /// The ingredients, and how many of each, that the inventory doesn't have enough of to make
+    /// the recipe once.
+    pub fn missing_ingredients(&self, inventory: &Inventory) -> Vec<ItemStack> {
+        self.ingredients()
+            .into_iter()
+            .filter_map(|needed| {
+                let missing = needed.count.saturating_sub(inventory.count(needed.item));
+                (missing > 0).then_some(ItemStack::new(needed.item, missing))
+            })
+            .collect()
+    }
+}
+
+impl RecipeRegistry {
+    pub fn recipes(&self) -> &[Recipe] {
+        &self.recipes
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.name == name)
+    }
+
+    /// The recipe the machine makes out of the input stack, if there's enough of it.
+    pub fn processing_recipe(
+        &self,
+        machine: ProcessingMachine,
+        input: ItemStack,
+    ) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| match recipe.kind {
+            RecipeKind::Processing {
+                machine: recipe_machine,
+                input: recipe_input,
+                ..
+            } => {
+                recipe_machine == machine
+                    && recipe_input.item == input.item
+                    && recipe_input.count <= input.count
+            }
+            _ => false,
+        })
+    }
+
+    /// The recipe made by the items laid out in a crafting grid, if there is one.
+    pub fn find_for_grid(&self, grid: &[Option<Item>], grid_width: usize) -> Option<&Recipe> {
+        self.recipes
+            .iter()
+            .find(|recipe| recipe.matches_grid(grid, grid_width))
+    }
+
+    /// Every recipe that can be crafted by hand with what's in the inventory, ignoring how the
+    /// items would have to be laid out.
+    pub fn craftable<'a, 'b: 'a>(
+        &'b self,
+        inventory: &'a Inventory,
+    ) -> impl Iterator<Item = &'b Recipe> + 'a {
+        self.recipes.iter().filter(move |recipe| {
+            !matches!(recipe.kind, RecipeKind::Processing { .. })
+                && recipe.missing_ingredients(inventory).is_empty()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::Material;
+    use crate::worldgen::block::Block;
+
+    const STONE: Item = Item::Block(Block::Stone);
+    const DIRT: Item = Item::Block(Block::Dirt);
+    const COAL: Item = Item::Material(Material::Coal);
+    const IRON: Item = Item::Material(Material::IronIngot);
+
+    /// An L shape of stone with dirt in the corner, which looks different mirrored.
+    ///
+    /// ```text
+    /// D .
+    /// S .
+    /// S S
+    /// ```
+    fn shaped() -> Recipe {
+        Recipe {
+            name: "shaped".to_string(),
+            kind: RecipeKind::Shaped {
+                width: 2,
+                height: 3,
+                pattern: vec![
+                    Some(DIRT),
+                    None,
+                    Some(STONE),
+                    None,
+                    Some(STONE),
+                    Some(STONE),
+                ],
+            },
+            output: ItemStack::new(COAL, 1),
+        }
+    }
+
+    fn shapeless() -> Recipe {
+        Recipe {
+            name: "shapeless".to_string(),
+            kind: RecipeKind::Shapeless {
+                ingredients: vec![IRON, IRON, COAL],
+            },
+            output: ItemStack::new(STONE, 4),
+        }
+    }
+
+    fn processing() -> Recipe {
+        Recipe {
+            name: "processing".to_string(),
+            kind: RecipeKind::Processing {
+                machine: ProcessingMachine::Furnace,
+                input: ItemStack::new(COAL, 1),
+                duration: 1.0,
+            },
+            output: ItemStack::new(IRON, 1),
+        }
+    }
+
+    fn registry() -> RecipeRegistry {
+        RecipeRegistry {
+            recipes: vec![shaped(), shapeless(), processing()],
+        }
+    }
+
+    fn inventory(stacks: &[(Item, u32)]) -> Inventory {
+        let mut inventory = Inventory::new(9);
+        for &(item, count) in stacks {
+            inventory.insert(ItemStack::new(item, count));
+        }
+        inventory
+    }
+
+    /// Lays out rows of a 3x3 crafting grid, where `.` is empty, `S` stone, `D` dirt, `C` coal and
+    /// `I` iron.
+    fn grid(rows: [&str; 3]) -> Vec<Option<Item>> {
+        rows.iter()
+            .flat_map(|row| row.chars())
+            .map(|slot| match slot {
+                'S' => Some(STONE),
+                'D' => Some(DIRT),
+                'C' => Some(COAL),
+                'I' => Some(IRON),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn trim_cuts_off_empty_rows_and_columns() {
+        let (width, height, trimmed) = trim(&grid(["...", ".DS", "..."]), 3).unwrap();
+
+        assert_eq!((width, height), (2, 1));
+        assert_eq!(trimmed, vec![Some(DIRT), Some(STONE)]);
+        assert_eq!(trim(&grid(["...", "...", "..."]), 3), None);
+    }
+
+    #[test]
+    fn shaped_recipe_matches_anywhere_in_the_grid() {
+        assert!(shaped().matches_grid(&grid(["D..", "S..", "SS."]), 3));
+        assert!(shaped().matches_grid(&grid([".D.", ".S.", ".SS"]), 3));
+    }
+
+    #[test]
+    fn shaped_recipe_matches_mirrored() {
+        assert!(shaped().matches_grid(&grid([".D.", ".S.", "SS."]), 3));
+        assert!(shaped().matches_grid(&grid(["..D", "..S", ".SS"]), 3));
+    }
+
+    #[test]
+    fn shaped_recipe_does_not_match_upside_down() {
+        assert!(!shaped().matches_grid(&grid(["SS.", "S..", "D.."]), 3));
+    }
+
+    #[test]
+    fn shaped_recipe_does_not_match_extra_items() {
+        assert!(!shaped().matches_grid(&grid(["D.C", "S..", "SS."]), 3));
+        assert!(!shaped().matches_grid(&grid(["D..", "S..", "S.."]), 3));
+    }
+
+    #[test]
+    fn shapeless_recipe_matches_in_any_order() {
+        assert!(shapeless().matches_grid(&grid(["I..", "...", "C.I"]), 3));
+        assert!(shapeless().matches_grid(&grid(["...", "CII", "..."]), 3));
+        assert!(!shapeless().matches_grid(&grid(["...", "CI.", "..."]), 3));
+        assert!(!shapeless().matches_grid(&grid(["...", "CII", "I.."]), 3));
+    }
+
+    #[test]
+    fn processing_recipe_never_matches_a_grid() {
+        assert!(!processing().matches_grid(&grid(["C..", "...", "..."]), 3));
+    }
+
+    #[test]
+    fn find_for_grid_finds_the_matching_recipe() {
+        let recipes = registry();
+
+        let found = recipes.find_for_grid(&grid(["..D", "..S", ".SS"]), 3);
+        assert_eq!(found.map(|recipe| recipe.name.as_str()), Some("shaped"));
+
+        let found = recipes.find_for_grid(&grid(["C..", "I..", "I.."]), 3);
+        assert_eq!(found.map(|recipe| recipe.name.as_str()), Some("shapeless"));
+
+        assert!(recipes
+            .find_for_grid(&grid(["C..", "...", "..."]), 3)
+            .is_none());
+    }
+
+    #[test]
+    fn recipes_match_their_own_grid_layout() {
+        for recipe in [shaped(), shapeless()] {
+            let layout = recipe.grid_layout().unwrap();
+
+            assert_eq!(layout.len(), CRAFTING_GRID_SIZE * CRAFTING_GRID_SIZE);
+            assert!(recipe.matches_grid(&layout, CRAFTING_GRID_SIZE));
+        }
+
+        assert_eq!(shaped().grid_layout(), Some(grid(["D..", "S..", "SS."])));
+        assert_eq!(processing().grid_layout(), None);
+    }
+
+    #[test]
+    fn ingredients_are_counted_by_item() {
+        assert_eq!(
+            shaped().ingredients(),
+            vec![ItemStack::new(DIRT, 1), ItemStack::new(STONE, 3)]
+        );
+        assert_eq!(
+            shapeless().ingredients(),
+            vec![ItemStack::new(IRON, 2), ItemStack::new(COAL, 1)]
+        );
+    }
+
+    #[test]
+    fn missing_ingredients_reports_how_many_more_are_needed() {
+        let recipe = shaped();
+
+        assert_eq!(
+            recipe.missing_ingredients(&inventory(&[(STONE, 1)])),
+            vec![ItemStack::new(DIRT, 1), ItemStack::new(STONE, 2)]
+        );
+        assert_eq!(
+            recipe.missing_ingredients(&inventory(&[(STONE, 5), (COAL, 1)])),
+            vec![ItemStack::new(DIRT, 1)]
+        );
+        assert!(recipe
+            .missing_ingredients(&inventory(&[(STONE, 3), (DIRT, 1)]))
+            .is_empty());
+    }
+
+    #[test]
+    fn craftable_lists_hand_recipes_with_enough_ingredients() {
+        let recipes = registry();
+        let names = |inventory: &Inventory| -> Vec<String> {
+            recipes
+                .craftable(inventory)
+                .map(|recipe| recipe.name.clone())
+                .collect()
+        };
+
+        assert!(names(&inventory(&[])).is_empty());
+        // Coal is enough for the processing recipe, but that's not crafted by hand
+        assert!(names(&inventory(&[(COAL, 5)])).is_empty());
+        assert_eq!(
+            names(&inventory(&[(IRON, 2), (COAL, 1)])),
+            vec!["shapeless"]
+        );
+        assert_eq!(
+            names(&inventory(&[(IRON, 2), (COAL, 1), (STONE, 3), (DIRT, 1)])),
+            vec!["shaped", "shapeless"]
+        );
+    }
+}
@@ -0,0 +1,54 @@
+pub mod matching;
+pub mod recipe_file;
+
+use crate::inventory::item::{Item, ItemStack};
+use crate::machine::ProcessingMachine;
+use bevy::prelude::*;
+
+/// Where the recipes are loaded from, relative to the working directory.
+pub const RECIPES_PATH: &str = "assets/recipes.ron";
+/// The width and height of the crafting grid. Shaped recipes can't be any bigger than this.
+pub const CRAFTING_GRID_SIZE: usize = 3;
+
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RecipeRegistry::load_or_empty(RECIPES_PATH));
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Recipe {
+    /// Unique among all recipes.
+    pub name: String,
+    pub kind: RecipeKind,
+    pub output: ItemStack,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RecipeKind {
+    /// The ingredients have to be laid out in the crafting grid like this, though the pattern can
+    /// be anywhere in the grid, and mirrored left to right. The pattern is stored row by row, and
+    /// trimmed so that its first and last rows and columns aren't empty.
+    Shaped {
+        width: usize,
+        height: usize,
+        pattern: Vec<Option<Item>>,
+    },
+    /// The ingredients can be anywhere in the crafting grid.
+    Shapeless { ingredients: Vec<Item> },
+    /// Turns the input into the output over time, in a machine.
+    Processing {
+        machine: ProcessingMachine,
+        input: ItemStack,
+        /// In seconds.
+        duration: f32,
+    },
+}
+
+/// Every recipe, loaded from the recipe file at startup.
+#[derive(Resource, Default, Debug)]
+pub struct RecipeRegistry {
+    recipes: Vec<Recipe>,
+}
@@ -0,0 +1,468 @@
+//! The format of the recipe file, and the checks a recipe has to pass to be loaded. Items are
+//! referred to by name in the file, and looked up in `Item::ALL`.
+
+use crate::crafting::matching::trim;
+use crate::crafting::{Recipe, RecipeKind, RecipeRegistry, CRAFTING_GRID_SIZE};
+use crate::inventory::item::{Item, ItemStack};
+use crate::machine::ProcessingMachine;
+use bevy::log::warn;
+use bevy::utils::HashSet;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Marks an empty slot in the pattern of a shaped recipe.
+const EMPTY_KEY: char = ' ';
+
+#[derive(Deserialize)]
+enum RawRecipe {
+    Shaped {
+        name: String,
+        /// One string per row; every character is a key, or a space for an empty slot.
+        pattern: Vec<String>,
+        key: BTreeMap<char, String>,
+        output: RawStack,
+    },
+    Shapeless {
+        name: String,
+        ingredients: Vec<String>,
+        output: RawStack,
+    },
+    Processing {
+        name: String,
+        machine: ProcessingMachine,
+        input: RawStack,
+        duration: f32,
+        output: RawStack,
+    },
+}
+
+#[derive(Deserialize)]
+struct RawStack {
+    item: String,
+    #[serde(default = "one")]
+    count: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Debug)]
+pub enum RecipeError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    DuplicateName(String),
+    UnknownItem {
+        recipe: String,
+        item: String,
+    },
+    UndefinedKey {
+        recipe: String,
+        key: char,
+    },
+    /// The pattern of a shaped recipe is empty, too big, or has rows of different lengths.
+    InvalidPattern {
+        recipe: String,
+    },
+    /// A shapeless recipe has no ingredients, or more than fit in the crafting grid.
+    InvalidIngredients {
+        recipe: String,
+    },
+    /// A stack of zero items, or a processing recipe that doesn't take any time.
+    InvalidAmount {
+        recipe: String,
+    },
+    /// The grid a recipe is crafted from makes another recipe defined before it instead, so the
+    /// recipe could never be crafted in the grid.
+    Conflict {
+        recipe: String,
+        other: String,
+    },
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecipeError::Io(err) => write!(f, "failed to read recipes: {}", err),
+            RecipeError::Parse(err) => write!(f, "failed to parse recipes: {}", err),
+            RecipeError::DuplicateName(name) => write!(f, "recipe {} is defined twice", name),
+            RecipeError::UnknownItem { recipe, item } => {
+                write!(f, "recipe {} uses unknown item {}", recipe, item)
+            }
+            RecipeError::UndefinedKey { recipe, key } => {
+                write!(
+                    f,
+                    "recipe {} uses key '{}' without defining it",
+                    recipe, key
+                )
+            }
+            RecipeError::InvalidPattern { recipe } => write!(
+                f,
+                "recipe {} needs a pattern of equally long rows, at most {} by {}",
+                recipe, CRAFTING_GRID_SIZE, CRAFTING_GRID_SIZE
+            ),
+            RecipeError::InvalidIngredients { recipe } => write!(
+                f,
+                "recipe {} needs between 1 and {} ingredients",
+                recipe,
+                CRAFTING_GRID_SIZE * CRAFTING_GRID_SIZE
+            ),
+            RecipeError::InvalidAmount { recipe } => write!(
+                f,
+                "recipe {} needs positive item counts and duration",
+                recipe
+            ),
+            RecipeError::Conflict { recipe, other } => write!(
+                f,
+                "recipe {} is laid out the same as recipe {}, so it can't be crafted",
+                recipe, other
+            ),
+        }
+    }
+}
+
+impl RecipeRegistry {
+    /// Loads the recipes from the file. If it can't be loaded, or any recipe in it is invalid,
+    /// there won't be any recipes at all, so that mistakes in the file are noticed.
+    pub fn load_or_empty(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        match Self::load(path) {
+            Ok(registry) => registry,
+            Err(err) => {
+                warn!("No recipes loaded from {}: {}", path.display(), err);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RecipeError> {
+        let contents = fs::read_to_string(path).map_err(RecipeError::Io)?;
+
+        Self::from_ron(&contents)
+    }
+
+    pub fn from_ron(contents: &str) -> Result<Self, RecipeError> {
+        let raw_recipes: Vec<RawRecipe> = ron::from_str(contents).map_err(RecipeError::Parse)?;
+
+        let mut names = HashSet::new();
+        let mut recipes = Vec::with_capacity(raw_recipes.len());
+
+        for raw_recipe in raw_recipes {
+            let recipe = raw_recipe.resolve()?;
+
+            if !names.insert(recipe.name.clone()) {
+                return Err(RecipeError::DuplicateName(recipe.name));
+            }
+
+            recipes.push(recipe);
+        }
+
+        let registry = Self { recipes };
+
+        // Only the first recipe that matches a grid is ever made from it
+        for recipe in registry.recipes() {
+            let Some(layout) = recipe.grid_layout() else {
+                continue;
+            };
+
+            match registry.find_for_grid(&layout, CRAFTING_GRID_SIZE) {
+                Some(other) if other.name != recipe.name => {
+                    return Err(RecipeError::Conflict {
+                        recipe: recipe.name.clone(),
+                        other: other.name.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(registry)
+    }
+}
+
+impl RawRecipe {
+    fn resolve(self) -> Result<Recipe, RecipeError> {
+        match self {
+            RawRecipe::Shaped {
+                name,
+                pattern,
+                key,
+                output,
+            } => {
+                let (width, height, pattern) = resolve_pattern(&name, &pattern, &key)?;
+
+                Ok(Recipe {
+                    output: output.resolve(&name)?,
+                    kind: RecipeKind::Shaped {
+                        width,
+                        height,
+                        pattern,
+                    },
+                    name,
+                })
+            }
+            RawRecipe::Shapeless {
+                name,
+                ingredients,
+                output,
+            } => {
+                if ingredients.is_empty()
+                    || ingredients.len() > CRAFTING_GRID_SIZE * CRAFTING_GRID_SIZE
+                {
+                    return Err(RecipeError::InvalidIngredients { recipe: name });
+                }
+
+                let ingredients = ingredients
+                    .iter()
+                    .map(|item| resolve_item(&name, item))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Recipe {
+                    output: output.resolve(&name)?,
+                    kind: RecipeKind::Shapeless { ingredients },
+                    name,
+                })
+            }
+            RawRecipe::Processing {
+                name,
+                machine,
+                input,
+                duration,
+                output,
+            } => {
+                if duration.is_nan() || duration <= 0.0 {
+                    return Err(RecipeError::InvalidAmount { recipe: name });
+                }
+
+                Ok(Recipe {
+                    output: output.resolve(&name)?,
+                    kind: RecipeKind::Processing {
+                        machine,
+                        input: input.resolve(&name)?,
+                        duration,
+                    },
+                    name,
+                })
+            }
+        }
+    }
+}
+
+impl RawStack {
+    fn resolve(&self, recipe: &str) -> Result<ItemStack, RecipeError> {
+        if self.count == 0 {
+            return Err(RecipeError::InvalidAmount {
+                recipe: recipe.to_string(),
+            });
+        }
+
+        Ok(ItemStack::new(
+            resolve_item(recipe, &self.item)?,
+            self.count,
+        ))
+    }
+}
+
+fn resolve_item(recipe: &str, item: &str) -> Result<Item, RecipeError> {
+    Item::from_name(item).ok_or_else(|| RecipeError::UnknownItem {
+        recipe: recipe.to_string(),
+        item: item.to_string(),
+    })
+}
+
+/// Looks up the items in the pattern, and trims off empty rows and columns around it. Returns the
+/// width and height of the trimmed pattern along with it.
+fn resolve_pattern(
+    recipe: &str,
+    rows: &[String],
+    key: &BTreeMap<char, String>,
+) -> Result<(usize, usize, Vec<Option<Item>>), RecipeError> {
+    let invalid = || RecipeError::InvalidPattern {
+        recipe: recipe.to_string(),
+    };
+
+    let width = rows.first().map_or(0, |row| row.chars().count());
+
+    if rows.len() > CRAFTING_GRID_SIZE
+        || width > CRAFTING_GRID_SIZE
+        || rows.iter().any(|row| row.chars().count() != width)
+    {
+        return Err(invalid());
+    }
+
+    let mut grid = Vec::with_capacity(width * rows.len());
+
+    for symbol in rows.iter().flat_map(|row| row.chars()) {
+        if symbol == EMPTY_KEY {
+            grid.push(None);
+            continue;
+        }
+
+        let item = key.get(&symbol).ok_or_else(|| RecipeError::UndefinedKey {
+            recipe: recipe.to_string(),
+            key: symbol,
+        })?;
+
+        grid.push(Some(resolve_item(recipe, item)?));
+    }
+
+    trim(&grid, width).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crafting::RECIPES_PATH;
+    use crate::inventory::item::Material;
+    use crate::worldgen::block::Block;
+
+    fn error(contents: &str) -> RecipeError {
+        RecipeRegistry::from_ron(contents).unwrap_err()
+    }
+
+    #[test]
+    fn the_recipe_file_loads() {
+        let recipes = RecipeRegistry::load(RECIPES_PATH).unwrap();
+
+        let packed_stone = recipes.get("packed_stone").unwrap();
+        assert_eq!(
+            packed_stone.output,
+            ItemStack::new(Item::Block(Block::Stone), 1)
+        );
+        assert!(matches!(
+            packed_stone.kind,
+            RecipeKind::Shaped {
+                width: 2,
+                height: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            recipes.get("baked_dirt").unwrap().kind,
+            RecipeKind::Processing { .. }
+        ));
+    }
+
+    #[test]
+    fn patterns_are_trimmed_and_looked_up() {
+        let recipes = RecipeRegistry::from_ron(
+            r#"[
+                Shaped(
+                    name: "bar",
+                    pattern: ["   ", " c ", " c "],
+                    key: {'c': "coal"},
+                    output: (item: "iron_ingot", count: 2),
+                ),
+            ]"#,
+        )
+        .unwrap();
+
+        let coal = Some(Item::Material(Material::Coal));
+        assert_eq!(
+            recipes.get("bar").unwrap().kind,
+            RecipeKind::Shaped {
+                width: 1,
+                height: 2,
+                pattern: vec![coal, coal],
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_items_are_rejected() {
+        let err = error(
+            r#"[Shapeless(name: "a", ingredients: ["dirt", "unobtainium"], output: (item: "stone"))]"#,
+        );
+
+        assert!(matches!(
+            err,
+            RecipeError::UnknownItem { recipe, item } if recipe == "a" && item == "unobtainium"
+        ));
+    }
+
+    #[test]
+    fn undefined_keys_are_rejected() {
+        let err = error(
+            r#"[Shaped(name: "a", pattern: ["dx"], key: {'d': "dirt"}, output: (item: "stone"))]"#,
+        );
+
+        assert!(matches!(
+            err,
+            RecipeError::UndefinedKey { recipe, key: 'x' } if recipe == "a"
+        ));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        for pattern in [
+            r#"["dddd"]"#,
+            r#"["d", "d", "d", "d"]"#,
+            r#"["dd", "d"]"#,
+            r#"["   "]"#,
+            "[]",
+        ] {
+            let err = error(&format!(
+                r#"[Shaped(name: "a", pattern: {}, key: {{'d': "dirt"}}, output: (item: "stone"))]"#,
+                pattern
+            ));
+
+            assert!(
+                matches!(err, RecipeError::InvalidPattern { ref recipe } if recipe == "a"),
+                "{} gave {:?}",
+                pattern,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let err = error(
+            r#"[
+                Shapeless(name: "a", ingredients: ["dirt"], output: (item: "stone")),
+                Shapeless(name: "a", ingredients: ["grass"], output: (item: "stone")),
+            ]"#,
+        );
+
+        assert!(matches!(err, RecipeError::DuplicateName(name) if name == "a"));
+    }
+
+    #[test]
+    fn invalid_amounts_are_rejected() {
+        for recipe in [
+            r#"Shapeless(name: "a", ingredients: ["dirt"], output: (item: "stone", count: 0))"#,
+            r#"Processing(name: "a", machine: Furnace, input: (item: "dirt"), duration: 0.0, output: (item: "stone"))"#,
+            r#"Processing(name: "a", machine: Furnace, input: (item: "dirt", count: 0), duration: 1.0, output: (item: "stone"))"#,
+        ] {
+            let err = error(&format!("[{}]", recipe));
+
+            assert!(
+                matches!(err, RecipeError::InvalidAmount { ref recipe } if recipe == "a"),
+                "{} gave {:?}",
+                recipe,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn recipes_made_from_the_same_grid_are_rejected() {
+        // Mirrored, these are the same shape
+        let err = error(
+            r#"[
+                Shaped(name: "a", pattern: ["dg"], key: {'d': "dirt", 'g': "grass"}, output: (item: "stone")),
+                Shaped(name: "b", pattern: ["gd"], key: {'d': "dirt", 'g': "grass"}, output: (item: "dirt")),
+            ]"#,
+        );
+
+        assert!(matches!(
+            err,
+            RecipeError::Conflict { recipe, other } if recipe == "b" && other == "a"
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+//! Shows how the world is split into chunks, and how far along each chunk near the player is:
+//! waiting to be generated, generated but empty, loaded, or generated but not loaded. The terrain
+//! can also be drawn as a wireframe, to see how it's meshed.
+
+use crate::camera::PlayerCamera;
+use crate::input::{Action, ActionState};
+use crate::worldgen::chunk::{
+    chunk_pos_containing, is_in_view_distance, loader_chunk_positions, ChunkLoader, ChunkMap,
+    ChunkedTerrain, GeneratedChunks, LoadedChunks, ViewDistance, CHUNK_SIZE,
+};
+use bevy::pbr::wireframe::Wireframe;
+use bevy::prelude::*;
+
+/// How many chunks around the player's chunk, in every direction, get a box showing their state.
+const CHUNK_STATE_RADIUS: i32 = 2;
+/// How much smaller the boxes are than the chunks, so the boxes of neighbouring chunks don't
+/// overlap.
+const CHUNK_STATE_INSET: f32 = 0.25;
+const PLAYER_CHUNK_COLOR: Color = Color::WHITE;
+
+/// Which debug views of the chunks are turned on.
+#[derive(Resource, Default)]
+pub struct ChunkDebugSettings {
+    pub borders: bool,
+    pub wireframe: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChunkState {
+    /// Close enough to a loader to be generated, but it hasn't been yet.
+    Queued,
+    /// Generated, but there's nothing in it to mesh.
+    Empty,
+    Loaded,
+    /// Generated, but its terrain isn't spawned.
+    Unloaded,
+}
+
+impl ChunkState {
+    /// The state of the chunk at the chunk position, or `None` if it hasn't been generated and
+    /// won't be, since it's outside the view distance of every chunk loader.
+    pub fn of(
+        chunk_pos: IVec3,
+        map: &ChunkMap,
+        loaded_chunks: &LoadedChunks,
+        loader_chunk_positions: &[IVec3],
+        view_distance: ViewDistance,
+    ) -> Option<Self> {
+        let key = (chunk_pos.x, chunk_pos.y, chunk_pos.z);
+
+        let state = match map.get(&key) {
+            None if is_in_view_distance(chunk_pos, loader_chunk_positions, view_distance) => {
+                ChunkState::Queued
+            }
+            None => return None,
+            Some(chunk) if chunk.is_empty() => ChunkState::Empty,
+            Some(_) if loaded_chunks.chunks.contains(&key) => ChunkState::Loaded,
+            Some(_) => ChunkState::Unloaded,
+        };
+
+        Some(state)
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            ChunkState::Queued => Color::YELLOW,
+            ChunkState::Empty => Color::GRAY,
+            ChunkState::Loaded => Color::GREEN,
+            ChunkState::Unloaded => Color::RED,
+        }
+    }
+}
+
+pub fn toggle_chunk_debug(
+    action_state: Res<ActionState>,
+    mut settings: ResMut<ChunkDebugSettings>,
+) {
+    if action_state.just_pressed(Action::ToggleChunkBorders) {
+        settings.borders = !settings.borders;
+    }
+    if action_state.just_pressed(Action::ToggleWireframe) {
+        settings.wireframe = !settings.wireframe;
+    }
+}
+
+/// Outlines the player's chunk, and draws a box in the colour of its state inside every chunk
+/// around it that's generated or going to be.
+pub fn draw_chunk_states(
+    mut gizmos: Gizmos,
+    settings: Res<ChunkDebugSettings>,
+    generated_chunks: Res<GeneratedChunks>,
+    loaded_chunks: Res<LoadedChunks>,
+    view_distance: Res<ViewDistance>,
+    player_query: Query<&Transform, With<PlayerCamera>>,
+    loader_query: Query<&Transform, With<ChunkLoader>>,
+) {
+    if !settings.borders {
+        return;
+    }
+
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+
+    let chunk_size = CHUNK_SIZE as f32;
+    let player_chunk = chunk_pos_containing(transform.translation);
+    let loader_chunk_positions = loader_chunk_positions(&loader_query);
+    let map = generated_chunks.map.lock().unwrap();
+
+    let chunk_box = |chunk_pos: IVec3, inset: f32| {
+        Transform::from_translation((chunk_pos.as_vec3() + 0.5) * chunk_size)
+            .with_scale(Vec3::splat(chunk_size - inset * 2.0))
+    };
+
+    gizmos.cuboid(chunk_box(player_chunk, 0.0), PLAYER_CHUNK_COLOR);
+
+    for x in -CHUNK_STATE_RADIUS..=CHUNK_STATE_RADIUS {
+        for y in -CHUNK_STATE_RADIUS..=CHUNK_STATE_RADIUS {
+            for z in -CHUNK_STATE_RADIUS..=CHUNK_STATE_RADIUS {
+                let chunk_pos = player_chunk + IVec3::new(x, y, z);
+
+                let Some(state) = ChunkState::of(
+                    chunk_pos,
+                    &map,
+                    &loaded_chunks,
+                    &loader_chunk_positions,
+                    *view_distance,
+                ) else {
+                    continue;
+                };
+
+                gizmos.cuboid(chunk_box(chunk_pos, CHUNK_STATE_INSET), state.color());
+            }
+        }
+    }
+}
+
+/// Draws the terrain as a wireframe while it's turned on, including terrain spawned since.
+pub fn update_chunk_wireframes(
+    mut commands: Commands,
+    settings: Res<ChunkDebugSettings>,
+    terrain_query: Query<Entity, With<ChunkedTerrain>>,
+    new_terrain_query: Query<Entity, Added<ChunkedTerrain>>,
+) {
+    if settings.is_changed() {
+        for entity in terrain_query.iter() {
+            if settings.wireframe {
+                commands.entity(entity).insert(Wireframe);
+            } else {
+                commands.entity(entity).remove::<Wireframe>();
+            }
+        }
+    } else if settings.wireframe {
+        for entity in new_terrain_query.iter() {
+            commands.entity(entity).insert(Wireframe);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::block::Block;
+    use crate::worldgen::chunk::Chunk;
+    use bevy::utils::HashSet;
+
+    #[test]
+    fn missing_chunks_are_only_queued_in_the_view_distance() {
+        let mut map = ChunkMap::new();
+        map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+        let mut filled = Chunk::empty(IVec3::new(16, 0, 0));
+        filled.set(UVec3::ZERO, Block::Stone);
+        map.insert((1, 0, 0), filled);
+        let mut filled = Chunk::empty(IVec3::new(32, 0, 0));
+        filled.set(UVec3::ZERO, Block::Stone);
+        map.insert((2, 0, 0), filled);
+
+        let loaded_chunks = LoadedChunks {
+            chunks: HashSet::from([(1, 0, 0)]),
+        };
+        let loaders = [IVec3::ZERO];
+        let view_distance = ViewDistance(IVec3::splat(2));
+        let state =
+            |chunk_pos| ChunkState::of(chunk_pos, &map, &loaded_chunks, &loaders, view_distance);
+
+        assert_eq!(state(IVec3::ZERO), Some(ChunkState::Empty));
+        assert_eq!(state(IVec3::X), Some(ChunkState::Loaded));
+        assert_eq!(state(IVec3::new(2, 0, 0)), Some(ChunkState::Unloaded));
+        assert_eq!(state(IVec3::new(0, -2, 0)), Some(ChunkState::Queued));
+        assert_eq!(state(IVec3::new(0, -3, 0)), None);
+    }
+}
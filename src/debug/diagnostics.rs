@@ -0,0 +1,125 @@
+use crate::camera::{PlayerCamera, PlayerView};
+use crate::worldgen::chunk::{ChunkQueue, ChunkedTerrain, GeneratedChunks, LoadedChunks};
+use crate::worldgen::gen::{NoiseSample, WorldSeed};
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+
+pub const GENERATED_CHUNKS: DiagnosticId =
+    DiagnosticId::from_u128(238861395909470748546465970910336286666);
+pub const LOADED_CHUNKS: DiagnosticId =
+    DiagnosticId::from_u128(218639521081858468899913141386300842578);
+pub const QUEUED_CHUNKS: DiagnosticId =
+    DiagnosticId::from_u128(182311315979943547662279588587073357257);
+pub const CHUNKS_GENERATED_PER_SECOND: DiagnosticId =
+    DiagnosticId::from_u128(294415544039838211228488774329409455450);
+pub const CHUNKS_MESHED_PER_SECOND: DiagnosticId =
+    DiagnosticId::from_u128(120105878491254359723017128962776643022);
+pub const PLAYER_X: DiagnosticId = DiagnosticId::from_u128(104270601065271063538509870912258777633);
+pub const PLAYER_Y: DiagnosticId = DiagnosticId::from_u128(248392788929295136529305680922619419511);
+pub const PLAYER_Z: DiagnosticId = DiagnosticId::from_u128(225720745508692452184750397328695485471);
+/// Which way the player looks, in degrees clockwise from north.
+pub const PLAYER_HEADING: DiagnosticId =
+    DiagnosticId::from_u128(270822326374230888509763722069095703286);
+pub const SURFACE_HEIGHT: DiagnosticId =
+    DiagnosticId::from_u128(273066529370614565809396997809521143250);
+pub const COAL_NOISE: DiagnosticId =
+    DiagnosticId::from_u128(259857623498278796135593004868104514495);
+pub const IRON_NOISE: DiagnosticId =
+    DiagnosticId::from_u128(43022453019869230505293057241638378579);
+pub const GRASS_NOISE: DiagnosticId =
+    DiagnosticId::from_u128(198337092750553361318846735410826178401);
+
+/// How many measurements are kept to average over.
+const HISTORY_LENGTH: usize = 20;
+
+const DIAGNOSTICS: [(DiagnosticId, &str, &str); 13] = [
+    (GENERATED_CHUNKS, "generated_chunks", ""),
+    (LOADED_CHUNKS, "loaded_chunks", ""),
+    (QUEUED_CHUNKS, "queued_chunks", ""),
+    (CHUNKS_GENERATED_PER_SECOND, "chunks_generated", "/s"),
+    (CHUNKS_MESHED_PER_SECOND, "chunks_meshed", "/s"),
+    (PLAYER_X, "player_x", ""),
+    (PLAYER_Y, "player_y", ""),
+    (PLAYER_Z, "player_z", ""),
+    (PLAYER_HEADING, "player_heading", "°"),
+    (SURFACE_HEIGHT, "surface_height", ""),
+    (COAL_NOISE, "coal_noise", ""),
+    (IRON_NOISE, "iron_noise", ""),
+    (GRASS_NOISE, "grass_noise", ""),
+];
+
+pub fn register_diagnostics(app: &mut App) {
+    for (id, name, suffix) in DIAGNOSTICS {
+        app.register_diagnostic(Diagnostic::new(id, name, HISTORY_LENGTH).with_suffix(suffix));
+    }
+}
+
+pub fn measure_chunk_diagnostics(
+    mut diagnostics: Diagnostics,
+    generated_chunks: Res<GeneratedChunks>,
+    loaded_chunks: Res<LoadedChunks>,
+    chunk_queue: Option<Res<ChunkQueue>>,
+    meshed_query: Query<(), Added<ChunkedTerrain>>,
+    time: Res<Time>,
+    mut last_generated: Local<Option<usize>>,
+) {
+    let generated = generated_chunks.map.lock().unwrap().len();
+    let newly_generated = generated.saturating_sub(last_generated.unwrap_or(generated));
+    *last_generated = Some(generated);
+
+    diagnostics.add_measurement(GENERATED_CHUNKS, || generated as f64);
+    diagnostics.add_measurement(LOADED_CHUNKS, || loaded_chunks.chunks.len() as f64);
+
+    // Clients receive their chunks from the server instead
+    if let Some(chunk_queue) = chunk_queue {
+        diagnostics.add_measurement(QUEUED_CHUNKS, || chunk_queue.0.len() as f64);
+    }
+
+    let delta_seconds = time.delta_seconds_f64();
+
+    if delta_seconds > 0.0 {
+        diagnostics.add_measurement(CHUNKS_GENERATED_PER_SECOND, || {
+            newly_generated as f64 / delta_seconds
+        });
+        diagnostics.add_measurement(CHUNKS_MESHED_PER_SECOND, || {
+            meshed_query.iter().count() as f64 / delta_seconds
+        });
+    }
+}
+
+pub fn measure_player_diagnostics(
+    mut diagnostics: Diagnostics,
+    player_query: Query<&Transform, With<PlayerCamera>>,
+    view_query: Query<&GlobalTransform, With<PlayerView>>,
+    seed: Option<Res<WorldSeed>>,
+) {
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+
+    let pos = transform.translation;
+
+    diagnostics.add_measurement(PLAYER_X, || pos.x as f64);
+    diagnostics.add_measurement(PLAYER_Y, || pos.y as f64);
+    diagnostics.add_measurement(PLAYER_Z, || pos.z as f64);
+
+    if let Ok(view) = view_query.get_single() {
+        let forward = view.forward();
+
+        diagnostics.add_measurement(PLAYER_HEADING, || {
+            (forward.x.atan2(-forward.z).to_degrees() as f64).rem_euclid(360.0)
+        });
+    }
+
+    // Clients don't know the seed the server generates the world with
+    let Some(seed) = seed else {
+        return;
+    };
+
+    let noise = NoiseSample::at(pos.into(), *seed);
+
+    diagnostics.add_measurement(SURFACE_HEIGHT, || noise.surface_height as f64);
+    diagnostics.add_measurement(COAL_NOISE, || noise.coal as f64);
+    diagnostics.add_measurement(IRON_NOISE, || noise.iron as f64);
+    diagnostics.add_measurement(GRASS_NOISE, || noise.grass as f64);
+}
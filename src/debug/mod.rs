@@ -0,0 +1,52 @@
+//! Tools for finding out what the game is doing: diagnostics about the world and the player, an
+//! overlay showing them in game, and views of the chunks.
+
+pub mod chunks;
+pub mod diagnostics;
+pub mod overlay;
+
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use bevy::prelude::*;
+
+/// Measures the world and player diagnostics. Works without a window, so the diagnostics can be
+/// logged by a headless server too.
+pub struct WorldDiagnosticsPlugin;
+
+impl Plugin for WorldDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+
+        diagnostics::register_diagnostics(app);
+
+        app.add_systems(
+            Update,
+            (
+                diagnostics::measure_chunk_diagnostics,
+                diagnostics::measure_player_diagnostics,
+            ),
+        );
+    }
+}
+
+/// The debug overlay and the chunk views, each toggled with their own action.
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<chunks::ChunkDebugSettings>()
+            .add_systems(Startup, overlay::spawn_debug_overlay)
+            .add_systems(
+                Update,
+                (
+                    (overlay::toggle_debug_overlay, overlay::update_debug_overlay).chain(),
+                    (
+                        chunks::toggle_chunk_debug,
+                        (chunks::draw_chunk_states, chunks::update_chunk_wireframes),
+                    )
+                        .chain(),
+                ),
+            );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::debug::diagnostics::*;
+use crate::input::{Action, ActionState};
+use crate::worldgen::block::Facing;
+use crate::worldgen::chunk::chunk_pos_containing;
+use bevy::diagnostic::{DiagnosticId, DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+
+const OVERLAY_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+
+#[derive(Component)]
+pub struct DebugOverlay;
+
+pub fn spawn_debug_overlay(mut commands: Commands) {
+    commands
+        .spawn(TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                // Below the targeted block's label
+                top: Val::Px(32.0),
+                left: Val::Px(8.0),
+                padding: UiRect::all(Val::Px(4.0)),
+                ..default()
+            },
+            background_color: OVERLAY_BACKGROUND.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(DebugOverlay);
+}
+
+pub fn toggle_debug_overlay(
+    action_state: Res<ActionState>,
+    mut overlay_query: Query<&mut Visibility, With<DebugOverlay>>,
+) {
+    if !action_state.just_pressed(Action::ToggleDebugOverlay) {
+        return;
+    }
+
+    for mut visibility in overlay_query.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+/// Fills in the overlay from the diagnostics, while it's shown.
+pub fn update_debug_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    mut overlay_query: Query<(&mut Text, &Visibility), With<DebugOverlay>>,
+) {
+    let Ok((mut text, visibility)) = overlay_query.get_single_mut() else {
+        return;
+    };
+
+    if *visibility == Visibility::Hidden {
+        return;
+    }
+
+    let value = |id: DiagnosticId| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.value())
+            .unwrap_or(0.0)
+    };
+    let smoothed = |id: DiagnosticId| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or(0.0)
+    };
+
+    let player_pos = Vec3::new(
+        value(PLAYER_X) as f32,
+        value(PLAYER_Y) as f32,
+        value(PLAYER_Z) as f32,
+    );
+    let block_pos = player_pos.floor().as_ivec3();
+    let chunk_pos = chunk_pos_containing(player_pos);
+
+    let heading = value(PLAYER_HEADING);
+    let heading_radians = heading.to_radians() as f32;
+    let facing = Facing::from_direction(Vec3::new(
+        heading_radians.sin(),
+        0.0,
+        -heading_radians.cos(),
+    ));
+
+    text.sections[0].value = [
+        format!(
+            "{:.0} fps ({:.1} ms)",
+            smoothed(FrameTimeDiagnosticsPlugin::FPS),
+            smoothed(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        ),
+        format!("Block: {} {} {}", block_pos.x, block_pos.y, block_pos.z),
+        format!("Chunk: {} {} {}", chunk_pos.x, chunk_pos.y, chunk_pos.z),
+        format!("Facing: {:?} ({:.0}°)", facing, heading),
+        format!(
+            "Chunks: {} generated, {} loaded, {} queued",
+            value(GENERATED_CHUNKS),
+            value(LOADED_CHUNKS),
+            value(QUEUED_CHUNKS)
+        ),
+        format!(
+            "Throughput: {:.1} generated/s, {:.1} meshed/s",
+            smoothed(CHUNKS_GENERATED_PER_SECOND),
+            smoothed(CHUNKS_MESHED_PER_SECOND)
+        ),
+        format!(
+            "Noise: surface {:.2}, coal {:.2}, iron {:.2}, grass {:.2}",
+            value(SURFACE_HEIGHT),
+            value(COAL_NOISE),
+            value(IRON_NOISE),
+            value(GRASS_NOISE)
+        ),
+    ]
+    .join("\n");
+}
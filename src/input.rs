@@ -0,0 +1,619 @@
+//! Maps raw keyboard, mouse and gamepad input to actions, so that controls can be rebound in a
+//! config file instead of being hard-coded in the systems that use them.
+
+use bevy::input::gamepad::{GamepadAxisType, GamepadButtonType};
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Where the bindings and other input settings are stored, next to the other data files, relative
+/// to the working directory.
+pub const CONTROLS_PATH: &str = "assets/controls.ron";
+
+/// How far an analog input has to be pushed to count as pressed.
+const PRESS_THRESHOLD: f32 = 0.5;
+/// How many pixels of scrolling on a touchpad count as scrolling one line.
+const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputSettings::load_or_default(CONTROLS_PATH))
+            .init_resource::<ActionState>()
+            .init_resource::<TextInputFocus>()
+            .add_systems(PreUpdate, update_action_state.after(InputSystem));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sprint,
+    Crouch,
+    Break,
+    Place,
+    ToggleNoclip,
+    ToggleCrosshair,
+    ToggleDebugOverlay,
+    ToggleChunkBorders,
+    ToggleWireframe,
+    OpenConsole,
+    /// Selects a hotbar slot, counting from 0.
+    HotbarSlot(u8),
+    HotbarNext,
+    HotbarPrevious,
+    /// Moves the held stack one hotbar slot to the left, swapping it with what's there.
+    MoveStackLeft,
+    MoveStackRight,
+    /// Moves half of the held stack into the first empty slot.
+    SplitStack,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+}
+
+/// A physical input that can trigger an action.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    /// One direction of a gamepad axis; `positive` is whether it's the positive direction.
+    GamepadAxis {
+        axis: GamepadAxisType,
+        positive: bool,
+    },
+}
+
+impl Binding {
+    /// How much the input is pressed, from 0 to 1. Buttons are always either 0 or 1.
+    fn value(self, devices: &InputDevices, deadzone: f32) -> f32 {
+        let pressed = |pressed: bool| if pressed { 1.0 } else { 0.0 };
+
+        match self {
+            Binding::Key(key) => pressed(devices.keys.pressed(key)),
+            Binding::Mouse(button) => pressed(devices.mouse_buttons.pressed(button)),
+            Binding::GamepadButton(button_type) => {
+                pressed(devices.gamepads.iter().any(|gamepad| {
+                    devices
+                        .gamepad_buttons
+                        .pressed(GamepadButton::new(gamepad, button_type))
+                }))
+            }
+            Binding::GamepadAxis { axis, positive } => devices
+                .gamepads
+                .iter()
+                .filter_map(|gamepad| devices.gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                .map(|value| if positive { value } else { -value })
+                .filter(|value| *value > deadzone)
+                .fold(0.0, f32::max),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    /// Degrees the view rotates per pixel of mouse movement.
+    pub mouse_sensitivity: f32,
+    /// Degrees per second the view rotates with the look axes fully pushed.
+    pub look_speed: f32,
+    pub invert_y: bool,
+    /// Gamepad axes are ignored until they're pushed further than this.
+    pub gamepad_deadzone: f32,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        use Binding::*;
+
+        let axis = |axis, positive| GamepadAxis { axis, positive };
+
+        let hotbar_keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+
+        let mut bindings = BTreeMap::from([
+            (
+                Action::MoveForward,
+                vec![Key(KeyCode::W), axis(GamepadAxisType::LeftStickY, true)],
+            ),
+            (
+                Action::MoveBackward,
+                vec![Key(KeyCode::S), axis(GamepadAxisType::LeftStickY, false)],
+            ),
+            (
+                Action::MoveLeft,
+                vec![Key(KeyCode::A), axis(GamepadAxisType::LeftStickX, false)],
+            ),
+            (
+                Action::MoveRight,
+                vec![Key(KeyCode::D), axis(GamepadAxisType::LeftStickX, true)],
+            ),
+            (
+                Action::Jump,
+                vec![Key(KeyCode::Space), GamepadButton(GamepadButtonType::South)],
+            ),
+            (
+                Action::Sprint,
+                vec![
+                    Key(KeyCode::ControlLeft),
+                    GamepadButton(GamepadButtonType::LeftThumb),
+                ],
+            ),
+            (
+                Action::Crouch,
+                vec![
+                    Key(KeyCode::ShiftLeft),
+                    GamepadButton(GamepadButtonType::East),
+                ],
+            ),
+            (
+                Action::Break,
+                vec![
+                    Mouse(MouseButton::Left),
+                    GamepadButton(GamepadButtonType::RightTrigger2),
+                ],
+            ),
+            (
+                Action::Place,
+                vec![
+                    Mouse(MouseButton::Right),
+                    GamepadButton(GamepadButtonType::LeftTrigger2),
+                ],
+            ),
+            (
+                Action::ToggleNoclip,
+                vec![Key(KeyCode::N), GamepadButton(GamepadButtonType::Select)],
+            ),
+            (Action::ToggleCrosshair, vec![Key(KeyCode::F6)]),
+            (Action::ToggleDebugOverlay, vec![Key(KeyCode::F3)]),
+            (Action::ToggleChunkBorders, vec![Key(KeyCode::F4)]),
+            (Action::ToggleWireframe, vec![Key(KeyCode::F8)]),
+            (
+                Action::OpenConsole,
+                vec![Key(KeyCode::Slash), Key(KeyCode::Grave)],
+            ),
+            (
+                Action::HotbarNext,
+                vec![GamepadButton(GamepadButtonType::RightTrigger)],
+            ),
+            (
+                Action::HotbarPrevious,
+                vec![GamepadButton(GamepadButtonType::LeftTrigger)],
+            ),
+            (
+                Action::MoveStackLeft,
+                vec![
+                    Key(KeyCode::BracketLeft),
+                    GamepadButton(GamepadButtonType::DPadLeft),
+                ],
+            ),
+            (
+                Action::MoveStackRight,
+                vec![
+                    Key(KeyCode::BracketRight),
+                    GamepadButton(GamepadButtonType::DPadRight),
+                ],
+            ),
+            (
+                Action::SplitStack,
+                vec![Key(KeyCode::X), GamepadButton(GamepadButtonType::DPadDown)],
+            ),
+            (
+                Action::LookUp,
+                vec![axis(GamepadAxisType::RightStickY, true)],
+            ),
+            (
+                Action::LookDown,
+                vec![axis(GamepadAxisType::RightStickY, false)],
+            ),
+            (
+                Action::LookLeft,
+                vec![axis(GamepadAxisType::RightStickX, false)],
+            ),
+            (
+                Action::LookRight,
+                vec![axis(GamepadAxisType::RightStickX, true)],
+            ),
+        ]);
+
+        for (slot, key) in hotbar_keys.into_iter().enumerate() {
+            bindings.insert(Action::HotbarSlot(slot as u8), vec![Key(key)]);
+        }
+
+        Self {
+            bindings,
+            mouse_sensitivity: 0.15,
+            look_speed: 180.0,
+            invert_y: false,
+            gamepad_deadzone: 0.15,
+        }
+    }
+}
+
+impl InputSettings {
+    /// Loads the settings from the file, or creates it with the default settings if it doesn't
+    /// exist yet. A file that can't be parsed is left alone, and the defaults are used instead.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        match fs::read_to_string(path) {
+            Ok(contents) => match ron::from_str::<Self>(&contents) {
+                Ok(mut settings) => {
+                    // Actions added since the file was saved get their default bindings
+                    for (action, bindings) in Self::default().bindings {
+                        settings.bindings.entry(action).or_insert(bindings);
+                    }
+
+                    settings
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to parse {}, using default controls: {}",
+                        path.display(),
+                        err
+                    );
+                    Self::default()
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let settings = Self::default();
+
+                if let Err(err) = settings.save(path) {
+                    warn!(
+                        "Failed to save default controls to {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+
+                settings
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to read {}, using default controls: {}",
+                    path.display(),
+                    err
+                );
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        fs::write(path, contents)
+    }
+}
+
+/// The state of every action this frame. Systems should read this instead of raw input.
+#[derive(Resource, Default)]
+pub struct ActionState {
+    values: BTreeMap<Action, f32>,
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+
+    /// How many degrees the view should rotate this frame; x to the right, y upwards.
+    pub look: Vec2,
+    /// How many lines the mouse wheel scrolled this frame; positive is upwards.
+    pub scroll: f32,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// How much the action is pressed, from 0 to 1, for analog movement.
+    pub fn value(&self, action: Action) -> f32 {
+        self.values.get(&action).copied().unwrap_or(0.0)
+    }
+
+    /// Presses the action this frame, for testing the systems that read actions without any
+    /// input devices.
+    #[cfg(test)]
+    pub fn press(&mut self, action: Action) {
+        self.values.insert(action, 1.0);
+        self.pressed.insert(action);
+        self.just_pressed.insert(action);
+    }
+}
+
+/// Set while the player is typing into something like the console, so that the keys they type
+/// don't also trigger actions. Every action is released until it's unset.
+#[derive(Resource, Default)]
+pub struct TextInputFocus(pub bool);
+
+/// Bundles the raw input resources, to pass them around together.
+struct InputDevices<'a> {
+    keys: &'a Input<KeyCode>,
+    mouse_buttons: &'a Input<MouseButton>,
+    gamepads: &'a Gamepads,
+    gamepad_buttons: &'a Input<GamepadButton>,
+    gamepad_axes: &'a Axis<GamepadAxis>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_action_state(
+    mut action_state: ResMut<ActionState>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    settings: Res<InputSettings>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    time: Res<Time>,
+    focus: Res<TextInputFocus>,
+) {
+    let devices = InputDevices {
+        keys: &keys,
+        mouse_buttons: &mouse_buttons,
+        gamepads: &gamepads,
+        gamepad_buttons: &gamepad_buttons,
+        gamepad_axes: &gamepad_axes,
+    };
+
+    let action_state = &mut *action_state;
+    action_state.just_pressed.clear();
+
+    for (action, bindings) in settings.bindings.iter() {
+        let value = if focus.0 {
+            0.0
+        } else {
+            bindings
+                .iter()
+                .map(|binding| binding.value(&devices, settings.gamepad_deadzone))
+                .fold(0.0, f32::max)
+        };
+
+        action_state.values.insert(*action, value);
+
+        if value >= PRESS_THRESHOLD {
+            if action_state.pressed.insert(*action) {
+                action_state.just_pressed.insert(*action);
+            }
+        } else {
+            action_state.pressed.remove(action);
+        }
+    }
+
+    let mut look = Vec2::ZERO;
+
+    for event in motion.iter() {
+        look += Vec2::new(event.delta.x, -event.delta.y) * settings.mouse_sensitivity;
+    }
+
+    let look_axes = Vec2::new(
+        action_state.value(Action::LookRight) - action_state.value(Action::LookLeft),
+        action_state.value(Action::LookUp) - action_state.value(Action::LookDown),
+    );
+    look += look_axes * settings.look_speed * time.delta_seconds();
+
+    if settings.invert_y {
+        look.y = -look.y;
+    }
+
+    action_state.look = look;
+
+    action_state.scroll = wheel
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_SCROLL_LINE,
+        })
+        .sum();
+
+    if focus.0 {
+        action_state.look = Vec2::ZERO;
+        action_state.scroll = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A controls file that's deleted again once the test is done.
+    struct TempControls(PathBuf);
+
+    impl TempControls {
+        fn new(name: &str, contents: Option<&str>) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "excavate-manufacturate-controls-{}-{}.ron",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_file(&path);
+
+            if let Some(contents) = contents {
+                fs::write(&path, contents).unwrap();
+            }
+
+            Self(path)
+        }
+    }
+
+    impl Drop for TempControls {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn app_with(settings: InputSettings) -> App {
+        let mut app = App::new();
+        app.insert_resource(settings)
+            .init_resource::<Time>()
+            .init_resource::<ActionState>()
+            .init_resource::<TextInputFocus>()
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<MouseButton>>()
+            .init_resource::<Gamepads>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .add_event::<MouseMotion>()
+            .add_event::<MouseWheel>()
+            .add_systems(Update, update_action_state);
+
+        app
+    }
+
+    /// How far the view turns for the mouse moving right and up.
+    fn look_for_mouse_motion(settings: InputSettings) -> Vec2 {
+        let mut app = app_with(settings);
+        app.world.send_event(MouseMotion {
+            delta: Vec2::new(10.0, -4.0),
+        });
+        app.update();
+
+        app.world.resource::<ActionState>().look
+    }
+
+    #[test]
+    fn bindings_are_read_from_the_file() {
+        let controls = TempControls::new(
+            "parse",
+            Some(
+                "(
+                    bindings: {
+                        Jump: [Key(J), GamepadButton(North)],
+                        Break: [Mouse(Middle)],
+                    },
+                    mouse_sensitivity: 0.5,
+                    invert_y: true,
+                )",
+            ),
+        );
+
+        let settings = InputSettings::load_or_default(&controls.0);
+
+        assert_eq!(
+            settings.bindings[&Action::Jump],
+            vec![
+                Binding::Key(KeyCode::J),
+                Binding::GamepadButton(GamepadButtonType::North)
+            ]
+        );
+        assert_eq!(
+            settings.bindings[&Action::Break],
+            vec![Binding::Mouse(MouseButton::Middle)]
+        );
+        assert_eq!(settings.mouse_sensitivity, 0.5);
+        assert!(settings.invert_y);
+        // Settings left out of the file keep their defaults
+        assert_eq!(settings.look_speed, InputSettings::default().look_speed);
+    }
+
+    #[test]
+    fn actions_missing_from_the_file_get_their_default_bindings() {
+        let controls = TempControls::new("missing", Some("(bindings: {Jump: [Key(J)]})"));
+        let defaults = InputSettings::default();
+
+        let settings = InputSettings::load_or_default(&controls.0);
+
+        assert_eq!(
+            settings.bindings[&Action::Jump],
+            vec![Binding::Key(KeyCode::J)]
+        );
+        assert_eq!(settings.bindings.len(), defaults.bindings.len());
+        assert_eq!(
+            settings.bindings[&Action::MoveForward],
+            defaults.bindings[&Action::MoveForward]
+        );
+    }
+
+    #[test]
+    fn missing_files_are_created_with_the_defaults() {
+        let controls = TempControls::new("created", None);
+
+        let settings = InputSettings::load_or_default(&controls.0);
+        let saved = InputSettings::load_or_default(&controls.0);
+
+        assert_eq!(settings.bindings, InputSettings::default().bindings);
+        assert_eq!(saved.bindings, settings.bindings);
+    }
+
+    #[test]
+    fn files_that_do_not_parse_are_left_alone() {
+        let controls = TempControls::new("invalid", Some("(bindings: {Jump: [Key(NotAKey)]})"));
+
+        let settings = InputSettings::load_or_default(&controls.0);
+
+        assert_eq!(settings.bindings, InputSettings::default().bindings);
+        assert_eq!(
+            fs::read_to_string(&controls.0).unwrap(),
+            "(bindings: {Jump: [Key(NotAKey)]})"
+        );
+    }
+
+    #[test]
+    fn mouse_motion_is_scaled_by_the_sensitivity() {
+        let settings = InputSettings {
+            mouse_sensitivity: 0.5,
+            ..default()
+        };
+
+        assert_eq!(look_for_mouse_motion(settings), Vec2::new(5.0, 2.0));
+    }
+
+    #[test]
+    fn invert_y_flips_looking_up_and_down() {
+        let settings = InputSettings {
+            mouse_sensitivity: 0.5,
+            invert_y: true,
+            ..default()
+        };
+
+        assert_eq!(look_for_mouse_motion(settings), Vec2::new(5.0, -2.0));
+    }
+
+    #[test]
+    fn bound_keys_press_their_action() {
+        let mut app = app_with(InputSettings::default());
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Space);
+
+        app.update();
+        let action_state = app.world.resource::<ActionState>();
+        assert!(action_state.just_pressed(Action::Jump));
+        assert_eq!(action_state.value(Action::Jump), 1.0);
+        assert!(!action_state.pressed(Action::Crouch));
+
+        // Still held, but no longer just pressed
+        app.update();
+        let action_state = app.world.resource::<ActionState>();
+        assert!(action_state.pressed(Action::Jump));
+        assert!(!action_state.just_pressed(Action::Jump));
+    }
+}
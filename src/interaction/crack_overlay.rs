@@ -0,0 +1,131 @@
+//! Draws cracks over the block the player is breaking, getting bigger as it gets closer to
+//! breaking.
+
+use crate::interaction::mining::{BreakProgress, CRACK_STAGES};
+use crate::worldgen::block::TEXTURE_SIZE;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// How much bigger the overlay is than the block, so it doesn't flicker with the block's faces.
+const OVERLAY_MARGIN: f32 = 0.005;
+/// How many lines make up the cracks of the last stage.
+const CRACK_LINES: usize = 24;
+/// How many pixels long each line is.
+const CRACK_LINE_LENGTH: usize = 5;
+const CRACK_COLOR: [u8; 4] = [0, 0, 0, 180];
+
+#[derive(Component)]
+pub struct CrackOverlay;
+
+/// One material per crack stage.
+#[derive(Resource)]
+pub struct CrackMaterials(pub Vec<Handle<StandardMaterial>>);
+
+/// The pixels of every crack line, in the order they appear as the block breaks. Every stage
+/// draws more of them, so the cracks grow instead of changing completely between stages.
+fn crack_pixels() -> Vec<(usize, usize)> {
+    // Always the same cracks, so they look the same every time
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut pixels = Vec::with_capacity(CRACK_LINES * CRACK_LINE_LENGTH);
+
+    for _ in 0..CRACK_LINES {
+        let mut x = rng.gen_range(0..TEXTURE_SIZE as i32);
+        let mut y = rng.gen_range(0..TEXTURE_SIZE as i32);
+        let mut direction = (rng.gen_range(-1..=1), rng.gen_range(-1..=1));
+
+        for _ in 0..CRACK_LINE_LENGTH {
+            pixels.push((x as usize, y as usize));
+
+            if direction == (0, 0) || rng.gen_bool(0.3) {
+                direction = (rng.gen_range(-1..=1), rng.gen_range(-1..=1));
+            }
+
+            x = (x + direction.0).clamp(0, TEXTURE_SIZE as i32 - 1);
+            y = (y + direction.1).clamp(0, TEXTURE_SIZE as i32 - 1);
+        }
+    }
+
+    pixels
+}
+
+fn crack_image(pixels: &[(usize, usize)], stage: usize) -> Image {
+    let mut data = vec![0; TEXTURE_SIZE * TEXTURE_SIZE * 4];
+    let drawn = pixels.len() * (stage + 1) / CRACK_STAGES;
+
+    for (x, y) in &pixels[..drawn] {
+        let index = (y * TEXTURE_SIZE + x) * 4;
+        data[index..index + 4].copy_from_slice(&CRACK_COLOR);
+    }
+
+    Image::new(
+        Extent3d {
+            width: TEXTURE_SIZE as u32,
+            height: TEXTURE_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+pub fn spawn_crack_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let pixels = crack_pixels();
+
+    let crack_materials: Vec<_> = (0..CRACK_STAGES)
+        .map(|stage| {
+            materials.add(StandardMaterial {
+                base_color_texture: Some(images.add(crack_image(&pixels, stage))),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            })
+        })
+        .collect();
+
+    commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube {
+                size: 1.0 + OVERLAY_MARGIN * 2.0,
+            })),
+            material: crack_materials[0].clone(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(CrackOverlay);
+
+    commands.insert_resource(CrackMaterials(crack_materials));
+}
+
+pub fn update_crack_overlay(
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut Handle<StandardMaterial>,
+            &mut Visibility,
+        ),
+        With<CrackOverlay>,
+    >,
+    break_progress: Res<BreakProgress>,
+    crack_materials: Res<CrackMaterials>,
+) {
+    let Ok((mut transform, mut material, mut visibility)) = query.get_single_mut() else {
+        return;
+    };
+
+    match (break_progress.target, break_progress.crack_stage()) {
+        (Some(target), Some(stage)) => {
+            transform.translation = target.as_vec3() + Vec3::splat(0.5);
+            *material = crack_materials.0[stage].clone();
+            *visibility = Visibility::Visible;
+        }
+        _ => *visibility = Visibility::Hidden,
+    }
+}
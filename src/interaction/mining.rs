@@ -0,0 +1,218 @@
+//! Breaking blocks takes a while of holding the break button on them, depending on how hard they
+//! are and on the tool that's held.
+
+use crate::inventory::item::Item;
+use crate::inventory::tool::Tool;
+use crate::worldgen::block::Block;
+use bevy::prelude::*;
+
+/// How many stages of cracks are drawn on a block as it's broken.
+pub const CRACK_STAGES: usize = 10;
+/// Multiplies the break time of a block that won't drop anything with the tool it's broken with.
+pub const NO_HARVEST_PENALTY: f32 = 10.0 / 3.0;
+
+/// Whether breaking the block with the tool (or by hand) drops anything.
+pub fn can_harvest(block: Block, tool: Option<Tool>) -> bool {
+    match block.harvest_tier() {
+        None => true,
+        Some(tier) => {
+            tool.is_some_and(|tool| Some(tool.kind) == block.preferred_tool() && tool.tier >= tier)
+        }
+    }
+}
+
+/// How much faster the tool breaks the block than a bare hand does.
+pub fn mining_speed(block: Block, tool: Option<Tool>) -> f32 {
+    match tool {
+        Some(tool) if Some(tool.kind) == block.preferred_tool() => tool.tier.speed(),
+        _ => 1.0,
+    }
+}
+
+/// How long it takes to break the block with the tool (or by hand), in seconds. Air and water
+/// can't be broken at all, so they take forever.
+pub fn break_time(block: Block, tool: Option<Tool>) -> f32 {
+    if !block.is_targetable() {
+        return f32::INFINITY;
+    }
+
+    let penalty = if can_harvest(block, tool) {
+        1.0
+    } else {
+        NO_HARVEST_PENALTY
+    };
+
+    block.hardness() * penalty / mining_speed(block, tool)
+}
+
+/// What breaking the block with the tool (or by hand) drops.
+pub fn harvest(block: Block, tool: Option<Tool>) -> Option<Item> {
+    can_harvest(block, tool).then_some(block.drop())
+}
+
+/// How far along the player is with breaking a block.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub struct BreakProgress {
+    /// The block being broken, if any.
+    pub target: Option<IVec3>,
+    /// From 0 to 1, where 1 is broken.
+    pub progress: f32,
+}
+
+impl BreakProgress {
+    /// Keeps breaking `target` for `delta_seconds`, starting over if it's a different block than
+    /// before. Returns true once the block breaks, which also resets the progress.
+    pub fn advance(&mut self, target: IVec3, break_time: f32, delta_seconds: f32) -> bool {
+        if self.target != Some(target) {
+            self.target = Some(target);
+            self.progress = 0.0;
+        }
+
+        self.progress += if break_time > 0.0 {
+            delta_seconds / break_time
+        } else {
+            1.0
+        };
+
+        if self.progress >= 1.0 {
+            self.reset();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Which stage of cracks should be drawn on the target, or `None` if nothing is being broken.
+    pub fn crack_stage(&self) -> Option<usize> {
+        self.target?;
+
+        Some(((self.progress * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::tool::{ToolKind, ToolTier};
+    use crate::worldgen::block::Axis;
+
+    const WOODEN_PICKAXE: Tool = Tool::new(ToolKind::Pickaxe, ToolTier::Wood);
+    const STONE_PICKAXE: Tool = Tool::new(ToolKind::Pickaxe, ToolTier::Stone);
+    const IRON_PICKAXE: Tool = Tool::new(ToolKind::Pickaxe, ToolTier::Iron);
+    const IRON_SHOVEL: Tool = Tool::new(ToolKind::Shovel, ToolTier::Iron);
+    const IRON_AXE: Tool = Tool::new(ToolKind::Axe, ToolTier::Iron);
+
+    #[test]
+    fn blocks_without_a_harvest_tier_drop_by_hand() {
+        assert!(can_harvest(Block::Dirt, None));
+        assert!(can_harvest(Block::Log(Axis::Y), None));
+        assert!(can_harvest(Block::Dirt, Some(WOODEN_PICKAXE)));
+    }
+
+    #[test]
+    fn harvesting_needs_the_right_tool_of_a_high_enough_tier() {
+        assert!(!can_harvest(Block::Stone, None));
+        assert!(!can_harvest(Block::Stone, Some(IRON_SHOVEL)));
+        assert!(can_harvest(Block::Stone, Some(WOODEN_PICKAXE)));
+
+        assert!(!can_harvest(Block::IronOre, Some(WOODEN_PICKAXE)));
+        assert!(can_harvest(Block::IronOre, Some(STONE_PICKAXE)));
+        assert!(can_harvest(Block::IronOre, Some(IRON_PICKAXE)));
+    }
+
+    #[test]
+    fn harvest_drops_nothing_without_the_right_tool() {
+        assert_eq!(harvest(Block::CoalOre, None), None);
+        assert_eq!(
+            harvest(Block::CoalOre, Some(WOODEN_PICKAXE)),
+            Some(Block::CoalOre.drop())
+        );
+        assert_eq!(harvest(Block::Grass, None), Some(Item::Block(Block::Dirt)));
+    }
+
+    #[test]
+    fn break_time_by_hand_is_the_hardness() {
+        assert_eq!(break_time(Block::Dirt, None), Block::Dirt.hardness());
+        assert_eq!(
+            break_time(Block::Log(Axis::X), None),
+            Block::Log(Axis::X).hardness()
+        );
+    }
+
+    #[test]
+    fn break_time_is_divided_by_the_tool_tier_speed() {
+        let hardness = Block::Dirt.hardness();
+
+        for tier in [ToolTier::Wood, ToolTier::Stone, ToolTier::Iron] {
+            let shovel = Tool::new(ToolKind::Shovel, tier);
+            assert_eq!(
+                break_time(Block::Dirt, Some(shovel)),
+                hardness / tier.speed()
+            );
+        }
+
+        assert!(
+            break_time(Block::Stone, Some(IRON_PICKAXE))
+                < break_time(Block::Stone, Some(STONE_PICKAXE))
+        );
+        assert!(
+            break_time(Block::Stone, Some(STONE_PICKAXE))
+                < break_time(Block::Stone, Some(WOODEN_PICKAXE))
+        );
+    }
+
+    #[test]
+    fn the_wrong_tool_breaks_as_slowly_as_a_hand() {
+        assert_eq!(
+            break_time(Block::Dirt, Some(IRON_AXE)),
+            break_time(Block::Dirt, None)
+        );
+        assert_eq!(mining_speed(Block::Log(Axis::Y), Some(IRON_PICKAXE)), 1.0);
+    }
+
+    #[test]
+    fn blocks_that_would_drop_nothing_take_longer_to_break() {
+        let hardness = Block::Stone.hardness();
+
+        assert_eq!(
+            break_time(Block::Stone, None),
+            hardness * NO_HARVEST_PENALTY
+        );
+        assert_eq!(
+            break_time(Block::Stone, Some(IRON_SHOVEL)),
+            hardness * NO_HARVEST_PENALTY
+        );
+        // Fast, but still too low a tier to drop anything
+        assert_eq!(
+            break_time(Block::IronOre, Some(WOODEN_PICKAXE)),
+            Block::IronOre.hardness() * NO_HARVEST_PENALTY / ToolTier::Wood.speed()
+        );
+    }
+
+    #[test]
+    fn air_and_water_never_break() {
+        for block in [Block::Air, Block::Water, Block::FlowingWater(3)] {
+            assert_eq!(break_time(block, None), f32::INFINITY);
+            assert_eq!(break_time(block, Some(IRON_PICKAXE)), f32::INFINITY);
+        }
+
+        let mut progress = BreakProgress::default();
+        for _ in 0..1000 {
+            assert!(!progress.advance(IVec3::ZERO, break_time(Block::Water, None), 1.0));
+        }
+    }
+
+    #[test]
+    fn break_progress_starts_over_on_a_different_block() {
+        let mut progress = BreakProgress::default();
+
+        assert!(!progress.advance(IVec3::ZERO, 1.0, 0.6));
+        assert!(!progress.advance(IVec3::X, 1.0, 0.6));
+        assert!(progress.advance(IVec3::X, 1.0, 0.6));
+        assert_eq!(progress, BreakProgress::default());
+    }
+}
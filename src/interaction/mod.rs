@@ -0,0 +1,260 @@
+pub mod crack_overlay;
+pub mod mining;
+pub mod raycast;
+pub mod selection;
+
+use crate::camera::body::player_aabb;
+use crate::camera::{MovementMode, PlayerCamera, PlayerCameraMovement, PlayerView};
+use crate::input::{Action, ActionState};
+use crate::interaction::mining::{break_time, harvest, BreakProgress};
+use crate::interaction::raycast::{raycast, RaycastHit};
+use crate::inventory::dropped_item::DropItem;
+use crate::inventory::item::ItemStack;
+use crate::inventory::Inventory;
+use crate::net::client::{Client, EditEffect};
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::{get_block, get_block_entity_mut};
+use crate::worldgen::chunk::GeneratedChunks;
+use crate::worldgen::edit::BlockEdit;
+use crate::worldgen::model::BlockModels;
+use bevy::prelude::*;
+
+/// How far away the player can break and place blocks, in metres.
+pub const REACH_DISTANCE: f32 = 5.0;
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TargetedBlock>()
+            .init_resource::<BreakProgress>()
+            .init_resource::<selection::CrosshairVisible>()
+            .add_systems(
+                Startup,
+                (
+                    crack_overlay::spawn_crack_overlay,
+                    selection::spawn_selection_hud,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_targeted_block,
+                    (mine_block, place_block, use_machine),
+                    (
+                        crack_overlay::update_crack_overlay,
+                        selection::draw_selection_outline,
+                        selection::update_targeted_block_label,
+                    ),
+                )
+                    .chain(),
+            )
+            .add_systems(Update, selection::toggle_crosshair);
+    }
+}
+
+/// The block the player is looking at, if it's within reach.
+#[derive(Resource, Default)]
+pub struct TargetedBlock(pub Option<RaycastHit>);
+
+fn update_targeted_block(
+    view_query: Query<&GlobalTransform, With<PlayerView>>,
+    generated_chunks: Res<GeneratedChunks>,
+    mut targeted_block: ResMut<TargetedBlock>,
+) {
+    let Ok(view) = view_query.get_single() else {
+        return;
+    };
+
+    let map = generated_chunks.map.lock().unwrap();
+
+    targeted_block.0 = raycast(view.translation(), view.forward(), REACH_DISTANCE, |pos| {
+        get_block(&map, pos).is_some_and(Block::is_targetable)
+    });
+}
+
+/// Breaks the targeted block while the break button is held on it, and drops whatever it drops.
+/// On a client, the drop waits for the server to accept the edit.
+#[allow(clippy::too_many_arguments)]
+fn mine_block(
+    inventory_query: Query<&Inventory, With<PlayerCamera>>,
+    mut edits: EventWriter<BlockEdit>,
+    mut drops: EventWriter<DropItem>,
+    mut break_progress: ResMut<BreakProgress>,
+    targeted_block: Res<TargetedBlock>,
+    generated_chunks: Res<GeneratedChunks>,
+    action_state: Res<ActionState>,
+    time: Res<Time>,
+    client: Option<ResMut<Client>>,
+) {
+    let target = targeted_block
+        .0
+        .filter(|_| action_state.pressed(Action::Break))
+        .and_then(|hit| {
+            Some((
+                hit.pos,
+                get_block(&generated_chunks.map.lock().unwrap(), hit.pos)?,
+            ))
+        });
+
+    let Some((pos, block)) = target else {
+        if break_progress.target.is_some() {
+            break_progress.reset();
+        }
+        return;
+    };
+
+    let tool = inventory_query
+        .single()
+        .selected_stack()
+        .and_then(|stack| stack.item.as_tool());
+
+    if !break_progress.advance(pos, break_time(block, tool), time.delta_seconds()) {
+        return;
+    }
+
+    edits.send(BlockEdit {
+        pos,
+        block: Block::Air,
+        drops_contents: true,
+    });
+
+    if let Some(mut client) = client {
+        client.expect_edit(pos, Block::Air, EditEffect::Break(tool));
+    } else if let Some(item) = harvest(block, tool) {
+        drops.send(DropItem {
+            pos: pos.as_vec3() + Vec3::splat(0.5),
+            stack: ItemStack::new(item, 1),
+        });
+    }
+}
+
+/// Places the held block against the face of the targeted block that the player is looking at.
+fn place_block(
+    mut player_query: Query<
+        (
+            &Transform,
+            &PlayerCameraMovement,
+            &MovementMode,
+            &mut Inventory,
+        ),
+        With<PlayerCamera>,
+    >,
+    mut edits: EventWriter<BlockEdit>,
+    targeted_block: Res<TargetedBlock>,
+    generated_chunks: Res<GeneratedChunks>,
+    models: Res<BlockModels>,
+    action_state: Res<ActionState>,
+    client: Option<ResMut<Client>>,
+) {
+    if !action_state.just_pressed(Action::Place) {
+        return;
+    }
+
+    // A zero normal means the view is inside the targeted block, so there's no face to place on
+    let Some(hit) = targeted_block.0.filter(|hit| hit.normal != IVec3::ZERO) else {
+        return;
+    };
+    let pos = hit.pos + hit.normal;
+
+    let (transform, movement, mode, mut inventory) = player_query.single_mut();
+    let map = generated_chunks.map.lock().unwrap();
+
+    // Clicking on a machine uses it instead, unless the player is sneaking
+    if !movement.crouching && get_block(&map, hit.pos).is_some_and(Block::is_machine) {
+        return;
+    }
+
+    let Some(block) = inventory
+        .selected_stack()
+        .and_then(|stack| stack.item.as_block())
+    else {
+        return;
+    };
+
+    let block = block.placed(transform.forward(), hit.normal);
+
+    let replaceable = get_block(&map, pos).is_some_and(Block::is_replaceable);
+
+    // Don't let the player place blocks inside themselves, unless they can't collide with them
+    let player = player_aabb(transform.translation, movement.crouching);
+    let obstructed = *mode != MovementMode::Noclip
+        && models
+            .collision_boxes(block, pos)
+            .iter()
+            .any(|model_box| player.overlaps(model_box));
+
+    if !replaceable || obstructed {
+        return;
+    }
+
+    // On a client, the block is only used up once the server accepts the edit
+    match client {
+        Some(mut client) => {
+            let item = inventory.selected_stack().unwrap().item;
+            client.expect_edit(pos, block, EditEffect::Place(item));
+        }
+        None => {
+            inventory.take_selected();
+        }
+    }
+
+    edits.send(BlockEdit {
+        pos,
+        block,
+        drops_contents: true,
+    });
+}
+
+/// Clicking on a machine puts the held stack into it, or takes out what it made if the player's
+/// hand is empty.
+fn use_machine(
+    mut player_query: Query<(&PlayerCameraMovement, &mut Inventory), With<PlayerCamera>>,
+    targeted_block: Res<TargetedBlock>,
+    generated_chunks: Res<GeneratedChunks>,
+    action_state: Res<ActionState>,
+    client: Option<Res<Client>>,
+) {
+    // The server simulates the machines, and clients can't change them yet
+    if !action_state.just_pressed(Action::Place) || client.is_some() {
+        return;
+    }
+
+    let Some(hit) = targeted_block.0 else {
+        return;
+    };
+
+    let (movement, mut inventory) = player_query.single_mut();
+
+    if movement.crouching {
+        return;
+    }
+
+    let mut map = generated_chunks.map.lock().unwrap();
+    let Some(block_entity) = get_block_entity_mut(&mut map, hit.pos) else {
+        return;
+    };
+
+    let selected = inventory.selected();
+
+    match inventory.remove(selected, u32::MAX) {
+        Some(held) => {
+            if let Some(left_over) = block_entity.insert(held) {
+                inventory.insert_into(selected, left_over);
+            }
+        }
+        None => {
+            let Some(output) = block_entity.output_mut() else {
+                return;
+            };
+
+            if let Some(stack) = output.get(0) {
+                let taken = stack.count.min(inventory.space_for(stack.item));
+
+                if let Some(taken) = output.remove(0, taken) {
+                    inventory.insert(taken);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+
+/// A block hit by a ray.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RaycastHit {
+    pub pos: IVec3,
+    /// The normal of the face the ray entered through, pointing out of the block. Zero if the ray
+    /// started inside the block.
+    pub normal: IVec3,
+    /// How far along the ray the block was hit.
+    pub distance: f32,
+}
+
+/// Walks through the blocks along the ray, one at a time, and returns the first one that
+/// `is_target` accepts within `max_distance`. `direction` has to be normalized.
+pub fn raycast(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    is_target: impl Fn(IVec3) -> bool,
+) -> Option<RaycastHit> {
+    let mut pos = origin.floor().as_ivec3();
+    let step = direction.signum().as_ivec3();
+
+    // How far along the ray it takes to cross one block along each axis
+    let delta = (1.0 / direction).abs();
+
+    // How far along the ray the next block boundary on each axis is
+    let mut next_boundary = Vec3::ZERO;
+    for axis in 0..3 {
+        next_boundary[axis] = if direction[axis] > 0.0 {
+            (pos[axis] as f32 + 1.0 - origin[axis]) * delta[axis]
+        } else if direction[axis] < 0.0 {
+            (origin[axis] - pos[axis] as f32) * delta[axis]
+        } else {
+            f32::INFINITY
+        };
+    }
+
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.0;
+
+    while distance <= max_distance {
+        if is_target(pos) {
+            return Some(RaycastHit {
+                pos,
+                normal,
+                distance,
+            });
+        }
+
+        let axis = if next_boundary.x < next_boundary.y && next_boundary.x < next_boundary.z {
+            0
+        } else if next_boundary.y < next_boundary.z {
+            1
+        } else {
+            2
+        };
+
+        distance = next_boundary[axis];
+        next_boundary[axis] += delta[axis];
+        pos[axis] += step[axis];
+
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+
+    None
+}
@@ -0,0 +1,169 @@
+//! Shows which block the player is looking at: an outline around it, a crosshair in the middle of
+//! the screen, and a label with the block's name and position.
+
+use crate::input::{Action, ActionState};
+use crate::interaction::TargetedBlock;
+use crate::inventory::item::Item;
+use crate::worldgen::chunk::access::get_block;
+use crate::worldgen::chunk::GeneratedChunks;
+use crate::worldgen::model::{BlockModels, ModelBox};
+use bevy::prelude::*;
+
+/// How much bigger the outline is than the block, so it isn't hidden by the block's faces.
+const OUTLINE_MARGIN: f32 = 0.005;
+const OUTLINE_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.8);
+const CROSSHAIR_SIZE: f32 = 16.0;
+const CROSSHAIR_THICKNESS: f32 = 2.0;
+const CROSSHAIR_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.8);
+
+/// Whether the crosshair is shown. It can be turned off with `Action::ToggleCrosshair`.
+#[derive(Resource)]
+pub struct CrosshairVisible(pub bool);
+
+impl Default for CrosshairVisible {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Component)]
+pub struct Crosshair;
+
+/// The name and position of the targeted block.
+#[derive(Component)]
+pub struct TargetedBlockLabel;
+
+pub fn spawn_selection_hud(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Crosshair)
+        .with_children(|parent| {
+            // One bar across and one up and down, crossing in the middle of the screen
+            for (width, height) in [
+                (CROSSHAIR_SIZE, CROSSHAIR_THICKNESS),
+                (CROSSHAIR_THICKNESS, CROSSHAIR_SIZE),
+            ] {
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(width),
+                        height: Val::Px(height),
+                        ..default()
+                    },
+                    background_color: CROSSHAIR_COLOR.into(),
+                    ..default()
+                });
+            }
+        });
+
+    commands
+        .spawn(TextBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                left: Val::Px(8.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(TargetedBlockLabel);
+}
+
+pub fn toggle_crosshair(
+    action_state: Res<ActionState>,
+    mut crosshair_visible: ResMut<CrosshairVisible>,
+    mut crosshair_query: Query<&mut Visibility, With<Crosshair>>,
+) {
+    if action_state.just_pressed(Action::ToggleCrosshair) {
+        crosshair_visible.0 = !crosshair_visible.0;
+    }
+
+    if !crosshair_visible.is_changed() {
+        return;
+    }
+
+    for mut visibility in crosshair_query.iter_mut() {
+        *visibility = if crosshair_visible.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Outlines every box of the targeted block's model. Blocks without any, like plants, are
+/// outlined as a whole block.
+pub fn draw_selection_outline(
+    mut gizmos: Gizmos,
+    targeted_block: Res<TargetedBlock>,
+    generated_chunks: Res<GeneratedChunks>,
+    models: Res<BlockModels>,
+) {
+    let Some(hit) = targeted_block.0 else {
+        return;
+    };
+    let Some(block) = get_block(&generated_chunks.map.lock().unwrap(), hit.pos) else {
+        return;
+    };
+
+    let mut boxes: Vec<ModelBox> = models.boxes(block).collect();
+
+    if boxes.is_empty() {
+        boxes.push(ModelBox::FULL);
+    }
+
+    for model_box in boxes {
+        let center = hit.pos.as_vec3() + (model_box.min + model_box.max) / 2.0;
+        let size = model_box.max - model_box.min + OUTLINE_MARGIN * 2.0;
+
+        gizmos.cuboid(
+            Transform::from_translation(center).with_scale(size),
+            OUTLINE_COLOR,
+        );
+    }
+}
+
+pub fn update_targeted_block_label(
+    targeted_block: Res<TargetedBlock>,
+    generated_chunks: Res<GeneratedChunks>,
+    mut label_query: Query<&mut Text, With<TargetedBlockLabel>>,
+) {
+    let Ok(mut text) = label_query.get_single_mut() else {
+        return;
+    };
+
+    let block = targeted_block.0.and_then(|hit| {
+        get_block(&generated_chunks.map.lock().unwrap(), hit.pos).map(|block| (block, hit.pos))
+    });
+
+    text.sections[0].value = match block {
+        Some((block, pos)) => format!(
+            "{} at {} {} {}",
+            Item::Block(block.with_default_state())
+                .name()
+                .replace('_', " "),
+            pos.x,
+            pos.y,
+            pos.z
+        ),
+        None => String::new(),
+    };
+}
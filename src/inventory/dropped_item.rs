@@ -0,0 +1,459 @@
+//! Items lying around in the world, like the drops of broken blocks. The player picks them up by
+//! walking close to them.
+
+use crate::camera::body::FEET_OFFSET;
+use crate::camera::voxel_collision::{move_aabb, Aabb};
+use crate::camera::{PlayerCamera, PlayerCollision};
+use crate::inventory::item::{Item, ItemStack};
+use crate::inventory::Inventory;
+use crate::worldgen::block::{ATLAS_SIZE, TEXTURE_SIZE};
+use crate::worldgen::chunk::access::get_block;
+use crate::worldgen::chunk::{chunk_material, GeneratedChunks};
+use crate::worldgen::model::BlockModels;
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use bevy::utils::{HashMap, HashSet};
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+/// The width of the cube a dropped item is drawn as, in metres.
+pub const DROPPED_ITEM_SIZE: f32 = 0.25;
+/// How long a dropped item lies around before it disappears, in seconds.
+pub const DROPPED_ITEM_LIFETIME: f32 = 300.0;
+/// How long after being dropped an item can be picked up, in seconds.
+pub const PICKUP_DELAY: f32 = 0.25;
+/// How close the player has to be for items to fly towards them, in metres.
+pub const MAGNET_RANGE: f32 = 2.5;
+/// How close an item has to be to the player to end up in their inventory, in metres.
+pub const PICKUP_RANGE: f32 = 0.6;
+/// How fast items fly towards the player, in metres per second.
+pub const MAGNET_SPEED: f32 = 8.0;
+/// How close identical items have to be to merge into one stack, in metres.
+pub const MERGE_RANGE: f32 = 0.75;
+/// The collision group of dropped items. They only collide with the terrain, and the player walks
+/// through them.
+pub const DROPPED_ITEM_GROUP: Group = Group::GROUP_2;
+
+/// How fast items pop out of where they're dropped, in metres per second.
+const DROP_SPEED: f32 = 2.0;
+/// Downwards acceleration of dropped items with voxel collision, in metres per second squared.
+/// Rapier applies its own gravity.
+const DROPPED_ITEM_GRAVITY: f32 = 20.0;
+/// How quickly dropped items slow down with voxel collision, per second.
+const DROPPED_ITEM_DRAG: f32 = 2.0;
+
+/// Spawns a stack of items lying in the world.
+#[derive(Event)]
+pub struct DropItem {
+    pub pos: Vec3,
+    pub stack: ItemStack,
+}
+
+#[derive(Component)]
+pub struct DroppedItem {
+    pub stack: ItemStack,
+    /// Seconds since the item was dropped.
+    pub age: f32,
+}
+
+/// Moves a dropped item when the player uses voxel collision, since there are no chunk colliders
+/// for Rapier to drop items onto. In metres per second.
+#[derive(Component)]
+pub struct DroppedItemVelocity(pub Vec3);
+
+/// The meshes and materials dropped items are drawn with. Meshes are made the first time an item
+/// is dropped.
+#[derive(Resource)]
+pub struct DroppedItemAssets {
+    meshes: HashMap<Item, Handle<Mesh>>,
+    material: Handle<StandardMaterial>,
+    /// For items without an icon to texture them with.
+    plain_material: Handle<StandardMaterial>,
+}
+
+impl DroppedItemAssets {
+    /// The mesh and material to draw the item with, making its mesh if it doesn't have one yet.
+    pub fn get(
+        &mut self,
+        item: Item,
+        meshes: &mut Assets<Mesh>,
+    ) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        let mesh = self
+            .meshes
+            .entry(item)
+            .or_insert_with(|| meshes.add(item_mesh(item)))
+            .clone();
+        let material = if item.icon().is_some() {
+            self.material.clone()
+        } else {
+            self.plain_material.clone()
+        };
+
+        (mesh, material)
+    }
+}
+
+/// A cube textured with the item's icon on every side.
+fn item_mesh(item: Item) -> Mesh {
+    let mut mesh = Mesh::from(shape::Cube {
+        size: DROPPED_ITEM_SIZE,
+    });
+
+    let Some(icon) = item.icon() else {
+        return mesh;
+    };
+
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        for uv in uvs.iter_mut() {
+            uv[0] = (icon.starting_x as f32 + uv[0] * TEXTURE_SIZE as f32) / ATLAS_SIZE.0 as f32;
+            uv[1] = (icon.starting_y as f32 + uv[1] * TEXTURE_SIZE as f32) / ATLAS_SIZE.1 as f32;
+        }
+    }
+
+    mesh
+}
+
+pub fn setup_dropped_item_assets(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(DroppedItemAssets {
+        meshes: HashMap::new(),
+        material: materials.add(chunk_material(&asset_server)),
+        plain_material: materials.add(Color::GRAY.into()),
+    });
+}
+
+pub fn spawn_dropped_items(
+    mut commands: Commands,
+    mut drops: EventReader<DropItem>,
+    mut dropped_item_assets: ResMut<DroppedItemAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    collision: Res<PlayerCollision>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for drop in drops.iter() {
+        let item = drop.stack.item;
+
+        let (mesh, material) = dropped_item_assets.get(item, &mut meshes);
+
+        let velocity =
+            Vec3::new(rng.gen_range(-0.5..0.5), 1.0, rng.gen_range(-0.5..0.5)) * DROP_SPEED;
+
+        let mut entity = commands.spawn((
+            PbrBundle {
+                mesh,
+                material,
+                transform: Transform::from_translation(drop.pos),
+                ..default()
+            },
+            DroppedItem {
+                stack: drop.stack,
+                age: 0.0,
+            },
+        ));
+
+        match *collision {
+            PlayerCollision::Rapier => {
+                let half_size = DROPPED_ITEM_SIZE / 2.0;
+
+                entity.insert((
+                    RigidBody::Dynamic,
+                    Collider::cuboid(half_size, half_size, half_size),
+                    CollisionGroups::new(DROPPED_ITEM_GROUP, !DROPPED_ITEM_GROUP),
+                    Velocity::linear(velocity),
+                    Damping {
+                        linear_damping: 0.5,
+                        angular_damping: 1.0,
+                    },
+                    // Small and fast enough to fall through the terrain's trimeshes otherwise
+                    Ccd::enabled(),
+                ));
+            }
+            PlayerCollision::Voxel => {
+                entity.insert(DroppedItemVelocity(velocity));
+            }
+        }
+    }
+}
+
+/// Moves dropped items when there's no Rapier to move them.
+pub fn move_dropped_items(
+    mut query: Query<(&mut Transform, &mut DroppedItemVelocity)>,
+    generated_chunks: Res<GeneratedChunks>,
+    models: Res<BlockModels>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    let map = generated_chunks.map.lock().unwrap();
+
+    // Like for the player, chunks that haven't been generated yet count as solid
+    let boxes_at = |pos| match get_block(&map, pos) {
+        Some(block) => models.collision_boxes(block, pos),
+        None => vec![Aabb::block(pos)],
+    };
+
+    for (mut transform, mut velocity) in query.iter_mut() {
+        velocity.0.y -= DROPPED_ITEM_GRAVITY * delta_seconds;
+        velocity.0 *= f32::exp(-DROPPED_ITEM_DRAG * delta_seconds);
+
+        let aabb = Aabb {
+            min: transform.translation - Vec3::splat(DROPPED_ITEM_SIZE / 2.0),
+            max: transform.translation + Vec3::splat(DROPPED_ITEM_SIZE / 2.0),
+        };
+        let displacement = velocity.0 * delta_seconds;
+        let result = move_aabb(aabb, displacement, 0.0, &boxes_at);
+
+        for axis in 0..3 {
+            if result.displacement[axis] != displacement[axis] {
+                velocity.0[axis] = 0.0;
+            }
+        }
+
+        transform.translation += result.displacement;
+    }
+}
+
+/// Despawns dropped items that have been lying around for too long.
+pub fn age_dropped_items(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut DroppedItem)>,
+    time: Res<Time>,
+) {
+    for (entity, mut dropped_item) in query.iter_mut() {
+        dropped_item.age += time.delta_seconds();
+
+        if dropped_item.age > DROPPED_ITEM_LIFETIME {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Merges stacks of the same item that are close to each other, to keep the number of entities
+/// down.
+pub fn merge_dropped_items(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, &mut DroppedItem)>,
+) {
+    let mut emptied = HashSet::new();
+    let mut combinations = query.iter_combinations_mut();
+
+    while let Some([(entity_a, transform_a, mut a), (entity_b, transform_b, mut b)]) =
+        combinations.fetch_next()
+    {
+        if a.stack.item != b.stack.item
+            || emptied.contains(&entity_a)
+            || emptied.contains(&entity_b)
+            || transform_a.translation.distance(transform_b.translation) > MERGE_RANGE
+        {
+            continue;
+        }
+
+        let space = a.stack.item.max_stack_size().saturating_sub(a.stack.count);
+        let moved = space.min(b.stack.count);
+
+        a.stack.count += moved;
+        b.stack.count -= moved;
+        // The merged item lies around for as long as the newer one would have
+        a.age = a.age.min(b.age);
+
+        if b.stack.count == 0 {
+            emptied.insert(entity_b);
+            commands.entity(entity_b).despawn();
+        }
+    }
+}
+
+/// Pulls items close to the player towards them, and puts them in their inventory once they're
+/// close enough.
+#[allow(clippy::type_complexity)]
+pub fn collect_dropped_items(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &mut Inventory), With<PlayerCamera>>,
+    mut item_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut DroppedItem,
+            Option<&mut Velocity>,
+            Option<&mut DroppedItemVelocity>,
+        ),
+        Without<PlayerCamera>,
+    >,
+) {
+    let Ok((player_transform, mut inventory)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    // Roughly the middle of the player's body
+    let player_center = player_transform.translation - Vec3::new(0.0, FEET_OFFSET / 2.0, 0.0);
+
+    for (entity, transform, mut dropped_item, rapier_velocity, voxel_velocity) in
+        item_query.iter_mut()
+    {
+        if dropped_item.age < PICKUP_DELAY || !inventory.can_insert(dropped_item.stack.item) {
+            continue;
+        }
+
+        let offset = player_center - transform.translation;
+        let distance = offset.length();
+
+        if distance < PICKUP_RANGE {
+            match inventory.insert(dropped_item.stack) {
+                Some(left_over) => dropped_item.stack = left_over,
+                None => commands.entity(entity).despawn(),
+            }
+        } else if distance < MAGNET_RANGE {
+            let velocity = offset / distance * MAGNET_SPEED;
+
+            if let Some(mut rapier_velocity) = rapier_velocity {
+                rapier_velocity.linvel = velocity;
+            }
+            if let Some(mut voxel_velocity) = voxel_velocity {
+                voxel_velocity.0 = velocity;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::DEFAULT_MAX_STACK_SIZE;
+    use crate::worldgen::block::Block;
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+    use std::time::Duration;
+
+    fn stone(count: u32) -> ItemStack {
+        ItemStack::new(Item::Block(Block::Stone), count)
+    }
+
+    /// An app that runs the system once a second.
+    fn app_with<M>(system: impl IntoSystemConfigs<M>) -> App {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)))
+            .add_systems(Update, system);
+
+        app
+    }
+
+    fn spawn_item(app: &mut App, pos: Vec3, stack: ItemStack, age: f32) -> Entity {
+        app.world
+            .spawn((
+                Transform::from_translation(pos),
+                DroppedItem { stack, age },
+                DroppedItemVelocity(Vec3::ZERO),
+            ))
+            .id()
+    }
+
+    fn stack_of(app: &App, entity: Entity) -> Option<ItemStack> {
+        app.world
+            .get::<DroppedItem>(entity)
+            .map(|dropped_item| dropped_item.stack)
+    }
+
+    #[test]
+    fn nearby_identical_stacks_merge() {
+        let mut app = app_with(merge_dropped_items);
+        let a = spawn_item(&mut app, Vec3::ZERO, stone(10), 5.0);
+        let b = spawn_item(&mut app, Vec3::new(0.5, 0.0, 0.0), stone(20), 1.0);
+        let far = spawn_item(&mut app, Vec3::new(5.0, 0.0, 0.0), stone(3), 0.0);
+        let dirt = spawn_item(
+            &mut app,
+            Vec3::new(0.0, 0.5, 0.0),
+            ItemStack::new(Item::Block(Block::Dirt), 4),
+            0.0,
+        );
+
+        app.update();
+
+        // Whichever of the two was merged into, it holds both and has the newer one's age
+        let (merged, emptied) = match stack_of(&app, a) {
+            Some(_) => (a, b),
+            None => (b, a),
+        };
+        assert_eq!(stack_of(&app, merged), Some(stone(30)));
+        assert_eq!(app.world.get::<DroppedItem>(merged).unwrap().age, 1.0);
+        assert!(app.world.get_entity(emptied).is_none());
+
+        assert_eq!(stack_of(&app, far), Some(stone(3)));
+        assert_eq!(
+            stack_of(&app, dirt),
+            Some(ItemStack::new(Item::Block(Block::Dirt), 4))
+        );
+    }
+
+    #[test]
+    fn merged_stacks_stay_within_the_stack_size() {
+        let mut app = app_with(merge_dropped_items);
+        let a = spawn_item(&mut app, Vec3::ZERO, stone(DEFAULT_MAX_STACK_SIZE - 5), 0.0);
+        let b = spawn_item(&mut app, Vec3::new(0.2, 0.0, 0.0), stone(10), 0.0);
+
+        app.update();
+
+        let mut counts = [stack_of(&app, a), stack_of(&app, b)].map(|stack| stack.unwrap().count);
+        counts.sort();
+        assert_eq!(counts, [5, DEFAULT_MAX_STACK_SIZE]);
+    }
+
+    #[test]
+    fn items_despawn_once_they_are_too_old() {
+        let mut app = app_with(age_dropped_items);
+        let old = spawn_item(&mut app, Vec3::ZERO, stone(1), DROPPED_ITEM_LIFETIME - 1.5);
+        let new = spawn_item(&mut app, Vec3::ZERO, stone(1), 0.0);
+
+        // The first update only starts the clock
+        app.update();
+        app.update();
+        assert!(app.world.get_entity(old).is_some());
+
+        app.update();
+        assert!(app.world.get_entity(old).is_none());
+        assert_eq!(app.world.get::<DroppedItem>(new).unwrap().age, 2.0);
+    }
+
+    #[test]
+    fn items_fly_to_the_player_and_end_up_in_their_inventory() {
+        let mut app = app_with(collect_dropped_items);
+        let player_pos = Vec3::new(0.0, FEET_OFFSET, 0.0);
+        let player = app
+            .world
+            .spawn((
+                Transform::from_translation(player_pos),
+                PlayerCamera,
+                Inventory::new(9),
+            ))
+            .id();
+        // Roughly the middle of the player's body
+        let center = player_pos - Vec3::new(0.0, FEET_OFFSET / 2.0, 0.0);
+
+        let nearby = spawn_item(&mut app, center + Vec3::new(1.5, 0.0, 0.0), stone(2), 1.0);
+        let close = spawn_item(&mut app, center + Vec3::new(0.0, 0.0, 0.3), stone(3), 1.0);
+        let just_dropped = spawn_item(&mut app, center, stone(4), 0.0);
+        let far = spawn_item(&mut app, center + Vec3::new(10.0, 0.0, 0.0), stone(5), 1.0);
+
+        app.update();
+
+        let velocity = app.world.get::<DroppedItemVelocity>(nearby).unwrap().0;
+        assert!((velocity - Vec3::new(-MAGNET_SPEED, 0.0, 0.0)).length() < 1e-4);
+
+        assert!(app.world.get_entity(close).is_none());
+        assert_eq!(
+            app.world
+                .get::<Inventory>(player)
+                .unwrap()
+                .count(Item::Block(Block::Stone)),
+            3
+        );
+
+        assert!(app.world.get_entity(just_dropped).is_some());
+        assert_eq!(
+            app.world.get::<DroppedItemVelocity>(far).unwrap().0,
+            Vec3::ZERO
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::camera::PlayerCamera;
+use crate::input::{Action, ActionState};
+use crate::inventory::{Inventory, HOTBAR_SIZE};
+use crate::worldgen::block::{ATLAS_SIZE, TEXTURE_SIZE};
+use bevy::prelude::*;
+
+/// The size of one hotbar slot on screen, in pixels.
+const SLOT_SIZE: f32 = 52.0;
+const ICON_SIZE: f32 = 32.0;
+const SLOT_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.6);
+const SELECTED_SLOT_COLOR: Color = Color::rgba(0.8, 0.8, 0.8, 0.8);
+
+/// The background of a hotbar slot on screen.
+#[derive(Component)]
+pub struct HotbarSlot(pub usize);
+
+/// The icon of the item in a hotbar slot.
+#[derive(Component)]
+pub struct HotbarIcon(pub usize);
+
+/// The name of the item in a hotbar slot, for items that don't have an icon.
+#[derive(Component)]
+pub struct HotbarLabel(pub usize);
+
+/// The number of items in a hotbar slot.
+#[derive(Component)]
+pub struct HotbarCount(pub usize);
+
+pub fn spawn_hotbar(
+    mut commands: Commands,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: Res<AssetServer>,
+) {
+    let texture_atlas = texture_atlases.add(TextureAtlas::from_grid(
+        asset_server.load("textures/atlas.png"),
+        Vec2::splat(TEXTURE_SIZE as f32),
+        ATLAS_SIZE.0 / TEXTURE_SIZE,
+        ATLAS_SIZE.1 / TEXTURE_SIZE,
+        None,
+        None,
+    ));
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(8.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(4.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for slot in 0..HOTBAR_SIZE {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(SLOT_SIZE),
+                            height: Val::Px(SLOT_SIZE),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: SLOT_COLOR.into(),
+                        ..default()
+                    })
+                    .insert(HotbarSlot(slot))
+                    .with_children(|parent| {
+                        parent
+                            .spawn(AtlasImageBundle {
+                                style: Style {
+                                    width: Val::Px(ICON_SIZE),
+                                    height: Val::Px(ICON_SIZE),
+                                    ..default()
+                                },
+                                texture_atlas: texture_atlas.clone(),
+                                visibility: Visibility::Hidden,
+                                ..default()
+                            })
+                            .insert(HotbarIcon(slot));
+
+                        parent
+                            .spawn(TextBundle {
+                                text: Text::from_section(
+                                    "",
+                                    TextStyle {
+                                        font_size: 11.0,
+                                        color: Color::WHITE,
+                                        ..default()
+                                    },
+                                ),
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    left: Val::Px(3.0),
+                                    top: Val::Px(3.0),
+                                    max_width: Val::Px(SLOT_SIZE - 6.0),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .insert(HotbarLabel(slot));
+
+                        parent
+                            .spawn(TextBundle {
+                                text: Text::from_section(
+                                    "",
+                                    TextStyle {
+                                        font_size: 18.0,
+                                        color: Color::WHITE,
+                                        ..default()
+                                    },
+                                ),
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    right: Val::Px(3.0),
+                                    bottom: Val::Px(1.0),
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .insert(HotbarCount(slot));
+                    });
+            }
+        });
+}
+
+/// Changes the selected slot with the number keys, the scroll wheel and the gamepad bumpers, and
+/// moves or splits the held stack.
+pub fn handle_input_hotbar(
+    mut query: Query<&mut Inventory, With<PlayerCamera>>,
+    action_state: Res<ActionState>,
+) {
+    let Ok(mut inventory) = query.get_single_mut() else {
+        return;
+    };
+
+    for slot in 0..HOTBAR_SIZE {
+        if action_state.just_pressed(Action::HotbarSlot(slot as u8)) {
+            inventory.select(slot);
+        }
+    }
+
+    let mut steps = 0;
+
+    if action_state.just_pressed(Action::HotbarNext) {
+        steps += 1;
+    }
+    if action_state.just_pressed(Action::HotbarPrevious) {
+        steps -= 1;
+    }
+
+    // Scrolling down moves to the right, like in most games
+    steps -= action_state.scroll.round() as i32;
+
+    if steps != 0 {
+        inventory.scroll_selection(steps);
+    }
+
+    if action_state.just_pressed(Action::MoveStackLeft) {
+        inventory.move_selected(-1);
+    }
+    if action_state.just_pressed(Action::MoveStackRight) {
+        inventory.move_selected(1);
+    }
+    if action_state.just_pressed(Action::SplitStack) {
+        inventory.split_into_empty(inventory.selected());
+    }
+}
+
+/// Shows the contents of the player's hotbar whenever their inventory changes.
+pub fn update_hotbar(
+    inventory_query: Query<&Inventory, (With<PlayerCamera>, Changed<Inventory>)>,
+    mut slot_query: Query<(&HotbarSlot, &mut BackgroundColor)>,
+    mut icon_query: Query<(&HotbarIcon, &mut UiTextureAtlasImage, &mut Visibility)>,
+    mut label_query: Query<(&HotbarLabel, &mut Text), Without<HotbarCount>>,
+    mut count_query: Query<(&HotbarCount, &mut Text), Without<HotbarLabel>>,
+) {
+    let Ok(inventory) = inventory_query.get_single() else {
+        return;
+    };
+
+    for (slot, mut color) in slot_query.iter_mut() {
+        *color = if slot.0 == inventory.selected() {
+            SELECTED_SLOT_COLOR
+        } else {
+            SLOT_COLOR
+        }
+        .into();
+    }
+
+    for (icon, mut image, mut visibility) in icon_query.iter_mut() {
+        match inventory.get(icon.0).and_then(|stack| stack.item.icon()) {
+            Some(texture_config) => {
+                image.index = texture_config.atlas_index();
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+
+    for (label, mut text) in label_query.iter_mut() {
+        text.sections[0].value = match inventory.get(label.0) {
+            Some(stack) if stack.item.icon().is_none() => stack.item.name().replace('_', " "),
+            _ => String::new(),
+        };
+    }
+
+    for (count, mut text) in count_query.iter_mut() {
+        text.sections[0].value = match inventory.get(count.0) {
+            Some(stack) if stack.count > 1 => stack.count.to_string(),
+            _ => String::new(),
+        };
+    }
+}
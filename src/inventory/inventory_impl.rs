@@ -0,0 +1,405 @@
+use crate::inventory::item::{Item, ItemStack};
+use crate::inventory::{Inventory, HOTBAR_SIZE};
+
+impl Inventory {
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size],
+            selected: 0,
+        }
+    }
+
+    /// The number of slots, whether or not they're empty.
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn get(&self, slot: usize) -> Option<ItemStack> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_stack(&self) -> Option<ItemStack> {
+        self.get(self.selected)
+    }
+
+    /// Selects a hotbar slot. Slots outside the hotbar are ignored.
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SIZE {
+            self.selected = slot;
+        }
+    }
+
+    /// Moves the selection along the hotbar by `steps`, wrapping around at the ends.
+    pub fn scroll_selection(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SIZE as i32) as usize;
+    }
+
+    /// Adds the stack to the inventory, first topping up stacks of the same item and then filling
+    /// empty slots. Returns whatever didn't fit.
+    pub fn insert(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        let max_stack_size = stack.item.max_stack_size();
+        let mut remaining = stack.count;
+
+        for existing in self.slots.iter_mut().flatten() {
+            if remaining == 0 {
+                break;
+            }
+
+            if existing.item == stack.item {
+                let moved = remaining.min(max_stack_size.saturating_sub(existing.count));
+                existing.count += moved;
+                remaining -= moved;
+            }
+        }
+
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if remaining == 0 {
+                break;
+            }
+
+            let moved = remaining.min(max_stack_size);
+            *slot = Some(ItemStack::new(stack.item, moved));
+            remaining -= moved;
+        }
+
+        (remaining > 0).then_some(ItemStack::new(stack.item, remaining))
+    }
+
+    /// How many of the item there are in the whole inventory.
+    pub fn count(&self, item: Item) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    /// How many more of the item fit in the inventory.
+    pub fn space_for(&self, item: Item) -> u32 {
+        self.slots
+            .iter()
+            .map(|slot| match slot {
+                Some(stack) if stack.item == item => {
+                    item.max_stack_size().saturating_sub(stack.count)
+                }
+                Some(_) => 0,
+                None => item.max_stack_size(),
+            })
+            .sum()
+    }
+
+    /// Whether at least one of the item fits in the inventory.
+    pub fn can_insert(&self, item: Item) -> bool {
+        self.space_for(item) > 0
+    }
+
+    /// Adds as much of the stack as fits to one specific slot. Returns whatever didn't fit.
+    pub fn insert_into(&mut self, slot: usize, stack: ItemStack) -> Option<ItemStack> {
+        if stack.count == 0 {
+            return None;
+        }
+
+        let Some(slot) = self.slots.get_mut(slot) else {
+            return Some(stack);
+        };
+
+        let existing = slot.get_or_insert(ItemStack::new(stack.item, 0));
+
+        if existing.item != stack.item {
+            return Some(stack);
+        }
+
+        let moved = stack
+            .count
+            .min(stack.item.max_stack_size().saturating_sub(existing.count));
+        existing.count += moved;
+
+        (moved < stack.count).then_some(ItemStack::new(stack.item, stack.count - moved))
+    }
+
+    /// Takes up to `count` items out of the slot. Returns `None` if the slot is empty.
+    pub fn remove(&mut self, slot: usize, count: u32) -> Option<ItemStack> {
+        let slot = self.slots.get_mut(slot)?;
+        let stack = slot.as_mut()?;
+
+        let taken = ItemStack::new(stack.item, count.min(stack.count));
+        stack.count -= taken.count;
+
+        if stack.count == 0 {
+            *slot = None;
+        }
+
+        (taken.count > 0).then_some(taken)
+    }
+
+    /// Takes up to `count` of the item out of the inventory, from wherever it is. Returns how many
+    /// were taken.
+    pub fn remove_item(&mut self, item: Item, count: u32) -> u32 {
+        let mut removed = 0;
+
+        for slot in 0..self.slots.len() {
+            if removed == count {
+                break;
+            }
+
+            if self.get(slot).is_some_and(|stack| stack.item == item) {
+                removed += self
+                    .remove(slot, count - removed)
+                    .map_or(0, |stack| stack.count);
+            }
+        }
+
+        removed
+    }
+
+    /// Takes one of the held item, if there is one.
+    pub fn take_selected(&mut self) -> Option<Item> {
+        self.remove(self.selected, 1).map(|stack| stack.item)
+    }
+
+    /// Takes half of the stack in the slot, rounded up.
+    pub fn split(&mut self, slot: usize) -> Option<ItemStack> {
+        let count = self.get(slot)?.count;
+        self.remove(slot, count.div_ceil(2))
+    }
+
+    /// Moves half of the stack in the slot, rounded up, into the first empty slot. Returns that
+    /// slot, or `None` if there's nothing to split or nowhere to put it.
+    pub fn split_into_empty(&mut self, slot: usize) -> Option<usize> {
+        let empty = self.slots.iter().position(Option::is_none)?;
+        let half = self.split(slot)?;
+
+        self.slots[empty] = Some(half);
+
+        Some(empty)
+    }
+
+    /// Moves the held stack along the hotbar by `steps`, wrapping around at the ends, and keeps
+    /// holding it. It's merged into or swapped with the stack it's moved onto like `move_stack`.
+    pub fn move_selected(&mut self, steps: i32) {
+        let from = self.selected;
+        self.scroll_selection(steps);
+        self.move_stack(from, self.selected);
+    }
+
+    /// Moves the stack in `from` onto `to`. If `to` holds the same item, as much as fits is merged
+    /// into it and the rest stays behind; otherwise the two slots are swapped.
+    pub fn move_stack(&mut self, from: usize, to: usize) {
+        if from == to || from >= self.slots.len() || to >= self.slots.len() {
+            return;
+        }
+
+        match (self.slots[from], self.slots[to]) {
+            (Some(source), Some(mut destination)) if source.item == destination.item => {
+                let space = source
+                    .item
+                    .max_stack_size()
+                    .saturating_sub(destination.count);
+                let moved = source.count.min(space);
+
+                destination.count += moved;
+                self.slots[to] = Some(destination);
+                self.remove(from, moved);
+            }
+            _ => self.slots.swap(from, to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::{Material, DEFAULT_MAX_STACK_SIZE};
+    use crate::inventory::tool::{Tool, ToolKind, ToolTier};
+    use crate::worldgen::block::Block;
+
+    const STONE: Item = Item::Block(Block::Stone);
+    const DIRT: Item = Item::Block(Block::Dirt);
+    const COAL: Item = Item::Material(Material::Coal);
+
+    fn stack(item: Item, count: u32) -> Option<ItemStack> {
+        Some(ItemStack::new(item, count))
+    }
+
+    #[test]
+    fn insert_tops_up_existing_stacks_first() {
+        let mut inventory = Inventory::new(3);
+        inventory.insert_into(1, ItemStack::new(STONE, 60));
+
+        assert_eq!(inventory.insert(ItemStack::new(STONE, 10)), None);
+
+        assert_eq!(inventory.get(0), stack(STONE, 6));
+        assert_eq!(inventory.get(1), stack(STONE, DEFAULT_MAX_STACK_SIZE));
+        assert_eq!(inventory.count(STONE), 70);
+    }
+
+    #[test]
+    fn insert_returns_what_overflows() {
+        let mut inventory = Inventory::new(2);
+        inventory.insert(ItemStack::new(DIRT, 1));
+
+        let left_over = inventory.insert(ItemStack::new(STONE, 100));
+
+        assert_eq!(left_over, stack(STONE, 100 - DEFAULT_MAX_STACK_SIZE));
+        assert_eq!(inventory.space_for(STONE), 0);
+        assert!(!inventory.can_insert(STONE));
+        assert!(inventory.can_insert(DIRT));
+    }
+
+    #[test]
+    fn tools_never_stack() {
+        let mut inventory = Inventory::new(2);
+        let pickaxe = Item::Tool(Tool::new(ToolKind::Pickaxe, ToolTier::Iron));
+
+        assert_eq!(
+            inventory.insert(ItemStack::new(pickaxe, 3)),
+            stack(pickaxe, 1)
+        );
+        assert_eq!(inventory.get(0), stack(pickaxe, 1));
+        assert_eq!(inventory.get(1), stack(pickaxe, 1));
+    }
+
+    #[test]
+    fn insert_into_a_slot_merges_only_the_same_item() {
+        let mut inventory = Inventory::new(2);
+        inventory.insert_into(0, ItemStack::new(STONE, 50));
+
+        assert_eq!(
+            inventory.insert_into(0, ItemStack::new(STONE, 20)),
+            stack(STONE, 6)
+        );
+        assert_eq!(
+            inventory.insert_into(0, ItemStack::new(DIRT, 1)),
+            stack(DIRT, 1)
+        );
+        assert_eq!(
+            inventory.insert_into(5, ItemStack::new(DIRT, 1)),
+            stack(DIRT, 1)
+        );
+        assert_eq!(inventory.get(0), stack(STONE, DEFAULT_MAX_STACK_SIZE));
+    }
+
+    #[test]
+    fn removing_everything_empties_the_slot() {
+        let mut inventory = Inventory::new(3);
+        inventory.insert_into(0, ItemStack::new(COAL, 5));
+        inventory.insert_into(2, ItemStack::new(COAL, 5));
+
+        assert_eq!(inventory.remove(0, 10), stack(COAL, 5));
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(inventory.remove(0, 1), None);
+
+        assert_eq!(inventory.remove_item(COAL, 3), 3);
+        assert_eq!(inventory.remove_item(COAL, 3), 2);
+        assert_eq!(inventory.count(COAL), 0);
+    }
+
+    #[test]
+    fn split_takes_half_rounded_up() {
+        let mut inventory = Inventory::new(2);
+        inventory.insert_into(0, ItemStack::new(STONE, 7));
+
+        assert_eq!(inventory.split(0), stack(STONE, 4));
+        assert_eq!(inventory.get(0), stack(STONE, 3));
+
+        // The last item can't be split, so the whole stack is taken
+        inventory.remove(0, 2);
+        assert_eq!(inventory.split(0), stack(STONE, 1));
+        assert_eq!(inventory.get(0), None);
+        assert_eq!(inventory.split(1), None);
+    }
+
+    #[test]
+    fn move_merges_the_same_item_and_leaves_the_rest() {
+        let mut inventory = Inventory::new(2);
+        inventory.insert_into(0, ItemStack::new(STONE, 40));
+        inventory.insert_into(1, ItemStack::new(STONE, 30));
+
+        inventory.move_stack(0, 1);
+
+        assert_eq!(inventory.get(0), stack(STONE, 6));
+        assert_eq!(inventory.get(1), stack(STONE, DEFAULT_MAX_STACK_SIZE));
+
+        inventory.move_stack(0, 1);
+
+        assert_eq!(inventory.get(0), stack(STONE, 6));
+    }
+
+    #[test]
+    fn move_swaps_different_items_and_fills_empty_slots() {
+        let mut inventory = Inventory::new(3);
+        inventory.insert_into(0, ItemStack::new(STONE, 4));
+        inventory.insert_into(1, ItemStack::new(DIRT, 2));
+
+        inventory.move_stack(0, 1);
+
+        assert_eq!(inventory.get(0), stack(DIRT, 2));
+        assert_eq!(inventory.get(1), stack(STONE, 4));
+
+        inventory.move_stack(1, 2);
+
+        assert_eq!(inventory.get(1), None);
+        assert_eq!(inventory.get(2), stack(STONE, 4));
+
+        // Slots that don't exist are ignored
+        inventory.move_stack(2, 7);
+        assert_eq!(inventory.get(2), stack(STONE, 4));
+    }
+
+    #[test]
+    fn split_into_empty_puts_half_in_the_first_empty_slot() {
+        let mut inventory = Inventory::new(3);
+        inventory.insert_into(1, ItemStack::new(STONE, 9));
+
+        assert_eq!(inventory.split_into_empty(1), Some(0));
+        assert_eq!(inventory.get(0), stack(STONE, 5));
+        assert_eq!(inventory.get(1), stack(STONE, 4));
+
+        assert_eq!(inventory.split_into_empty(2), None);
+
+        // Nowhere to put the half, so the stack is left whole
+        inventory.insert_into(2, ItemStack::new(DIRT, 1));
+        assert_eq!(inventory.split_into_empty(1), None);
+        assert_eq!(inventory.get(1), stack(STONE, 4));
+    }
+
+    #[test]
+    fn moving_the_held_stack_keeps_holding_it() {
+        let mut inventory = Inventory::new(HOTBAR_SIZE);
+        inventory.insert_into(0, ItemStack::new(STONE, 4));
+        inventory.insert_into(1, ItemStack::new(DIRT, 2));
+
+        inventory.move_selected(1);
+
+        assert_eq!(inventory.selected(), 1);
+        assert_eq!(inventory.get(0), stack(DIRT, 2));
+        assert_eq!(inventory.selected_stack(), stack(STONE, 4));
+
+        // Off the left end and around to the last slot
+        inventory.move_selected(-2);
+
+        assert_eq!(inventory.selected(), HOTBAR_SIZE - 1);
+        assert_eq!(inventory.get(1), None);
+        assert_eq!(inventory.selected_stack(), stack(STONE, 4));
+    }
+
+    #[test]
+    fn selection_wraps_around_the_hotbar() {
+        let mut inventory = Inventory::new(HOTBAR_SIZE * 2);
+
+        inventory.scroll_selection(-1);
+        assert_eq!(inventory.selected(), HOTBAR_SIZE - 1);
+
+        inventory.scroll_selection(2);
+        assert_eq!(inventory.selected(), 1);
+
+        // Only hotbar slots can be held
+        inventory.select(HOTBAR_SIZE);
+        assert_eq!(inventory.selected(), 1);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::inventory::tool::{Tool, ToolKind, ToolTier};
+use crate::worldgen::block::{Axis, Block, BlockTextureConfig, Facing, Half};
+use serde::{Deserialize, Serialize};
+
+/// How many of an item fit in one inventory slot, unless the item says otherwise.
+pub const DEFAULT_MAX_STACK_SIZE: u32 = 64;
+
+/// Anything that can be held in an inventory.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Item {
+    Block(Block),
+    Tool(Tool),
+    Material(Material),
+}
+
+/// Items that are only used to make other things.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Material {
+    Coal,
+    CrushedIron,
+    IronIngot,
+}
+
+impl Item {
+    /// Every item there is, for looking items up by name.
+    pub const ALL: [Item; 36] = [
+        Item::Block(Block::Grass),
+        Item::Block(Block::Dirt),
+        Item::Block(Block::Stone),
+        Item::Block(Block::IronOre),
+        Item::Block(Block::CoalOre),
+        Item::Block(Block::Log(Axis::Y)),
+        Item::Block(Block::Furnace(Facing::North)),
+        Item::Block(Block::Crusher(Facing::North)),
+        Item::Block(Block::Assembler(Facing::North)),
+        Item::Block(Block::Conveyor(Facing::North)),
+        Item::Block(Block::Splitter(Facing::North)),
+        Item::Block(Block::Merger(Facing::North)),
+        Item::Block(Block::Inserter(Facing::North)),
+        Item::Block(Block::Generator(Facing::North)),
+        Item::Block(Block::Cable(false)),
+        Item::Block(Block::Battery),
+        Item::Block(Block::Pipe),
+        Item::Block(Block::Tank),
+        Item::Block(Block::Pump),
+        Item::Block(Block::Boiler(Facing::North)),
+        Item::Block(Block::StoneSlab(Half::Bottom)),
+        Item::Block(Block::StoneStairs(Facing::North)),
+        Item::Block(Block::IronBars(Facing::North)),
+        Item::Block(Block::TallGrass),
+        Item::Material(Material::Coal),
+        Item::Material(Material::CrushedIron),
+        Item::Material(Material::IronIngot),
+        Item::Tool(Tool::new(ToolKind::Pickaxe, ToolTier::Wood)),
+        Item::Tool(Tool::new(ToolKind::Shovel, ToolTier::Wood)),
+        Item::Tool(Tool::new(ToolKind::Axe, ToolTier::Wood)),
+        Item::Tool(Tool::new(ToolKind::Pickaxe, ToolTier::Stone)),
+        Item::Tool(Tool::new(ToolKind::Shovel, ToolTier::Stone)),
+        Item::Tool(Tool::new(ToolKind::Axe, ToolTier::Stone)),
+        Item::Tool(Tool::new(ToolKind::Pickaxe, ToolTier::Iron)),
+        Item::Tool(Tool::new(ToolKind::Shovel, ToolTier::Iron)),
+        Item::Tool(Tool::new(ToolKind::Axe, ToolTier::Iron)),
+    ];
+
+    /// The id that data files like recipes refer to the item with.
+    pub fn name(self) -> &'static str {
+        match self {
+            Item::Block(Block::Air) => "air",
+            Item::Block(Block::Water) => "water",
+            Item::Block(Block::FlowingWater(_)) => "flowing_water",
+            Item::Block(Block::Grass) => "grass",
+            Item::Block(Block::Dirt) => "dirt",
+            Item::Block(Block::Stone) => "stone",
+            Item::Block(Block::IronOre) => "iron_ore",
+            Item::Block(Block::CoalOre) => "coal_ore",
+            Item::Block(Block::Log(_)) => "log",
+            Item::Block(Block::Furnace(_)) => "furnace",
+            Item::Block(Block::Crusher(_)) => "crusher",
+            Item::Block(Block::Assembler(_)) => "assembler",
+            Item::Block(Block::Conveyor(_)) => "conveyor",
+            Item::Block(Block::Splitter(_)) => "splitter",
+            Item::Block(Block::Merger(_)) => "merger",
+            Item::Block(Block::Inserter(_)) => "inserter",
+            Item::Block(Block::Generator(_)) => "generator",
+            Item::Block(Block::Cable(_)) => "cable",
+            Item::Block(Block::Battery) => "battery",
+            Item::Block(Block::Pipe) => "pipe",
+            Item::Block(Block::Tank) => "tank",
+            Item::Block(Block::Pump) => "pump",
+            Item::Block(Block::Boiler(_)) => "boiler",
+            Item::Block(Block::StoneSlab(_)) => "stone_slab",
+            Item::Block(Block::StoneStairs(_)) => "stone_stairs",
+            Item::Block(Block::IronBars(_)) => "iron_bars",
+            Item::Block(Block::TallGrass) => "tall_grass",
+            Item::Tool(tool) => tool.name(),
+            Item::Material(Material::Coal) => "coal",
+            Item::Material(Material::CrushedIron) => "crushed_iron",
+            Item::Material(Material::IronIngot) => "iron_ingot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Item> {
+        Item::ALL.into_iter().find(|item| item.name() == name)
+    }
+
+    pub fn max_stack_size(self) -> u32 {
+        match self {
+            Item::Tool(_) => 1,
+            _ => DEFAULT_MAX_STACK_SIZE,
+        }
+    }
+
+    /// The texture the item is drawn with in the UI. Items without one are drawn as their name.
+    pub fn icon(self) -> Option<BlockTextureConfig> {
+        match self {
+            // Nothing to draw
+            Item::Block(Block::Air) => None,
+            Item::Block(block) => Some(block.get_texture_config()),
+            Item::Tool(_) => None,
+            Item::Material(Material::Coal) => Some(BlockTextureConfig::new(32, 32)),
+            Item::Material(Material::CrushedIron) => Some(BlockTextureConfig::new(48, 32)),
+            Item::Material(Material::IronIngot) => Some(BlockTextureConfig::new(16, 32)),
+        }
+    }
+
+    /// How many seconds the item keeps a furnace burning for, if it can be burnt at all.
+    pub fn fuel_value(self) -> Option<f32> {
+        match self {
+            Item::Material(Material::Coal) => Some(40.0),
+            _ => None,
+        }
+    }
+
+    /// The block this item places, if any.
+    pub fn as_block(self) -> Option<Block> {
+        match self {
+            Item::Block(block) => Some(block),
+            _ => None,
+        }
+    }
+
+    pub fn as_tool(self) -> Option<Tool> {
+        match self {
+            Item::Tool(tool) => Some(tool),
+            _ => None,
+        }
+    }
+}
+
+/// Some number of the same item. Stacks in an inventory are never empty.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: Item,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: Item, count: u32) -> Self {
+        Self { item, count }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_item_is_found_by_its_name() {
+        for item in Item::ALL {
+            assert_eq!(Item::from_name(item.name()), Some(item));
+        }
+    }
+
+    #[test]
+    fn blocks_that_are_not_items_still_have_names() {
+        assert_eq!(Item::Block(Block::Water).name(), "water");
+        assert_eq!(Item::Block(Block::FlowingWater(3)).name(), "flowing_water");
+        assert_eq!(Item::from_name("water"), None);
+    }
+
+    #[test]
+    fn air_has_no_icon() {
+        assert!(Item::Block(Block::Air).icon().is_none());
+        assert!(Item::Block(Block::Water).icon().is_some());
+    }
+}
@@ -0,0 +1,54 @@
+pub mod dropped_item;
+pub mod hotbar;
+pub mod inventory_impl;
+pub mod item;
+pub mod tool;
+
+use crate::inventory::item::ItemStack;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How many slots the player has in their inventory, including the hotbar.
+pub const PLAYER_INVENTORY_SIZE: usize = 36;
+/// The first slots of an inventory make up its hotbar.
+pub const HOTBAR_SIZE: usize = 9;
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<dropped_item::DropItem>()
+            .add_systems(
+                Startup,
+                (
+                    hotbar::spawn_hotbar,
+                    dropped_item::setup_dropped_item_assets,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        dropped_item::spawn_dropped_items,
+                        dropped_item::move_dropped_items,
+                        dropped_item::age_dropped_items,
+                        dropped_item::merge_dropped_items,
+                        dropped_item::collect_dropped_items,
+                    )
+                        .chain(),
+                    (hotbar::handle_input_hotbar, hotbar::update_hotbar)
+                        .chain()
+                        .after(dropped_item::collect_dropped_items),
+                ),
+            );
+    }
+}
+
+/// Slots that each hold at most one stack of items. The player has one on the `PlayerCamera`
+/// entity, and machines have them for their inputs and outputs.
+#[derive(Component, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    /// The hotbar slot that's held, which is always less than `HOTBAR_SIZE`.
+    selected: usize,
+}
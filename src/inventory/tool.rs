@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// What a tool is good at breaking.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ToolKind {
+    Pickaxe,
+    Shovel,
+    Axe,
+}
+
+/// What a tool is made of. Higher tiers break blocks faster, and some blocks only drop anything
+/// when broken with a high enough tier.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ToolTier {
+    Wood,
+    Stone,
+    Iron,
+}
+
+impl ToolTier {
+    /// Multiplies the mining speed, when the tool is used on a block it's good at breaking.
+    pub fn speed(self) -> f32 {
+        match self {
+            ToolTier::Wood => 2.0,
+            ToolTier::Stone => 4.0,
+            ToolTier::Iron => 6.0,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Tool {
+    pub kind: ToolKind,
+    pub tier: ToolTier,
+}
+
+impl Tool {
+    pub const fn new(kind: ToolKind, tier: ToolTier) -> Self {
+        Self { kind, tier }
+    }
+
+    pub fn name(self) -> &'static str {
+        use ToolKind::*;
+        use ToolTier::*;
+
+        match (self.tier, self.kind) {
+            (Wood, Pickaxe) => "wooden_pickaxe",
+            (Wood, Shovel) => "wooden_shovel",
+            (Wood, Axe) => "wooden_axe",
+            (Stone, Pickaxe) => "stone_pickaxe",
+            (Stone, Shovel) => "stone_shovel",
+            (Stone, Axe) => "stone_axe",
+            (Iron, Pickaxe) => "iron_pickaxe",
+            (Iron, Shovel) => "iron_shovel",
+            (Iron, Axe) => "iron_axe",
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::crafting::{RecipeKind, RecipeRegistry};
+use crate::inventory::Inventory;
+use crate::machine::seconds_to_ticks;
+use serde::{Deserialize, Serialize};
+
+/// How many different ingredients an assembler holds.
+pub const ASSEMBLER_INPUT_SLOTS: usize = 9;
+/// How long an assembler takes to craft a recipe, in seconds.
+pub const ASSEMBLY_TIME: f32 = 2.0;
+/// How much energy an assembler uses every tick it's working.
+pub const ASSEMBLER_POWER_USE: u32 = 6;
+
+/// Crafts recipes that are otherwise made by hand, out of the ingredients put into it. Runs on
+/// power.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Assembler {
+    /// The name of the recipe to craft. Without one, the assembler crafts the first recipe its
+    /// ingredients are enough for.
+    pub recipe: Option<String>,
+    pub input: Inventory,
+    pub output: Inventory,
+    /// Ticks spent crafting the current recipe.
+    pub progress: u32,
+    /// Energy received from the power network, that hasn't been used yet.
+    pub energy: u32,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self {
+            recipe: None,
+            input: Inventory::new(ASSEMBLER_INPUT_SLOTS),
+            output: Inventory::new(1),
+            progress: 0,
+            energy: 0,
+        }
+    }
+
+    pub fn tick(&mut self, recipes: &RecipeRegistry) {
+        let recipe = match &self.recipe {
+            Some(name) => recipes
+                .get(name)
+                .filter(|recipe| recipe.missing_ingredients(&self.input).is_empty()),
+            None => recipes.craftable(&self.input).next(),
+        };
+
+        let Some(recipe) = recipe.filter(|recipe| {
+            !matches!(recipe.kind, RecipeKind::Processing { .. })
+                && self.output.space_for(recipe.output.item) >= recipe.output.count
+        }) else {
+            self.progress = 0;
+            return;
+        };
+
+        // Out of power, so wait for more without losing progress
+        if self.energy < ASSEMBLER_POWER_USE {
+            return;
+        }
+
+        self.energy -= ASSEMBLER_POWER_USE;
+        self.progress += 1;
+
+        if self.progress >= seconds_to_ticks(ASSEMBLY_TIME) {
+            self.progress = 0;
+
+            for ingredient in recipe.ingredients() {
+                self.input.remove_item(ingredient.item, ingredient.count);
+            }
+            self.output.insert(recipe.output);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// The most energy one battery holds.
+pub const BATTERY_CAPACITY: u32 = 20_000;
+
+/// Stores the energy generators make that nothing uses, and gives it back when they can't keep up.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Battery {
+    pub charge: u32,
+}
+
+impl Battery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How much more energy fits in the battery.
+    pub fn room(&self) -> u32 {
+        BATTERY_CAPACITY - self.charge
+    }
+
+    /// Stores up to `energy`, and returns how much was stored.
+    pub fn charge(&mut self, energy: u32) -> u32 {
+        let stored = energy.min(self.room());
+        self.charge += stored;
+
+        stored
+    }
+
+    /// Takes out up to `energy`, and returns how much was taken.
+    pub fn discharge(&mut self, energy: u32) -> u32 {
+        let taken = energy.min(self.charge);
+        self.charge -= taken;
+
+        taken
+    }
+}
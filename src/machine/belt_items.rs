@@ -0,0 +1,122 @@
+//! Draws the items travelling along conveyors. The items only exist in the conveyors' block
+//! entities, so every frame a pool of entities is moved to wherever the items near the player are.
+
+use crate::camera::PlayerCamera;
+use crate::inventory::dropped_item::{DroppedItemAssets, DROPPED_ITEM_SIZE};
+use crate::machine::conveyor::BELT_LENGTH;
+use crate::machine::BlockEntity;
+use crate::worldgen::block::Facing;
+use crate::worldgen::chunk::{chunk_pos_containing, GeneratedChunks, CHUNK_SIZE};
+use bevy::prelude::*;
+
+/// How far away from the player items on conveyors are drawn, in chunks.
+pub const BELT_ITEM_DRAW_DISTANCE: i32 = 2;
+
+/// Marker for the entities items on conveyors are drawn with.
+#[derive(Component)]
+pub struct BeltItemVisual;
+
+/// Every entity items on conveyors have been drawn with. The ones that aren't needed right now are
+/// hidden, to be reused later.
+#[derive(Resource, Default)]
+pub struct BeltItemVisuals(Vec<Entity>);
+
+/// Where an item is drawn on top of a conveyor, from the conveyor's position and how far along it
+/// the item is.
+pub fn belt_item_translation(pos: IVec3, facing: Facing, progress: u32) -> Vec3 {
+    let center = pos.as_vec3() + Vec3::new(0.5, 1.0 + DROPPED_ITEM_SIZE / 2.0, 0.5);
+    let along = progress as f32 / BELT_LENGTH as f32 - 0.5;
+
+    center + facing.offset().as_vec3() * along
+}
+
+#[allow(clippy::type_complexity)]
+pub fn draw_belt_items(
+    mut commands: Commands,
+    mut visuals: ResMut<BeltItemVisuals>,
+    mut visual_query: Query<
+        (
+            &mut Transform,
+            &mut Handle<Mesh>,
+            &mut Handle<StandardMaterial>,
+            &mut Visibility,
+        ),
+        With<BeltItemVisual>,
+    >,
+    player_query: Query<&Transform, (With<PlayerCamera>, Without<BeltItemVisual>)>,
+    mut dropped_item_assets: ResMut<DroppedItemAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    generated_chunks: Res<GeneratedChunks>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    let player_chunk = chunk_pos_containing(player_transform.translation);
+    let mut items = Vec::new();
+
+    {
+        let map = generated_chunks.map.lock().unwrap();
+
+        for chunk in map.values() {
+            let chunk_offset = chunk.pos / CHUNK_SIZE as i32 - player_chunk;
+
+            if chunk_offset.abs().max_element() > BELT_ITEM_DRAW_DISTANCE {
+                continue;
+            }
+
+            for (local_pos, block_entity) in chunk.block_entities.iter() {
+                let BlockEntity::Conveyor(conveyor) = block_entity else {
+                    continue;
+                };
+                let Some(facing) = chunk.get(*local_pos).facing() else {
+                    continue;
+                };
+                let pos = chunk.pos + local_pos.as_ivec3();
+
+                items.extend(conveyor.items.iter().map(|belt_item| {
+                    (
+                        belt_item.item,
+                        belt_item_translation(pos, facing, belt_item.progress),
+                    )
+                }));
+            }
+        }
+    }
+
+    for (i, &entity) in visuals.0.iter().enumerate() {
+        let Ok((mut transform, mut mesh, mut material, mut visibility)) =
+            visual_query.get_mut(entity)
+        else {
+            continue;
+        };
+
+        match items.get(i) {
+            Some(&(item, translation)) => {
+                (*mesh, *material) = dropped_item_assets.get(item, &mut meshes);
+                transform.translation = translation;
+                *visibility = Visibility::Inherited;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+
+    // More items than ever before, so there aren't enough entities to draw them all yet
+    for &(item, translation) in items.iter().skip(visuals.0.len()) {
+        let (mesh, material) = dropped_item_assets.get(item, &mut meshes);
+
+        let entity = commands
+            .spawn((
+                PbrBundle {
+                    mesh,
+                    material,
+                    transform: Transform::from_translation(translation),
+                    ..default()
+                },
+                BeltItemVisual,
+            ))
+            .id();
+
+        visuals.0.push(entity);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::inventory::item::ItemStack;
+use crate::inventory::Inventory;
+use crate::machine::fluid_tank::{Fluid, FluidTank};
+use crate::machine::seconds_to_ticks;
+use serde::{Deserialize, Serialize};
+
+/// How much water a boiler turns into steam every tick while it's burning fuel.
+pub const BOILER_RATE: u32 = 10;
+/// How much water and steam a boiler holds.
+pub const BOILER_CAPACITY: u32 = 4000;
+
+/// Burns fuel to boil the water piped into it, and pipes out the steam.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Boiler {
+    pub fuel: Inventory,
+    pub water: FluidTank,
+    pub steam: FluidTank,
+    /// Ticks the boiler keeps burning for before it needs more fuel.
+    pub burn_ticks: u32,
+}
+
+impl Boiler {
+    pub fn new() -> Self {
+        Self {
+            fuel: Inventory::new(1),
+            water: FluidTank::new(BOILER_CAPACITY, BOILER_RATE * 2),
+            steam: FluidTank::new(BOILER_CAPACITY, BOILER_RATE * 2),
+            burn_ticks: 0,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let can_boil =
+            self.water.amount >= BOILER_RATE && self.steam.room_for(Fluid::Steam) >= BOILER_RATE;
+
+        // Only take more fuel when there's water to boil
+        if can_boil && self.burn_ticks == 0 {
+            let fuel_value = self.fuel.get(0).and_then(|stack| stack.item.fuel_value());
+
+            if let Some(fuel_value) = fuel_value {
+                self.fuel.remove(0, 1);
+                self.burn_ticks = seconds_to_ticks(fuel_value);
+            }
+        }
+
+        let burning = self.burn_ticks > 0;
+        self.burn_ticks = self.burn_ticks.saturating_sub(1);
+
+        if burning && can_boil {
+            self.water.drain(BOILER_RATE);
+            self.steam.fill(Fluid::Steam, BOILER_RATE);
+        }
+    }
+
+    /// The tank that takes in the fluid, if the boiler uses it.
+    pub fn fluid_input(&mut self, fluid: Fluid) -> Option<&mut FluidTank> {
+        (fluid == Fluid::Water).then_some(&mut self.water)
+    }
+
+    /// Only takes items that burn.
+    pub fn insert(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        if stack.item.fuel_value().is_some() {
+            self.fuel.insert(stack)
+        } else {
+            Some(stack)
+        }
+    }
+}
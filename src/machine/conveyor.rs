@@ -0,0 +1,82 @@
+//! Conveyor belts, which carry items along one at a time. Splitters and mergers are conveyors too,
+//! that only differ in where their items go; that's up to `transport`.
+
+use crate::inventory::item::{Item, ItemStack};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// How far an item has to travel to get from the back of a conveyor to its front, in steps.
+pub const BELT_LENGTH: u32 = 60;
+/// How many steps items move along a conveyor every machine tick. A block per second.
+pub const BELT_SPEED: u32 = 3;
+/// The closest items get to each other on a conveyor, in steps. Four items fit on one conveyor.
+pub const BELT_ITEM_SPACING: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct BeltItem {
+    pub item: Item,
+    /// How far along the conveyor the item is, from 0 at the back to `BELT_LENGTH` at the front.
+    pub progress: u32,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Conveyor {
+    /// Ordered from front to back, so the first item is the furthest along.
+    pub items: VecDeque<BeltItem>,
+    /// For splitters and mergers, which of their sides is next in turn.
+    pub next_side: usize,
+}
+
+impl Conveyor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves every item forward, until it's at the front or runs into the item in front of it.
+    pub fn advance(&mut self) {
+        let mut limit = BELT_LENGTH;
+
+        for belt_item in self.items.iter_mut() {
+            belt_item.progress = (belt_item.progress + BELT_SPEED).min(limit);
+            limit = belt_item.progress.saturating_sub(BELT_ITEM_SPACING);
+        }
+    }
+
+    /// Whether there's room for another item at the back.
+    pub fn can_accept(&self) -> bool {
+        self.items
+            .back()
+            .is_none_or(|last| last.progress >= BELT_ITEM_SPACING)
+    }
+
+    /// Puts the item at the back, if there's room for it. Returns whether there was.
+    pub fn accept(&mut self, item: Item) -> bool {
+        if !self.can_accept() {
+            return false;
+        }
+
+        self.items.push_back(BeltItem { item, progress: 0 });
+
+        true
+    }
+
+    /// The item at the front, once it's reached the end of the conveyor and can be passed on.
+    pub fn front_ready(&self) -> Option<Item> {
+        self.items
+            .front()
+            .filter(|belt_item| belt_item.progress == BELT_LENGTH)
+            .map(|belt_item| belt_item.item)
+    }
+
+    /// Takes the item that's furthest along off the conveyor, wherever it is.
+    pub fn take_front(&mut self) -> Option<Item> {
+        self.items.pop_front().map(|belt_item| belt_item.item)
+    }
+
+    /// Puts one of the stack on the conveyor, if there's room. Returns whatever didn't fit.
+    pub fn insert(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        let placed = (stack.count > 0 && self.accept(stack.item)) as u32;
+
+        (stack.count > placed).then_some(ItemStack::new(stack.item, stack.count - placed))
+    }
+}
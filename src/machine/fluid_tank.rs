@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Fluids that flow through pipes. Amounts of fluid are in litres, so a block of water is 1000.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Fluid {
+    Water,
+    Steam,
+}
+
+/// Holds one kind of fluid at a time.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct FluidTank {
+    /// `None` while the tank is empty, so it can take any fluid.
+    pub fluid: Option<Fluid>,
+    pub amount: u32,
+    pub capacity: u32,
+    /// The most fluid that flows in or out of the tank in one tick.
+    pub flow_rate: u32,
+}
+
+impl FluidTank {
+    pub fn new(capacity: u32, flow_rate: u32) -> Self {
+        Self {
+            fluid: None,
+            amount: 0,
+            capacity,
+            flow_rate,
+        }
+    }
+
+    /// How much more of the fluid fits in the tank.
+    pub fn room_for(&self, fluid: Fluid) -> u32 {
+        if self.fluid.is_none_or(|existing| existing == fluid) {
+            self.capacity - self.amount
+        } else {
+            0
+        }
+    }
+
+    /// Adds as much of the fluid as fits, and returns how much that was.
+    pub fn fill(&mut self, fluid: Fluid, amount: u32) -> u32 {
+        let filled = amount.min(self.room_for(fluid));
+
+        if filled > 0 {
+            self.fluid = Some(fluid);
+            self.amount += filled;
+        }
+
+        filled
+    }
+
+    /// Takes out up to `amount` of the fluid in the tank, and returns how much was taken.
+    pub fn drain(&mut self, amount: u32) -> u32 {
+        let drained = amount.min(self.amount);
+        self.amount -= drained;
+
+        if self.amount == 0 {
+            self.fluid = None;
+        }
+
+        drained
+    }
+}
@@ -0,0 +1,243 @@
+use crate::crafting::RecipeRegistry;
+use crate::inventory::item::ItemStack;
+use crate::inventory::Inventory;
+use crate::machine::processing::{advance_recipe, ready_recipe};
+use crate::machine::{seconds_to_ticks, ProcessingMachine};
+use serde::{Deserialize, Serialize};
+
+/// Smelts its input with the furnace's processing recipes, as long as it has fuel to burn.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Furnace {
+    pub input: Inventory,
+    pub fuel: Inventory,
+    pub output: Inventory,
+    /// Ticks the furnace keeps burning for before it needs more fuel.
+    pub burn_ticks: u32,
+    /// Ticks spent smelting the current input.
+    pub progress: u32,
+}
+
+impl Furnace {
+    pub fn new() -> Self {
+        Self {
+            input: Inventory::new(1),
+            fuel: Inventory::new(1),
+            output: Inventory::new(1),
+            burn_ticks: 0,
+            progress: 0,
+        }
+    }
+
+    pub fn tick(&mut self, recipes: &RecipeRegistry) {
+        let recipe = ready_recipe(
+            recipes,
+            ProcessingMachine::Furnace,
+            &self.input,
+            &self.output,
+        );
+
+        // Only take more fuel when there's something to smelt
+        if recipe.is_some() && self.burn_ticks == 0 {
+            let fuel_value = self.fuel.get(0).and_then(|stack| stack.item.fuel_value());
+
+            if let Some(fuel_value) = fuel_value {
+                self.fuel.remove(0, 1);
+                self.burn_ticks = seconds_to_ticks(fuel_value);
+            }
+        }
+
+        let burning = self.burn_ticks > 0;
+        self.burn_ticks = self.burn_ticks.saturating_sub(1);
+
+        match recipe {
+            Some(recipe) if burning => advance_recipe(
+                recipe,
+                &mut self.input,
+                &mut self.output,
+                &mut self.progress,
+            ),
+            // Out of fuel, so wait for more without losing progress
+            Some(_) => {}
+            None => self.progress = 0,
+        }
+    }
+
+    /// Puts fuel in the fuel slot, and anything else in the input.
+    pub fn insert(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        if stack.item.fuel_value().is_some() {
+            self.fuel.insert(stack)
+        } else {
+            self.input.insert(stack)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::{Item, Material, DEFAULT_MAX_STACK_SIZE};
+    use crate::worldgen::block::Block;
+
+    const ORE: Item = Item::Block(Block::IronOre);
+    const INGOT: Item = Item::Material(Material::IronIngot);
+    const COAL: Item = Item::Material(Material::Coal);
+
+    /// Smelting takes half a second, and the slow recipe longer than a piece of coal burns for.
+    fn recipes() -> RecipeRegistry {
+        RecipeRegistry::from_ron(
+            r#"[
+                Processing(
+                    name: "iron_ingot",
+                    machine: Furnace,
+                    input: (item: "iron_ore"),
+                    duration: 0.5,
+                    output: (item: "iron_ingot"),
+                ),
+                Processing(
+                    name: "slow",
+                    machine: Furnace,
+                    input: (item: "dirt"),
+                    duration: 100.0,
+                    output: (item: "stone"),
+                ),
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn furnace(input: Option<ItemStack>, fuel: u32) -> Furnace {
+        let mut furnace = Furnace::new();
+        if let Some(input) = input {
+            furnace.insert(input);
+        }
+        if fuel > 0 {
+            furnace.insert(ItemStack::new(COAL, fuel));
+        }
+        furnace
+    }
+
+    fn tick(furnace: &mut Furnace, recipes: &RecipeRegistry, ticks: u32) {
+        for _ in 0..ticks {
+            furnace.tick(recipes);
+        }
+    }
+
+    #[test]
+    fn insert_puts_fuel_and_input_in_their_own_slots() {
+        let furnace = furnace(Some(ItemStack::new(ORE, 3)), 2);
+
+        assert_eq!(furnace.input.get(0), Some(ItemStack::new(ORE, 3)));
+        assert_eq!(furnace.fuel.get(0), Some(ItemStack::new(COAL, 2)));
+    }
+
+    #[test]
+    fn smelts_once_the_recipe_duration_has_passed() {
+        let recipes = recipes();
+        let mut furnace = furnace(Some(ItemStack::new(ORE, 2)), 1);
+        let duration = seconds_to_ticks(0.5);
+
+        tick(&mut furnace, &recipes, duration - 1);
+        assert_eq!(furnace.progress, duration - 1);
+        assert_eq!(furnace.output.get(0), None);
+
+        tick(&mut furnace, &recipes, 1);
+        assert_eq!(furnace.progress, 0);
+        assert_eq!(furnace.input.get(0), Some(ItemStack::new(ORE, 1)));
+        assert_eq!(furnace.output.get(0), Some(ItemStack::new(INGOT, 1)));
+
+        tick(&mut furnace, &recipes, duration);
+        assert_eq!(furnace.input.get(0), None);
+        assert_eq!(furnace.output.get(0), Some(ItemStack::new(INGOT, 2)));
+    }
+
+    #[test]
+    fn burns_one_fuel_at_a_time() {
+        let recipes = recipes();
+        let mut furnace = furnace(Some(ItemStack::new(ORE, 1)), 3);
+
+        tick(&mut furnace, &recipes, 1);
+
+        assert_eq!(furnace.fuel.get(0), Some(ItemStack::new(COAL, 2)));
+        assert_eq!(furnace.burn_ticks, seconds_to_ticks(40.0) - 1);
+    }
+
+    #[test]
+    fn does_not_burn_fuel_without_anything_to_smelt() {
+        let recipes = recipes();
+        let mut furnace = furnace(None, 1);
+
+        tick(&mut furnace, &recipes, 10);
+
+        assert_eq!(furnace.fuel.get(0), Some(ItemStack::new(COAL, 1)));
+        assert_eq!(furnace.burn_ticks, 0);
+        assert_eq!(furnace.progress, 0);
+    }
+
+    #[test]
+    fn does_not_smelt_without_fuel() {
+        let recipes = recipes();
+        let mut furnace = furnace(Some(ItemStack::new(ORE, 1)), 0);
+
+        tick(&mut furnace, &recipes, 100);
+
+        assert_eq!(furnace.progress, 0);
+        assert_eq!(furnace.output.get(0), None);
+    }
+
+    #[test]
+    fn waits_for_more_fuel_once_it_burns_out_without_losing_progress() {
+        let recipes = recipes();
+        let mut furnace = furnace(Some(ItemStack::new(Item::Block(Block::Dirt), 1)), 1);
+        let burn_time = seconds_to_ticks(40.0);
+
+        tick(&mut furnace, &recipes, burn_time);
+        assert_eq!(furnace.burn_ticks, 0);
+        assert_eq!(furnace.fuel.get(0), None);
+        assert_eq!(furnace.progress, burn_time);
+
+        tick(&mut furnace, &recipes, 50);
+        assert_eq!(furnace.progress, burn_time);
+
+        furnace.insert(ItemStack::new(COAL, 1));
+        tick(&mut furnace, &recipes, 1);
+        assert_eq!(furnace.progress, burn_time + 1);
+        assert_eq!(furnace.fuel.get(0), None);
+    }
+
+    #[test]
+    fn stalls_while_the_output_is_full() {
+        let recipes = recipes();
+        let mut furnace = furnace(Some(ItemStack::new(ORE, 5)), 1);
+        furnace
+            .output
+            .insert(ItemStack::new(INGOT, DEFAULT_MAX_STACK_SIZE));
+
+        tick(&mut furnace, &recipes, 100);
+
+        assert_eq!(furnace.progress, 0);
+        assert_eq!(furnace.input.get(0), Some(ItemStack::new(ORE, 5)));
+        // Nothing to smelt, so the fuel is saved for later
+        assert_eq!(furnace.fuel.get(0), Some(ItemStack::new(COAL, 1)));
+
+        furnace.output.remove(0, 1);
+        tick(&mut furnace, &recipes, seconds_to_ticks(0.5));
+
+        assert_eq!(furnace.input.get(0), Some(ItemStack::new(ORE, 4)));
+        assert_eq!(
+            furnace.output.get(0),
+            Some(ItemStack::new(INGOT, DEFAULT_MAX_STACK_SIZE))
+        );
+    }
+
+    #[test]
+    fn stalls_while_the_output_holds_something_else() {
+        let recipes = recipes();
+        let mut furnace = furnace(Some(ItemStack::new(ORE, 1)), 1);
+        furnace.output.insert(ItemStack::new(COAL, 1));
+
+        tick(&mut furnace, &recipes, 100);
+
+        assert_eq!(furnace.input.get(0), Some(ItemStack::new(ORE, 1)));
+        assert_eq!(furnace.output.get(0), Some(ItemStack::new(COAL, 1)));
+    }
+}
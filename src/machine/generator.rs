@@ -0,0 +1,70 @@
+use crate::inventory::item::ItemStack;
+use crate::inventory::Inventory;
+use crate::machine::fluid_tank::{Fluid, FluidTank};
+use crate::machine::seconds_to_ticks;
+use serde::{Deserialize, Serialize};
+
+/// How much energy a generator makes every tick while it's running.
+pub const GENERATOR_POWER: u32 = 10;
+/// How much steam a generator uses every tick it runs on steam.
+pub const GENERATOR_STEAM_USE: u32 = 10;
+
+/// Powers the network it's connected to with steam piped into it, or by burning fuel when there's
+/// no steam. It only takes more fuel when the network has a use for the power.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Generator {
+    pub fuel: Inventory,
+    pub steam: FluidTank,
+    /// Ticks the generator keeps burning for before it needs more fuel.
+    pub burn_ticks: u32,
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Self {
+            fuel: Inventory::new(1),
+            steam: FluidTank::new(GENERATOR_STEAM_USE * 100, GENERATOR_STEAM_USE * 2),
+            burn_ticks: 0,
+        }
+    }
+
+    /// Runs the generator for one tick, and returns how much energy it made.
+    pub fn generate(&mut self, wanted: bool) -> u32 {
+        // Fuel keeps burning once it's lit, so steam is only used when there's no fire
+        if wanted && self.burn_ticks == 0 && self.steam.amount >= GENERATOR_STEAM_USE {
+            self.steam.drain(GENERATOR_STEAM_USE);
+            return GENERATOR_POWER;
+        }
+
+        if wanted && self.burn_ticks == 0 {
+            let fuel_value = self.fuel.get(0).and_then(|stack| stack.item.fuel_value());
+
+            if let Some(fuel_value) = fuel_value {
+                self.fuel.remove(0, 1);
+                self.burn_ticks = seconds_to_ticks(fuel_value);
+            }
+        }
+
+        if self.burn_ticks == 0 {
+            return 0;
+        }
+
+        self.burn_ticks -= 1;
+
+        GENERATOR_POWER
+    }
+
+    /// The tank that takes in the fluid, if the generator runs on it.
+    pub fn fluid_input(&mut self, fluid: Fluid) -> Option<&mut FluidTank> {
+        (fluid == Fluid::Steam).then_some(&mut self.steam)
+    }
+
+    /// Only takes items that burn.
+    pub fn insert(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        if stack.item.fuel_value().is_some() {
+            self.fuel.insert(stack)
+        } else {
+            Some(stack)
+        }
+    }
+}
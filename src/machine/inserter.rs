@@ -0,0 +1,43 @@
+use crate::inventory::item::Item;
+use crate::machine::seconds_to_ticks;
+use serde::{Deserialize, Serialize};
+
+/// How long an inserter takes to move an item across, in seconds.
+pub const INSERTER_SWING_TIME: f32 = 0.5;
+
+/// An arm that picks items up from the block behind it and puts them into the block in front of
+/// it. If the block in front is full, it keeps holding the item until there's room.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Inserter {
+    pub held: Option<Item>,
+    /// Ticks spent moving the held item across.
+    pub progress: u32,
+}
+
+impl Inserter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pick_up(&mut self, item: Item) {
+        self.held = Some(item);
+        self.progress = 0;
+    }
+
+    /// Swings the arm for one tick. Returns the held item once the arm is over the block in front,
+    /// for it to be put down.
+    pub fn swing(&mut self) -> Option<Item> {
+        let item = self.held?;
+        let swing_ticks = seconds_to_ticks(INSERTER_SWING_TIME);
+
+        self.progress = (self.progress + 1).min(swing_ticks);
+
+        (self.progress == swing_ticks).then_some(item)
+    }
+
+    /// Lets go of the held item, after it's been put down.
+    pub fn put_down(&mut self) {
+        self.held = None;
+        self.progress = 0;
+    }
+}
@@ -0,0 +1,394 @@
+//! Blocks that carry state and do work over time, like furnaces. Their state lives in the chunk
+//! as a `BlockEntity`, next to the block itself.
+
+pub mod assembler;
+pub mod battery;
+pub mod belt_items;
+pub mod boiler;
+pub mod conveyor;
+pub mod fluid_tank;
+pub mod furnace;
+pub mod generator;
+pub mod inserter;
+pub mod network;
+pub mod pipes;
+pub mod power;
+pub mod processing;
+pub mod transport;
+
+use crate::crafting::RecipeRegistry;
+use crate::inventory::dropped_item::DropItem;
+use crate::inventory::item::{Item, ItemStack};
+use crate::inventory::Inventory;
+use crate::machine::assembler::Assembler;
+use crate::machine::battery::Battery;
+use crate::machine::boiler::Boiler;
+use crate::machine::conveyor::Conveyor;
+use crate::machine::fluid_tank::{Fluid, FluidTank};
+use crate::machine::furnace::Furnace;
+use crate::machine::generator::Generator;
+use crate::machine::inserter::Inserter;
+use crate::machine::network::{add_generated_blocks, update_networks};
+use crate::machine::pipes::{
+    step_fluids, PipeNetworks, Pipes, Pump, PIPE_CAPACITY, PIPE_FLOW_RATE, TANK_CAPACITY,
+    TANK_FLOW_RATE,
+};
+use crate::machine::power::{step_power, Power, PowerNetworks, MACHINE_ENERGY_BUFFER};
+use crate::machine::processing::Crusher;
+use crate::machine::transport::{step_transport, transport_positions};
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::{get_block_entity_mut, world_to_chunk_pos};
+use crate::worldgen::chunk::{
+    is_in_view_distance, loader_chunk_positions, ChunkLoader, GeneratedChunks, ViewDistance,
+};
+use crate::worldgen::edit::{self, BlockChanged, BlockEntityEdit};
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+/// How long one machine tick is, in seconds.
+pub const MACHINE_TICK_INTERVAL: f32 = 0.05;
+
+pub struct MachinePlugin;
+
+impl Plugin for MachinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DropItem>()
+            .insert_resource(MachineTickTimer(Timer::from_seconds(
+                MACHINE_TICK_INTERVAL,
+                TimerMode::Repeating,
+            )))
+            .init_resource::<PowerNetworks>()
+            .init_resource::<PipeNetworks>()
+            .add_systems(
+                Update,
+                (
+                    tick_machine_timer,
+                    (
+                        (sync_block_entities, apply_block_entity_edits).chain(),
+                        (add_generated_blocks::<Power>, update_networks::<Power>).chain(),
+                        (add_generated_blocks::<Pipes>, update_networks::<Pipes>).chain(),
+                    )
+                        .after(edit::apply_block_edits),
+                    tick_block_entities,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Draws the items on conveyors. Kept apart from `MachinePlugin`, since clients draw the conveyors
+/// that the server simulates, and a headless server has nothing to draw with.
+pub struct BeltItemPlugin;
+
+impl Plugin for BeltItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<belt_items::BeltItemVisuals>()
+            .add_systems(Update, belt_items::draw_belt_items);
+    }
+}
+
+#[derive(Resource)]
+pub struct MachineTickTimer(pub Timer);
+
+/// Machines that turn an input into an output with processing recipes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum ProcessingMachine {
+    Furnace,
+    Crusher,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum BlockEntity {
+    Furnace(Furnace),
+    Crusher(Crusher),
+    Assembler(Assembler),
+    /// Conveyors, splitters and mergers, which all carry items the same way.
+    Conveyor(Conveyor),
+    Inserter(Inserter),
+    Generator(Generator),
+    Battery(Battery),
+    Pipe(FluidTank),
+    Tank(FluidTank),
+    Pump(Pump),
+    Boiler(Boiler),
+}
+
+/// Converts a duration in seconds to a whole number of machine ticks, which is at least one.
+pub fn seconds_to_ticks(seconds: f32) -> u32 {
+    (seconds / MACHINE_TICK_INTERVAL).round().max(1.0) as u32
+}
+
+impl BlockEntity {
+    /// A fresh block entity for the block, if it needs one.
+    pub fn for_block(block: Block) -> Option<Self> {
+        match block {
+            Block::Furnace(_) => Some(BlockEntity::Furnace(Furnace::new())),
+            Block::Crusher(_) => Some(BlockEntity::Crusher(Crusher::new())),
+            Block::Assembler(_) => Some(BlockEntity::Assembler(Assembler::new())),
+            Block::Conveyor(_) | Block::Splitter(_) | Block::Merger(_) => {
+                Some(BlockEntity::Conveyor(Conveyor::new()))
+            }
+            Block::Inserter(_) => Some(BlockEntity::Inserter(Inserter::new())),
+            Block::Generator(_) => Some(BlockEntity::Generator(Generator::new())),
+            Block::Battery => Some(BlockEntity::Battery(Battery::new())),
+            Block::Pipe => Some(BlockEntity::Pipe(FluidTank::new(
+                PIPE_CAPACITY,
+                PIPE_FLOW_RATE,
+            ))),
+            Block::Tank => Some(BlockEntity::Tank(FluidTank::new(
+                TANK_CAPACITY,
+                TANK_FLOW_RATE,
+            ))),
+            Block::Pump => Some(BlockEntity::Pump(Pump::new())),
+            Block::Boiler(_) => Some(BlockEntity::Boiler(Boiler::new())),
+            _ => None,
+        }
+    }
+
+    /// Runs one machine tick. Conveyors and inserters move items between blocks, so they're run
+    /// by `transport` instead, generators and batteries are run by their power network, and pipes,
+    /// tanks and pumps by `pipes`.
+    pub fn tick(&mut self, recipes: &RecipeRegistry) {
+        match self {
+            BlockEntity::Furnace(furnace) => furnace.tick(recipes),
+            BlockEntity::Crusher(crusher) => crusher.tick(recipes),
+            BlockEntity::Assembler(assembler) => assembler.tick(recipes),
+            BlockEntity::Boiler(boiler) => boiler.tick(),
+            _ => {}
+        }
+    }
+
+    /// How much energy the machine asks its power network for this tick.
+    pub fn power_demand(&self) -> u32 {
+        match self {
+            BlockEntity::Crusher(crusher) => MACHINE_ENERGY_BUFFER.saturating_sub(crusher.energy),
+            BlockEntity::Assembler(assembler) => {
+                MACHINE_ENERGY_BUFFER.saturating_sub(assembler.energy)
+            }
+            BlockEntity::Pump(pump) => MACHINE_ENERGY_BUFFER.saturating_sub(pump.energy),
+            _ => 0,
+        }
+    }
+
+    /// Gives the machine energy from its power network, at most as much as it asked for.
+    pub fn receive_power(&mut self, energy: u32) {
+        match self {
+            BlockEntity::Crusher(crusher) => crusher.energy += energy,
+            BlockEntity::Assembler(assembler) => assembler.energy += energy,
+            BlockEntity::Pump(pump) => pump.energy += energy,
+            _ => {}
+        }
+    }
+
+    /// Puts items into the machine, wherever they belong. Returns whatever didn't fit.
+    pub fn insert(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        match self {
+            BlockEntity::Furnace(furnace) => furnace.insert(stack),
+            BlockEntity::Crusher(crusher) => crusher.input.insert(stack),
+            BlockEntity::Assembler(assembler) => assembler.input.insert(stack),
+            BlockEntity::Conveyor(conveyor) => conveyor.insert(stack),
+            BlockEntity::Generator(generator) => generator.insert(stack),
+            BlockEntity::Boiler(boiler) => boiler.insert(stack),
+            _ => Some(stack),
+        }
+    }
+
+    /// Where finished items are taken out of the machine, if it makes anything.
+    pub fn output_mut(&mut self) -> Option<&mut Inventory> {
+        match self {
+            BlockEntity::Furnace(furnace) => Some(&mut furnace.output),
+            BlockEntity::Crusher(crusher) => Some(&mut crusher.output),
+            BlockEntity::Assembler(assembler) => Some(&mut assembler.output),
+            _ => None,
+        }
+    }
+
+    /// Whether fluid flows both in and out of the same tank, evening out with the blocks next to
+    /// it. Machines instead push out all the fluid they make, and take in as much as they can.
+    pub fn is_fluid_storage(&self) -> bool {
+        matches!(self, BlockEntity::Pipe(_) | BlockEntity::Tank(_))
+    }
+
+    /// The tank fluid flows out of, into the blocks next to it.
+    pub fn fluid_output(&mut self) -> Option<&mut FluidTank> {
+        match self {
+            BlockEntity::Pipe(tank) | BlockEntity::Tank(tank) => Some(tank),
+            BlockEntity::Pump(pump) => Some(&mut pump.tank),
+            BlockEntity::Boiler(boiler) => Some(&mut boiler.steam),
+            _ => None,
+        }
+    }
+
+    /// The tank that takes in the fluid, if the block takes that fluid at all.
+    pub fn fluid_input(&mut self, fluid: Fluid) -> Option<&mut FluidTank> {
+        match self {
+            BlockEntity::Pipe(tank) | BlockEntity::Tank(tank) => Some(tank),
+            BlockEntity::Boiler(boiler) => boiler.fluid_input(fluid),
+            BlockEntity::Generator(generator) => generator.fluid_input(fluid),
+            _ => None,
+        }
+    }
+
+    /// Everything in the machine's inventories, which is dropped when it's broken.
+    pub fn contents(&self) -> Vec<ItemStack> {
+        let inventories = match self {
+            BlockEntity::Furnace(furnace) => vec![&furnace.input, &furnace.fuel, &furnace.output],
+            BlockEntity::Crusher(crusher) => vec![&crusher.input, &crusher.output],
+            BlockEntity::Assembler(assembler) => vec![&assembler.input, &assembler.output],
+            BlockEntity::Generator(generator) => vec![&generator.fuel],
+            BlockEntity::Boiler(boiler) => vec![&boiler.fuel],
+            _ => vec![],
+        };
+
+        // Items that are being moved along, rather than kept in an inventory
+        let carried: Vec<Item> = match self {
+            BlockEntity::Conveyor(conveyor) => conveyor
+                .items
+                .iter()
+                .map(|belt_item| belt_item.item)
+                .collect(),
+            BlockEntity::Inserter(inserter) => inserter.held.into_iter().collect(),
+            _ => Vec::new(),
+        };
+
+        inventories
+            .into_iter()
+            .flat_map(|inventory| (0..inventory.size()).filter_map(|slot| inventory.get(slot)))
+            .chain(carried.into_iter().map(|item| ItemStack::new(item, 1)))
+            .collect()
+    }
+}
+
+pub fn tick_machine_timer(mut timer: ResMut<MachineTickTimer>, time: Res<Time>) {
+    timer.0.tick(time.delta());
+}
+
+/// Creates block entities for machines that were placed, and removes those of machines that were
+/// broken, dropping what was in them unless the change says not to.
+pub fn sync_block_entities(
+    mut changes: EventReader<BlockChanged>,
+    mut drops: EventWriter<DropItem>,
+    generated_chunks: Res<GeneratedChunks>,
+) {
+    let mut map = generated_chunks.map.lock().unwrap();
+
+    for change in changes.iter() {
+        let (chunk_pos, local_pos) = world_to_chunk_pos(change.pos);
+
+        let Some(chunk) = map.get_mut(&chunk_pos) else {
+            continue;
+        };
+
+        let block_entity = BlockEntity::for_block(change.block);
+
+        // Still the same kind of machine, so keep its state
+        let existing = chunk.block_entities.get(&local_pos);
+        if existing.map(mem::discriminant) == block_entity.as_ref().map(mem::discriminant) {
+            continue;
+        }
+
+        let removed = chunk.block_entities.remove(&local_pos);
+
+        if let Some(removed) = removed.filter(|_| change.drops_contents) {
+            for stack in removed.contents() {
+                drops.send(DropItem {
+                    pos: change.pos.as_vec3() + Vec3::splat(0.5),
+                    stack,
+                });
+            }
+        }
+
+        if let Some(block_entity) = block_entity {
+            chunk.block_entities.insert(local_pos, block_entity);
+        }
+    }
+}
+
+/// Replaces the state of block entities, once they've been created for their blocks.
+pub fn apply_block_entity_edits(
+    mut edits: EventReader<BlockEntityEdit>,
+    generated_chunks: Res<GeneratedChunks>,
+) {
+    let mut map = generated_chunks.map.lock().unwrap();
+
+    for edit in edits.iter() {
+        let Some(existing) = get_block_entity_mut(&mut map, edit.pos) else {
+            continue;
+        };
+
+        if mem::discriminant(existing) == mem::discriminant(&edit.block_entity) {
+            *existing = edit.block_entity.clone();
+        }
+    }
+}
+
+/// Runs a machine tick for every block entity in the view distance of a chunk loader, for every
+/// tick interval that passed since the last frame. Power is handed out before every tick, and
+/// fluids and items are moved between blocks after it. Cables that lit up or went dark are sent
+/// on as changed blocks, so they're meshed again.
+#[allow(clippy::too_many_arguments)]
+pub fn tick_block_entities(
+    generated_chunks: Res<GeneratedChunks>,
+    loader_query: Query<&Transform, With<ChunkLoader>>,
+    view_distance: Res<ViewDistance>,
+    recipes: Res<RecipeRegistry>,
+    power_networks: Res<PowerNetworks>,
+    pipe_networks: Res<PipeNetworks>,
+    mut changes: EventWriter<BlockChanged>,
+    timer: Res<MachineTickTimer>,
+) {
+    let ticks = timer.0.times_finished_this_tick();
+
+    if ticks == 0 {
+        return;
+    }
+
+    let loader_chunk_positions = loader_chunk_positions(&loader_query);
+    let mut map = generated_chunks.map.lock().unwrap();
+
+    let active_chunks: HashSet<(i32, i32, i32)> = map
+        .iter()
+        .filter(|(chunk_pos, chunk)| {
+            !chunk.block_entities.is_empty()
+                && is_in_view_distance(
+                    IVec3::from(**chunk_pos),
+                    &loader_chunk_positions,
+                    *view_distance,
+                )
+        })
+        .map(|(chunk_pos, _)| *chunk_pos)
+        .collect();
+
+    let transport_positions = transport_positions(
+        active_chunks
+            .iter()
+            .filter_map(|chunk_pos| map.get(chunk_pos)),
+    );
+    let is_active = |pos: IVec3| active_chunks.contains(&world_to_chunk_pos(pos).0);
+    let mut changed_cables = HashMap::new();
+
+    for _ in 0..ticks {
+        changed_cables.extend(step_power(&power_networks, &mut map, is_active));
+
+        for chunk_pos in &active_chunks {
+            let chunk = map.get_mut(chunk_pos).unwrap();
+
+            for block_entity in chunk.block_entities.values_mut() {
+                block_entity.tick(&recipes);
+            }
+        }
+
+        step_fluids(&pipe_networks, &mut map, is_active);
+        step_transport(&mut map, &transport_positions);
+    }
+
+    for (pos, block) in changed_cables {
+        changes.send(BlockChanged {
+            pos,
+            block,
+            drops_contents: true,
+        });
+    }
+}
@@ -0,0 +1,220 @@
+//! Groups of connected blocks that work together, like cables and the machines they power, or
+//! pipes and the tanks they fill.
+//!
+//! Every group of connecting blocks touching each other is one network. Networks are found by flood
+//! fill, but only where blocks change: placing a block joins up the networks around it, and
+//! breaking one only searches the network it was part of, to see whether it fell apart.
+
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::{Chunk, ChunkGenerated, GeneratedChunks, CHUNK_SIZE};
+use crate::worldgen::edit::BlockChanged;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::marker::PhantomData;
+
+const NEIGHBOR_OFFSETS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Which blocks make up a kind of network.
+pub trait NetworkKind: Send + Sync + 'static {
+    fn connects(block: Block) -> bool;
+}
+
+#[derive(Default, Debug)]
+pub struct Network {
+    pub members: HashSet<IVec3>,
+}
+
+/// Every network of one kind in the world, kept up to date as their blocks are placed and broken.
+#[derive(Resource, Debug)]
+pub struct Networks<K: NetworkKind> {
+    networks: HashMap<u32, Network>,
+    network_of: HashMap<IVec3, u32>,
+    next_id: u32,
+    kind: PhantomData<K>,
+}
+
+impl<K: NetworkKind> Default for Networks<K> {
+    fn default() -> Self {
+        Self {
+            networks: HashMap::new(),
+            network_of: HashMap::new(),
+            next_id: 0,
+            kind: PhantomData,
+        }
+    }
+}
+
+impl<K: NetworkKind> Networks<K> {
+    /// The id of the network the block at the position is part of.
+    pub fn network_at(&self, pos: IVec3) -> Option<u32> {
+        self.network_of.get(&pos).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &Network)> {
+        self.networks.iter().map(|(id, network)| (*id, network))
+    }
+
+    fn new_network(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.networks.insert(id, Network::default());
+
+        id
+    }
+
+    /// Adds a block, joining together every network it touches.
+    pub fn add(&mut self, pos: IVec3) {
+        if self.network_of.contains_key(&pos) {
+            return;
+        }
+
+        let mut touching: Vec<u32> = NEIGHBOR_OFFSETS
+            .iter()
+            .filter_map(|offset| self.network_at(pos + *offset))
+            .collect();
+        touching.sort_unstable();
+        touching.dedup();
+
+        // Everything joins the biggest network, so the fewest blocks have to move
+        let id = touching
+            .iter()
+            .copied()
+            .max_by_key(|id| (self.networks[id].members.len(), Reverse(*id)))
+            .unwrap_or_else(|| self.new_network());
+
+        for other in touching.into_iter().filter(|other| *other != id) {
+            let merged = self.networks.remove(&other).unwrap();
+
+            for member in merged.members.iter() {
+                self.network_of.insert(*member, id);
+            }
+            self.networks
+                .get_mut(&id)
+                .unwrap()
+                .members
+                .extend(merged.members);
+        }
+
+        self.network_of.insert(pos, id);
+        self.networks.get_mut(&id).unwrap().members.insert(pos);
+    }
+
+    /// Adds every block of this kind in the chunk, joining them up with the networks around it.
+    pub fn add_chunk(&mut self, chunk: &Chunk) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    if K::connects(chunk.voxels[x][y][z]) {
+                        self.add(chunk.pos + UVec3::new(x as u32, y as u32, z as u32).as_ivec3());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes a block. If it was what held its network together, the network is split up
+    /// into one for every part that's left.
+    pub fn remove(&mut self, pos: IVec3) {
+        let Some(id) = self.network_of.remove(&pos) else {
+            return;
+        };
+
+        let network = self.networks.get_mut(&id).unwrap();
+        network.members.remove(&pos);
+
+        if network.members.is_empty() {
+            self.networks.remove(&id);
+            return;
+        }
+
+        // Only the blocks that were connected through the removed one can have been cut off
+        let mut parts: Vec<HashSet<IVec3>> = Vec::new();
+
+        for offset in NEIGHBOR_OFFSETS {
+            let neighbor = pos + offset;
+
+            if self.network_at(neighbor) == Some(id)
+                && !parts.iter().any(|part| part.contains(&neighbor))
+            {
+                parts.push(self.flood_fill(neighbor, id));
+            }
+        }
+
+        if parts.len() <= 1 {
+            return;
+        }
+
+        // The biggest part keeps the network's id, so the fewest blocks have to move
+        parts.sort_by_key(|part| Reverse(part.len()));
+
+        for part in parts.into_iter().skip(1) {
+            let new_id = self.new_network();
+
+            for member in part.iter() {
+                self.network_of.insert(*member, new_id);
+                self.networks.get_mut(&id).unwrap().members.remove(member);
+            }
+            self.networks.get_mut(&new_id).unwrap().members = part;
+        }
+    }
+
+    /// Every block of the network that's connected to `start`.
+    fn flood_fill(&self, start: IVec3, id: u32) -> HashSet<IVec3> {
+        let mut found = HashSet::from_iter([start]);
+        let mut stack = vec![start];
+
+        while let Some(pos) = stack.pop() {
+            for offset in NEIGHBOR_OFFSETS {
+                let neighbor = pos + offset;
+
+                if self.network_at(neighbor) == Some(id) && found.insert(neighbor) {
+                    stack.push(neighbor);
+                }
+            }
+        }
+
+        found
+    }
+}
+
+/// Adds placed blocks to the networks, and removes broken ones.
+pub fn update_networks<K: NetworkKind>(
+    mut changes: EventReader<BlockChanged>,
+    mut networks: ResMut<Networks<K>>,
+) {
+    for change in changes.iter() {
+        if K::connects(change.block) {
+            networks.add(change.pos);
+        } else {
+            networks.remove(change.pos);
+        }
+    }
+}
+
+/// Adds the blocks that chunks already have when they're generated. Blocks placed after that are
+/// added by `update_networks`.
+pub fn add_generated_blocks<K: NetworkKind>(
+    mut generated: EventReader<ChunkGenerated>,
+    generated_chunks: Res<GeneratedChunks>,
+    mut networks: ResMut<Networks<K>>,
+) {
+    if generated.is_empty() {
+        return;
+    }
+
+    let map = generated_chunks.map.lock().unwrap();
+
+    for ChunkGenerated(chunk_pos) in generated.iter() {
+        if let Some(chunk) = map.get(&(chunk_pos.x, chunk_pos.y, chunk_pos.z)) {
+            networks.add_chunk(chunk);
+        }
+    }
+}
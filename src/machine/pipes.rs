@@ -0,0 +1,433 @@
+//! Moves fluids between pipes, tanks and the machines connected to them.
+//!
+//! Every group of fluid blocks touching each other is one pipe network, kept up to date like power
+//! networks are. Every pipe segment, tank and machine in a network has a tank of its own, with its
+//! own capacity and flow rate, so fluid moves through a network only as fast as its slowest
+//! segment lets it.
+//!
+//! Pipes and tanks even out with each other, so fluid spreads through a network of them until
+//! they're all equally full. Machines push out all the fluid they make, and take in as much as
+//! they can. Fluid only ever moves from one tank to another, so none is made or lost on the way;
+//! only pumps and machines make and use it up.
+//!
+//! Every tick, each pair of neighbouring blocks in a network gets one turn to move fluid, in order
+//! of position, so the same world always ends up the same way.
+
+use crate::machine::fluid_tank::{Fluid, FluidTank};
+use crate::machine::network::{NetworkKind, Networks};
+use crate::machine::BlockEntity;
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::{get_block, get_block_entity_mut};
+use crate::worldgen::chunk::Chunk;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+/// How much fluid one pipe holds.
+pub const PIPE_CAPACITY: u32 = 200;
+/// The most fluid that flows in or out of a pipe in one tick.
+pub const PIPE_FLOW_RATE: u32 = 50;
+/// How much fluid a tank holds.
+pub const TANK_CAPACITY: u32 = 16_000;
+/// The most fluid that flows in or out of a tank in one tick.
+pub const TANK_FLOW_RATE: u32 = 200;
+/// How much water a pump draws from a water source every tick.
+pub const PUMP_RATE: u32 = 20;
+/// How much energy a pump uses every tick it's pumping.
+pub const PUMP_POWER_USE: u32 = 2;
+
+const NEIGHBORS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+/// Only the positive directions, so every pair of neighbours is only visited once.
+const FORWARD_NEIGHBORS: [IVec3; 3] = [IVec3::X, IVec3::Y, IVec3::Z];
+
+/// Draws water out of a water source next to it, as long as it has power. The source never runs
+/// dry.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Pump {
+    pub tank: FluidTank,
+    /// Energy received from the power network, that hasn't been used yet.
+    pub energy: u32,
+}
+
+impl Pump {
+    pub fn new() -> Self {
+        Self {
+            tank: FluidTank::new(PUMP_RATE * 10, PIPE_FLOW_RATE),
+            energy: 0,
+        }
+    }
+}
+
+/// Pipes, tanks, and the pumps and machines they connect.
+#[derive(Debug)]
+pub struct Pipes;
+
+impl NetworkKind for Pipes {
+    fn connects(block: Block) -> bool {
+        block.holds_fluid()
+    }
+}
+
+pub type PipeNetworks = Networks<Pipes>;
+
+/// Runs one tick of every pipe network: pumps draw water, and fluid flows between the blocks of
+/// the network.
+///
+/// Only the blocks that `is_active` is true for take part.
+pub fn step_fluids(
+    networks: &PipeNetworks,
+    map: &mut HashMap<(i32, i32, i32), Chunk>,
+    is_active: impl Fn(IVec3) -> bool,
+) {
+    for (_, network) in networks.iter() {
+        let mut members: Vec<IVec3> = network
+            .members
+            .iter()
+            .copied()
+            .filter(|pos| is_active(*pos))
+            .collect();
+        members.sort_unstable_by_key(|pos| pos.to_array());
+
+        step_network(map, &members);
+    }
+}
+
+fn step_network(map: &mut HashMap<(i32, i32, i32), Chunk>, members: &[IVec3]) {
+    for &pos in members {
+        let next_to_water = NEIGHBORS
+            .iter()
+            .any(|offset| get_block(map, pos + *offset) == Some(Block::Water));
+
+        if let Some(BlockEntity::Pump(pump)) = get_block_entity_mut(map, pos) {
+            if next_to_water && pump.energy >= PUMP_POWER_USE {
+                pump.energy -= PUMP_POWER_USE;
+                pump.tank.fill(Fluid::Water, PUMP_RATE);
+            }
+        }
+    }
+
+    let in_network: HashSet<IVec3> = members.iter().copied().collect();
+
+    for &pos in members {
+        for offset in FORWARD_NEIGHBORS {
+            let neighbor = pos + offset;
+
+            if in_network.contains(&neighbor) {
+                flow(map, pos, neighbor);
+                flow(map, neighbor, pos);
+            }
+        }
+    }
+}
+
+/// The state of a tank, copied out so two tanks can be compared without borrowing both.
+struct TankState {
+    fluid: Option<Fluid>,
+    amount: u32,
+    capacity: u32,
+    flow_rate: u32,
+    /// Whether the tank belongs to a pipe or a tank, rather than a machine.
+    storage: bool,
+}
+
+impl TankState {
+    fn new(tank: &FluidTank, storage: bool) -> Self {
+        Self {
+            fluid: tank.fluid,
+            amount: tank.amount,
+            capacity: tank.capacity,
+            flow_rate: tank.flow_rate,
+            storage,
+        }
+    }
+}
+
+fn output_state(map: &mut HashMap<(i32, i32, i32), Chunk>, pos: IVec3) -> Option<TankState> {
+    let block_entity = get_block_entity_mut(map, pos)?;
+    let storage = block_entity.is_fluid_storage();
+
+    block_entity
+        .fluid_output()
+        .map(|tank| TankState::new(tank, storage))
+}
+
+fn input_state(
+    map: &mut HashMap<(i32, i32, i32), Chunk>,
+    pos: IVec3,
+    fluid: Fluid,
+) -> Option<TankState> {
+    let block_entity = get_block_entity_mut(map, pos)?;
+    let storage = block_entity.is_fluid_storage();
+
+    block_entity
+        .fluid_input(fluid)
+        .map(|tank| TankState::new(tank, storage))
+}
+
+/// Moves fluid from the block at `from` to the block at `to`, as much as both their flow rates
+/// allow.
+fn flow(map: &mut HashMap<(i32, i32, i32), Chunk>, from: IVec3, to: IVec3) {
+    let Some(source) = output_state(map, from) else {
+        return;
+    };
+    let Some(fluid) = source.fluid else {
+        return;
+    };
+    let Some(destination) = input_state(map, to, fluid) else {
+        return;
+    };
+
+    let room = match destination.fluid {
+        Some(existing) if existing != fluid => 0,
+        _ => destination.capacity - destination.amount,
+    };
+
+    let mut amount = source
+        .amount
+        .min(room)
+        .min(source.flow_rate)
+        .min(destination.flow_rate);
+
+    // Pipes and tanks only flow into each other until they're equally full
+    if source.storage && destination.storage {
+        let imbalance = (source.amount as u64 * destination.capacity as u64)
+            .saturating_sub(destination.amount as u64 * source.capacity as u64);
+        let even_out = imbalance / (source.capacity + destination.capacity) as u64;
+
+        amount = amount.min(even_out as u32);
+    }
+
+    if amount == 0 {
+        return;
+    }
+
+    let filled = get_block_entity_mut(map, to)
+        .and_then(|block_entity| block_entity.fluid_input(fluid))
+        .map_or(0, |tank| tank.fill(fluid, amount));
+
+    if let Some(tank) =
+        get_block_entity_mut(map, from).and_then(|block_entity| block_entity.fluid_output())
+    {
+        tank.drain(filled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::block::Facing;
+    use crate::worldgen::chunk::access::set_block;
+
+    /// A single chunk at the origin with fluid blocks in it, connected into networks like
+    /// `update_networks` does.
+    struct World {
+        map: HashMap<(i32, i32, i32), Chunk>,
+        networks: PipeNetworks,
+    }
+
+    impl World {
+        fn new(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> Self {
+            let mut map = HashMap::new();
+            map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+            let mut networks = PipeNetworks::default();
+
+            for (pos, block) in blocks {
+                set_block(&mut map, pos, block);
+                if let Some(block_entity) = BlockEntity::for_block(block) {
+                    let chunk = map.get_mut(&(0, 0, 0)).unwrap();
+                    chunk.block_entities.insert(pos.as_uvec3(), block_entity);
+                }
+                if block.holds_fluid() {
+                    networks.add(pos);
+                }
+            }
+
+            Self { map, networks }
+        }
+
+        fn step(&mut self, ticks: u32) {
+            for _ in 0..ticks {
+                step_fluids(&self.networks, &mut self.map, |_| true);
+            }
+        }
+
+        fn tank(&mut self, pos: IVec3) -> &mut FluidTank {
+            match get_block_entity_mut(&mut self.map, pos).unwrap() {
+                BlockEntity::Pipe(tank) | BlockEntity::Tank(tank) => tank,
+                BlockEntity::Pump(pump) => &mut pump.tank,
+                _ => panic!("no tank"),
+            }
+        }
+
+        fn water(&mut self, pos: IVec3) -> u32 {
+            let tank = self.tank(pos);
+            if tank.fluid == Some(Fluid::Water) {
+                tank.amount
+            } else {
+                0
+            }
+        }
+    }
+
+    fn at(x: i32, z: i32) -> IVec3 {
+        IVec3::new(x, 1, z)
+    }
+
+    #[test]
+    fn touching_fluid_blocks_make_one_network() {
+        let world = World::new([
+            (at(1, 1), Block::Tank),
+            (at(2, 1), Block::Pipe),
+            (at(3, 1), Block::Generator(Facing::North)),
+            (at(5, 1), Block::Pipe),
+            (at(6, 1), Block::Cable(false)),
+        ]);
+
+        assert_eq!(world.networks.iter().count(), 2);
+        assert_eq!(
+            world.networks.network_at(at(1, 1)),
+            world.networks.network_at(at(3, 1))
+        );
+        assert_ne!(
+            world.networks.network_at(at(3, 1)),
+            world.networks.network_at(at(5, 1))
+        );
+        assert_eq!(world.networks.network_at(at(6, 1)), None);
+    }
+
+    #[test]
+    fn pipes_and_tanks_even_out() {
+        let mut world = World::new([
+            (at(1, 1), Block::Tank),
+            (at(2, 1), Block::Pipe),
+            (at(3, 1), Block::Tank),
+        ]);
+        world.tank(at(1, 1)).fill(Fluid::Water, 10_000);
+
+        world.step(1000);
+
+        // Only whole litres flow, and a litre in the pipe is as full as 80 in a tank, so the
+        // tanks can each be that far off from the pipe
+        let tolerance = 2 * TANK_CAPACITY / PIPE_CAPACITY;
+        assert!(world.water(at(1, 1)).abs_diff(world.water(at(3, 1))) <= tolerance);
+        assert!(world.water(at(2, 1)) > 0);
+    }
+
+    #[test]
+    fn fluid_is_neither_made_nor_lost_in_a_branching_network() {
+        // A tank feeding a pipe that branches three ways, with a tank at the end of every branch
+        let mut blocks = vec![(at(1, 4), Block::Tank)];
+        blocks.extend((2..=6).map(|x| (at(x, 4), Block::Pipe)));
+        blocks.extend([1, 2, 3, 5, 6, 7].map(|z| (at(4, z), Block::Pipe)));
+        blocks.extend([
+            (at(7, 4), Block::Tank),
+            (at(4, 0), Block::Tank),
+            (at(4, 8), Block::Tank),
+        ]);
+        let tanks: Vec<IVec3> = blocks.iter().map(|(pos, _)| *pos).collect();
+
+        let mut world = World::new(blocks);
+        world.tank(at(1, 4)).fill(Fluid::Water, 12_000);
+        assert_eq!(world.networks.iter().count(), 1);
+
+        for _ in 0..500 {
+            world.step(1);
+
+            let total: u32 = tanks.iter().map(|pos| world.water(*pos)).sum();
+            assert_eq!(total, 12_000);
+        }
+
+        for end in [at(7, 4), at(4, 0), at(4, 8)] {
+            assert!(world.water(end) > 0);
+        }
+    }
+
+    #[test]
+    fn flow_is_limited_by_the_slowest_segment() {
+        let mut world = World::new([
+            (at(1, 1), Block::Tank),
+            (at(2, 1), Block::Pipe),
+            (at(3, 1), Block::Pipe),
+            (at(4, 1), Block::Tank),
+        ]);
+        world.tank(at(1, 1)).fill(Fluid::Water, TANK_CAPACITY);
+        world.tank(at(3, 1)).flow_rate = 5;
+
+        world.step(100);
+
+        for _ in 0..20 {
+            let before = world.water(at(4, 1));
+            world.step(1);
+            let flowed = world.water(at(4, 1)) - before;
+
+            assert!(flowed > 0 && flowed <= 5, "{} flowed", flowed);
+        }
+    }
+
+    #[test]
+    fn segments_never_hold_more_than_their_capacity() {
+        let mut world = World::new([
+            (at(1, 1), Block::Tank),
+            (at(2, 1), Block::Pipe),
+            (at(3, 1), Block::Pipe),
+        ]);
+        world.tank(at(1, 1)).fill(Fluid::Water, TANK_CAPACITY);
+        world.tank(at(2, 1)).capacity = 30;
+
+        for _ in 0..100 {
+            world.step(1);
+
+            assert!(world.water(at(2, 1)) <= 30);
+            assert!(world.water(at(3, 1)) <= PIPE_CAPACITY);
+        }
+    }
+
+    #[test]
+    fn separate_networks_do_not_share_fluid() {
+        let mut world = World::new([(at(1, 1), Block::Tank), (at(3, 1), Block::Tank)]);
+        world.tank(at(1, 1)).fill(Fluid::Water, 1000);
+
+        world.step(100);
+
+        assert_eq!(world.water(at(1, 1)), 1000);
+        assert_eq!(world.water(at(3, 1)), 0);
+    }
+
+    #[test]
+    fn different_fluids_do_not_mix() {
+        let mut world = World::new([(at(1, 1), Block::Tank), (at(2, 1), Block::Tank)]);
+        world.tank(at(1, 1)).fill(Fluid::Water, 1000);
+        world.tank(at(2, 1)).fill(Fluid::Steam, 10);
+
+        world.step(100);
+
+        assert_eq!(world.water(at(1, 1)), 1000);
+        assert_eq!(world.tank(at(2, 1)).amount, 10);
+    }
+
+    #[test]
+    fn pumps_draw_water_from_a_source_with_power() {
+        let mut world = World::new([
+            (at(1, 1), Block::Water),
+            (at(2, 1), Block::Pump),
+            (at(3, 1), Block::Tank),
+        ]);
+
+        world.step(10);
+        assert_eq!(world.water(at(3, 1)), 0);
+
+        if let Some(BlockEntity::Pump(pump)) = get_block_entity_mut(&mut world.map, at(2, 1)) {
+            pump.energy = PUMP_POWER_USE * 3;
+        }
+        world.step(10);
+
+        assert_eq!(world.water(at(2, 1)) + world.water(at(3, 1)), PUMP_RATE * 3);
+    }
+}
@@ -0,0 +1,483 @@
+//! Power networks: generators, batteries and the machines that use power, connected by cables.
+
+use crate::machine::network::{NetworkKind, Networks};
+use crate::machine::BlockEntity;
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::{get_block, get_block_entity_mut, set_block};
+use crate::worldgen::chunk::Chunk;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// The most energy a machine stores to run on, so it keeps going through a short lack of power.
+pub const MACHINE_ENERGY_BUFFER: u32 = 100;
+
+/// Cables, and the generators, batteries and machines they connect.
+#[derive(Debug)]
+pub struct Power;
+
+impl NetworkKind for Power {
+    fn connects(block: Block) -> bool {
+        block.conducts_power()
+    }
+}
+
+pub type PowerNetworks = Networks<Power>;
+
+/// Runs one tick of every power network: generators make energy, machines take what they need,
+/// and batteries store what's left over or make up for what's missing. If there isn't enough for
+/// everything, every machine gets the same share of what there is. Cables light up while their
+/// network has power to give, and the ones that changed are returned.
+///
+/// Only the blocks that `is_active` is true for take part.
+pub fn step_power(
+    networks: &PowerNetworks,
+    map: &mut HashMap<(i32, i32, i32), Chunk>,
+    is_active: impl Fn(IVec3) -> bool,
+) -> Vec<(IVec3, Block)> {
+    let mut changed = Vec::new();
+
+    for (_, network) in networks.iter() {
+        let mut members: Vec<IVec3> = network
+            .members
+            .iter()
+            .copied()
+            .filter(|pos| is_active(*pos))
+            .collect();
+        members.sort_unstable_by_key(|pos| pos.to_array());
+
+        let powered = balance_network(map, &members);
+
+        for &pos in &members {
+            if matches!(get_block(map, pos), Some(Block::Cable(was_powered)) if was_powered != powered)
+            {
+                set_block(map, pos, Block::Cable(powered));
+                changed.push((pos, Block::Cable(powered)));
+            }
+        }
+    }
+
+    changed
+}
+
+/// Returns whether the network has any power to give, from generators or batteries.
+fn balance_network(map: &mut HashMap<(i32, i32, i32), Chunk>, members: &[IVec3]) -> bool {
+    let mut demand = 0;
+    let mut battery_room = 0;
+    let mut battery_charge = 0;
+
+    for &pos in members {
+        match get_block_entity_mut(map, pos) {
+            Some(BlockEntity::Battery(battery)) => {
+                battery_room += battery.room();
+                battery_charge += battery.charge;
+            }
+            Some(block_entity) => demand += block_entity.power_demand(),
+            None => {}
+        }
+    }
+
+    // Generators only burn fuel when there's a use for the power
+    let wanted = demand > 0 || battery_room > 0;
+    let mut supply = 0;
+
+    for &pos in members {
+        if let Some(BlockEntity::Generator(generator)) = get_block_entity_mut(map, pos) {
+            supply += generator.generate(wanted);
+        }
+    }
+
+    let mut to_discharge = demand.saturating_sub(supply).min(battery_charge);
+    let mut to_charge = supply.saturating_sub(demand);
+    let available = (supply + to_discharge).min(demand);
+
+    for &pos in members {
+        match get_block_entity_mut(map, pos) {
+            Some(BlockEntity::Battery(battery)) => {
+                to_discharge -= battery.discharge(to_discharge);
+                to_charge -= battery.charge(to_charge);
+            }
+            Some(block_entity) => {
+                let wants = block_entity.power_demand();
+
+                if wants > 0 {
+                    let share = (wants as u64 * available as u64 / demand as u64) as u32;
+                    block_entity.receive_power(share);
+                }
+            }
+            None => {}
+        }
+    }
+
+    supply > 0 || battery_charge > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::{Item, ItemStack, Material};
+    use crate::machine::battery::BATTERY_CAPACITY;
+    use crate::machine::generator::GENERATOR_POWER;
+    use crate::worldgen::block::Facing;
+    use crate::worldgen::chunk::CHUNK_SIZE;
+
+    const GENERATOR: Block = Block::Generator(Facing::North);
+    const CRUSHER: Block = Block::Crusher(Facing::North);
+
+    fn networks_of(positions: impl IntoIterator<Item = IVec3>) -> PowerNetworks {
+        let mut networks = PowerNetworks::default();
+        for pos in positions {
+            networks.add(pos);
+        }
+        networks
+    }
+
+    fn line(xs: impl IntoIterator<Item = i32>) -> Vec<IVec3> {
+        xs.into_iter().map(|x| IVec3::new(x, 1, 1)).collect()
+    }
+
+    /// The members of every network, sorted so they can be compared.
+    fn members(networks: &PowerNetworks) -> Vec<Vec<IVec3>> {
+        let mut all: Vec<Vec<IVec3>> = networks
+            .iter()
+            .map(|(_, network)| {
+                let mut members: Vec<IVec3> = network.members.iter().copied().collect();
+                members.sort_unstable_by_key(|pos| pos.to_array());
+                members
+            })
+            .collect();
+        all.sort_by_key(|members| members.first().map(|pos| pos.to_array()));
+        all
+    }
+
+    /// A single chunk at the origin with power blocks in it, connected into networks like
+    /// `update_power_networks` does.
+    struct World {
+        map: HashMap<(i32, i32, i32), Chunk>,
+        networks: PowerNetworks,
+    }
+
+    impl World {
+        fn new(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> Self {
+            let mut map = HashMap::new();
+            map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+            let mut networks = PowerNetworks::default();
+
+            for (pos, block) in blocks {
+                set_block(&mut map, pos, block);
+                if let Some(block_entity) = BlockEntity::for_block(block) {
+                    let chunk = map.get_mut(&(0, 0, 0)).unwrap();
+                    chunk.block_entities.insert(pos.as_uvec3(), block_entity);
+                }
+                networks.add(pos);
+            }
+
+            Self { map, networks }
+        }
+
+        /// Returns the cables that changed on the last tick.
+        fn step(&mut self, ticks: u32) -> Vec<(IVec3, Block)> {
+            let mut changed = Vec::new();
+            for _ in 0..ticks {
+                changed = step_power(&self.networks, &mut self.map, |_| true);
+            }
+            changed
+        }
+
+        fn block_entity(&mut self, pos: IVec3) -> &mut BlockEntity {
+            get_block_entity_mut(&mut self.map, pos).unwrap()
+        }
+
+        fn fuel(&mut self, pos: IVec3, count: u32) {
+            self.block_entity(pos)
+                .insert(ItemStack::new(Item::Material(Material::Coal), count));
+        }
+
+        fn crusher_energy(&mut self, pos: IVec3) -> u32 {
+            match self.block_entity(pos) {
+                BlockEntity::Crusher(crusher) => crusher.energy,
+                _ => panic!("not a crusher"),
+            }
+        }
+
+        fn battery_charge(&mut self, pos: IVec3) -> u32 {
+            match self.block_entity(pos) {
+                BlockEntity::Battery(battery) => battery.charge,
+                _ => panic!("not a battery"),
+            }
+        }
+
+        fn set_battery_charge(&mut self, pos: IVec3, charge: u32) {
+            if let BlockEntity::Battery(battery) = self.block_entity(pos) {
+                battery.charge = charge;
+            }
+        }
+    }
+
+    fn at(x: i32) -> IVec3 {
+        IVec3::new(x, 1, 1)
+    }
+
+    #[test]
+    fn touching_blocks_make_one_network() {
+        let networks = networks_of(line(1..=4));
+
+        assert_eq!(members(&networks), vec![line(1..=4)]);
+        assert_eq!(networks.network_at(at(1)), networks.network_at(at(4)));
+        assert_eq!(networks.network_at(at(5)), None);
+    }
+
+    #[test]
+    fn blocks_apart_make_separate_networks() {
+        let networks = networks_of(line([1, 2, 4, 5]));
+
+        assert_eq!(members(&networks), vec![line(1..=2), line(4..=5)]);
+        assert_ne!(networks.network_at(at(1)), networks.network_at(at(4)));
+    }
+
+    #[test]
+    fn bridging_two_networks_merges_them() {
+        let mut networks = networks_of(line([1, 2, 3, 5, 6]));
+        let bigger = networks.network_at(at(1));
+
+        networks.add(at(4));
+
+        assert_eq!(members(&networks), vec![line(1..=6)]);
+        // The smaller network moves over to the bigger one
+        assert_eq!(networks.network_at(at(6)), bigger);
+    }
+
+    #[test]
+    fn bridging_several_networks_merges_them_all() {
+        let mut networks = networks_of([
+            IVec3::new(0, 1, 1),
+            IVec3::new(2, 1, 1),
+            IVec3::new(1, 2, 1),
+            IVec3::new(1, 1, 0),
+        ]);
+        assert_eq!(networks.iter().count(), 4);
+
+        networks.add(IVec3::new(1, 1, 1));
+
+        assert_eq!(networks.iter().count(), 1);
+    }
+
+    #[test]
+    fn removing_a_bridge_cable_splits_the_network() {
+        let mut networks = networks_of(line(1..=7));
+        let id = networks.network_at(at(1));
+
+        networks.remove(at(3));
+
+        assert_eq!(members(&networks), vec![line(1..=2), line(4..=7)]);
+        // The bigger part keeps the id
+        assert_eq!(networks.network_at(at(7)), id);
+        assert_ne!(networks.network_at(at(1)), id);
+        assert_eq!(networks.network_at(at(3)), None);
+    }
+
+    #[test]
+    fn removing_the_middle_of_a_cross_splits_it_four_ways() {
+        let center = IVec3::new(5, 5, 5);
+        let arms = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+        let mut networks =
+            networks_of(std::iter::once(center).chain(arms.iter().map(|arm| center + *arm)));
+
+        networks.remove(center);
+
+        assert_eq!(networks.iter().count(), 4);
+    }
+
+    #[test]
+    fn removing_a_cable_from_a_loop_keeps_one_network() {
+        let ring = [
+            IVec3::new(0, 1, 0),
+            IVec3::new(1, 1, 0),
+            IVec3::new(2, 1, 0),
+            IVec3::new(2, 1, 1),
+            IVec3::new(2, 1, 2),
+            IVec3::new(1, 1, 2),
+            IVec3::new(0, 1, 2),
+            IVec3::new(0, 1, 1),
+        ];
+        let mut networks = networks_of(ring);
+
+        networks.remove(ring[1]);
+
+        assert_eq!(networks.iter().count(), 1);
+        assert_eq!(networks.network_at(ring[0]), networks.network_at(ring[2]));
+    }
+
+    #[test]
+    fn removing_the_last_block_removes_the_network() {
+        let mut networks = networks_of([at(1)]);
+
+        networks.remove(at(1));
+
+        assert_eq!(networks.iter().count(), 0);
+    }
+
+    #[test]
+    fn add_chunk_connects_the_power_blocks_already_in_it() {
+        let mut chunk = Chunk::empty(IVec3::splat(CHUNK_SIZE as i32));
+        chunk.voxels[0][0][0] = Block::Cable(false);
+        chunk.voxels[1][0][0] = GENERATOR;
+        chunk.voxels[5][0][0] = Block::Battery;
+        chunk.voxels[6][0][0] = Block::Stone;
+        let mut networks = PowerNetworks::default();
+
+        networks.add_chunk(&chunk);
+
+        let origin = chunk.pos;
+        assert_eq!(
+            members(&networks),
+            vec![
+                vec![origin, origin + IVec3::X],
+                vec![origin + IVec3::new(5, 0, 0)]
+            ]
+        );
+    }
+
+    #[test]
+    fn generator_powers_the_machines_on_its_network() {
+        let mut world = World::new([
+            (at(1), GENERATOR),
+            (at(2), Block::Cable(false)),
+            (at(3), CRUSHER),
+        ]);
+        world.fuel(at(1), 1);
+
+        world.step(3);
+
+        assert_eq!(world.crusher_energy(at(3)), GENERATOR_POWER * 3);
+    }
+
+    #[test]
+    fn machines_share_a_shortage_evenly() {
+        let mut world = World::new([(at(1), CRUSHER), (at(2), GENERATOR), (at(3), CRUSHER)]);
+        world.fuel(at(2), 1);
+
+        world.step(1);
+
+        assert_eq!(world.crusher_energy(at(1)), GENERATOR_POWER / 2);
+        assert_eq!(world.crusher_energy(at(3)), GENERATOR_POWER / 2);
+    }
+
+    #[test]
+    fn leftover_power_charges_batteries() {
+        let mut world = World::new([(at(1), GENERATOR), (at(2), Block::Battery)]);
+        world.fuel(at(1), 1);
+
+        world.step(5);
+
+        assert_eq!(world.battery_charge(at(2)), GENERATOR_POWER * 5);
+    }
+
+    #[test]
+    fn batteries_make_up_for_what_generators_cannot() {
+        let mut world = World::new([(at(1), Block::Battery), (at(2), CRUSHER)]);
+        world.set_battery_charge(at(1), 1000);
+
+        world.step(1);
+
+        assert_eq!(world.crusher_energy(at(2)), MACHINE_ENERGY_BUFFER);
+        assert_eq!(world.battery_charge(at(1)), 1000 - MACHINE_ENERGY_BUFFER);
+    }
+
+    #[test]
+    fn energy_is_neither_made_nor_lost_in_balancing() {
+        let mut world = World::new([
+            (at(1), GENERATOR),
+            (at(2), Block::Battery),
+            (at(3), CRUSHER),
+            (at(4), Block::Battery),
+        ]);
+        world.fuel(at(1), 1);
+        world.set_battery_charge(at(4), 35);
+
+        for tick in 1..=20 {
+            world.step(1);
+
+            let stored = world.battery_charge(at(2))
+                + world.battery_charge(at(4))
+                + world.crusher_energy(at(3));
+            assert_eq!(stored, 35 + GENERATOR_POWER * tick);
+        }
+    }
+
+    #[test]
+    fn full_batteries_do_not_take_more() {
+        let mut world = World::new([(at(1), GENERATOR), (at(2), Block::Battery)]);
+        world.fuel(at(1), 1);
+        world.set_battery_charge(at(2), BATTERY_CAPACITY);
+
+        world.step(5);
+
+        assert_eq!(world.battery_charge(at(2)), BATTERY_CAPACITY);
+        // Nothing wants the power, so the fuel is saved
+        assert!(matches!(
+            world.block_entity(at(1)),
+            BlockEntity::Generator(generator) if generator.burn_ticks == 0
+        ));
+    }
+
+    #[test]
+    fn separate_networks_do_not_share_power() {
+        let mut world = World::new([(at(1), GENERATOR), (at(3), CRUSHER)]);
+        world.fuel(at(1), 1);
+
+        world.step(5);
+
+        assert_eq!(world.crusher_energy(at(3)), 0);
+    }
+
+    #[test]
+    fn cables_light_up_while_the_network_has_power() {
+        let mut world = World::new([
+            (at(1), Block::Battery),
+            (at(2), Block::Cable(false)),
+            (at(3), Block::Cable(false)),
+            (at(5), Block::Cable(false)),
+        ]);
+        world.set_battery_charge(at(1), 10);
+
+        let changed = world.step(1);
+        assert_eq!(
+            changed,
+            vec![(at(2), Block::Cable(true)), (at(3), Block::Cable(true))]
+        );
+        assert_eq!(get_block(&world.map, at(3)), Some(Block::Cable(true)));
+        // Not connected to anything with power
+        assert_eq!(get_block(&world.map, at(5)), Some(Block::Cable(false)));
+
+        assert!(world.step(1).is_empty());
+
+        world.set_battery_charge(at(1), 0);
+        let changed = world.step(1);
+        assert_eq!(
+            changed,
+            vec![(at(2), Block::Cable(false)), (at(3), Block::Cable(false))]
+        );
+    }
+
+    #[test]
+    fn cables_stay_lit_while_generators_run() {
+        let mut world = World::new([
+            (at(1), GENERATOR),
+            (at(2), Block::Cable(false)),
+            (at(3), CRUSHER),
+        ]);
+
+        world.step(5);
+        assert_eq!(get_block(&world.map, at(2)), Some(Block::Cable(false)));
+
+        world.fuel(at(1), 1);
+        world.step(5);
+        assert_eq!(get_block(&world.map, at(2)), Some(Block::Cable(true)));
+    }
+
+    #[test]
+    fn powered_cables_are_the_same_item() {
+        assert_eq!(Block::Cable(true).with_default_state(), Block::Cable(false));
+        assert!(Block::Cable(true).conducts_power());
+    }
+}
@@ -0,0 +1,90 @@
+//! Machines that turn one input into an output over time, with processing recipes.
+
+use crate::crafting::{Recipe, RecipeKind, RecipeRegistry};
+use crate::inventory::Inventory;
+use crate::machine::{seconds_to_ticks, ProcessingMachine};
+use serde::{Deserialize, Serialize};
+
+/// The recipe the machine can make from what's in its input, if its output has room for the result.
+pub fn ready_recipe<'a>(
+    recipes: &'a RecipeRegistry,
+    machine: ProcessingMachine,
+    input: &Inventory,
+    output: &Inventory,
+) -> Option<&'a Recipe> {
+    let recipe = recipes.processing_recipe(machine, input.get(0)?)?;
+
+    (output.space_for(recipe.output.item) >= recipe.output.count).then_some(recipe)
+}
+
+/// Works on the recipe for one tick, and turns the input into the output once it's done.
+pub fn advance_recipe(
+    recipe: &Recipe,
+    input: &mut Inventory,
+    output: &mut Inventory,
+    progress: &mut u32,
+) {
+    let RecipeKind::Processing {
+        input: recipe_input,
+        duration,
+        ..
+    } = recipe.kind
+    else {
+        return;
+    };
+
+    *progress += 1;
+
+    if *progress >= seconds_to_ticks(duration) {
+        *progress = 0;
+        input.remove_item(recipe_input.item, recipe_input.count);
+        output.insert(recipe.output);
+    }
+}
+
+/// How much energy a crusher uses every tick it's working.
+pub const CRUSHER_POWER_USE: u32 = 4;
+
+/// Crushes ores into more than one piece, to get more out of them. Runs on power.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Crusher {
+    pub input: Inventory,
+    pub output: Inventory,
+    /// Ticks spent on the current input.
+    pub progress: u32,
+    /// Energy received from the power network, that hasn't been used yet.
+    pub energy: u32,
+}
+
+impl Crusher {
+    pub fn new() -> Self {
+        Self {
+            input: Inventory::new(1),
+            output: Inventory::new(1),
+            progress: 0,
+            energy: 0,
+        }
+    }
+
+    pub fn tick(&mut self, recipes: &RecipeRegistry) {
+        match ready_recipe(
+            recipes,
+            ProcessingMachine::Crusher,
+            &self.input,
+            &self.output,
+        ) {
+            Some(recipe) if self.energy >= CRUSHER_POWER_USE => {
+                self.energy -= CRUSHER_POWER_USE;
+                advance_recipe(
+                    recipe,
+                    &mut self.input,
+                    &mut self.output,
+                    &mut self.progress,
+                );
+            }
+            // Out of power, so wait for more without losing progress
+            Some(_) => {}
+            None => self.progress = 0,
+        }
+    }
+}
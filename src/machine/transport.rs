@@ -0,0 +1,513 @@
+//! Moves items between blocks: conveyors passing items on to whatever they point into, and
+//! inserters moving items from one inventory to another.
+//!
+//! Everything happens in a fixed order every tick, so the same world always ends up the same way.
+//! First every conveyor moves its items along, and then every conveyor and inserter gets a turn to
+//! hand items over, in order of position. A block that's full doesn't take anything, so items back
+//! up behind it.
+
+use crate::inventory::item::{Item, ItemStack};
+use crate::machine::conveyor::Conveyor;
+use crate::machine::BlockEntity;
+use crate::worldgen::block::{Block, Facing};
+use crate::worldgen::chunk::access::{get_block, get_block_entity_mut};
+use crate::worldgen::chunk::Chunk;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// The positions of every conveyor and inserter in the chunks, in the order they take their turns.
+pub fn transport_positions<'a>(chunks: impl Iterator<Item = &'a Chunk>) -> Vec<IVec3> {
+    let mut positions: Vec<IVec3> = chunks
+        .flat_map(|chunk| {
+            chunk
+                .block_entities
+                .iter()
+                .filter(|(_, block_entity)| {
+                    matches!(
+                        block_entity,
+                        BlockEntity::Conveyor(_) | BlockEntity::Inserter(_)
+                    )
+                })
+                .map(|(local_pos, _)| chunk.pos + local_pos.as_ivec3())
+        })
+        .collect();
+
+    positions.sort_unstable_by_key(|pos| pos.to_array());
+
+    positions
+}
+
+/// Runs one tick of item transport for the conveyors and inserters at the positions.
+pub fn step_transport(map: &mut HashMap<(i32, i32, i32), Chunk>, positions: &[IVec3]) {
+    for &pos in positions {
+        if let Some(conveyor) = conveyor_mut(map, pos) {
+            conveyor.advance();
+        }
+    }
+
+    for &pos in positions {
+        match get_block(map, pos) {
+            Some(Block::Conveyor(facing)) => pass_on(map, pos, &[facing]),
+            Some(Block::Splitter(facing)) => pass_on(map, pos, &[facing.left(), facing.right()]),
+            Some(Block::Merger(facing)) => {
+                pull_into_merger(map, pos, facing);
+                pass_on(map, pos, &[facing]);
+            }
+            Some(Block::Inserter(facing)) => move_with_inserter(map, pos, facing),
+            _ => {}
+        }
+    }
+}
+
+fn conveyor_mut(map: &mut HashMap<(i32, i32, i32), Chunk>, pos: IVec3) -> Option<&mut Conveyor> {
+    match get_block_entity_mut(map, pos)? {
+        BlockEntity::Conveyor(conveyor) => Some(conveyor),
+        _ => None,
+    }
+}
+
+/// Hands the item at the front of the conveyor to the first of its outputs that takes it. The
+/// outputs take turns being first, so items are spread evenly between them.
+fn pass_on(map: &mut HashMap<(i32, i32, i32), Chunk>, pos: IVec3, outputs: &[Facing]) {
+    let Some(conveyor) = conveyor_mut(map, pos) else {
+        return;
+    };
+    let Some(item) = conveyor.front_ready() else {
+        return;
+    };
+    let first = conveyor.next_side;
+
+    for i in 0..outputs.len() {
+        let side = (first + i) % outputs.len();
+
+        if give(map, pos + outputs[side].offset(), outputs[side], item) {
+            let conveyor = conveyor_mut(map, pos).unwrap();
+            conveyor.take_front();
+            conveyor.next_side = (side + 1) % outputs.len();
+
+            return;
+        }
+    }
+}
+
+/// Takes an item from whichever of the conveyors pointing into the merger's back and sides is next
+/// in turn and has one ready.
+fn pull_into_merger(map: &mut HashMap<(i32, i32, i32), Chunk>, pos: IVec3, facing: Facing) {
+    let inputs = [facing.left(), facing.opposite(), facing.right()];
+
+    let Some(merger) = conveyor_mut(map, pos).filter(|merger| merger.can_accept()) else {
+        return;
+    };
+    let first = merger.next_side;
+
+    for i in 0..inputs.len() {
+        let side = (first + i) % inputs.len();
+        let from = pos + inputs[side].offset();
+
+        // Only conveyors that pass items on straight ahead can feed a merger
+        let points_here = matches!(
+            get_block(map, from),
+            Some(Block::Conveyor(source_facing) | Block::Merger(source_facing))
+                if source_facing == inputs[side].opposite()
+        );
+
+        let Some(source) = conveyor_mut(map, from).filter(|_| points_here) else {
+            continue;
+        };
+
+        if let Some(item) = source.front_ready() {
+            source.take_front();
+
+            let merger = conveyor_mut(map, pos).unwrap();
+            merger.accept(item);
+            merger.next_side = (side + 1) % inputs.len();
+
+            return;
+        }
+    }
+}
+
+/// Picks an item up from behind the inserter, or swings the held item over and puts it down in
+/// front of it.
+fn move_with_inserter(map: &mut HashMap<(i32, i32, i32), Chunk>, pos: IVec3, facing: Facing) {
+    let Some(BlockEntity::Inserter(inserter)) = get_block_entity_mut(map, pos) else {
+        return;
+    };
+
+    if inserter.held.is_none() {
+        if let Some(item) = take(map, pos - facing.offset()) {
+            if let Some(BlockEntity::Inserter(inserter)) = get_block_entity_mut(map, pos) {
+                inserter.pick_up(item);
+            }
+        }
+    } else if let Some(item) = inserter.swing() {
+        if give(map, pos + facing.offset(), facing, item) {
+            if let Some(BlockEntity::Inserter(inserter)) = get_block_entity_mut(map, pos) {
+                inserter.put_down();
+            }
+        }
+    }
+}
+
+/// Gives the item to the block at the position, moving in `direction` to get there. Returns whether
+/// the block took it.
+fn give(
+    map: &mut HashMap<(i32, i32, i32), Chunk>,
+    pos: IVec3,
+    direction: Facing,
+    item: Item,
+) -> bool {
+    let refuses = match get_block(map, pos) {
+        // These take items themselves
+        Some(Block::Merger(_) | Block::Inserter(_)) => true,
+        // Passing items onto a conveyor that points right back would just bounce them around
+        Some(Block::Conveyor(facing) | Block::Splitter(facing)) => facing == direction.opposite(),
+        _ => false,
+    };
+
+    if refuses {
+        return false;
+    }
+
+    get_block_entity_mut(map, pos)
+        .is_some_and(|block_entity| block_entity.insert(ItemStack::new(item, 1)).is_none())
+}
+
+/// Takes one item out of the block at the position: the furthest item along a conveyor, or the
+/// output of a machine.
+fn take(map: &mut HashMap<(i32, i32, i32), Chunk>, pos: IVec3) -> Option<Item> {
+    match get_block_entity_mut(map, pos)? {
+        BlockEntity::Conveyor(conveyor) => conveyor.take_front(),
+        block_entity => {
+            let output = block_entity.output_mut()?;
+
+            (0..output.size())
+                .find_map(|slot| output.remove(slot, 1))
+                .map(|stack| stack.item)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::Material;
+    use crate::machine::conveyor::{BELT_LENGTH, BELT_SPEED};
+    use crate::machine::furnace::Furnace;
+    use crate::machine::inserter::INSERTER_SWING_TIME;
+    use crate::machine::seconds_to_ticks;
+    use crate::worldgen::chunk::access::set_block;
+
+    const STONE: Item = Item::Block(Block::Stone);
+    const DIRT: Item = Item::Block(Block::Dirt);
+    const ORE: Item = Item::Block(Block::IronOre);
+    const INGOT: Item = Item::Material(Material::IronIngot);
+
+    /// How many ticks it takes an item to get from the back of a conveyor to its front.
+    const BELT_TICKS: u32 = BELT_LENGTH / BELT_SPEED;
+
+    /// A single chunk at the origin, with machines placed in it like `sync_block_entities` does.
+    struct World {
+        map: HashMap<(i32, i32, i32), Chunk>,
+    }
+
+    impl World {
+        fn new(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> Self {
+            let mut map = HashMap::new();
+            map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+
+            for (pos, block) in blocks {
+                set_block(&mut map, pos, block);
+
+                if let Some(block_entity) = BlockEntity::for_block(block) {
+                    let chunk = map.get_mut(&(0, 0, 0)).unwrap();
+                    chunk.block_entities.insert(pos.as_uvec3(), block_entity);
+                }
+            }
+
+            Self { map }
+        }
+
+        fn step(&mut self, ticks: u32) {
+            let positions = transport_positions(self.map.values());
+
+            for _ in 0..ticks {
+                step_transport(&mut self.map, &positions);
+            }
+        }
+
+        fn block_entity(&mut self, pos: IVec3) -> &mut BlockEntity {
+            get_block_entity_mut(&mut self.map, pos).unwrap()
+        }
+
+        fn conveyor(&mut self, pos: IVec3) -> &mut Conveyor {
+            conveyor_mut(&mut self.map, pos).unwrap()
+        }
+
+        fn furnace(&mut self, pos: IVec3) -> &mut Furnace {
+            match self.block_entity(pos) {
+                BlockEntity::Furnace(furnace) => furnace,
+                _ => panic!("not a furnace"),
+            }
+        }
+
+        /// The items on the conveyor, from front to back.
+        fn items_on(&mut self, pos: IVec3) -> Vec<Item> {
+            self.conveyor(pos)
+                .items
+                .iter()
+                .map(|belt_item| belt_item.item)
+                .collect()
+        }
+
+        /// Puts the items on the conveyor one after another, as soon as there's room for each.
+        fn feed(&mut self, pos: IVec3, items: &[Item]) {
+            for &item in items {
+                for _ in 0..100 {
+                    if self.conveyor(pos).accept(item) {
+                        break;
+                    }
+                    self.step(1);
+                }
+            }
+        }
+    }
+
+    fn at(x: i32, z: i32) -> IVec3 {
+        IVec3::new(x, 1, z)
+    }
+
+    #[test]
+    fn conveyor_chain_carries_items_to_the_end() {
+        let mut world = World::new((1..=3).map(|x| (at(x, 4), Block::Conveyor(Facing::East))));
+        world.conveyor(at(1, 4)).accept(STONE);
+
+        world.step(BELT_TICKS);
+        assert!(world.items_on(at(1, 4)).is_empty());
+        assert_eq!(world.items_on(at(2, 4)), vec![STONE]);
+
+        world.step(BELT_TICKS * 2);
+        assert_eq!(world.items_on(at(3, 4)), vec![STONE]);
+        assert_eq!(world.conveyor(at(3, 4)).front_ready(), Some(STONE));
+
+        // Nothing to pass it on to, so it waits at the end
+        world.step(BELT_TICKS);
+        assert_eq!(world.items_on(at(3, 4)), vec![STONE]);
+    }
+
+    #[test]
+    fn items_back_up_without_getting_lost() {
+        let mut world = World::new((1..=3).map(|x| (at(x, 4), Block::Conveyor(Facing::East))));
+        let items = [
+            STONE, DIRT, ORE, INGOT, STONE, DIRT, ORE, INGOT, STONE, DIRT,
+        ];
+
+        world.feed(at(1, 4), &items);
+        world.step(BELT_TICKS * 10);
+
+        let mut carried = world.items_on(at(3, 4));
+        carried.extend(world.items_on(at(2, 4)));
+        carried.extend(world.items_on(at(1, 4)));
+        assert_eq!(carried, items);
+        assert_eq!(world.items_on(at(3, 4)).len(), 4);
+    }
+
+    #[test]
+    fn conveyors_do_not_pass_items_back_into_each_other() {
+        let mut world = World::new([
+            (at(1, 4), Block::Conveyor(Facing::East)),
+            (at(2, 4), Block::Conveyor(Facing::West)),
+        ]);
+        world.conveyor(at(1, 4)).accept(STONE);
+
+        world.step(BELT_TICKS * 3);
+
+        assert_eq!(world.items_on(at(1, 4)), vec![STONE]);
+        assert!(world.items_on(at(2, 4)).is_empty());
+    }
+
+    #[test]
+    fn splitter_alternates_between_its_sides() {
+        let mut world = World::new([
+            (at(1, 4), Block::Conveyor(Facing::East)),
+            (at(2, 4), Block::Splitter(Facing::East)),
+            (at(2, 3), Block::Conveyor(Facing::North)),
+            (at(2, 5), Block::Conveyor(Facing::South)),
+        ]);
+
+        world.feed(at(1, 4), &[STONE, DIRT, ORE, INGOT]);
+        world.step(BELT_TICKS * 6);
+
+        // Left of east is north
+        assert_eq!(world.items_on(at(2, 3)), vec![STONE, ORE]);
+        assert_eq!(world.items_on(at(2, 5)), vec![DIRT, INGOT]);
+        assert!(world.items_on(at(2, 4)).is_empty());
+    }
+
+    #[test]
+    fn splitter_uses_the_other_side_when_one_is_blocked() {
+        let mut world = World::new([
+            (at(1, 4), Block::Conveyor(Facing::East)),
+            (at(2, 4), Block::Splitter(Facing::East)),
+            (at(2, 5), Block::Conveyor(Facing::South)),
+        ]);
+
+        world.feed(at(1, 4), &[STONE, DIRT, ORE]);
+        world.step(BELT_TICKS * 6);
+
+        assert_eq!(world.items_on(at(2, 5)), vec![STONE, DIRT, ORE]);
+    }
+
+    /// A merger facing east at x = 5, fed from the north, west and south, passing items on to
+    /// two conveyors in a row.
+    fn merger_world() -> World {
+        World::new([
+            (at(5, 3), Block::Conveyor(Facing::South)),
+            (at(4, 4), Block::Conveyor(Facing::East)),
+            (at(5, 5), Block::Conveyor(Facing::North)),
+            (at(5, 4), Block::Merger(Facing::East)),
+            (at(6, 4), Block::Conveyor(Facing::East)),
+            (at(7, 4), Block::Conveyor(Facing::East)),
+        ])
+    }
+
+    fn merged(world: &mut World) -> Vec<Item> {
+        let mut merged = world.items_on(at(7, 4));
+        merged.extend(world.items_on(at(6, 4)));
+        merged
+    }
+
+    #[test]
+    fn merger_takes_turns_between_its_inputs() {
+        let mut world = merger_world();
+        for (pos, item) in [(at(5, 3), STONE), (at(4, 4), DIRT), (at(5, 5), ORE)] {
+            world.feed(pos, &[item, item]);
+        }
+
+        world.step(BELT_TICKS * 10);
+
+        // Left of east is north, so that goes first, then the back, then the right
+        assert_eq!(merged(&mut world), vec![STONE, DIRT, ORE, STONE, DIRT, ORE]);
+    }
+
+    #[test]
+    fn merger_skips_inputs_without_anything_ready() {
+        let mut world = merger_world();
+        world.feed(at(5, 3), &[STONE]);
+        world.feed(at(4, 4), &[DIRT, DIRT, DIRT]);
+
+        world.step(BELT_TICKS * 10);
+
+        assert_eq!(merged(&mut world), vec![STONE, DIRT, DIRT, DIRT]);
+    }
+
+    #[test]
+    fn merger_ignores_conveyors_pointing_elsewhere() {
+        let mut world = World::new([
+            (at(5, 3), Block::Conveyor(Facing::North)),
+            (at(5, 4), Block::Merger(Facing::East)),
+        ]);
+        world.conveyor(at(5, 3)).accept(STONE);
+
+        world.step(BELT_TICKS * 3);
+
+        assert_eq!(world.items_on(at(5, 3)), vec![STONE]);
+        assert!(world.items_on(at(5, 4)).is_empty());
+    }
+
+    #[test]
+    fn inserter_feeds_a_furnace_from_a_conveyor() {
+        let mut world = World::new([
+            (at(1, 4), Block::Conveyor(Facing::East)),
+            (at(2, 4), Block::Inserter(Facing::East)),
+            (at(3, 4), Block::Furnace(Facing::East)),
+        ]);
+        world.conveyor(at(1, 4)).accept(ORE);
+        world.conveyor(at(1, 4)).items[0].progress = BELT_LENGTH;
+
+        world.step(1);
+        assert!(world.items_on(at(1, 4)).is_empty());
+        assert!(matches!(
+            world.block_entity(at(2, 4)),
+            BlockEntity::Inserter(inserter) if inserter.held == Some(ORE)
+        ));
+
+        world.step(seconds_to_ticks(INSERTER_SWING_TIME));
+        assert_eq!(
+            world.furnace(at(3, 4)).input.get(0),
+            Some(ItemStack::new(ORE, 1))
+        );
+        assert!(matches!(
+            world.block_entity(at(2, 4)),
+            BlockEntity::Inserter(inserter) if inserter.held.is_none()
+        ));
+    }
+
+    #[test]
+    fn inserter_puts_fuel_in_the_fuel_slot() {
+        let coal = Item::Material(Material::Coal);
+        let mut world = World::new([
+            (at(1, 4), Block::Conveyor(Facing::East)),
+            (at(2, 4), Block::Inserter(Facing::East)),
+            (at(3, 4), Block::Furnace(Facing::East)),
+        ]);
+        world.conveyor(at(1, 4)).accept(coal);
+
+        world.step(seconds_to_ticks(INSERTER_SWING_TIME) + 1);
+
+        let furnace = world.furnace(at(3, 4));
+        assert_eq!(furnace.fuel.get(0), Some(ItemStack::new(coal, 1)));
+        assert_eq!(furnace.input.get(0), None);
+    }
+
+    #[test]
+    fn inserter_takes_from_a_furnace_output() {
+        let mut world = World::new([
+            (at(1, 4), Block::Furnace(Facing::East)),
+            (at(2, 4), Block::Inserter(Facing::East)),
+            (at(3, 4), Block::Conveyor(Facing::East)),
+        ]);
+        world.furnace(at(1, 4)).input.insert(ItemStack::new(ORE, 3));
+        world
+            .furnace(at(1, 4))
+            .output
+            .insert(ItemStack::new(INGOT, 2));
+
+        world.step((seconds_to_ticks(INSERTER_SWING_TIME) + 1) * 2);
+
+        // Only the output is taken, never the input
+        assert_eq!(world.furnace(at(1, 4)).output.get(0), None);
+        assert_eq!(
+            world.furnace(at(1, 4)).input.get(0),
+            Some(ItemStack::new(ORE, 3))
+        );
+        assert_eq!(world.items_on(at(3, 4)), vec![INGOT, INGOT]);
+    }
+
+    #[test]
+    fn inserter_holds_on_until_the_furnace_has_room() {
+        let mut world = World::new([
+            (at(1, 4), Block::Conveyor(Facing::East)),
+            (at(2, 4), Block::Inserter(Facing::East)),
+            (at(3, 4), Block::Furnace(Facing::East)),
+        ]);
+        world
+            .furnace(at(3, 4))
+            .input
+            .insert(ItemStack::new(DIRT, 1));
+        world.conveyor(at(1, 4)).accept(ORE);
+
+        world.step(seconds_to_ticks(INSERTER_SWING_TIME) * 4);
+        assert!(matches!(
+            world.block_entity(at(2, 4)),
+            BlockEntity::Inserter(inserter) if inserter.held == Some(ORE)
+        ));
+
+        world.furnace(at(3, 4)).input.remove(0, 1);
+        world.step(1);
+        assert_eq!(
+            world.furnace(at(3, 4)).input.get(0),
+            Some(ItemStack::new(ORE, 1))
+        );
+    }
+}
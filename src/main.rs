@@ -1,22 +1,153 @@
 mod camera;
+mod command;
+mod crafting;
+mod debug;
+mod input;
+mod interaction;
+mod inventory;
+mod machine;
+mod net;
+mod sky;
+mod worldedit;
 mod worldgen;
 
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::diagnostic::LogDiagnosticsPlugin;
+use bevy::log::LogPlugin;
 use bevy::pbr::wireframe::WireframePlugin;
 use bevy::prelude::*;
 use bevy::window::{CursorGrabMode, PrimaryWindow};
 use bevy_rapier3d::prelude::*;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often the headless app runs its schedules, since there's no window to vsync to.
+const HEADLESS_TICK_RATE: f64 = 60.0;
 
 fn main() {
-    App::new()
-        .insert_resource(Msaa::Off)
-        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default().with_physics_scale(1.0))
-        .add_plugins(WireframePlugin)
-        .add_plugins(camera::CameraPlugin)
-        .add_plugins(worldgen::WorldgenPlugin)
-        .add_systems(Startup, (configure_window, spawn_light))
-        .add_systems(Update, bevy::window::close_on_esc)
-        .run();
+    let args: Vec<String> = std::env::args().collect();
+
+    // A server is always headless
+    let server_address = address_arg(&args, "--server");
+    let client_address = address_arg(&args, "--connect");
+    let headless = server_address.is_some() || args.iter().any(|arg| arg == "--headless");
+    let voxel_collision = args.iter().any(|arg| arg == "--voxel-collision");
+    let log_diagnostics = args.iter().any(|arg| arg == "--log-diagnostics");
+    let seed = seed_arg(&args);
+    let save_dir = world_dir_arg(&args);
+
+    let mut app = App::new();
+
+    if headless {
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(
+            Duration::from_secs_f64(1.0 / HEADLESS_TICK_RATE),
+        )))
+        .add_plugins(LogPlugin::default())
+        .add_plugins(command::StdinCommandPlugin);
+
+        add_headless_plugins(&mut app, seed, save_dir);
+
+        if let Some(address) = server_address {
+            app.add_plugins(net::server::ServerPlugin { address });
+        }
+    } else {
+        if voxel_collision {
+            app.insert_resource(camera::PlayerCollision::Voxel);
+        }
+
+        app.insert_resource(Msaa::Off)
+            .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default().with_physics_scale(1.0))
+            .add_plugins(WireframePlugin)
+            .add_plugins(input::InputMapPlugin)
+            .add_plugins(camera::CameraPlugin)
+            .add_plugins(inventory::InventoryPlugin)
+            .add_plugins(crafting::CraftingPlugin)
+            .add_plugins(interaction::InteractionPlugin)
+            .add_plugins(machine::BeltItemPlugin)
+            .add_plugins(debug::WorldDiagnosticsPlugin)
+            .add_plugins(debug::DebugOverlayPlugin)
+            .add_plugins(sky::SkyPlugin)
+            .add_plugins(command::CommandPlugin)
+            .add_plugins(command::ConsolePlugin)
+            .add_plugins(worldedit::WorldEditPlugin)
+            .add_plugins(worldgen::WorldgenPlugin {
+                headless: false,
+                remote: client_address.is_some(),
+                seed,
+                save_dir: Some(save_dir),
+            })
+            .add_systems(Startup, configure_window)
+            .add_systems(Update, bevy::window::close_on_esc);
+
+        // Machines are simulated by the server
+        match client_address {
+            Some(address) => app.add_plugins(net::client::ClientPlugin { address }),
+            None => app.add_plugins(machine::MachinePlugin),
+        };
+    }
+
+    if log_diagnostics {
+        app.add_plugins(LogDiagnosticsPlugin::default());
+    }
+
+    app.run();
+}
+
+/// Returns the address following the flag, or the default address if the flag is given without
+/// one. Returns `None` if the flag isn't given at all.
+fn address_arg(args: &[String], flag: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == flag)?;
+
+    match args.get(index + 1) {
+        Some(address) if !address.starts_with("--") => Some(address.clone()),
+        _ => Some(net::DEFAULT_ADDRESS.to_string()),
+    }
+}
+
+/// The number following `--seed`, or 0 if it isn't given.
+fn seed_arg(args: &[String]) -> u64 {
+    let Some(index) = args.iter().position(|arg| arg == "--seed") else {
+        return 0;
+    };
+
+    match args.get(index + 1).map(|seed| seed.parse()) {
+        Some(Ok(seed)) => seed,
+        _ => {
+            eprintln!("--seed should be followed by a whole number, using seed 0");
+            0
+        }
+    }
+}
+
+/// The folder following `--world`, or `worldgen::chunk::storage::DEFAULT_WORLD_DIR` if it isn't
+/// given.
+fn world_dir_arg(args: &[String]) -> PathBuf {
+    let dir = args
+        .iter()
+        .position(|arg| arg == "--world")
+        .and_then(|index| args.get(index + 1))
+        .filter(|dir| !dir.starts_with("--"));
+
+    match dir {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(worldgen::chunk::storage::DEFAULT_WORLD_DIR),
+    }
+}
+
+/// Everything the headless app simulates, leaving how it's run and logged to the caller.
+fn add_headless_plugins(app: &mut App, seed: u64, save_dir: PathBuf) {
+    app.add_plugins(worldgen::WorldgenPlugin {
+        headless: true,
+        remote: false,
+        seed,
+        save_dir: Some(save_dir),
+    })
+    .add_plugins(crafting::CraftingPlugin)
+    .add_plugins(machine::MachinePlugin)
+    .add_plugins(debug::WorldDiagnosticsPlugin)
+    .add_plugins(command::CommandPlugin)
+    .add_systems(Startup, spawn_headless_chunk_loader);
 }
 
 fn configure_window(mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
@@ -26,15 +157,107 @@ fn configure_window(mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
     window.cursor.visible = false;
 }
 
-fn spawn_light(mut commands: Commands) {
-    commands.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            color: Color::rgb(1.0, 0.9, 0.8),
-            illuminance: 10000.0,
-            shadows_enabled: true,
-            ..default()
-        },
-        transform: Transform::from_xyz(50.0, 200.0, 100.0).looking_at(Vec3::ZERO, Vec3::Y),
-        ..default()
-    });
+/// Without a player, keep the area around the spawn point generated.
+fn spawn_headless_chunk_loader(mut commands: Commands) {
+    commands
+        .spawn(TransformBundle::from_transform(Transform::from_xyz(
+            0.0, 20.0, 0.0,
+        )))
+        .insert(worldgen::chunk::ChunkLoader);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::BlockEntity;
+    use crate::worldgen::block::Block;
+    use crate::worldgen::chunk::access::get_block;
+    use crate::worldgen::chunk::{GeneratedChunks, ViewDistance};
+    use bevy::app::AppExit;
+    use std::thread;
+
+    /// The chunk the headless chunk loader is in.
+    const SPAWN_CHUNK: (i32, i32, i32) = (0, 1, 0);
+
+    fn headless_app(save_dir: PathBuf) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        add_headless_plugins(&mut app, 3, save_dir);
+        // Only the chunks right around the spawn point, to keep the test quick
+        app.insert_resource(ViewDistance(IVec3::ONE));
+
+        app
+    }
+
+    /// Runs the app until the chunk has been generated or loaded, which happens on other threads.
+    fn update_until_generated(app: &mut App, chunk_pos: (i32, i32, i32)) {
+        for _ in 0..500 {
+            app.update();
+
+            let generated_chunks = app.world.resource::<GeneratedChunks>();
+            if generated_chunks
+                .map
+                .lock()
+                .unwrap()
+                .contains_key(&chunk_pos)
+            {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("Chunk {:?} was never generated", chunk_pos);
+    }
+
+    fn block_entity_at(app: &App, local_pos: UVec3) -> Option<BlockEntity> {
+        let map = app.world.resource::<GeneratedChunks>().map.lock().unwrap();
+
+        map[&SPAWN_CHUNK].block_entities.get(&local_pos).cloned()
+    }
+
+    #[test]
+    fn headless_app_runs_commands_and_keeps_the_world_between_runs() {
+        let save_dir = std::env::temp_dir().join(format!(
+            "excavate-manufacturate-headless-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&save_dir);
+        let furnace_pos = IVec3::new(1, 17, 1);
+
+        let mut app = headless_app(save_dir.clone());
+        update_until_generated(&mut app, SPAWN_CHUNK);
+
+        app.world
+            .send_event(command::RunCommand("/setblock 1 17 1 furnace".to_string()));
+        app.update();
+        app.update();
+
+        {
+            let map = app.world.resource::<GeneratedChunks>().map.lock().unwrap();
+            assert!(matches!(
+                get_block(&map, furnace_pos),
+                Some(Block::Furnace(_))
+            ));
+        }
+        assert!(block_entity_at(&app, UVec3::new(1, 1, 1)).is_some());
+
+        // Closing the app saves it
+        app.world.send_event(AppExit);
+        app.update();
+
+        let mut app = headless_app(save_dir.clone());
+        update_until_generated(&mut app, SPAWN_CHUNK);
+
+        {
+            let map = app.world.resource::<GeneratedChunks>().map.lock().unwrap();
+            assert!(matches!(
+                get_block(&map, furnace_pos),
+                Some(Block::Furnace(_))
+            ));
+        }
+        assert!(block_entity_at(&app, UVec3::new(1, 1, 1)).is_some());
+
+        std::fs::remove_dir_all(save_dir).unwrap();
+    }
 }
@@ -0,0 +1,261 @@
+use crate::camera::PlayerCamera;
+use crate::interaction::mining::harvest;
+use crate::inventory::dropped_item::DropItem;
+use crate::inventory::item::{Item, ItemStack};
+use crate::inventory::tool::Tool;
+use crate::inventory::Inventory;
+use crate::net::protocol::{ClientMessage, Connection, ServerMessage};
+use crate::net::{tick_net_timer, NetTickTimer, RemotePlayer, NET_TICK_INTERVAL};
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::set_block;
+use crate::worldgen::chunk::storage::decode_chunk;
+use crate::worldgen::chunk::{DirtyChunks, GeneratedChunks, LoadedChunks};
+use crate::worldgen::edit::{BlockChanged, BlockEdit};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::net::TcpStream;
+
+pub struct ClientPlugin {
+    pub address: String,
+}
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        let stream = TcpStream::connect(&self.address).expect("Failed to connect to the server");
+
+        info!("Connected to {}", self.address);
+
+        app.insert_resource(Client {
+            connection: Connection::new(stream).unwrap(),
+            id: None,
+            players: HashMap::new(),
+            pending_edits: Vec::new(),
+        })
+        .insert_resource(NetTickTimer(Timer::from_seconds(
+            NET_TICK_INTERVAL,
+            TimerMode::Repeating,
+        )))
+        .add_systems(
+            Update,
+            (
+                tick_net_timer,
+                receive_server_messages,
+                send_player_position,
+                send_block_edits,
+                flush_client,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct Client {
+    connection: Connection,
+    /// Our own player id, once the server has sent it.
+    id: Option<u32>,
+    /// The entities of the other players.
+    players: HashMap<u32, Entity>,
+    /// Edits the player made that the server hasn't answered yet, in the order they were sent.
+    pending_edits: Vec<PendingEdit>,
+}
+
+/// What happens to the player's inventory once the server accepts one of their edits.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditEffect {
+    /// Uses up one of the item the block was placed from.
+    Place(Item),
+    /// Drops what the broken block drops when it's broken with the tool.
+    Break(Option<Tool>),
+}
+
+struct PendingEdit {
+    pos: IVec3,
+    block: Block,
+    effect: EditEffect,
+}
+
+impl Client {
+    /// Remembers to apply the effect on the inventory if the server accepts the edit, which still
+    /// has to be sent as a `BlockEdit`.
+    pub fn expect_edit(&mut self, pos: IVec3, block: Block, effect: EditEffect) {
+        self.pending_edits.push(PendingEdit { pos, block, effect });
+    }
+
+    /// Forgets the oldest pending edit matching the server's answer, and returns its effect.
+    fn answer_edit(&mut self, pos: IVec3, block: Block) -> Option<EditEffect> {
+        let index = self
+            .pending_edits
+            .iter()
+            .position(|edit| edit.pos == pos && edit.block == block)?;
+
+        Some(self.pending_edits.remove(index).effect)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn receive_server_messages(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut client: ResMut<Client>,
+    mut player_query: Query<&mut Transform, With<RemotePlayer>>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    mut inventory_query: Query<&mut Inventory, With<PlayerCamera>>,
+    mut changes: EventWriter<BlockChanged>,
+    mut drops: EventWriter<DropItem>,
+    mut exit: EventWriter<AppExit>,
+    generated_chunks: Res<GeneratedChunks>,
+    loaded_chunks: Res<LoadedChunks>,
+) {
+    let messages = match client.connection.receive::<ServerMessage>() {
+        Ok(messages) => messages,
+        Err(err) => {
+            error!("Lost connection to the server: {}", err);
+            exit.send(AppExit);
+            return;
+        }
+    };
+
+    let mut map = generated_chunks.map.lock().unwrap();
+
+    for message in messages {
+        match message {
+            ServerMessage::Welcome { id } => {
+                info!("Joined as player {}", id);
+                client.id = Some(id);
+            }
+            ServerMessage::Chunk { pos, data } => {
+                let Some(chunk) = decode_chunk(IVec3::from(pos), &data) else {
+                    warn!("Received invalid chunk data for chunk {:?}", pos);
+                    continue;
+                };
+
+                let pos_tuple = (pos[0], pos[1], pos[2]);
+
+                map.insert(pos_tuple, chunk);
+
+                // The chunk was loaded with outdated blocks
+                if loaded_chunks.chunks.contains(&pos_tuple) {
+                    dirty_chunks.chunks.insert(pos_tuple);
+                }
+            }
+            ServerMessage::BlockChanged { pos, block } => {
+                let pos = IVec3::from(pos);
+
+                if set_block(&mut map, pos, block) {
+                    changes.send(BlockChanged {
+                        pos,
+                        block,
+                        drops_contents: true,
+                    });
+                }
+            }
+            ServerMessage::BlockEditResult {
+                pos,
+                block,
+                replaced,
+            } => {
+                let pos = IVec3::from(pos);
+
+                // Edits made by commands have no effect to apply
+                let (Some(effect), Some(replaced)) = (client.answer_edit(pos, block), replaced)
+                else {
+                    continue;
+                };
+
+                match effect {
+                    EditEffect::Place(item) => {
+                        if let Ok(mut inventory) = inventory_query.get_single_mut() {
+                            inventory.remove_item(item, 1);
+                        }
+                    }
+                    EditEffect::Break(tool) => {
+                        if let Some(item) = harvest(replaced, tool) {
+                            drops.send(DropItem {
+                                pos: pos.as_vec3() + Vec3::splat(0.5),
+                                stack: ItemStack::new(item, 1),
+                            });
+                        }
+                    }
+                }
+            }
+            ServerMessage::DropItem { pos, stack } => {
+                drops.send(DropItem {
+                    pos: Vec3::from(pos),
+                    stack,
+                });
+            }
+            // The server doesn't send us our own position, but don't spawn ourselves if it does
+            ServerMessage::PlayerPosition { id, .. } if client.id == Some(id) => {}
+            ServerMessage::PlayerPosition { id, pos } => match client.players.get(&id) {
+                Some(entity) => {
+                    // Might have only just been spawned, in which case it's fine to skip an update
+                    if let Ok(mut transform) = player_query.get_mut(*entity) {
+                        transform.translation = Vec3::from(pos);
+                    }
+                }
+                None => {
+                    let entity = commands
+                        .spawn(PbrBundle {
+                            mesh: meshes.add(
+                                shape::Capsule {
+                                    radius: 0.3,
+                                    depth: 1.2,
+                                    ..default()
+                                }
+                                .into(),
+                            ),
+                            material: materials.add(Color::rgb(0.8, 0.3, 0.2).into()),
+                            transform: Transform::from_translation(Vec3::from(pos)),
+                            ..default()
+                        })
+                        .insert(RemotePlayer { id })
+                        .id();
+
+                    client.players.insert(id, entity);
+                }
+            },
+            ServerMessage::PlayerLeft { id } => {
+                if let Some(entity) = client.players.remove(&id) {
+                    commands.entity(entity).despawn();
+                }
+            }
+        }
+    }
+}
+
+fn send_player_position(
+    mut client: ResMut<Client>,
+    camera_query: Query<&Transform, With<PlayerCamera>>,
+    net_tick_timer: Res<NetTickTimer>,
+) {
+    if !net_tick_timer.0.just_finished() {
+        return;
+    }
+
+    let camera_transform = camera_query.get_single().unwrap();
+
+    client.connection.send(&ClientMessage::Position(
+        camera_transform.translation.to_array(),
+    ));
+}
+
+/// Block edits aren't applied locally, the server sends them back if they went through. Neither
+/// is what they do to the inventory, until the server says they went through.
+fn send_block_edits(mut client: ResMut<Client>, mut edits: EventReader<BlockEdit>) {
+    for edit in edits.iter() {
+        client.connection.send(&ClientMessage::BlockEdit {
+            pos: edit.pos.to_array(),
+            block: edit.block,
+        });
+    }
+}
+
+fn flush_client(mut client: ResMut<Client>, mut exit: EventWriter<AppExit>) {
+    if let Err(err) = client.connection.flush() {
+        error!("Lost connection to the server: {}", err);
+        exit.send(AppExit);
+    }
+}
@@ -0,0 +1,33 @@
+//! Client-server multiplayer over TCP. The server is a headless app that owns the world, and
+//! streams chunks to its clients, which only mesh and render what they receive.
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+use bevy::prelude::*;
+
+/// The address the server listens on and clients connect to if none is given.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:9000";
+
+/// How often player positions are sent, in seconds.
+pub const NET_TICK_INTERVAL: f32 = 0.05;
+
+/// The maximum amount of chunks sent to each client per frame, so that one client loading a lot of
+/// chunks doesn't stall the server.
+pub const MAX_CHUNKS_SENT_PER_TICK: usize = 16;
+
+/// Used to delay sending positions so it doesn't happen every frame
+#[derive(Resource)]
+pub struct NetTickTimer(pub Timer);
+
+pub fn tick_net_timer(mut timer: ResMut<NetTickTimer>, time: Res<Time>) {
+    timer.0.tick(time.delta());
+}
+
+/// A player controlled by another instance of the game; a client on the server, or another
+/// client's player on a client.
+#[derive(Component)]
+pub struct RemotePlayer {
+    pub id: u32,
+}
@@ -0,0 +1,239 @@
+use crate::inventory::item::ItemStack;
+use crate::worldgen::block::Block;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+/// Messages sent from a client to the server.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ClientMessage {
+    /// Where the client's player currently is.
+    Position([f32; 3]),
+    /// Asks the server to change a block. The server answers with `BlockEditResult`, and with
+    /// `BlockChanged` if it went through.
+    BlockEdit { pos: [i32; 3], block: Block },
+}
+
+/// Messages sent from the server to its clients.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ServerMessage {
+    /// First message a client receives, telling it its player id.
+    Welcome {
+        id: u32,
+    },
+    /// The blocks of a chunk, compressed with `storage::encode_chunk`.
+    Chunk {
+        pos: [i32; 3],
+        data: Vec<u8>,
+    },
+    BlockChanged {
+        pos: [i32; 3],
+        block: Block,
+    },
+    /// Whether the client's own edit went through. `replaced` is the block it replaced, or `None`
+    /// if it was rejected.
+    BlockEditResult {
+        pos: [i32; 3],
+        block: Block,
+        replaced: Option<Block>,
+    },
+    PlayerPosition {
+        id: u32,
+        pos: [f32; 3],
+    },
+    PlayerLeft {
+        id: u32,
+    },
+    /// Items dropped by the server, like the contents of a broken machine, for the client to spawn
+    /// in its world.
+    DropItem {
+        pos: [f32; 3],
+        stack: ItemStack,
+    },
+}
+
+/// The longest message that's accepted, in bytes. A chunk full of machines is well under this.
+pub const MAX_MESSAGE_SIZE: usize = 1 << 20;
+/// How many bytes can be waiting to be sent before the other side counts as having fallen too far
+/// behind, and the connection is dropped.
+pub const MAX_QUEUED_BYTES: usize = 16 << 20;
+
+/// A non-blocking TCP connection that sends and receives length-prefixed messages.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    /// Set once more was queued than `MAX_QUEUED_BYTES`. The next flush fails.
+    overflowed: bool,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            overflowed: false,
+        })
+    }
+
+    /// How many bytes are waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.outgoing.len()
+    }
+
+    /// Queues a message to be sent on the next flush. If the other side has fallen too far behind,
+    /// the message is dropped, and the next flush fails instead.
+    pub fn send<T: Serialize>(&mut self, message: &T) {
+        let bytes = bincode::serialize(message).unwrap();
+
+        if self.overflowed || self.outgoing.len() + 4 + bytes.len() > MAX_QUEUED_BYTES {
+            self.overflowed = true;
+            return;
+        }
+
+        self.outgoing
+            .extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.outgoing.extend_from_slice(&bytes);
+    }
+
+    /// Writes as much of the queued messages as the socket accepts without blocking.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.overflowed {
+            return Err(io::Error::other("too many messages are waiting to be sent"));
+        }
+
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads everything available on the socket, and returns every complete message received.
+    /// Returns an error once the other side has disconnected, or if it sends a message longer
+    /// than `MAX_MESSAGE_SIZE`.
+    pub fn receive<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut buffer = [0; 4096];
+
+        // Stop reading once there's enough for a whole message, so a client sending faster than
+        // it's read can't fill up the memory
+        while self.incoming.len() < 4 + MAX_MESSAGE_SIZE {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::ConnectionAborted.into()),
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut messages = Vec::new();
+
+        // Length prefix, then the message itself
+        while self.incoming.len() >= 4 {
+            let len = u32::from_le_bytes(self.incoming[..4].try_into().unwrap()) as usize;
+
+            if len > MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("a message is {} bytes long", len),
+                ));
+            }
+
+            if self.incoming.len() < 4 + len {
+                break;
+            }
+
+            let message = bincode::deserialize(&self.incoming[4..4 + len])
+                .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+            messages.push(message);
+
+            self.incoming.drain(..4 + len);
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    /// A connection, and the raw socket on the other end of it.
+    fn connect() -> (Connection, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let other = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        (Connection::new(stream).unwrap(), other)
+    }
+
+    /// Receives until something arrives, since the socket doesn't block.
+    fn receive_eventually(connection: &mut Connection) -> io::Result<Vec<ClientMessage>> {
+        for _ in 0..100 {
+            let messages = connection.receive()?;
+            if !messages.is_empty() {
+                return Ok(messages);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        Ok(Vec::new())
+    }
+
+    #[test]
+    fn messages_arrive_whole() {
+        let (mut connection, mut other) = connect();
+
+        let bytes = bincode::serialize(&ClientMessage::Position([1.0, 2.0, 3.0])).unwrap();
+        other
+            .write_all(&(bytes.len() as u32).to_le_bytes())
+            .unwrap();
+        other.write_all(&bytes).unwrap();
+
+        let messages = receive_eventually(&mut connection).unwrap();
+        assert!(matches!(
+            messages.as_slice(),
+            [ClientMessage::Position([1.0, 2.0, 3.0])]
+        ));
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected() {
+        let (mut connection, mut other) = connect();
+
+        other
+            .write_all(&(MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes())
+            .unwrap();
+
+        let err = receive_eventually(&mut connection).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn falling_behind_fails_the_flush() {
+        let (mut connection, _other) = connect();
+
+        let chunk = vec![0u8; MAX_MESSAGE_SIZE / 2];
+        for _ in 0..(MAX_QUEUED_BYTES / chunk.len() + 1) {
+            connection.send(&chunk);
+        }
+
+        assert!(connection.queued() <= MAX_QUEUED_BYTES);
+        assert!(connection.flush().is_err());
+    }
+}
@@ -0,0 +1,488 @@
+use crate::interaction::REACH_DISTANCE;
+use crate::inventory::dropped_item::DropItem;
+use crate::inventory::item::Item;
+use crate::net::protocol::{ClientMessage, Connection, ServerMessage};
+use crate::net::{
+    tick_net_timer, NetTickTimer, RemotePlayer, MAX_CHUNKS_SENT_PER_TICK, NET_TICK_INTERVAL,
+};
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::get_block;
+use crate::worldgen::chunk::storage::encode_chunk;
+use crate::worldgen::chunk::{
+    chunk_pos_containing, ChunkLoader, ChunkMap, GeneratedChunks, ViewDistance,
+};
+use crate::worldgen::edit::{BlockChanged, BlockEdit};
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::TcpListener;
+
+/// How much further than `REACH_DISTANCE` a client's edits can be from where the server last heard
+/// it was, since it sends its position less often than it can edit.
+const EDIT_REACH_TOLERANCE: f32 = 3.0;
+/// Chunks aren't queued for a client while this many bytes are still waiting to be sent to it, so
+/// a slow client gets its chunks more slowly instead of being disconnected.
+const MAX_CHUNK_BACKLOG: usize = 1 << 20;
+
+pub struct ServerPlugin {
+    pub address: String,
+}
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        let listener = TcpListener::bind(&self.address).expect("Failed to bind the server address");
+        listener.set_nonblocking(true).unwrap();
+
+        info!("Listening on {}", self.address);
+
+        app.insert_resource(Server {
+            listener,
+            clients: HashMap::new(),
+            next_id: 0,
+            chunk_offsets: Vec::new(),
+        })
+        .add_event::<DropItem>()
+        .insert_resource(NetTickTimer(Timer::from_seconds(
+            NET_TICK_INTERVAL,
+            TimerMode::Repeating,
+        )))
+        .add_systems(
+            Update,
+            (
+                tick_net_timer,
+                accept_clients,
+                receive_client_messages,
+                remove_disconnected_clients,
+                stream_chunks,
+                broadcast_block_changes,
+                send_dropped_items,
+                broadcast_player_positions,
+                flush_clients,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct Server {
+    listener: TcpListener,
+    clients: HashMap<u32, ServerClient>,
+    next_id: u32,
+
+    /// Offsets of every chunk in the view distance, nearest first, so that the chunks around a
+    /// player are streamed before the ones further away. Worked out again whenever the view
+    /// distance changes.
+    chunk_offsets: Vec<IVec3>,
+}
+
+struct ServerClient {
+    connection: Connection,
+    /// The entity chunks are generated around, following the client's player.
+    entity: Entity,
+    /// Chunks in the client's view distance that it's already been sent. Block changes are
+    /// broadcast to every client, so these don't have to be sent again until the client leaves
+    /// and comes back.
+    sent_chunks: HashSet<(i32, i32, i32)>,
+    disconnected: bool,
+}
+
+fn chunk_offsets_by_distance(view_distance: ViewDistance) -> Vec<IVec3> {
+    let distance = view_distance.0;
+    let mut offsets = Vec::new();
+
+    for x in -distance.x..=distance.x {
+        for y in -distance.y..=distance.y {
+            for z in -distance.z..=distance.z {
+                offsets.push(IVec3::new(x, y, z));
+            }
+        }
+    }
+
+    offsets.sort_by_key(|offset| offset.length_squared());
+
+    offsets
+}
+
+fn accept_clients(mut commands: Commands, mut server: ResMut<Server>) {
+    loop {
+        let (stream, address) = match server.listener.accept() {
+            Ok(accepted) => accepted,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("Failed to accept a client: {}", err);
+                break;
+            }
+        };
+
+        let mut connection = match Connection::new(stream) {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("Failed to set up the connection to {}: {}", address, err);
+                continue;
+            }
+        };
+
+        let id = server.next_id;
+        server.next_id += 1;
+
+        let entity = commands
+            .spawn(TransformBundle::default())
+            .insert(ChunkLoader)
+            .insert(RemotePlayer { id })
+            .id();
+
+        connection.send(&ServerMessage::Welcome { id });
+
+        server.clients.insert(
+            id,
+            ServerClient {
+                connection,
+                entity,
+                sent_chunks: HashSet::new(),
+                disconnected: false,
+            },
+        );
+
+        info!("Player {} connected from {}", id, address);
+    }
+}
+
+/// Checks that a player at `player_pos` could make the edit: the block has to be in reach, and
+/// either be broken, or be replaced with a block that the player could be holding. Returns the
+/// block the edit replaces if it's allowed.
+pub fn validate_edit(map: &ChunkMap, player_pos: Vec3, pos: IVec3, block: Block) -> Option<Block> {
+    let distance = player_pos.distance(pos.as_vec3() + 0.5);
+    if distance > REACH_DISTANCE + EDIT_REACH_TOLERANCE {
+        return None;
+    }
+
+    let existing = get_block(map, pos)?;
+
+    let allowed = if block == Block::Air {
+        existing.is_targetable()
+    } else {
+        existing.is_replaceable() && Item::ALL.contains(&Item::Block(block.with_default_state()))
+    };
+
+    allowed.then_some(existing)
+}
+
+fn receive_client_messages(
+    mut server: ResMut<Server>,
+    mut transforms: Query<&mut Transform, With<RemotePlayer>>,
+    mut edits: EventWriter<BlockEdit>,
+    generated_chunks: Res<GeneratedChunks>,
+) {
+    let map = generated_chunks.map.lock().unwrap();
+
+    for client in server.clients.values_mut() {
+        let messages = match client.connection.receive::<ClientMessage>() {
+            Ok(messages) => messages,
+            Err(_) => {
+                client.disconnected = true;
+                continue;
+            }
+        };
+
+        for message in messages {
+            match message {
+                ClientMessage::Position(pos) => {
+                    if let Ok(mut transform) = transforms.get_mut(client.entity) {
+                        transform.translation = Vec3::from(pos);
+                    }
+                }
+                ClientMessage::BlockEdit { pos, block } => {
+                    let player_pos = transforms
+                        .get(client.entity)
+                        .map_or(Vec3::ZERO, |transform| transform.translation);
+                    let replaced = validate_edit(&map, player_pos, IVec3::from(pos), block);
+
+                    if replaced.is_some() {
+                        edits.send(BlockEdit {
+                            pos: IVec3::from(pos),
+                            block,
+                            drops_contents: true,
+                        });
+                    }
+
+                    client.connection.send(&ServerMessage::BlockEditResult {
+                        pos,
+                        block,
+                        replaced,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn remove_disconnected_clients(mut commands: Commands, mut server: ResMut<Server>) {
+    let disconnected: Vec<u32> = server
+        .clients
+        .iter()
+        .filter(|(_, client)| client.disconnected)
+        .map(|(id, _)| *id)
+        .collect();
+
+    for id in disconnected {
+        let client = server.clients.remove(&id).unwrap();
+        commands.entity(client.entity).despawn();
+
+        for other in server.clients.values_mut() {
+            other.connection.send(&ServerMessage::PlayerLeft { id });
+        }
+
+        info!("Player {} disconnected", id);
+    }
+}
+
+/// Sends every generated chunk in a client's view distance that it hasn't received yet.
+fn stream_chunks(
+    mut server: ResMut<Server>,
+    generated_chunks: Res<GeneratedChunks>,
+    transforms: Query<&Transform, With<RemotePlayer>>,
+    view_distance: Res<ViewDistance>,
+) {
+    let map = generated_chunks.map.lock().unwrap();
+
+    let server = &mut *server;
+
+    if view_distance.is_changed() {
+        server.chunk_offsets = chunk_offsets_by_distance(*view_distance);
+    }
+
+    for client in server.clients.values_mut() {
+        let Ok(transform) = transforms.get(client.entity) else {
+            continue;
+        };
+
+        let client_chunk_pos = chunk_pos_containing(transform.translation);
+
+        // Forget chunks the client has left behind, so they're sent again if it comes back
+        client
+            .sent_chunks
+            .retain(|&(x, y, z)| view_distance.contains(IVec3::new(x, y, z) - client_chunk_pos));
+
+        let mut chunks_sent = 0;
+
+        for offset in server.chunk_offsets.iter() {
+            if chunks_sent == MAX_CHUNKS_SENT_PER_TICK
+                || client.connection.queued() > MAX_CHUNK_BACKLOG
+            {
+                break;
+            }
+
+            let pos = client_chunk_pos + *offset;
+            let pos_tuple = (pos.x, pos.y, pos.z);
+
+            if client.sent_chunks.contains(&pos_tuple) {
+                continue;
+            }
+
+            // Not generated yet, it'll be sent once it is
+            let Some(chunk) = map.get(&pos_tuple) else {
+                continue;
+            };
+
+            client.connection.send(&ServerMessage::Chunk {
+                pos: pos.to_array(),
+                data: encode_chunk(chunk),
+            });
+            client.sent_chunks.insert(pos_tuple);
+
+            chunks_sent += 1;
+        }
+    }
+}
+
+fn broadcast_block_changes(mut server: ResMut<Server>, mut changes: EventReader<BlockChanged>) {
+    for change in changes.iter() {
+        for client in server.clients.values_mut() {
+            client.connection.send(&ServerMessage::BlockChanged {
+                pos: change.pos.to_array(),
+                block: change.block,
+            });
+        }
+    }
+}
+
+/// The player nearest to the position, out of players by id and position.
+pub fn nearest_player(players: impl IntoIterator<Item = (u32, Vec3)>, pos: Vec3) -> Option<u32> {
+    players
+        .into_iter()
+        .min_by(|(_, a), (_, b)| a.distance(pos).total_cmp(&b.distance(pos)))
+        .map(|(id, _)| id)
+}
+
+/// A headless server has nothing to spawn dropped items with, so each is sent to the nearest
+/// client to spawn instead. When a player breaks a machine they're in reach of it, so its contents
+/// almost always go to them.
+fn send_dropped_items(
+    mut server: ResMut<Server>,
+    mut drops: EventReader<DropItem>,
+    transforms: Query<&Transform, With<RemotePlayer>>,
+) {
+    for drop in drops.iter() {
+        let players = server.clients.iter().filter_map(|(id, client)| {
+            let transform = transforms.get(client.entity).ok()?;
+            Some((*id, transform.translation))
+        });
+
+        // Nobody's around to pick it up
+        let Some(id) = nearest_player(players, drop.pos) else {
+            continue;
+        };
+
+        server
+            .clients
+            .get_mut(&id)
+            .unwrap()
+            .connection
+            .send(&ServerMessage::DropItem {
+                pos: drop.pos.to_array(),
+                stack: drop.stack,
+            });
+    }
+}
+
+fn broadcast_player_positions(
+    mut server: ResMut<Server>,
+    transforms: Query<(&RemotePlayer, &Transform)>,
+    net_tick_timer: Res<NetTickTimer>,
+) {
+    if !net_tick_timer.0.just_finished() {
+        return;
+    }
+
+    for (player, transform) in transforms.iter() {
+        for (id, client) in server.clients.iter_mut() {
+            if *id == player.id {
+                continue;
+            }
+
+            client.connection.send(&ServerMessage::PlayerPosition {
+                id: player.id,
+                pos: transform.translation.to_array(),
+            });
+        }
+    }
+}
+
+fn flush_clients(mut server: ResMut<Server>) {
+    for client in server.clients.values_mut() {
+        if client.connection.flush().is_err() {
+            client.disconnected = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::chunk::access::set_block;
+    use crate::worldgen::chunk::Chunk;
+
+    fn world() -> ChunkMap {
+        let mut map = ChunkMap::new();
+        map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+        set_block(&mut map, IVec3::new(2, 2, 2), Block::Stone);
+        set_block(&mut map, IVec3::new(3, 2, 2), Block::Water);
+
+        map
+    }
+
+    #[test]
+    fn edits_out_of_reach_are_rejected() {
+        let map = world();
+
+        assert_eq!(
+            validate_edit(
+                &map,
+                Vec3::new(2.5, 4.0, 2.5),
+                IVec3::new(2, 2, 2),
+                Block::Air
+            ),
+            Some(Block::Stone)
+        );
+        assert_eq!(
+            validate_edit(
+                &map,
+                Vec3::new(2.5, 4.0, 14.5),
+                IVec3::new(2, 2, 2),
+                Block::Air
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn only_solid_blocks_can_be_broken() {
+        let map = world();
+        let player = Vec3::new(2.5, 4.0, 2.5);
+
+        assert_eq!(
+            validate_edit(&map, player, IVec3::new(2, 3, 2), Block::Air),
+            None
+        );
+        assert_eq!(
+            validate_edit(&map, player, IVec3::new(3, 2, 2), Block::Air),
+            None
+        );
+    }
+
+    #[test]
+    fn only_items_can_be_placed_into_replaceable_blocks() {
+        let map = world();
+        let player = Vec3::new(2.5, 4.0, 2.5);
+
+        assert_eq!(
+            validate_edit(&map, player, IVec3::new(2, 3, 2), Block::Dirt),
+            Some(Block::Air)
+        );
+        assert_eq!(
+            validate_edit(&map, player, IVec3::new(3, 2, 2), Block::Dirt),
+            Some(Block::Water)
+        );
+        assert_eq!(
+            validate_edit(&map, player, IVec3::new(2, 2, 2), Block::Dirt),
+            None
+        );
+        assert_eq!(
+            validate_edit(&map, player, IVec3::new(2, 3, 2), Block::Water),
+            None
+        );
+        assert_eq!(
+            validate_edit(&map, player, IVec3::new(2, 3, 2), Block::FlowingWater(3)),
+            None
+        );
+    }
+
+    #[test]
+    fn edits_outside_generated_chunks_are_rejected() {
+        let map = world();
+
+        assert_eq!(
+            validate_edit(
+                &map,
+                Vec3::new(0.0, -1.0, 0.0),
+                IVec3::new(0, -2, 0),
+                Block::Dirt
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn drops_go_to_the_nearest_player() {
+        let players = [
+            (0, Vec3::new(10.0, 0.0, 0.0)),
+            (1, Vec3::new(2.0, 1.0, 0.0)),
+            (2, Vec3::new(-5.0, 0.0, 0.0)),
+        ];
+
+        assert_eq!(nearest_player(players, Vec3::new(1.0, 0.0, 0.0)), Some(1));
+        assert_eq!(nearest_player(players, Vec3::new(-3.0, 0.0, 0.0)), Some(2));
+        assert_eq!(nearest_player([], Vec3::ZERO), None);
+    }
+}
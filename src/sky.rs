@@ -0,0 +1,98 @@
+//! The day and night cycle: the sun moves across the sky over the course of a day, and the world and
+//! the sky darken at night.
+
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+/// How long a whole day and night lasts, in seconds.
+pub const DAY_LENGTH: f32 = 1200.0;
+/// Times of day are counted in ticks, with this many in a day, starting at sunrise.
+pub const TICKS_PER_DAY: u32 = 24000;
+
+const SUN_COLOR: Color = Color::rgb(1.0, 0.9, 0.8);
+const NOON_ILLUMINANCE: f32 = 10000.0;
+const DAY_SKY_COLOR: Color = Color::rgb(0.5, 0.7, 0.9);
+const NIGHT_SKY_COLOR: Color = Color::rgb(0.02, 0.02, 0.05);
+/// How far the sun's path is tilted away from straight overhead, so that shadows don't only fall
+/// east and west.
+const SUN_TILT: f32 = 0.5;
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeOfDay>()
+            .add_systems(Startup, spawn_sun)
+            .add_systems(Update, (advance_time_of_day, update_sun).chain());
+    }
+}
+
+/// How far through the day it is, from 0 to 1. 0 is sunrise, 0.25 noon, 0.5 sunset and 0.75
+/// midnight.
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+pub struct TimeOfDay(pub f32);
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self(0.25)
+    }
+}
+
+impl TimeOfDay {
+    pub fn from_ticks(ticks: u32) -> Self {
+        Self((ticks % TICKS_PER_DAY) as f32 / TICKS_PER_DAY as f32)
+    }
+
+    /// How bright the day is, from 0 at night to 1 once the sun is high enough in the sky.
+    pub fn daylight(self) -> f32 {
+        // Starts brightening a little before sunrise, and is fully bright a while after
+        (((self.0 * TAU).sin() + 0.1) / 0.4).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Component)]
+pub struct Sun;
+
+fn spawn_sun(mut commands: Commands) {
+    commands
+        .spawn(DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                color: SUN_COLOR,
+                illuminance: NOON_ILLUMINANCE,
+                shadows_enabled: true,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Sun);
+}
+
+fn advance_time_of_day(mut time_of_day: ResMut<TimeOfDay>, time: Res<Time>) {
+    time_of_day.0 = (time_of_day.0 + time.delta_seconds() / DAY_LENGTH).rem_euclid(1.0);
+}
+
+/// Moves the sun to where it is at this time of day, and sets how bright it and the sky are.
+fn update_sun(
+    time_of_day: Res<TimeOfDay>,
+    mut clear_color: ResMut<ClearColor>,
+    mut sun_query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    let angle = time_of_day.0 * TAU;
+    // Rises in the east, and sets in the west
+    let towards_sun = Vec3::new(angle.cos(), angle.sin(), SUN_TILT);
+    let daylight = time_of_day.daylight();
+
+    for (mut transform, mut light) in sun_query.iter_mut() {
+        *transform = Transform::from_translation(towards_sun).looking_at(Vec3::ZERO, Vec3::Y);
+        light.illuminance = NOON_ILLUMINANCE * daylight;
+    }
+
+    let [night_r, night_g, night_b, _] = NIGHT_SKY_COLOR.as_rgba_f32();
+    let [day_r, day_g, day_b, _] = DAY_SKY_COLOR.as_rgba_f32();
+
+    clear_color.0 = Color::rgb(
+        night_r + (day_r - night_r) * daylight,
+        night_g + (day_g - night_g) * daylight,
+        night_b + (day_b - night_b) * daylight,
+    );
+}
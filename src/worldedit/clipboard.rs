@@ -0,0 +1,294 @@
+use crate::machine::BlockEntity;
+use crate::worldedit::history::EditRecord;
+use crate::worldedit::region::Region;
+use crate::worldgen::block::{Axis, Block};
+use crate::worldgen::chunk::access::{get_block, get_block_entity};
+use crate::worldgen::chunk::Chunk;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Blocks copied out of the world, to be pasted somewhere else.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Clipboard {
+    /// How many blocks across the copy is, in x, y, and z.
+    pub size: UVec3,
+    /// In x, then y, then z order. Blocks that weren't generated when copied are `None`, and are
+    /// left alone when pasting.
+    pub blocks: Vec<Option<Block>>,
+    /// The state of the machines in the copy, by position relative to its minimum corner.
+    pub block_entities: HashMap<UVec3, BlockEntity>,
+}
+
+impl Clipboard {
+    pub fn copy(map: &HashMap<(i32, i32, i32), Chunk>, region: Region) -> Self {
+        Self {
+            size: region.size().as_uvec3(),
+            blocks: region.positions().map(|pos| get_block(map, pos)).collect(),
+            block_entities: region
+                .positions()
+                .filter_map(|pos| {
+                    let block_entity = get_block_entity(map, pos)?.clone();
+
+                    Some(((pos - region.min).as_uvec3(), block_entity))
+                })
+                .collect(),
+        }
+    }
+
+    fn index(&self, pos: UVec3) -> usize {
+        ((pos.x * self.size.y + pos.y) * self.size.z + pos.z) as usize
+    }
+
+    /// The block at a position relative to the copy's minimum corner.
+    pub fn get(&self, pos: UVec3) -> Option<Block> {
+        if pos.cmplt(self.size).all() {
+            self.blocks[self.index(pos)]
+        } else {
+            None
+        }
+    }
+
+    /// Sets the block at a position relative to the copy's minimum corner, which has to be inside
+    /// it.
+    pub fn set(&mut self, pos: UVec3, block: Option<Block>) {
+        let index = self.index(pos);
+        self.blocks[index] = block;
+    }
+
+    /// Every position in the copy, in the same order as `blocks`.
+    fn positions(&self) -> impl Iterator<Item = UVec3> {
+        Region::new(IVec3::ZERO, self.size.as_ivec3() - 1)
+            .positions()
+            .map(|pos| pos.as_uvec3())
+    }
+
+    /// Builds a copy of the given size, taking each block from a position in this one.
+    fn remapped(&self, size: UVec3, source: impl Fn(UVec3) -> UVec3) -> Self {
+        let mut remapped = Self {
+            size,
+            blocks: Vec::new(),
+            block_entities: HashMap::new(),
+        };
+        remapped.blocks = remapped
+            .positions()
+            .map(|pos| self.get(source(pos)))
+            .collect();
+        remapped.block_entities = remapped
+            .positions()
+            .filter_map(|pos| Some((pos, self.block_entities.get(&source(pos))?.clone())))
+            .collect();
+
+        remapped
+    }
+
+    /// The copy turned clockwise, seen from above, by some quarter turns. The blocks in it are
+    /// turned too, so conveyors still lead into each other.
+    pub fn rotated(&self, quarter_turns: u8) -> Self {
+        (0..quarter_turns % 4).fold(self.clone(), |clipboard, _| clipboard.rotated_once())
+    }
+
+    fn rotated_once(&self) -> Self {
+        let size = UVec3::new(self.size.z, self.size.y, self.size.x);
+
+        // Clockwise, -Z goes to +X, and +X goes to +Z
+        let mut rotated = self.remapped(size, |pos| UVec3::new(pos.z, pos.y, size.x - 1 - pos.x));
+
+        for block in rotated.blocks.iter_mut().flatten() {
+            *block = block.rotated(1);
+        }
+
+        rotated
+    }
+
+    /// The copy reflected in a mirror across the axis, with the blocks in it reflected too.
+    pub fn mirrored(&self, axis: Axis) -> Self {
+        let size = self.size;
+
+        let mut mirrored = self.remapped(size, |pos| match axis {
+            Axis::X => UVec3::new(size.x - 1 - pos.x, pos.y, pos.z),
+            Axis::Y => UVec3::new(pos.x, size.y - 1 - pos.y, pos.z),
+            Axis::Z => UVec3::new(pos.x, pos.y, size.z - 1 - pos.z),
+        });
+
+        for block in mirrored.blocks.iter_mut().flatten() {
+            *block = block.mirrored(axis);
+        }
+
+        mirrored
+    }
+
+    /// Works out what pasting the copy with its minimum corner at the position would change,
+    /// including the state of the machines in it.
+    pub fn paste(&self, map: &HashMap<(i32, i32, i32), Chunk>, min: IVec3) -> EditRecord {
+        EditRecord::plan_with_block_entities(
+            map,
+            self.positions().filter_map(|pos| {
+                let block = self.get(pos)?;
+
+                Some((
+                    min + pos.as_ivec3(),
+                    block,
+                    self.block_entities.get(&pos).cloned(),
+                ))
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::{Item, ItemStack};
+    use crate::machine::furnace::Furnace;
+    use crate::worldgen::block::Facing;
+    use crate::worldgen::chunk::access::{get_block_entity_mut, set_block, world_to_chunk_pos};
+
+    const ORE: Item = Item::Block(Block::IronOre);
+
+    /// Two chunks of air side by side along x, with the blocks set in them and machines placed
+    /// like `sync_block_entities` does.
+    fn world(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> HashMap<(i32, i32, i32), Chunk> {
+        let mut map = HashMap::new();
+        map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+        map.insert((1, 0, 0), Chunk::empty(IVec3::new(16, 0, 0)));
+
+        for (pos, block) in blocks {
+            set_block(&mut map, pos, block);
+
+            if let Some(block_entity) = BlockEntity::for_block(block) {
+                let (chunk_pos, local_pos) = world_to_chunk_pos(pos);
+                let chunk = map.get_mut(&chunk_pos).unwrap();
+                chunk.block_entities.insert(local_pos, block_entity);
+            }
+        }
+
+        map
+    }
+
+    fn furnace_with_ore(count: u32) -> BlockEntity {
+        let mut furnace = Furnace::new();
+        furnace.input.insert(ItemStack::new(ORE, count));
+
+        BlockEntity::Furnace(furnace)
+    }
+
+    /// A conveyor facing north next to a stone block, both along x.
+    fn conveyor_and_stone() -> Clipboard {
+        let map = world([
+            (IVec3::new(0, 0, 0), Block::Conveyor(Facing::North)),
+            (IVec3::new(1, 0, 0), Block::Stone),
+        ]);
+
+        Clipboard::copy(&map, Region::new(IVec3::ZERO, IVec3::new(1, 0, 0)))
+    }
+
+    #[test]
+    fn copies_keep_blocks_and_machines_relative_to_the_corner() {
+        let clipboard = conveyor_and_stone();
+
+        assert_eq!(clipboard.size, UVec3::new(2, 1, 1));
+        assert_eq!(
+            clipboard.get(UVec3::ZERO),
+            Some(Block::Conveyor(Facing::North))
+        );
+        assert_eq!(clipboard.get(UVec3::X), Some(Block::Stone));
+        assert!(clipboard.block_entities.contains_key(&UVec3::ZERO));
+        assert_eq!(clipboard.get(UVec3::new(2, 0, 0)), None);
+    }
+
+    #[test]
+    fn blocks_that_are_not_generated_are_copied_as_gaps() {
+        let map = world([]);
+        let clipboard = Clipboard::copy(&map, Region::new(IVec3::new(-1, 0, 0), IVec3::ZERO));
+
+        assert_eq!(clipboard.blocks, [None, Some(Block::Air)]);
+    }
+
+    #[test]
+    fn turning_moves_blocks_and_turns_them() {
+        let rotated = conveyor_and_stone().rotated(1);
+
+        // Along x becomes along z, with the first block still at the back
+        assert_eq!(rotated.size, UVec3::new(1, 1, 2));
+        assert_eq!(
+            rotated.get(UVec3::ZERO),
+            Some(Block::Conveyor(Facing::East))
+        );
+        assert_eq!(rotated.get(UVec3::Z), Some(Block::Stone));
+        assert!(rotated.block_entities.contains_key(&UVec3::ZERO));
+    }
+
+    #[test]
+    fn turning_all_the_way_round_changes_nothing() {
+        let clipboard = conveyor_and_stone();
+
+        assert_eq!(clipboard.rotated(4), clipboard);
+        assert_eq!(clipboard.rotated(1).rotated(3), clipboard);
+        assert_eq!(clipboard.rotated(2).rotated(2), clipboard);
+    }
+
+    #[test]
+    fn mirroring_flips_blocks_and_their_facings() {
+        let map = world([
+            (IVec3::new(0, 0, 0), Block::Conveyor(Facing::East)),
+            (IVec3::new(1, 0, 0), Block::Stone),
+        ]);
+        let clipboard = Clipboard::copy(&map, Region::new(IVec3::ZERO, IVec3::new(1, 0, 0)));
+
+        let across_x = clipboard.mirrored(Axis::X);
+        assert_eq!(across_x.get(UVec3::ZERO), Some(Block::Stone));
+        assert_eq!(across_x.get(UVec3::X), Some(Block::Conveyor(Facing::West)));
+        assert!(across_x.block_entities.contains_key(&UVec3::X));
+
+        // Across z nothing moves, and an east facing conveyor still faces east
+        assert_eq!(clipboard.mirrored(Axis::Z), clipboard);
+        assert_eq!(across_x.mirrored(Axis::X), clipboard);
+    }
+
+    #[test]
+    fn pasting_places_blocks_and_sets_machine_contents() {
+        let mut map = world([(IVec3::ZERO, Block::Furnace(Facing::North))]);
+        *get_block_entity_mut(&mut map, IVec3::ZERO).unwrap() = furnace_with_ore(5);
+        let clipboard = Clipboard::copy(&map, Region::new(IVec3::ZERO, IVec3::X));
+
+        // Across the chunk border, onto a stone block
+        let mut target = world([(IVec3::new(16, 0, 0), Block::Stone)]);
+        let record = clipboard.paste(&target, IVec3::new(15, 0, 0));
+
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.changes[0].pos, IVec3::new(15, 0, 0));
+        assert_eq!(record.changes[0].after, Block::Furnace(Facing::North));
+        assert_eq!(record.changes[0].after_entity, Some(furnace_with_ore(5)));
+        assert_eq!(record.changes[1].before, Block::Stone);
+        assert_eq!(record.changes[1].after, Block::Air);
+
+        // A furnace that's already there is still set to the copied contents
+        set_block(
+            &mut target,
+            IVec3::new(15, 0, 0),
+            Block::Furnace(Facing::North),
+        );
+        target
+            .get_mut(&(0, 0, 0))
+            .unwrap()
+            .block_entities
+            .insert(UVec3::new(15, 0, 0), furnace_with_ore(1));
+        let record = clipboard.paste(&target, IVec3::new(15, 0, 0));
+
+        assert_eq!(record.changes[0].before_entity, Some(furnace_with_ore(1)));
+        assert_eq!(record.changes[0].after_entity, Some(furnace_with_ore(5)));
+
+        // But not once it already has them
+        target
+            .get_mut(&(0, 0, 0))
+            .unwrap()
+            .block_entities
+            .insert(UVec3::new(15, 0, 0), furnace_with_ore(5));
+        let record = clipboard.paste(&target, IVec3::new(15, 0, 0));
+
+        assert!(record
+            .changes
+            .iter()
+            .all(|change| change.pos != IVec3::new(15, 0, 0)));
+    }
+}
@@ -0,0 +1,467 @@
+use crate::machine::BlockEntity;
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::{get_block, get_block_entity};
+use crate::worldgen::chunk::Chunk;
+use crate::worldgen::edit::{BlockEdit, BlockEntityEdit};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::VecDeque;
+
+/// How many edits can be undone.
+pub const MAX_HISTORY: usize = 50;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlockChange {
+    pub pos: IVec3,
+    pub before: Block,
+    pub after: Block,
+    /// The state of the machine that was there, so undoing puts back what was in it.
+    pub before_entity: Option<BlockEntity>,
+    /// The state the machine is set to, or `None` to start it fresh.
+    pub after_entity: Option<BlockEntity>,
+}
+
+/// The blocks one edit changes, and what they were before, so it can be undone.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EditRecord {
+    pub changes: Vec<BlockChange>,
+}
+
+impl EditRecord {
+    /// Works out what setting the blocks would change. Blocks that haven't been generated, or
+    /// already are what they'd be set to, are left out.
+    pub fn plan(
+        map: &HashMap<(i32, i32, i32), Chunk>,
+        blocks: impl IntoIterator<Item = (IVec3, Block)>,
+    ) -> Self {
+        Self::plan_with_block_entities(
+            map,
+            blocks.into_iter().map(|(pos, block)| (pos, block, None)),
+        )
+    }
+
+    /// Like `plan`, but also setting the state of the machines that are placed. A block that's
+    /// already there is still changed if its machine would be set to a different state.
+    pub fn plan_with_block_entities(
+        map: &HashMap<(i32, i32, i32), Chunk>,
+        blocks: impl IntoIterator<Item = (IVec3, Block, Option<BlockEntity>)>,
+    ) -> Self {
+        let changes = blocks
+            .into_iter()
+            .filter_map(|(pos, after, after_entity)| {
+                let before = get_block(map, pos)?;
+                let before_entity = get_block_entity(map, pos).cloned();
+
+                let entity_changes = after_entity.is_some() && after_entity != before_entity;
+
+                (before != after || entity_changes).then_some(BlockChange {
+                    pos,
+                    before,
+                    after,
+                    before_entity,
+                    after_entity,
+                })
+            })
+            .collect();
+
+        Self { changes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The edit that changes every block, and the machine in it, back.
+    pub fn inverted(&self) -> Self {
+        Self {
+            changes: self
+                .changes
+                .iter()
+                .map(|change| BlockChange {
+                    pos: change.pos,
+                    before: change.after,
+                    after: change.before,
+                    before_entity: change.after_entity.clone(),
+                    after_entity: change.before_entity.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Sends an edit for every change, and for the state of every machine it sets. They're all
+    /// applied in the same frame, so each chunk is only meshed again once, however many of its
+    /// blocks changed. Machines that are replaced don't drop what's in them, since it's kept here
+    /// for undoing.
+    pub fn apply(
+        &self,
+        edits: &mut EventWriter<BlockEdit>,
+        block_entity_edits: &mut EventWriter<BlockEntityEdit>,
+    ) {
+        edits.send_batch(self.changes.iter().map(|change| BlockEdit {
+            pos: change.pos,
+            block: change.after,
+            drops_contents: false,
+        }));
+        block_entity_edits.send_batch(self.changes.iter().filter_map(|change| {
+            Some(BlockEntityEdit {
+                pos: change.pos,
+                block_entity: change.after_entity.clone()?,
+            })
+        }));
+    }
+}
+
+/// Edits that can be undone, and undone edits that can be redone.
+#[derive(Default)]
+pub struct EditHistory {
+    undo: VecDeque<EditRecord>,
+    redo: Vec<EditRecord>,
+}
+
+impl EditHistory {
+    /// Remembers an edit that's just been made. Edits that were undone can't be redone after this.
+    pub fn push(&mut self, record: EditRecord) {
+        if record.is_empty() {
+            return;
+        }
+
+        self.redo.clear();
+        self.undo.push_back(record);
+
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.pop_front();
+        }
+    }
+
+    /// The edit that undoes the last one, if there is one.
+    pub fn undo(&mut self) -> Option<EditRecord> {
+        let record = self.undo.pop_back()?;
+        let inverted = record.inverted();
+        self.redo.push(record);
+
+        Some(inverted)
+    }
+
+    /// The last undone edit, to make again.
+    pub fn redo(&mut self) -> Option<EditRecord> {
+        let record = self.redo.pop()?;
+        self.undo.push_back(record.clone());
+
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::dropped_item::DropItem;
+    use crate::inventory::item::{Item, ItemStack};
+    use crate::machine::furnace::Furnace;
+    use crate::machine::{apply_block_entity_edits, sync_block_entities};
+    use crate::worldedit::clipboard::Clipboard;
+    use crate::worldedit::operations;
+    use crate::worldedit::region::Region;
+    use crate::worldgen::block::{Axis, Facing};
+    use crate::worldgen::chunk::access::get_block_entity_mut;
+    use crate::worldgen::chunk::{ChunkMap, GeneratedChunks};
+    use crate::worldgen::edit::{apply_block_edits, BlockChanged};
+    use bevy::ecs::event::ManualEventReader;
+    use std::sync::{Arc, Mutex};
+
+    const ORE: Item = Item::Block(Block::IronOre);
+    const FURNACE: Block = Block::Furnace(Facing::North);
+
+    /// Edit records waiting to be applied on the next update.
+    #[derive(Resource, Default)]
+    struct PendingEdits(Vec<EditRecord>);
+
+    fn apply_pending_edits(
+        mut pending: ResMut<PendingEdits>,
+        mut edits: EventWriter<BlockEdit>,
+        mut block_entity_edits: EventWriter<BlockEntityEdit>,
+    ) {
+        for record in pending.0.drain(..) {
+            record.apply(&mut edits, &mut block_entity_edits);
+        }
+    }
+
+    /// A single chunk at the origin, with edits applied to it by the same systems as in the game,
+    /// without a window.
+    struct World {
+        app: App,
+        drops: ManualEventReader<DropItem>,
+    }
+
+    impl World {
+        fn new() -> Self {
+            let mut map = HashMap::new();
+            map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+
+            let mut app = App::new();
+
+            app.add_event::<BlockEdit>()
+                .add_event::<BlockChanged>()
+                .add_event::<BlockEntityEdit>()
+                .add_event::<DropItem>()
+                .init_resource::<PendingEdits>()
+                .insert_resource(GeneratedChunks {
+                    map: Arc::new(Mutex::new(map)),
+                })
+                .add_systems(
+                    Update,
+                    (
+                        apply_pending_edits,
+                        apply_block_edits,
+                        sync_block_entities,
+                        apply_block_entity_edits,
+                    )
+                        .chain(),
+                );
+
+            Self {
+                app,
+                drops: ManualEventReader::default(),
+            }
+        }
+
+        fn map(&self) -> std::sync::MutexGuard<'_, ChunkMap> {
+            self.app
+                .world
+                .resource::<GeneratedChunks>()
+                .map
+                .lock()
+                .unwrap()
+        }
+
+        fn apply(&mut self, record: EditRecord) {
+            self.app.world.resource_mut::<PendingEdits>().0.push(record);
+            self.app.update();
+        }
+
+        /// Breaks a block like a player does.
+        fn break_block(&mut self, pos: IVec3) {
+            self.app.world.send_event(BlockEdit {
+                pos,
+                block: Block::Air,
+                drops_contents: true,
+            });
+            self.app.update();
+        }
+
+        fn block(&self, pos: IVec3) -> Block {
+            get_block(&self.map(), pos).unwrap()
+        }
+
+        fn block_entity(&self, pos: IVec3) -> Option<BlockEntity> {
+            get_block_entity(&self.map(), pos).cloned()
+        }
+
+        /// The stacks dropped since this was last called.
+        fn take_drops(&mut self) -> Vec<ItemStack> {
+            let events = self.app.world.resource::<Events<DropItem>>();
+
+            self.drops.iter(events).map(|drop| drop.stack).collect()
+        }
+
+        fn place_furnace_with_ore(&mut self, pos: IVec3, count: u32) {
+            let record = EditRecord::plan(&self.map(), [(pos, FURNACE)]);
+            self.apply(record);
+
+            let mut map = self.map();
+            let Some(BlockEntity::Furnace(furnace)) = get_block_entity_mut(&mut map, pos) else {
+                panic!("no furnace was placed");
+            };
+            furnace.input.insert(ItemStack::new(ORE, count));
+        }
+    }
+
+    fn furnace_with_ore(count: u32) -> Option<BlockEntity> {
+        let mut furnace = Furnace::new();
+        furnace.input.insert(ItemStack::new(ORE, count));
+
+        Some(BlockEntity::Furnace(furnace))
+    }
+
+    #[test]
+    fn setting_blocks_places_them_in_the_world() {
+        let mut world = World::new();
+        let region = Region::new(IVec3::ZERO, IVec3::new(1, 1, 0));
+
+        let record = operations::fill(&world.map(), region, Block::Stone);
+        world.apply(record);
+
+        assert!(region
+            .positions()
+            .all(|pos| world.block(pos) == Block::Stone));
+        assert_eq!(world.block(IVec3::new(2, 0, 0)), Block::Air);
+    }
+
+    #[test]
+    fn replacing_machines_keeps_their_contents_instead_of_dropping_them() {
+        let mut world = World::new();
+        world.place_furnace_with_ore(IVec3::ZERO, 5);
+
+        let record = operations::replace(
+            &world.map(),
+            Region::new(IVec3::ZERO, IVec3::ZERO),
+            FURNACE,
+            Block::Stone,
+        );
+        assert_eq!(record.changes[0].before_entity, furnace_with_ore(5));
+        world.apply(record);
+
+        assert_eq!(world.block(IVec3::ZERO), Block::Stone);
+        assert_eq!(world.block_entity(IVec3::ZERO), None);
+        assert!(world.take_drops().is_empty());
+    }
+
+    #[test]
+    fn breaking_a_machine_still_drops_its_contents() {
+        let mut world = World::new();
+        world.place_furnace_with_ore(IVec3::ZERO, 5);
+
+        world.break_block(IVec3::ZERO);
+
+        assert_eq!(world.take_drops(), [ItemStack::new(ORE, 5)]);
+    }
+
+    #[test]
+    fn undoing_puts_machines_back_with_their_contents() {
+        let mut world = World::new();
+        world.place_furnace_with_ore(IVec3::ZERO, 5);
+        let mut history = EditHistory::default();
+
+        let record = operations::fill(
+            &world.map(),
+            Region::new(IVec3::ZERO, IVec3::X),
+            Block::Stone,
+        );
+        history.push(record.clone());
+        world.apply(record);
+
+        world.apply(history.undo().unwrap());
+
+        assert_eq!(world.block(IVec3::ZERO), FURNACE);
+        assert_eq!(world.block(IVec3::X), Block::Air);
+        assert_eq!(world.block_entity(IVec3::ZERO), furnace_with_ore(5));
+
+        world.apply(history.redo().unwrap());
+
+        assert_eq!(world.block(IVec3::ZERO), Block::Stone);
+        assert_eq!(world.block(IVec3::X), Block::Stone);
+        assert_eq!(world.block_entity(IVec3::ZERO), None);
+        assert!(world.take_drops().is_empty());
+    }
+
+    #[test]
+    fn pasted_machines_get_their_contents_and_undo_takes_them_away() {
+        let mut world = World::new();
+        world.place_furnace_with_ore(IVec3::ZERO, 5);
+        let mut history = EditHistory::default();
+
+        let clipboard = Clipboard::copy(&world.map(), Region::new(IVec3::ZERO, IVec3::ZERO));
+        let record = clipboard.paste(&world.map(), IVec3::new(4, 0, 0));
+        history.push(record.clone());
+        world.apply(record);
+
+        assert_eq!(world.block(IVec3::new(4, 0, 0)), FURNACE);
+        assert_eq!(world.block_entity(IVec3::new(4, 0, 0)), furnace_with_ore(5));
+
+        world.apply(history.undo().unwrap());
+
+        assert_eq!(world.block(IVec3::new(4, 0, 0)), Block::Air);
+        assert_eq!(world.block_entity(IVec3::new(4, 0, 0)), None);
+        assert!(world.take_drops().is_empty());
+    }
+
+    #[test]
+    fn pasting_turned_and_mirrored_copies_places_them_the_new_way_round() {
+        let mut world = World::new();
+        let record = EditRecord::plan(
+            &world.map(),
+            [
+                (IVec3::new(0, 0, 0), Block::Conveyor(Facing::North)),
+                (IVec3::new(1, 0, 0), Block::Stone),
+            ],
+        );
+        world.apply(record);
+
+        let clipboard = Clipboard::copy(&world.map(), Region::new(IVec3::ZERO, IVec3::X));
+
+        let record = clipboard
+            .rotated(1)
+            .paste(&world.map(), IVec3::new(0, 2, 0));
+        world.apply(record);
+
+        assert_eq!(
+            world.block(IVec3::new(0, 2, 0)),
+            Block::Conveyor(Facing::East)
+        );
+        assert_eq!(world.block(IVec3::new(0, 2, 1)), Block::Stone);
+        assert!(world.block_entity(IVec3::new(0, 2, 0)).is_some());
+
+        let record = clipboard
+            .rotated(1)
+            .mirrored(Axis::Z)
+            .paste(&world.map(), IVec3::new(0, 4, 0));
+        world.apply(record);
+
+        assert_eq!(world.block(IVec3::new(0, 4, 0)), Block::Stone);
+        assert_eq!(
+            world.block(IVec3::new(0, 4, 1)),
+            Block::Conveyor(Facing::East)
+        );
+        assert!(world.block_entity(IVec3::new(0, 4, 1)).is_some());
+    }
+
+    #[test]
+    fn new_edits_can_not_be_redone_past() {
+        let mut history = EditHistory::default();
+        let edit = |block| EditRecord {
+            changes: vec![BlockChange {
+                pos: IVec3::ZERO,
+                before: Block::Air,
+                after: block,
+                before_entity: None,
+                after_entity: None,
+            }],
+        };
+
+        history.push(edit(Block::Stone));
+        history.push(EditRecord::default());
+        history.undo();
+        history.push(edit(Block::Dirt));
+
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(edit(Block::Dirt).inverted()));
+        assert_eq!(history.undo(), None);
+    }
+
+    #[test]
+    fn only_the_newest_edits_can_be_undone() {
+        let mut history = EditHistory::default();
+
+        for x in 0..MAX_HISTORY as i32 + 5 {
+            history.push(EditRecord {
+                changes: vec![BlockChange {
+                    pos: IVec3::new(x, 0, 0),
+                    before: Block::Air,
+                    after: Block::Stone,
+                    before_entity: None,
+                    after_entity: None,
+                }],
+            });
+        }
+
+        let mut undone = 0;
+        while history.undo().is_some() {
+            undone += 1;
+        }
+
+        assert_eq!(undone, MAX_HISTORY);
+    }
+}
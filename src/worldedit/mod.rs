@@ -0,0 +1,99 @@
+//! Tools for building many blocks at once: select a box between two corners, then fill, replace or
+//! hollow it, or copy it and paste it somewhere else, turned or mirrored. Every edit can be undone.
+//!
+//! Edits are worked out against `GeneratedChunks` without the ECS, and are applied as `BlockEdit`
+//! events all in one frame, so they work the same on a headless server. They're run with commands;
+//! see `command::execute`.
+
+pub mod clipboard;
+pub mod history;
+pub mod operations;
+pub mod region;
+pub mod schematic;
+pub mod vox;
+
+use crate::worldedit::clipboard::Clipboard;
+use crate::worldedit::history::EditHistory;
+use crate::worldedit::region::Region;
+use bevy::prelude::*;
+
+/// The most blocks one edit can change, so that a typo can't stall the game.
+pub const MAX_EDIT_VOLUME: i64 = 32768;
+
+const FIRST_CORNER_COLOR: Color = Color::GREEN;
+const SECOND_CORNER_COLOR: Color = Color::RED;
+const SELECTION_COLOR: Color = Color::YELLOW;
+/// How much bigger the selection box is drawn than the blocks in it, so it isn't hidden inside
+/// their faces.
+const SELECTION_OUTSET: f32 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Corner {
+    First,
+    Second,
+}
+
+/// The two corners of the selected box. Edits need both to be set.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Selection {
+    pub first: Option<IVec3>,
+    pub second: Option<IVec3>,
+}
+
+impl Selection {
+    pub fn set(&mut self, corner: Corner, pos: IVec3) {
+        match corner {
+            Corner::First => self.first = Some(pos),
+            Corner::Second => self.second = Some(pos),
+        }
+    }
+
+    pub fn region(self) -> Option<Region> {
+        Some(Region::new(self.first?, self.second?))
+    }
+}
+
+/// What's selected, what's been copied, and the edits that can be undone.
+#[derive(Resource, Default)]
+pub struct WorldEdit {
+    pub selection: Selection,
+    pub clipboard: Option<Clipboard>,
+    pub history: EditHistory,
+}
+
+/// Shows the selection in the world.
+pub struct WorldEditPlugin;
+
+impl Plugin for WorldEditPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldEdit>()
+            .add_systems(Update, draw_selection);
+    }
+}
+
+/// Outlines the selected box, and each corner that's been set.
+fn draw_selection(mut gizmos: Gizmos, world_edit: Res<WorldEdit>) {
+    let block_box = |region: Region| {
+        let size = region.size().as_vec3();
+
+        Transform::from_translation(region.min.as_vec3() + size / 2.0)
+            .with_scale(size + SELECTION_OUTSET * 2.0)
+    };
+
+    let selection = world_edit.selection;
+
+    if let Some(region) = selection.region() {
+        gizmos.cuboid(block_box(region), SELECTION_COLOR);
+    }
+
+    let corners = [
+        (selection.first, FIRST_CORNER_COLOR),
+        (selection.second, SECOND_CORNER_COLOR),
+    ];
+
+    for (corner, color) in corners {
+        if let Some(pos) = corner {
+            gizmos.cuboid(block_box(Region::new(pos, pos)), color);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Edits to every block in a region. Each works out the changes without making them, so they can
+//! be checked, applied, and undone later.
+
+use crate::worldedit::history::EditRecord;
+use crate::worldedit::region::Region;
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::Chunk;
+use bevy::utils::HashMap;
+
+/// Sets every block in the region.
+pub fn fill(map: &HashMap<(i32, i32, i32), Chunk>, region: Region, block: Block) -> EditRecord {
+    EditRecord::plan(map, region.positions().map(|pos| (pos, block)))
+}
+
+/// Sets every block in the region that's `from`, whichever way it's facing, to `to`.
+pub fn replace(
+    map: &HashMap<(i32, i32, i32), Chunk>,
+    region: Region,
+    from: Block,
+    to: Block,
+) -> EditRecord {
+    let mut record = fill(map, region, to);
+    record
+        .changes
+        .retain(|change| change.before.with_default_state() == from.with_default_state());
+
+    record
+}
+
+/// Sets the faces of the region to the block, and empties everything inside them.
+pub fn hollow(map: &HashMap<(i32, i32, i32), Chunk>, region: Region, block: Block) -> EditRecord {
+    EditRecord::plan(
+        map,
+        region.positions().map(|pos| {
+            if region.is_on_surface(pos) {
+                (pos, block)
+            } else {
+                (pos, Block::Air)
+            }
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::block::Half;
+    use crate::worldgen::chunk::access::set_block;
+    use bevy::prelude::*;
+
+    /// A single chunk of air at the origin, with the blocks set in it.
+    fn world(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> HashMap<(i32, i32, i32), Chunk> {
+        let mut map = HashMap::new();
+        map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+
+        for (pos, block) in blocks {
+            set_block(&mut map, pos, block);
+        }
+
+        map
+    }
+
+    fn changed(record: &EditRecord) -> Vec<(IVec3, Block, Block)> {
+        record
+            .changes
+            .iter()
+            .map(|change| (change.pos, change.before, change.after))
+            .collect()
+    }
+
+    #[test]
+    fn setting_a_region_changes_every_block_that_differs() {
+        let map = world([(IVec3::new(1, 0, 0), Block::Stone)]);
+        let record = fill(
+            &map,
+            Region::new(IVec3::ZERO, IVec3::new(2, 0, 0)),
+            Block::Stone,
+        );
+
+        assert_eq!(
+            changed(&record),
+            [
+                (IVec3::new(0, 0, 0), Block::Air, Block::Stone),
+                (IVec3::new(2, 0, 0), Block::Air, Block::Stone),
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks_that_are_not_generated_are_left_out() {
+        let map = world([]);
+        let record = fill(
+            &map,
+            Region::new(IVec3::new(-1, 0, 0), IVec3::new(0, 0, 0)),
+            Block::Stone,
+        );
+
+        assert_eq!(changed(&record), [(IVec3::ZERO, Block::Air, Block::Stone)]);
+    }
+
+    #[test]
+    fn replacing_only_changes_matching_blocks_in_any_state() {
+        let map = world([
+            (IVec3::new(0, 0, 0), Block::StoneSlab(Half::Top)),
+            (IVec3::new(1, 0, 0), Block::StoneSlab(Half::Bottom)),
+            (IVec3::new(2, 0, 0), Block::Dirt),
+        ]);
+        let record = replace(
+            &map,
+            Region::new(IVec3::ZERO, IVec3::new(3, 0, 0)),
+            Block::StoneSlab(Half::Bottom),
+            Block::Stone,
+        );
+
+        assert_eq!(
+            changed(&record),
+            [
+                (
+                    IVec3::new(0, 0, 0),
+                    Block::StoneSlab(Half::Top),
+                    Block::Stone
+                ),
+                (
+                    IVec3::new(1, 0, 0),
+                    Block::StoneSlab(Half::Bottom),
+                    Block::Stone
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hollowing_keeps_the_faces_and_empties_the_inside() {
+        let region = Region::new(IVec3::ZERO, IVec3::splat(2));
+        let map = world(region.positions().map(|pos| (pos, Block::Dirt)));
+        let record = hollow(&map, region, Block::Stone);
+
+        // Every block on the faces becomes stone, and the one in the middle air
+        assert_eq!(record.len(), 27);
+        assert!(record
+            .changes
+            .iter()
+            .all(|change| (change.after == Block::Air) == (change.pos == IVec3::ONE)));
+    }
+}
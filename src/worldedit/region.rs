@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+
+/// A box of blocks, including both its corners.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Region {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Region {
+    /// The box between two opposite corners, in either order.
+    pub fn new(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// How many blocks across the box is, in x, y, and z.
+    pub fn size(self) -> IVec3 {
+        self.max - self.min + 1
+    }
+
+    /// How many blocks are in the box. Can be more than fits in an `i32`.
+    pub fn volume(self) -> i64 {
+        let size = self.size();
+
+        size.x as i64 * size.y as i64 * size.z as i64
+    }
+
+    pub fn contains(self, pos: IVec3) -> bool {
+        pos.cmpge(self.min).all() && pos.cmple(self.max).all()
+    }
+
+    /// Whether the block is on one of the box's faces, rather than inside it.
+    pub fn is_on_surface(self, pos: IVec3) -> bool {
+        self.contains(pos) && (pos.cmpeq(self.min).any() || pos.cmpeq(self.max).any())
+    }
+
+    /// Every block in the box, in x, then y, then z order.
+    pub fn positions(self) -> impl Iterator<Item = IVec3> {
+        (self.min.x..=self.max.x).flat_map(move |x| {
+            (self.min.y..=self.max.y)
+                .flat_map(move |y| (self.min.z..=self.max.z).map(move |z| IVec3::new(x, y, z)))
+        })
+    }
+}
@@ -0,0 +1,511 @@
+//! Saving copied blocks to files, and loading them back. Files ending in `.vox` are MagicaVoxel
+//! models (see `vox`); anything else is in this game's own format, which keeps every block's state
+//! and the state of the machines too. Blocks are stored by name, so adding or reordering blocks
+//! doesn't change what a saved schematic loads as.
+
+use crate::inventory::item::Item;
+use crate::machine::BlockEntity;
+use crate::worldedit::clipboard::Clipboard;
+use crate::worldedit::vox;
+use crate::worldgen::block::{Axis, Block, Facing, Half, MAX_FLOW_LEVEL};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
+
+/// Where schematics are saved, and loaded from.
+pub const SCHEMATICS_DIR: &str = "schematics";
+/// The extension given to schematics in the native format, if they're named without one.
+pub const SCHEMATIC_EXTENSION: &str = "schem";
+
+/// The start of every schematic in the native format.
+const MAGIC: &[u8; 4] = b"EMS\0";
+const VERSION: u32 = 2;
+/// The most a schematic is decompressed to before it's given up on, far more than anything that
+/// can be copied needs, so a small damaged or malicious file can't use up all the memory.
+const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
+const FACING_NAMES: [(Facing, &str); 4] = [
+    (Facing::North, "north"),
+    (Facing::East, "east"),
+    (Facing::South, "south"),
+    (Facing::West, "west"),
+];
+const AXIS_NAMES: [(Axis, &str); 3] = [(Axis::X, "x"), (Axis::Y, "y"), (Axis::Z, "z")];
+const HALF_NAMES: [(Half, &str); 2] = [(Half::Bottom, "bottom"), (Half::Top, "top")];
+const CABLE_NAMES: [(bool, &str); 2] = [(false, "off"), (true, "on")];
+
+#[derive(Debug)]
+pub enum SchematicError {
+    Io(io::Error),
+    /// The file isn't a schematic, or is damaged.
+    Invalid(&'static str),
+    /// Saved by another version of the game.
+    UnsupportedVersion(u32),
+    /// Too big to save in the format.
+    TooBig {
+        size: UVec3,
+        max: u32,
+    },
+}
+
+impl fmt::Display for SchematicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchematicError::Io(err) => write!(f, "{}", err),
+            SchematicError::Invalid(reason) => write!(f, "invalid schematic: {}", reason),
+            SchematicError::UnsupportedVersion(version) => {
+                write!(f, "schematic version {} isn't supported", version)
+            }
+            SchematicError::TooBig { size, max } => write!(
+                f,
+                "{}x{}x{} is too big, it can be at most {} blocks across",
+                size.x, size.y, size.z, max
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for SchematicError {
+    fn from(err: io::Error) -> Self {
+        SchematicError::Io(err)
+    }
+}
+
+/// A block as it's stored in the palette: its name, and its state written out, like `north` for
+/// a furnace or `top` for a slab. Blocks without a state have an empty one.
+#[derive(Serialize, Deserialize)]
+struct PaletteEntry {
+    name: String,
+    state: String,
+}
+
+impl PaletteEntry {
+    fn new(block: Block) -> Self {
+        let state = match block {
+            Block::FlowingWater(level) => level.to_string(),
+            Block::Log(axis) => state_name(&AXIS_NAMES, axis),
+            Block::StoneSlab(half) => state_name(&HALF_NAMES, half),
+            Block::Cable(powered) => state_name(&CABLE_NAMES, powered),
+            block => block
+                .facing()
+                .map_or_else(String::new, |facing| state_name(&FACING_NAMES, facing)),
+        };
+
+        Self {
+            name: Item::Block(block.with_default_state()).name().to_string(),
+            state,
+        }
+    }
+
+    /// The block the entry stands for, if there's a block with its name and it can be in its
+    /// state.
+    fn block(&self) -> Option<Block> {
+        let block = [Block::Air, Block::Water, Block::FlowingWater(1)]
+            .into_iter()
+            .chain(Item::ALL.into_iter().filter_map(Item::as_block))
+            .find(|block| Item::Block(*block).name() == self.name)?;
+
+        match block {
+            Block::FlowingWater(_) => self
+                .state
+                .parse()
+                .ok()
+                .filter(|level| (1..=MAX_FLOW_LEVEL).contains(level))
+                .map(Block::FlowingWater),
+            Block::Log(_) => state_value(&AXIS_NAMES, &self.state).map(Block::Log),
+            Block::StoneSlab(_) => state_value(&HALF_NAMES, &self.state).map(Block::StoneSlab),
+            Block::Cable(_) => state_value(&CABLE_NAMES, &self.state).map(Block::Cable),
+            block if block.facing().is_some() => {
+                state_value(&FACING_NAMES, &self.state).map(|facing| block.with_facing(facing))
+            }
+            block => self.state.is_empty().then_some(block),
+        }
+    }
+}
+
+fn state_name<T: PartialEq>(names: &[(T, &str)], value: T) -> String {
+    names
+        .iter()
+        .find(|(entry, _)| *entry == value)
+        .map_or("", |(_, name)| name)
+        .to_string()
+}
+
+fn state_value<T: Copy>(names: &[(T, &str)], name: &str) -> Option<T> {
+    names
+        .iter()
+        .find(|(_, entry)| *entry == name)
+        .map(|(value, _)| *value)
+}
+
+/// Everything stored in a schematic, in the form it's serialized as. Each different block is only
+/// stored once, in the palette, and blocks refer to it by index.
+#[derive(Serialize, Deserialize)]
+struct SchematicData {
+    size: [u32; 3],
+    palette: Vec<PaletteEntry>,
+    /// In x, then y, then z order. `None` for blocks that weren't copied.
+    blocks: Vec<Option<u16>>,
+    /// By position in the schematic.
+    block_entities: Vec<([u32; 3], BlockEntity)>,
+}
+
+/// Compresses the clipboard into the native format.
+pub fn encode(clipboard: &Clipboard) -> Vec<u8> {
+    let mut palette: Vec<Block> = Vec::new();
+
+    let blocks = clipboard
+        .blocks
+        .iter()
+        .map(|block| {
+            block.map(
+                |block| match palette.iter().position(|entry| *entry == block) {
+                    Some(index) => index as u16,
+                    None => {
+                        palette.push(block);
+                        (palette.len() - 1) as u16
+                    }
+                },
+            )
+        })
+        .collect();
+
+    let data = SchematicData {
+        size: clipboard.size.to_array(),
+        palette: palette.into_iter().map(PaletteEntry::new).collect(),
+        blocks,
+        block_entities: clipboard
+            .block_entities
+            .iter()
+            .map(|(pos, block_entity)| (pos.to_array(), block_entity.clone()))
+            .collect(),
+    };
+
+    compress(&bincode::serialize(&data).unwrap())
+}
+
+/// Puts the header in front of the serialized data, and compresses it.
+fn compress(serialized: &[u8]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+
+    let mut encoder = DeflateEncoder::new(bytes, Compression::default());
+    encoder.write_all(serialized).unwrap();
+
+    encoder.finish().unwrap()
+}
+
+/// Reverse of `encode`.
+pub fn decode(bytes: &[u8]) -> Result<Clipboard, SchematicError> {
+    if bytes.len() < 8 {
+        return Err(SchematicError::Invalid("the file is too short"));
+    }
+
+    let (header, compressed) = bytes.split_at(8);
+
+    if &header[..4] != MAGIC {
+        return Err(SchematicError::Invalid("the file isn't a schematic"));
+    }
+
+    let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if version != VERSION {
+        return Err(SchematicError::UnsupportedVersion(version));
+    }
+
+    let mut decompressed = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut decompressed)?;
+
+    if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(SchematicError::Invalid("the blocks take up too much space"));
+    }
+
+    let data: SchematicData = bincode::deserialize(&decompressed)
+        .map_err(|_| SchematicError::Invalid("the blocks couldn't be read"))?;
+
+    let size = UVec3::from_array(data.size);
+    let volume = size.x as u64 * size.y as u64 * size.z as u64;
+
+    if volume == 0 || data.blocks.len() as u64 != volume {
+        return Err(SchematicError::Invalid("the size doesn't match the blocks"));
+    }
+
+    let palette = data
+        .palette
+        .iter()
+        .map(PaletteEntry::block)
+        .collect::<Option<Vec<_>>>()
+        .ok_or(SchematicError::Invalid("a block in the palette is unknown"))?;
+
+    let blocks = data
+        .blocks
+        .iter()
+        .map(|index| match index {
+            Some(index) => palette.get(*index as usize).copied().map(Some),
+            None => Some(None),
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(SchematicError::Invalid("a block isn't in the palette"))?;
+
+    let block_entities = data
+        .block_entities
+        .into_iter()
+        .map(|(pos, block_entity)| (UVec3::from_array(pos), block_entity))
+        .collect::<HashMap<_, _>>();
+
+    if block_entities.keys().any(|pos| !pos.cmplt(size).all()) {
+        return Err(SchematicError::Invalid("a machine is outside the blocks"));
+    }
+
+    Ok(Clipboard {
+        size,
+        blocks,
+        block_entities,
+    })
+}
+
+/// Where a schematic with the name is saved. Names without an extension are given the native one.
+/// Returns `None` for names that would lead outside the schematics folder.
+pub fn schematic_path(name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+
+    if path.file_name() != Some(OsStr::new(name)) || name.starts_with('.') {
+        return None;
+    }
+
+    let path = Path::new(SCHEMATICS_DIR).join(path);
+
+    Some(if path.extension().is_some() {
+        path
+    } else {
+        path.with_extension(SCHEMATIC_EXTENSION)
+    })
+}
+
+fn is_vox(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(vox::VOX_EXTENSION))
+}
+
+/// Saves the clipboard, as a MagicaVoxel model if the path ends in `.vox`.
+pub fn save(clipboard: &Clipboard, path: &Path) -> Result<(), SchematicError> {
+    let bytes = if is_vox(path) {
+        vox::encode(clipboard)?
+    } else {
+        encode(clipboard)
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Clipboard, SchematicError> {
+    let bytes = fs::read(path)?;
+
+    if is_vox(path) {
+        vox::decode(&bytes)
+    } else {
+        decode(&bytes)
+    }
+}
+
+/// The names of every saved schematic, for tab completion.
+pub fn saved_schematics() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(SCHEMATICS_DIR) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::ItemStack;
+    use crate::machine::furnace::Furnace;
+
+    /// Every state of a block that has one, and one of each block that doesn't.
+    fn every_block() -> Vec<Block> {
+        let facings = [Facing::North, Facing::East, Facing::South, Facing::West];
+
+        let mut blocks = vec![Block::Air, Block::Water];
+        blocks.extend((1..=MAX_FLOW_LEVEL).map(Block::FlowingWater));
+        blocks.extend([Axis::X, Axis::Y, Axis::Z].map(Block::Log));
+        blocks.extend([Half::Bottom, Half::Top].map(Block::StoneSlab));
+        blocks.extend([false, true].map(Block::Cable));
+
+        for item in Item::ALL {
+            match item.as_block() {
+                Some(block) if block.facing().is_some() => {
+                    blocks.extend(facings.map(|facing| block.with_facing(facing)))
+                }
+                Some(block) => blocks.push(block),
+                None => {}
+            }
+        }
+
+        blocks.sort_by_key(|block| format!("{:?}", block));
+        blocks.dedup();
+
+        blocks
+    }
+
+    fn furnace_with_ore(count: u32) -> BlockEntity {
+        let mut furnace = Furnace::new();
+        furnace
+            .input
+            .insert(ItemStack::new(Item::Block(Block::IronOre), count));
+
+        BlockEntity::Furnace(furnace)
+    }
+
+    /// Two blocks along x, with `blocks` referring to the palette.
+    fn data(palette: Vec<PaletteEntry>, blocks: Vec<Option<u16>>) -> SchematicData {
+        SchematicData {
+            size: [2, 1, 1],
+            palette,
+            blocks,
+            block_entities: Vec::new(),
+        }
+    }
+
+    fn stone() -> PaletteEntry {
+        PaletteEntry::new(Block::Stone)
+    }
+
+    fn decode_data(data: &SchematicData) -> Result<Clipboard, SchematicError> {
+        decode(&compress(&bincode::serialize(data).unwrap()))
+    }
+
+    fn assert_invalid(result: Result<Clipboard, SchematicError>, expected: &str) {
+        match result {
+            Err(SchematicError::Invalid(reason)) => assert_eq!(reason, expected),
+            other => panic!("expected '{}', got {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn every_block_state_is_kept_in_the_palette() {
+        for block in every_block() {
+            assert_eq!(PaletteEntry::new(block).block(), Some(block), "{:?}", block);
+        }
+    }
+
+    #[test]
+    fn blocks_are_stored_by_name() {
+        let entry = PaletteEntry::new(Block::Furnace(Facing::East));
+
+        assert_eq!(entry.name, "furnace");
+        assert_eq!(entry.state, "east");
+        assert_eq!(PaletteEntry::new(Block::Stone).state, "");
+    }
+
+    #[test]
+    fn clipboards_round_trip_with_their_states_and_machines() {
+        let blocks = every_block();
+        let mut clipboard = Clipboard {
+            size: UVec3::new(blocks.len() as u32 + 1, 1, 1),
+            blocks: blocks.iter().copied().map(Some).chain([None]).collect(),
+            block_entities: HashMap::new(),
+        };
+        let furnace = blocks
+            .iter()
+            .position(|block| *block == Block::Furnace(Facing::North))
+            .unwrap();
+        clipboard
+            .block_entities
+            .insert(UVec3::new(furnace as u32, 0, 0), furnace_with_ore(7));
+
+        assert_eq!(decode(&encode(&clipboard)).unwrap(), clipboard);
+    }
+
+    #[test]
+    fn files_that_are_not_schematics_are_rejected() {
+        let mut bytes =
+            compress(&bincode::serialize(&data(vec![stone()], vec![Some(0); 2])).unwrap());
+        bytes[0] = b'X';
+
+        assert_invalid(decode(&bytes), "the file isn't a schematic");
+        assert_invalid(decode(b"EMS"), "the file is too short");
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes =
+            compress(&bincode::serialize(&data(vec![stone()], vec![Some(0); 2])).unwrap());
+        bytes[4..8].copy_from_slice(&1u32.to_le_bytes());
+
+        assert!(matches!(
+            decode(&bytes),
+            Err(SchematicError::UnsupportedVersion(1))
+        ));
+    }
+
+    #[test]
+    fn blocks_outside_the_palette_are_rejected() {
+        assert_invalid(
+            decode_data(&data(vec![stone()], vec![Some(0), Some(1)])),
+            "a block isn't in the palette",
+        );
+    }
+
+    #[test]
+    fn unknown_blocks_and_states_are_rejected() {
+        let entry = |name: &str, state: &str| PaletteEntry {
+            name: name.to_string(),
+            state: state.to_string(),
+        };
+
+        for palette_entry in [
+            entry("diamond_block", ""),
+            entry("furnace", "up"),
+            entry("stone", "north"),
+            entry("flowing_water", "0"),
+        ] {
+            assert_invalid(
+                decode_data(&data(vec![palette_entry], vec![Some(0), None])),
+                "a block in the palette is unknown",
+            );
+        }
+    }
+
+    #[test]
+    fn sizes_that_do_not_match_the_blocks_are_rejected() {
+        assert_invalid(
+            decode_data(&data(vec![stone()], vec![Some(0)])),
+            "the size doesn't match the blocks",
+        );
+    }
+
+    #[test]
+    fn machines_outside_the_blocks_are_rejected() {
+        let mut data = data(vec![stone()], vec![Some(0), Some(0)]);
+        data.block_entities.push(([2, 0, 0], furnace_with_ore(1)));
+
+        assert_invalid(decode_data(&data), "a machine is outside the blocks");
+    }
+
+    #[test]
+    fn decompressing_stops_at_the_limit() {
+        let huge = vec![0; MAX_DECOMPRESSED_SIZE as usize + 1];
+
+        assert_invalid(
+            decode(&compress(&huge)),
+            "the blocks take up too much space",
+        );
+    }
+}
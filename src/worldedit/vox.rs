@@ -0,0 +1,516 @@
+//! MagicaVoxel `.vox` models. Voxels only have a colour, so blocks are saved as the colour in
+//! `BLOCK_COLORS` closest to how they look, and each colour is loaded as the block with the
+//! nearest one. Which way blocks face, and the state of machines, isn't kept.
+//!
+//! MagicaVoxel's Z is up, and its Y points north, so the game's Y and Z are swapped over.
+
+use crate::worldedit::clipboard::Clipboard;
+use crate::worldedit::schematic::SchematicError;
+use crate::worldgen::block::{Axis, Block, Facing, Half};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+pub const VOX_EXTENSION: &str = "vox";
+/// The most voxels a model can be across, on every axis.
+pub const MAX_VOX_SIZE: u32 = 256;
+
+const MAGIC: &[u8; 4] = b"VOX ";
+const VERSION: i32 = 150;
+/// How many colours are in a palette, including the unused first index for empty voxels.
+const PALETTE_SIZE: usize = 256;
+/// The levels of red, green and blue that make up the colour cube at the start of MagicaVoxel's
+/// default palette.
+const DEFAULT_PALETTE_STEPS: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+/// The levels of the red, green, blue and grey ramps at the end of the default palette.
+const DEFAULT_PALETTE_RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+/// The colour each block is saved as, and loaded from.
+pub const BLOCK_COLORS: [(Block, [u8; 3]); 25] = [
+    (Block::Grass, [95, 159, 53]),
+    (Block::Dirt, [134, 96, 67]),
+    (Block::Stone, [125, 125, 125]),
+    (Block::Water, [63, 118, 228]),
+    (Block::IronOre, [216, 175, 147]),
+    (Block::CoalOre, [40, 40, 40]),
+    (Block::Log(Axis::Y), [102, 81, 51]),
+    (Block::Furnace(Facing::North), [90, 90, 95]),
+    (Block::Crusher(Facing::North), [150, 110, 60]),
+    (Block::Assembler(Facing::North), [70, 110, 150]),
+    (Block::Conveyor(Facing::North), [60, 60, 60]),
+    (Block::Splitter(Facing::North), [200, 160, 40]),
+    (Block::Merger(Facing::North), [40, 160, 200]),
+    (Block::Inserter(Facing::North), [230, 200, 60]),
+    (Block::Generator(Facing::North), [180, 60, 40]),
+    (Block::Cable(false), [200, 90, 30]),
+    (Block::Battery, [60, 180, 80]),
+    (Block::Pipe, [170, 170, 180]),
+    (Block::Tank, [110, 130, 140]),
+    (Block::Pump, [50, 90, 120]),
+    (Block::Boiler(Facing::North), [150, 70, 70]),
+    (Block::StoneSlab(Half::Bottom), [160, 160, 160]),
+    (Block::StoneStairs(Facing::North), [145, 145, 150]),
+    (Block::IronBars(Facing::North), [200, 200, 210]),
+    (Block::TallGrass, [120, 190, 70]),
+];
+
+/// Where the block's colour is in `BLOCK_COLORS`, ignoring its state.
+fn color_index(block: Block) -> Option<usize> {
+    let block = match block {
+        Block::FlowingWater(_) => Block::Water,
+        block => block.with_default_state(),
+    };
+
+    BLOCK_COLORS.iter().position(|(entry, _)| *entry == block)
+}
+
+/// The block whose colour is closest to the colour.
+pub fn nearest_block(color: [u8; 3]) -> Block {
+    let distance = |other: [u8; 3]| {
+        color
+            .iter()
+            .zip(other)
+            .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+
+    BLOCK_COLORS
+        .iter()
+        .min_by_key(|(_, block_color)| distance(*block_color))
+        .map(|(block, _)| *block)
+        .unwrap()
+}
+
+/// The colour at the palette index, counting from 1, in the palette MagicaVoxel uses for models
+/// saved without one: a cube of colours without black, then ramps of red, green, blue and grey.
+fn default_palette_color(index: usize) -> [u8; 3] {
+    let cube_size = DEFAULT_PALETTE_STEPS.len().pow(3) - 1;
+
+    if index <= cube_size {
+        let i = index - 1;
+        let step = |place: usize| DEFAULT_PALETTE_STEPS[i / place % 6];
+
+        return [step(36), step(6), step(1)];
+    }
+
+    let i = index - cube_size - 1;
+    let level = DEFAULT_PALETTE_RAMP[i % DEFAULT_PALETTE_RAMP.len()];
+
+    match i / DEFAULT_PALETTE_RAMP.len() {
+        0 => [level, 0, 0],
+        1 => [0, level, 0],
+        2 => [0, 0, level],
+        _ => [level; 3],
+    }
+}
+
+/// The position in the model of a position in the clipboard, where `size` is the size of the
+/// clipboard.
+fn to_vox_pos(pos: UVec3, size: UVec3) -> UVec3 {
+    UVec3::new(pos.x, size.z - 1 - pos.z, pos.y)
+}
+
+/// The reverse of `to_vox_pos`, where `vox_size` is the size of the model.
+fn from_vox_pos(pos: UVec3, vox_size: UVec3) -> UVec3 {
+    UVec3::new(pos.x, pos.z, vox_size.y - 1 - pos.y)
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+}
+
+/// Saves the clipboard as a model. Air, and blocks that weren't copied, are left empty.
+pub fn encode(clipboard: &Clipboard) -> Result<Vec<u8>, SchematicError> {
+    let size = clipboard.size;
+
+    if size.max_element() > MAX_VOX_SIZE {
+        return Err(SchematicError::TooBig {
+            size,
+            max: MAX_VOX_SIZE,
+        });
+    }
+
+    let vox_size = UVec3::new(size.x, size.z, size.y);
+
+    let mut voxels = Vec::new();
+    let mut count: u32 = 0;
+
+    for x in 0..size.x {
+        for y in 0..size.y {
+            for z in 0..size.z {
+                let pos = UVec3::new(x, y, z);
+                let Some(index) = clipboard.get(pos).and_then(color_index) else {
+                    continue;
+                };
+                let vox_pos = to_vox_pos(pos, size);
+
+                // Palette indices start at 1, since 0 is empty
+                voxels.extend_from_slice(&[
+                    vox_pos.x as u8,
+                    vox_pos.y as u8,
+                    vox_pos.z as u8,
+                    index as u8 + 1,
+                ]);
+                count += 1;
+            }
+        }
+    }
+
+    let mut size_content = Vec::new();
+    for length in vox_size.to_array() {
+        size_content.extend_from_slice(&length.to_le_bytes());
+    }
+
+    let mut xyzi_content = count.to_le_bytes().to_vec();
+    xyzi_content.extend_from_slice(&voxels);
+
+    // The palette is shifted by one: its first colour is for index 1
+    let mut rgba_content = vec![0; PALETTE_SIZE * 4];
+    for (index, (_, [r, g, b])) in BLOCK_COLORS.iter().enumerate() {
+        rgba_content[index * 4..index * 4 + 4].copy_from_slice(&[*r, *g, *b, 255]);
+    }
+
+    let mut children = Vec::new();
+    write_chunk(&mut children, b"SIZE", &size_content, &[]);
+    write_chunk(&mut children, b"XYZI", &xyzi_content, &[]);
+    write_chunk(&mut children, b"RGBA", &rgba_content, &[]);
+
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    write_chunk(&mut out, b"MAIN", &[], &children);
+
+    Ok(out)
+}
+
+/// A chunk of a model: what kind it is, what's in it, and the chunks nested in it.
+struct RawChunk<'a> {
+    id: &'a [u8],
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+/// Reads little-endian numbers and chunks from a model, failing if it ends too soon.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], SchematicError> {
+        if self.bytes.len() < count {
+            return Err(SchematicError::Invalid("the file ends too soon"));
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, SchematicError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn chunk(&mut self) -> Result<RawChunk<'a>, SchematicError> {
+        let id = self.take(4)?;
+        let content_length = self.u32()? as usize;
+        let children_length = self.u32()? as usize;
+
+        Ok(RawChunk {
+            id,
+            content: self.take(content_length)?,
+            children: self.take(children_length)?,
+        })
+    }
+}
+
+/// Loads the first model in the file. Empty voxels are loaded as air, and models without a palette
+/// use MagicaVoxel's default one.
+pub fn decode(bytes: &[u8]) -> Result<Clipboard, SchematicError> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(4)? != MAGIC {
+        return Err(SchematicError::Invalid(
+            "the file isn't a MagicaVoxel model",
+        ));
+    }
+    reader.u32()?;
+
+    let main = reader.chunk()?;
+    if main.id != b"MAIN" {
+        return Err(SchematicError::Invalid("the model has no main chunk"));
+    }
+
+    let mut children = Reader {
+        bytes: main.children,
+    };
+    let mut vox_size = None;
+    let mut voxels = None;
+    let mut palette = None;
+
+    while !children.bytes.is_empty() {
+        let chunk = children.chunk()?;
+        let mut content = Reader {
+            bytes: chunk.content,
+        };
+
+        match chunk.id {
+            b"SIZE" if vox_size.is_none() => {
+                vox_size = Some(UVec3::new(content.u32()?, content.u32()?, content.u32()?));
+            }
+            b"XYZI" if voxels.is_none() => {
+                let count = content.u32()? as usize;
+                voxels = Some(content.take(count.saturating_mul(4))?);
+            }
+            b"RGBA" => palette = Some(content.take(PALETTE_SIZE * 4)?),
+            // Transforms, materials and the like, and any models after the first
+            _ => {}
+        }
+    }
+
+    let (Some(vox_size), Some(voxels)) = (vox_size, voxels) else {
+        return Err(SchematicError::Invalid("the file has no model in it"));
+    };
+
+    if vox_size.cmpeq(UVec3::ZERO).any() || vox_size.max_element() > MAX_VOX_SIZE {
+        return Err(SchematicError::Invalid("the model's size is out of range"));
+    }
+
+    let size = UVec3::new(vox_size.x, vox_size.z, vox_size.y);
+    let mut clipboard = Clipboard {
+        size,
+        blocks: vec![Some(Block::Air); (size.x * size.y * size.z) as usize],
+        block_entities: HashMap::new(),
+    };
+
+    for voxel in voxels.chunks_exact(4) {
+        let vox_pos = UVec3::new(voxel[0] as u32, voxel[1] as u32, voxel[2] as u32);
+        let index = voxel[3] as usize;
+
+        if index == 0 {
+            return Err(SchematicError::Invalid("a voxel has no colour"));
+        }
+        if !vox_pos.cmplt(vox_size).all() {
+            return Err(SchematicError::Invalid("a voxel is outside the model"));
+        }
+
+        let color = match palette {
+            Some(palette) => {
+                let color = &palette[(index - 1) * 4..(index - 1) * 4 + 3];
+                [color[0], color[1], color[2]]
+            }
+            None => default_palette_color(index),
+        };
+        let block = nearest_block(color);
+
+        clipboard.set(from_vox_pos(vox_pos, vox_size), Some(block));
+    }
+
+    Ok(clipboard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A model file with a palette of `BLOCK_COLORS`, holding one model of the size.
+    fn vox_file(vox_size: [u32; 3], voxels: &[[u8; 4]]) -> Vec<u8> {
+        model_file(vox_size, voxels, true)
+    }
+
+    /// A model file holding one model of the size, with a palette of `BLOCK_COLORS` if
+    /// `with_palette` is set.
+    fn model_file(vox_size: [u32; 3], voxels: &[[u8; 4]], with_palette: bool) -> Vec<u8> {
+        let mut size_content = Vec::new();
+        for length in vox_size {
+            size_content.extend_from_slice(&length.to_le_bytes());
+        }
+
+        let mut xyzi_content = (voxels.len() as u32).to_le_bytes().to_vec();
+        xyzi_content.extend(voxels.iter().flatten());
+
+        let mut rgba_content = vec![0; PALETTE_SIZE * 4];
+        for (index, (_, [r, g, b])) in BLOCK_COLORS.iter().enumerate() {
+            rgba_content[index * 4..index * 4 + 4].copy_from_slice(&[*r, *g, *b, 255]);
+        }
+
+        let mut children = Vec::new();
+        write_chunk(&mut children, b"SIZE", &size_content, &[]);
+        write_chunk(&mut children, b"XYZI", &xyzi_content, &[]);
+        if with_palette {
+            write_chunk(&mut children, b"RGBA", &rgba_content, &[]);
+        }
+
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        write_chunk(&mut out, b"MAIN", &[], &children);
+
+        out
+    }
+
+    /// The palette index, counting from 1, of the block's colour.
+    fn color(block: Block) -> u8 {
+        color_index(block).unwrap() as u8 + 1
+    }
+
+    fn assert_invalid(result: Result<Clipboard, SchematicError>, expected: &str) {
+        match result {
+            Err(SchematicError::Invalid(reason)) => assert_eq!(reason, expected),
+            other => panic!("expected '{}', got {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn every_block_colour_loads_as_its_block() {
+        for (block, color) in BLOCK_COLORS {
+            assert_eq!(nearest_block(color), block);
+        }
+    }
+
+    #[test]
+    fn models_round_trip_without_block_states() {
+        let size = UVec3::new(2, 3, 4);
+        let mut clipboard = Clipboard {
+            size,
+            blocks: vec![Some(Block::Air); 24],
+            block_entities: HashMap::new(),
+        };
+        clipboard.set(UVec3::new(0, 0, 0), Some(Block::Stone));
+        clipboard.set(UVec3::new(1, 2, 0), Some(Block::Furnace(Facing::East)));
+        clipboard.set(UVec3::new(0, 1, 3), Some(Block::FlowingWater(3)));
+        clipboard.set(UVec3::new(1, 0, 2), None);
+
+        let loaded = decode(&encode(&clipboard).unwrap()).unwrap();
+
+        let mut expected = clipboard.clone();
+        expected.set(UVec3::new(1, 2, 0), Some(Block::Furnace(Facing::North)));
+        expected.set(UVec3::new(0, 1, 3), Some(Block::Water));
+        expected.set(UVec3::new(1, 0, 2), Some(Block::Air));
+
+        assert_eq!(loaded, expected);
+    }
+
+    #[test]
+    fn the_game_y_is_saved_as_the_model_z() {
+        let mut clipboard = Clipboard {
+            size: UVec3::new(1, 2, 3),
+            blocks: vec![Some(Block::Air); 6],
+            block_entities: HashMap::new(),
+        };
+        // The top block, furthest north
+        clipboard.set(UVec3::new(0, 1, 0), Some(Block::Stone));
+
+        let bytes = encode(&clipboard).unwrap();
+
+        // The size comes first in the main chunk, then the voxels
+        let size_content = &bytes[8 + 12 + 12..8 + 12 + 12 + 12];
+        let vox_size: Vec<u32> = size_content
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(vox_size, [1, 3, 2]);
+
+        let voxel = &bytes[8 + 12 + 24 + 12 + 4..8 + 12 + 24 + 12 + 8];
+        // North is the model's +Y, and up is its +Z
+        assert_eq!(voxel, [0, 2, 1, color(Block::Stone)]);
+    }
+
+    #[test]
+    fn model_z_is_loaded_as_the_game_y() {
+        let bytes = vox_file([1, 2, 3], &[[0, 0, 2, color(Block::Dirt)]]);
+        let clipboard = decode(&bytes).unwrap();
+
+        assert_eq!(clipboard.size, UVec3::new(1, 3, 2));
+        // The top of the model, at the south end
+        assert_eq!(clipboard.get(UVec3::new(0, 2, 1)), Some(Block::Dirt));
+        assert_eq!(
+            clipboard
+                .blocks
+                .iter()
+                .filter(|block| **block == Some(Block::Air))
+                .count(),
+            5
+        );
+    }
+
+    #[test]
+    fn the_default_palette_matches_magicavoxel() {
+        assert_eq!(default_palette_color(1), [0xff, 0xff, 0xff]);
+        assert_eq!(default_palette_color(2), [0xff, 0xff, 0xcc]);
+        assert_eq!(default_palette_color(7), [0xff, 0xcc, 0xff]);
+        assert_eq!(default_palette_color(36), [0xff, 0x00, 0x00]);
+        assert_eq!(default_palette_color(37), [0xcc, 0xff, 0xff]);
+        assert_eq!(default_palette_color(215), [0x00, 0x00, 0x33]);
+        assert_eq!(default_palette_color(216), [0xee, 0x00, 0x00]);
+        assert_eq!(default_palette_color(226), [0x00, 0xee, 0x00]);
+        assert_eq!(default_palette_color(236), [0x00, 0x00, 0xee]);
+        assert_eq!(default_palette_color(246), [0xee, 0xee, 0xee]);
+        assert_eq!(default_palette_color(255), [0x11, 0x11, 0x11]);
+    }
+
+    #[test]
+    fn models_without_a_palette_use_the_default_one() {
+        // Bright red, and dark grey
+        let bytes = model_file([2, 1, 1], &[[0, 0, 0, 36], [1, 0, 0, 254]], false);
+
+        let clipboard = decode(&bytes).unwrap();
+
+        assert_eq!(
+            clipboard.get(UVec3::new(0, 0, 0)),
+            Some(Block::Generator(Facing::North))
+        );
+        assert_eq!(clipboard.get(UVec3::new(1, 0, 0)), Some(Block::CoalOre));
+    }
+
+    #[test]
+    fn files_that_are_not_models_are_rejected() {
+        let mut bytes = vox_file([1, 1, 1], &[]);
+        bytes[0] = b'X';
+
+        assert_invalid(decode(&bytes), "the file isn't a MagicaVoxel model");
+        assert_invalid(decode(b"VOX "), "the file ends too soon");
+    }
+
+    #[test]
+    fn voxels_outside_the_model_are_rejected() {
+        let bytes = vox_file([2, 2, 2], &[[0, 2, 0, color(Block::Stone)]]);
+
+        assert_invalid(decode(&bytes), "a voxel is outside the model");
+    }
+
+    #[test]
+    fn voxels_without_a_colour_are_rejected() {
+        let bytes = vox_file([2, 2, 2], &[[0, 0, 0, 0]]);
+
+        assert_invalid(decode(&bytes), "a voxel has no colour");
+    }
+
+    #[test]
+    fn models_of_the_wrong_size_are_rejected() {
+        assert_invalid(
+            decode(&vox_file([0, 1, 1], &[])),
+            "the model's size is out of range",
+        );
+        assert_invalid(
+            decode(&vox_file([MAX_VOX_SIZE + 1, 1, 1], &[])),
+            "the model's size is out of range",
+        );
+    }
+
+    #[test]
+    fn clipboards_too_big_for_a_model_are_not_saved() {
+        let size = UVec3::new(MAX_VOX_SIZE + 1, 1, 1);
+        let clipboard = Clipboard {
+            size,
+            blocks: vec![None; size.x as usize],
+            block_entities: HashMap::new(),
+        };
+
+        assert!(matches!(
+            encode(&clipboard),
+            Err(SchematicError::TooBig { .. })
+        ));
+    }
+}
@@ -1,36 +1,500 @@
-pub const ATLAS_SIZE: (usize, usize) = (32, 32);
+use crate::inventory::item::{Item, Material};
+use crate::inventory::tool::{ToolKind, ToolTier};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub const ATLAS_SIZE: (usize, usize) = (64, 128);
+/// The size of one texture in the atlas, in pixels.
+pub const TEXTURE_SIZE: usize = 16;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Block {
     Grass,
     Dirt,
     Stone,
+    /// A water source, which never drains.
     Water,
+    /// Water flowing away from a source. The level goes from 1 next to a source (or falling down)
+    /// up to `MAX_FLOW_LEVEL` at the furthest it spreads.
+    FlowingWater(u8),
+    IronOre,
+    CoalOre,
+    /// Lies along the axis of the face it was placed against.
+    Log(Axis),
+    /// Machines face the player that placed them, and their front is drawn on that side.
+    Furnace(Facing),
+    Crusher(Facing),
+    Assembler(Facing),
+    /// Carries items along in the direction it faces.
+    Conveyor(Facing),
+    /// Takes items in like a conveyor, and hands them out to its left and right in turns.
+    Splitter(Facing),
+    /// Pulls items in from the conveyors pointing into its back and sides, in turns, and passes
+    /// them on in front.
+    Merger(Facing),
+    /// Moves items from the block behind it to the block in front of it.
+    Inserter(Facing),
+    /// Burns fuel to power the network it's connected to.
+    Generator(Facing),
+    /// Connects blocks into a power network. Lights up while the network has power to give.
+    Cable(bool),
+    /// Stores power that's left over, for when generators can't keep up.
+    Battery,
+    /// Carries fluids between the tanks and machines it's connected to.
+    Pipe,
+    /// Stores a lot of one fluid.
+    Tank,
+    /// Pumps water out of a water source next to it, using power.
+    Pump,
+    /// Burns fuel to turn water into steam.
+    Boiler(Facing),
+    /// Half a block of stone, in the bottom or top half of the block.
+    StoneSlab(Half),
+    /// Stone steps, going up in the direction they face.
+    StoneStairs(Facing),
+    /// A thin wall of bars, across the direction it faces.
+    IronBars(Facing),
+    /// Grows on grass. The player walks right through it.
+    TallGrass,
     Air,
 }
 
+/// A horizontal direction, for blocks that point somewhere.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Facing {
+    /// Towards -Z, which is Bevy's forward direction.
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Facing {
+    /// The position of the neighbouring block in this direction, relative to a block.
+    pub fn offset(self) -> IVec3 {
+        match self {
+            Facing::North => IVec3::NEG_Z,
+            Facing::East => IVec3::X,
+            Facing::South => IVec3::Z,
+            Facing::West => IVec3::NEG_X,
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        self.right().right()
+    }
+
+    /// The direction to the left of this one, when looking in this direction.
+    pub fn left(self) -> Self {
+        self.right().right().right()
+    }
+
+    /// The direction to the right of this one, when looking in this direction.
+    pub fn right(self) -> Self {
+        match self {
+            Facing::North => Facing::East,
+            Facing::East => Facing::South,
+            Facing::South => Facing::West,
+            Facing::West => Facing::North,
+        }
+    }
+
+    /// The direction reflected in a mirror across the axis. Mirroring across Y changes nothing,
+    /// since facings are horizontal.
+    pub fn mirrored(self, axis: Axis) -> Self {
+        match (self, axis) {
+            (Facing::East | Facing::West, Axis::X) | (Facing::North | Facing::South, Axis::Z) => {
+                self.opposite()
+            }
+            _ => self,
+        }
+    }
+
+    /// The facing closest to the direction, ignoring which way it points vertically.
+    pub fn from_direction(direction: Vec3) -> Self {
+        if direction.x.abs() > direction.z.abs() {
+            if direction.x > 0.0 {
+                Facing::East
+            } else {
+                Facing::West
+            }
+        } else if direction.z > 0.0 {
+            Facing::South
+        } else {
+            Facing::North
+        }
+    }
+}
+
+/// One of the three directions a block can lie along.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Axis {
+    X,
+    #[default]
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// The axis a face with the normal points along.
+    pub fn from_normal(normal: IVec3) -> Self {
+        if normal.x != 0 {
+            Axis::X
+        } else if normal.z != 0 {
+            Axis::Z
+        } else {
+            Axis::Y
+        }
+    }
+}
+
+/// Which half of the block a slab fills.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum Half {
+    #[default]
+    Bottom,
+    Top,
+}
+
+/// The furthest flowing water spreads sideways from its source.
+pub const MAX_FLOW_LEVEL: u8 = 7;
+
 impl Block {
     pub fn get_texture_config(self) -> BlockTextureConfig {
         match self {
             Block::Dirt => BlockTextureConfig::new(0, 16),
             Block::Grass => BlockTextureConfig::new(0, 0),
             Block::Stone => BlockTextureConfig::new(16, 0),
-            Block::Water => BlockTextureConfig::new(16, 16),
+            Block::Water | Block::FlowingWater(_) => BlockTextureConfig::new(16, 16),
+            Block::IronOre => BlockTextureConfig::new(32, 0),
+            Block::CoalOre => BlockTextureConfig::new(48, 0),
+            Block::Log(_) => BlockTextureConfig::new(16, 96),
+            Block::Furnace(_) => BlockTextureConfig::new(32, 16),
+            Block::Crusher(_) => BlockTextureConfig::new(48, 16),
+            Block::Assembler(_) => BlockTextureConfig::new(0, 32),
+            Block::Conveyor(_) => BlockTextureConfig::new(0, 48),
+            Block::Splitter(_) => BlockTextureConfig::new(16, 48),
+            Block::Merger(_) => BlockTextureConfig::new(32, 48),
+            Block::Inserter(_) => BlockTextureConfig::new(48, 48),
+            Block::Generator(_) => BlockTextureConfig::new(0, 64),
+            Block::Cable(false) => BlockTextureConfig::new(16, 64),
+            Block::Cable(true) => BlockTextureConfig::new(16, 112),
+            Block::Battery => BlockTextureConfig::new(32, 64),
+            Block::Pipe => BlockTextureConfig::new(48, 64),
+            Block::Tank => BlockTextureConfig::new(0, 80),
+            Block::Pump => BlockTextureConfig::new(16, 80),
+            Block::Boiler(_) => BlockTextureConfig::new(32, 80),
+            Block::StoneSlab(_) | Block::StoneStairs(_) => BlockTextureConfig::new(16, 0),
+            Block::IronBars(_) => BlockTextureConfig::new(48, 96),
+            Block::TallGrass => BlockTextureConfig::new(0, 112),
             _ => panic!(
                 "Tried to query block texture config for a block that doesn't have a texture"
             ),
         }
     }
 
+    /// The texture of the block's face with the normal, turned to match which way the block
+    /// points.
+    pub fn face_texture(self, normal: IVec3) -> BlockTextureConfig {
+        match self {
+            // The ends show the rings, and the bark's grain runs along the axis
+            Block::Log(axis) => {
+                if Axis::from_normal(normal) == axis {
+                    BlockTextureConfig::new(32, 96)
+                } else {
+                    let turned = match axis {
+                        Axis::X => true,
+                        Axis::Y => false,
+                        Axis::Z => normal.x != 0,
+                    };
+
+                    self.get_texture_config().rotated(turned as u8)
+                }
+            }
+            Block::Furnace(facing)
+            | Block::Crusher(facing)
+            | Block::Assembler(facing)
+            | Block::Generator(facing)
+                if normal != facing.offset() =>
+            {
+                BlockTextureConfig::new(48, 80)
+            }
+            Block::Boiler(facing) if normal != facing.offset() => BlockTextureConfig::new(0, 96),
+            // The top and bottom of conveyors turn to run the way they carry items
+            Block::Conveyor(facing)
+            | Block::Splitter(facing)
+            | Block::Merger(facing)
+            | Block::Inserter(facing)
+                if normal.y != 0 =>
+            {
+                self.get_texture_config().rotated(facing as u8)
+            }
+            block => block.get_texture_config(),
+        }
+    }
+
     pub fn is_opaque(self) -> bool {
         self != Block::Air
     }
+
+    /// Whether the player collides with this block.
+    pub fn is_solid(self) -> bool {
+        !matches!(
+            self,
+            Block::Air | Block::Water | Block::FlowingWater(_) | Block::TallGrass
+        )
+    }
+
+    /// Whether placing a block here replaces this one.
+    pub fn is_replaceable(self) -> bool {
+        self == Block::Air || self == Block::TallGrass || self.is_liquid()
+    }
+
+    /// Roughly how long the block takes to break by hand, in seconds. The right tool speeds this up.
+    pub fn hardness(self) -> f32 {
+        match self {
+            Block::Grass => 0.6,
+            Block::Dirt => 0.5,
+            Block::Stone => 1.5,
+            Block::IronOre | Block::CoalOre => 3.0,
+            Block::Log(_) | Block::StoneSlab(_) | Block::StoneStairs(_) => 2.0,
+            Block::IronBars(_) => 3.0,
+            Block::Furnace(_)
+            | Block::Crusher(_)
+            | Block::Assembler(_)
+            | Block::Generator(_)
+            | Block::Boiler(_) => 3.5,
+            Block::Battery | Block::Tank | Block::Pump => 2.0,
+            Block::Cable(_) | Block::Pipe => 0.5,
+            Block::Conveyor(_) | Block::Splitter(_) | Block::Merger(_) | Block::Inserter(_) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// The kind of tool that breaks this block faster.
+    pub fn preferred_tool(self) -> Option<ToolKind> {
+        match self {
+            Block::Grass | Block::Dirt => Some(ToolKind::Shovel),
+            Block::Log(_) => Some(ToolKind::Axe),
+            Block::Stone
+            | Block::IronOre
+            | Block::CoalOre
+            | Block::Furnace(_)
+            | Block::Crusher(_)
+            | Block::Assembler(_)
+            | Block::Conveyor(_)
+            | Block::Splitter(_)
+            | Block::Merger(_)
+            | Block::Inserter(_)
+            | Block::Generator(_)
+            | Block::Cable(_)
+            | Block::Battery
+            | Block::Pipe
+            | Block::Tank
+            | Block::Pump
+            | Block::Boiler(_)
+            | Block::StoneSlab(_)
+            | Block::StoneStairs(_)
+            | Block::IronBars(_) => Some(ToolKind::Pickaxe),
+            _ => None,
+        }
+    }
+
+    /// The lowest tier of the preferred tool the block has to be broken with to drop anything.
+    /// `None` if it always drops.
+    pub fn harvest_tier(self) -> Option<ToolTier> {
+        match self {
+            Block::Stone | Block::CoalOre | Block::StoneSlab(_) | Block::StoneStairs(_) => {
+                Some(ToolTier::Wood)
+            }
+            Block::IronOre => Some(ToolTier::Stone),
+            _ => None,
+        }
+    }
+
+    /// The item that's dropped when this block is broken.
+    pub fn drop(self) -> Item {
+        match self {
+            Block::Grass => Item::Block(Block::Dirt),
+            Block::CoalOre => Item::Material(Material::Coal),
+            // Blocks are all the same item no matter which way they were placed
+            block => Item::Block(block.with_default_state()),
+        }
+    }
+
+    /// Whether the block is a machine, with a `BlockEntity` holding its state. Conveyors and
+    /// inserters count as machines too.
+    pub fn is_machine(self) -> bool {
+        matches!(
+            self,
+            Block::Furnace(_)
+                | Block::Crusher(_)
+                | Block::Assembler(_)
+                | Block::Conveyor(_)
+                | Block::Splitter(_)
+                | Block::Merger(_)
+                | Block::Inserter(_)
+                | Block::Generator(_)
+                | Block::Battery
+                | Block::Pump
+                | Block::Boiler(_)
+        )
+    }
+
+    /// Whether the block is part of a pipe network, with fluid flowing to and from the fluid blocks
+    /// next to it.
+    pub fn holds_fluid(self) -> bool {
+        matches!(
+            self,
+            Block::Pipe | Block::Tank | Block::Pump | Block::Boiler(_) | Block::Generator(_)
+        )
+    }
+
+    /// Whether the block is part of a power network, connecting to the power blocks next to it.
+    pub fn conducts_power(self) -> bool {
+        matches!(
+            self,
+            Block::Generator(_)
+                | Block::Cable(_)
+                | Block::Battery
+                | Block::Crusher(_)
+                | Block::Assembler(_)
+                | Block::Pump
+        )
+    }
+
+    /// Which half of the block it fills, for blocks that only fill half of it.
+    pub fn half(self) -> Option<Half> {
+        match self {
+            Block::StoneSlab(half) => Some(half),
+            _ => None,
+        }
+    }
+
+    /// Which way the block points, for blocks that are placed facing a way.
+    pub fn facing(self) -> Option<Facing> {
+        match self {
+            Block::Furnace(facing)
+            | Block::Crusher(facing)
+            | Block::Assembler(facing)
+            | Block::Conveyor(facing)
+            | Block::Splitter(facing)
+            | Block::Merger(facing)
+            | Block::Inserter(facing)
+            | Block::Generator(facing)
+            | Block::Boiler(facing)
+            | Block::StoneStairs(facing)
+            | Block::IronBars(facing) => Some(facing),
+            _ => None,
+        }
+    }
+
+    /// The same block, pointing the other way. Blocks that don't point anywhere stay the same.
+    pub fn with_facing(self, facing: Facing) -> Self {
+        match self {
+            Block::Furnace(_) => Block::Furnace(facing),
+            Block::Crusher(_) => Block::Crusher(facing),
+            Block::Assembler(_) => Block::Assembler(facing),
+            Block::Conveyor(_) => Block::Conveyor(facing),
+            Block::Splitter(_) => Block::Splitter(facing),
+            Block::Merger(_) => Block::Merger(facing),
+            Block::Inserter(_) => Block::Inserter(facing),
+            Block::Generator(_) => Block::Generator(facing),
+            Block::Boiler(_) => Block::Boiler(facing),
+            Block::StoneStairs(_) => Block::StoneStairs(facing),
+            Block::IronBars(_) => Block::IronBars(facing),
+            block => block,
+        }
+    }
+
+    /// The same block, with the state it has as an item: facing north, standing upright, in the
+    /// bottom half and unpowered.
+    pub fn with_default_state(self) -> Self {
+        match self {
+            Block::Cable(_) => Block::Cable(false),
+            Block::Log(_) => Block::Log(Axis::default()),
+            Block::StoneSlab(_) => Block::StoneSlab(Half::default()),
+            block => block.with_facing(Facing::default()),
+        }
+    }
+
+    /// The block turned clockwise, seen from above, by some quarter turns.
+    pub fn rotated(self, quarter_turns: u8) -> Self {
+        let quarter_turns = quarter_turns % 4;
+
+        match self {
+            Block::Log(Axis::X) if quarter_turns % 2 == 1 => Block::Log(Axis::Z),
+            Block::Log(Axis::Z) if quarter_turns % 2 == 1 => Block::Log(Axis::X),
+            block => match block.facing() {
+                Some(facing) => {
+                    block.with_facing((0..quarter_turns).fold(facing, |facing, _| facing.right()))
+                }
+                None => block,
+            },
+        }
+    }
+
+    /// The block reflected in a mirror across the axis.
+    pub fn mirrored(self, axis: Axis) -> Self {
+        match (self, axis) {
+            (Block::StoneSlab(Half::Bottom), Axis::Y) => Block::StoneSlab(Half::Top),
+            (Block::StoneSlab(Half::Top), Axis::Y) => Block::StoneSlab(Half::Bottom),
+            (block, axis) => match block.facing() {
+                Some(facing) => block.with_facing(facing.mirrored(axis)),
+                None => block,
+            },
+        }
+    }
+
+    /// The block as it's placed by a player looking in the direction, against a face with the
+    /// normal.
+    pub fn placed(self, look: Vec3, normal: IVec3) -> Self {
+        let facing = Facing::from_direction(look);
+
+        match self {
+            Block::Log(_) => Block::Log(Axis::from_normal(normal)),
+            // Slabs placed against the underside of a block hang from it
+            Block::StoneSlab(_) if normal == IVec3::NEG_Y => Block::StoneSlab(Half::Top),
+            Block::StoneSlab(_) => Block::StoneSlab(Half::Bottom),
+            // Machines turn their front to the player, and bars stretch across their view
+            Block::Furnace(_)
+            | Block::Crusher(_)
+            | Block::Assembler(_)
+            | Block::Generator(_)
+            | Block::Boiler(_)
+            | Block::IronBars(_) => self.with_facing(facing.opposite()),
+            // Everything else points away, like conveyors carrying items away from the player
+            block => block.with_facing(facing),
+        }
+    }
+
+    /// Whether the player can look at this block to break it, or to place blocks against it.
+    pub fn is_targetable(self) -> bool {
+        self != Block::Air && !self.is_liquid()
+    }
+
+    /// Whether the player can swim in this block.
+    pub fn is_liquid(self) -> bool {
+        self.flow_level().is_some()
+    }
+
+    /// 0 for water sources, the level of flowing water, and `None` for anything that isn't water.
+    pub fn flow_level(self) -> Option<u8> {
+        match self {
+            Block::Water => Some(0),
+            Block::FlowingWater(level) => Some(level),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
 pub struct BlockTextureConfig {
     pub starting_x: u32,
     pub starting_y: u32,
+    /// How many quarter turns the texture is turned by, clockwise when seen from above.
+    pub rotation: u8,
 }
 
 impl BlockTextureConfig {
@@ -38,6 +502,141 @@ impl BlockTextureConfig {
         Self {
             starting_x,
             starting_y,
+            rotation: 0,
+        }
+    }
+
+    pub fn rotated(self, quarter_turns: u8) -> Self {
+        Self {
+            rotation: (self.rotation + quarter_turns) % 4,
+            ..self
         }
     }
+
+    /// The index of this texture in a `TextureAtlas` that splits the atlas into a grid of textures.
+    pub fn atlas_index(self) -> usize {
+        let columns = ATLAS_SIZE.0 / TEXTURE_SIZE;
+
+        (self.starting_y as usize / TEXTURE_SIZE) * columns
+            + self.starting_x as usize / TEXTURE_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looking mostly east, and a little down.
+    const LOOKING_EAST: Vec3 = Vec3::new(1.0, -0.5, 0.2);
+
+    #[test]
+    fn machines_placed_face_the_player() {
+        assert_eq!(
+            Block::Furnace(Facing::North).placed(LOOKING_EAST, IVec3::Y),
+            Block::Furnace(Facing::West)
+        );
+        assert_eq!(
+            Block::IronBars(Facing::North).placed(Vec3::new(0.1, 0.0, 1.0), IVec3::NEG_X),
+            Block::IronBars(Facing::North)
+        );
+    }
+
+    #[test]
+    fn conveyors_placed_point_away_from_the_player() {
+        assert_eq!(
+            Block::Conveyor(Facing::North).placed(LOOKING_EAST, IVec3::Y),
+            Block::Conveyor(Facing::East)
+        );
+        assert_eq!(
+            Block::StoneStairs(Facing::North).placed(Vec3::new(-0.3, 0.0, -1.0), IVec3::Y),
+            Block::StoneStairs(Facing::North)
+        );
+    }
+
+    #[test]
+    fn logs_placed_lie_along_the_face_they_are_placed_against() {
+        let log = Block::Log(Axis::Y);
+
+        assert_eq!(log.placed(LOOKING_EAST, IVec3::X), Block::Log(Axis::X));
+        assert_eq!(log.placed(LOOKING_EAST, IVec3::NEG_Z), Block::Log(Axis::Z));
+        assert_eq!(log.placed(LOOKING_EAST, IVec3::Y), Block::Log(Axis::Y));
+    }
+
+    #[test]
+    fn slabs_placed_against_an_underside_fill_the_top_half() {
+        let slab = Block::StoneSlab(Half::Bottom);
+
+        assert_eq!(
+            slab.placed(LOOKING_EAST, IVec3::NEG_Y),
+            Block::StoneSlab(Half::Top)
+        );
+        assert_eq!(
+            slab.placed(LOOKING_EAST, IVec3::Y),
+            Block::StoneSlab(Half::Bottom)
+        );
+        assert_eq!(
+            Block::StoneSlab(Half::Top).placed(LOOKING_EAST, IVec3::X),
+            Block::StoneSlab(Half::Bottom)
+        );
+    }
+
+    #[test]
+    fn blocks_rotate_clockwise() {
+        assert_eq!(
+            Block::Conveyor(Facing::North).rotated(1),
+            Block::Conveyor(Facing::East)
+        );
+        assert_eq!(
+            Block::Furnace(Facing::West).rotated(3),
+            Block::Furnace(Facing::South)
+        );
+        assert_eq!(
+            Block::Inserter(Facing::South).rotated(4),
+            Block::Inserter(Facing::South)
+        );
+
+        assert_eq!(Block::Log(Axis::X).rotated(1), Block::Log(Axis::Z));
+        assert_eq!(Block::Log(Axis::Z).rotated(2), Block::Log(Axis::Z));
+        assert_eq!(Block::Log(Axis::Y).rotated(1), Block::Log(Axis::Y));
+        assert_eq!(Block::Stone.rotated(1), Block::Stone);
+    }
+
+    #[test]
+    fn blocks_mirror_across_an_axis() {
+        assert_eq!(
+            Block::Conveyor(Facing::East).mirrored(Axis::X),
+            Block::Conveyor(Facing::West)
+        );
+        assert_eq!(
+            Block::Conveyor(Facing::East).mirrored(Axis::Z),
+            Block::Conveyor(Facing::East)
+        );
+        assert_eq!(
+            Block::StoneStairs(Facing::North).mirrored(Axis::Z),
+            Block::StoneStairs(Facing::South)
+        );
+        assert_eq!(
+            Block::Furnace(Facing::North).mirrored(Axis::Y),
+            Block::Furnace(Facing::North)
+        );
+
+        assert_eq!(
+            Block::StoneSlab(Half::Bottom).mirrored(Axis::Y),
+            Block::StoneSlab(Half::Top)
+        );
+        assert_eq!(
+            Block::StoneSlab(Half::Top).mirrored(Axis::X),
+            Block::StoneSlab(Half::Top)
+        );
+    }
+
+    #[test]
+    fn texture_rotation_wraps_around() {
+        let texture = BlockTextureConfig::new(16, 32).rotated(3);
+
+        assert_eq!(texture.rotation, 3);
+        assert_eq!(texture.rotated(2).rotation, 1);
+        assert_eq!(texture.rotated(4).rotation, 3);
+        assert_eq!((texture.starting_x, texture.starting_y), (16, 32));
+    }
 }
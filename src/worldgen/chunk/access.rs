@@ -0,0 +1,61 @@
+use crate::machine::BlockEntity;
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::{Chunk, CHUNK_SIZE};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Splits a world-space block position into the position of the chunk containing it, and the
+/// position of the block inside that chunk.
+pub fn world_to_chunk_pos(pos: IVec3) -> ((i32, i32, i32), UVec3) {
+    let size = CHUNK_SIZE as i32;
+
+    let chunk_pos = (
+        pos.x.div_euclid(size),
+        pos.y.div_euclid(size),
+        pos.z.div_euclid(size),
+    );
+    let local_pos = UVec3::new(
+        pos.x.rem_euclid(size) as u32,
+        pos.y.rem_euclid(size) as u32,
+        pos.z.rem_euclid(size) as u32,
+    );
+
+    (chunk_pos, local_pos)
+}
+
+/// Gets the block at the world-space position, or `None` if its chunk hasn't been generated.
+pub fn get_block(map: &HashMap<(i32, i32, i32), Chunk>, pos: IVec3) -> Option<Block> {
+    let (chunk_pos, local_pos) = world_to_chunk_pos(pos);
+
+    map.get(&chunk_pos).map(|chunk| chunk.get(local_pos))
+}
+
+/// Sets the block at the world-space position. Returns false if its chunk hasn't been generated.
+pub fn set_block(map: &mut HashMap<(i32, i32, i32), Chunk>, pos: IVec3, block: Block) -> bool {
+    let (chunk_pos, local_pos) = world_to_chunk_pos(pos);
+
+    match map.get_mut(&chunk_pos) {
+        Some(chunk) => {
+            chunk.set(local_pos, block);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Gets the block entity at the world-space position, if there is one.
+pub fn get_block_entity(map: &HashMap<(i32, i32, i32), Chunk>, pos: IVec3) -> Option<&BlockEntity> {
+    let (chunk_pos, local_pos) = world_to_chunk_pos(pos);
+
+    map.get(&chunk_pos)?.block_entities.get(&local_pos)
+}
+
+/// Gets the block entity at the world-space position, if there is one.
+pub fn get_block_entity_mut(
+    map: &mut HashMap<(i32, i32, i32), Chunk>,
+    pos: IVec3,
+) -> Option<&mut BlockEntity> {
+    let (chunk_pos, local_pos) = world_to_chunk_pos(pos);
+
+    map.get_mut(&chunk_pos)?.block_entities.get_mut(&local_pos)
+}
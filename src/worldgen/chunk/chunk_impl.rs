@@ -1,7 +1,10 @@
 use crate::worldgen::block::*;
 use crate::worldgen::chunk::{Chunk, CHUNK_SIZE};
+use crate::worldgen::gen::WorldSeed;
+use crate::worldgen::model::{BlockModels, Model, ModelBox};
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::utils::HashMap;
 use std::hash::{Hash, Hasher};
 
 // The following trait implementations are required for using a hash map
@@ -23,19 +26,19 @@ impl Chunk {
         Self {
             pos,
             voxels: [[[Block::Air; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
-            mesh: None,
+            block_entities: HashMap::new(),
             empty: true,
         }
     }
 
-    pub fn generate(&mut self) {
+    pub fn generate(&mut self, seed: WorldSeed) {
         for x in 0..CHUNK_SIZE {
             for y in 0..CHUNK_SIZE {
                 for z in 0..CHUNK_SIZE {
                     let world_pos: Vec3 =
                         self.pos.as_vec3() + Vec3::new(x as f32, y as f32, z as f32);
 
-                    let block = crate::worldgen::gen::at_pos(world_pos.into());
+                    let block = crate::worldgen::gen::at_pos(world_pos.into(), seed);
                     self.voxels[x][y][z] = block;
                     self.empty &= block == Block::Air;
                 }
@@ -43,162 +46,130 @@ impl Chunk {
         }
     }
 
+    /// Creates a chunk from existing block data, e.g. received over the network.
+    pub fn from_voxels(
+        pos: IVec3,
+        voxels: [[[Block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+    ) -> Self {
+        let mut chunk = Self {
+            pos,
+            voxels,
+            block_entities: HashMap::new(),
+            empty: true,
+        };
+        chunk.update_empty();
+
+        chunk
+    }
+
     pub fn is_empty(&self) -> bool {
         self.empty
     }
 
-    pub fn get_mesh(&self) -> Mesh {
+    fn update_empty(&mut self) {
+        self.empty = self
+            .voxels
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|&block| block == Block::Air);
+    }
+
+    /// Gets the block at the given position, relative to the chunk's origin.
+    pub fn get(&self, local_pos: UVec3) -> Block {
+        self.voxels[local_pos.x as usize][local_pos.y as usize][local_pos.z as usize]
+    }
+
+    /// Sets the block at the given position, relative to the chunk's origin.
+    pub fn set(&mut self, local_pos: UVec3, block: Block) {
+        self.voxels[local_pos.x as usize][local_pos.y as usize][local_pos.z as usize] = block;
+
+        if block == Block::Air {
+            self.update_empty();
+        } else {
+            self.empty = false;
+        }
+    }
+
+    pub fn get_mesh(&self, models: &BlockModels) -> Mesh {
+        self.build_mesh(models, Block::is_opaque)
+    }
+
+    /// A mesh of only the blocks the player collides with, for building colliders from.
+    pub fn get_collision_mesh(&self, models: &BlockModels) -> Mesh {
+        self.build_mesh(models, Block::is_solid)
+    }
+
+    /// The block next to the one at the local position, if it's in this chunk.
+    fn neighbor(&self, local_pos: IVec3, normal: IVec3) -> Option<Block> {
+        let pos = local_pos + normal;
+
+        if pos.min_element() < 0 || pos.max_element() >= CHUNK_SIZE as i32 {
+            return None;
+        }
+
+        Some(self.get(pos.as_uvec3()))
+    }
+
+    /// Meshes every block that `include` is true for, in the shape of its model. Faces on the
+    /// surface of a block are culled where they're covered by another included block.
+    fn build_mesh(&self, models: &BlockModels, include: impl Fn(Block) -> bool) -> Mesh {
         let mut builder = MeshBuilder::new();
 
         for x in 0..CHUNK_SIZE {
             for y in 0..CHUNK_SIZE {
                 for z in 0..CHUNK_SIZE {
-                    if !self.voxels[x][y][z].is_opaque() {
+                    let block = self.voxels[x][y][z];
+
+                    if !include(block) {
                         continue;
                     }
 
-                    let local_pos = Vec3::new(x as f32, y as f32, z as f32);
-
-                    let texture_config = self.voxels[x][y][z].get_texture_config();
-
-                    let pos_z = z.overflowing_add(1).0;
-                    let neg_z = z.overflowing_sub(1).0;
-                    let pos_y = y.overflowing_add(1).0;
-                    let neg_y = y.overflowing_sub(1).0;
-                    let pos_x = x.overflowing_add(1).0;
-                    let neg_x = x.overflowing_sub(1).0;
-
-                    let neighbor_pos_z = if (pos_z).clamp(0, CHUNK_SIZE - 1) == (pos_z) {
-                        Some(self.voxels[x][y][pos_z])
-                    } else {
-                        None
-                    };
-                    let neighbor_neg_z = if (neg_z).clamp(0, CHUNK_SIZE - 1) == (neg_z) {
-                        Some(self.voxels[x][y][neg_z])
-                    } else {
-                        None
-                    };
-                    let neighbor_pos_y = if (pos_y).clamp(0, CHUNK_SIZE - 1) == (pos_y) {
-                        Some(self.voxels[x][pos_y][z])
-                    } else {
-                        None
-                    };
-                    let neighbor_neg_y = if (neg_y).clamp(0, CHUNK_SIZE - 1) == (neg_y) {
-                        Some(self.voxels[x][neg_y][z])
-                    } else {
-                        None
-                    };
-                    let neighbor_pos_x = if (pos_x).clamp(0, CHUNK_SIZE - 1) == (pos_x) {
-                        Some(self.voxels[pos_x][y][z])
-                    } else {
-                        None
-                    };
-                    let neighbor_neg_x = if (neg_x).clamp(0, CHUNK_SIZE - 1) == (neg_x) {
-                        Some(self.voxels[neg_x][y][z])
-                    } else {
-                        None
-                    };
-
-                    let mut add_face_pos_z = true;
-                    let mut add_face_neg_z = true;
-                    let mut add_face_pos_y = true;
-                    let mut add_face_neg_y = true;
-                    let mut add_face_pos_x = true;
-                    let mut add_face_neg_x = true;
-
-                    if let Some(neighbor_pos_z) = neighbor_pos_z {
-                        if neighbor_pos_z.is_opaque() {
-                            add_face_pos_z = false;
-                        }
-                    }
-                    if let Some(neighbor_neg_z) = neighbor_neg_z {
-                        if neighbor_neg_z.is_opaque() {
-                            add_face_neg_z = false;
-                        }
-                    }
-                    if let Some(neighbor_pos_y) = neighbor_pos_y {
-                        if neighbor_pos_y.is_opaque() {
-                            add_face_pos_y = false;
-                        }
-                    }
-                    if let Some(neighbor_neg_y) = neighbor_neg_y {
-                        if neighbor_neg_y.is_opaque() {
-                            add_face_neg_y = false;
-                        }
-                    }
-                    if let Some(neighbor_pos_x) = neighbor_pos_x {
-                        if neighbor_pos_x.is_opaque() {
-                            add_face_pos_x = false;
-                        }
-                    }
-                    if let Some(neighbor_neg_x) = neighbor_neg_x {
-                        if neighbor_neg_x.is_opaque() {
-                            add_face_neg_x = false;
-                        }
-                    }
+                    let local_pos = IVec3::new(x as i32, y as i32, z as i32);
 
-                    if add_face_pos_z {
-                        builder.add_face(
-                            MeshBuilder::FACE_Z_FRONT,
-                            MeshBuilder::NORMAL_Z_FRONT,
-                            MeshBuilder::UV_Z_FRONT,
-                            local_pos,
-                            texture_config,
-                        );
-                    }
-                    if add_face_neg_z {
-                        builder.add_face(
-                            MeshBuilder::FACE_Z_BACK,
-                            MeshBuilder::NORMAL_Z_BACK,
-                            MeshBuilder::UV_Z_BACK,
-                            local_pos,
-                            texture_config,
-                        );
-                    }
-                    if add_face_pos_y {
-                        builder.add_face(
-                            MeshBuilder::FACE_Y_FRONT,
-                            MeshBuilder::NORMAL_Y_FRONT,
-                            MeshBuilder::UV_Y_FRONT,
-                            local_pos,
-                            texture_config,
-                        );
-                    }
-                    if add_face_neg_y {
-                        builder.add_face(
-                            MeshBuilder::FACE_Y_BACK,
-                            MeshBuilder::NORMAL_Y_BACK,
-                            MeshBuilder::UV_Y_BACK,
-                            local_pos,
-                            texture_config,
-                        );
-                    }
-                    if add_face_pos_x {
-                        builder.add_face(
-                            MeshBuilder::FACE_X_FRONT,
-                            MeshBuilder::NORMAL_X_FRONT,
-                            MeshBuilder::UV_X_FRONT,
-                            local_pos,
-                            texture_config,
-                        );
+                    if *models.get(block) == Model::Cross {
+                        builder.add_cross(local_pos.as_vec3(), block.get_texture_config());
+                        continue;
                     }
-                    if add_face_neg_x {
-                        builder.add_face(
-                            MeshBuilder::FACE_X_BACK,
-                            MeshBuilder::NORMAL_X_BACK,
-                            MeshBuilder::UV_X_BACK,
-                            local_pos,
-                            texture_config,
-                        );
+
+                    for model_box in models.boxes(block) {
+                        for side in MeshBuilder::SIDES {
+                            // Faces inside the block can't be covered by anything
+                            let covered = model_box.touches_side(side.normal)
+                                && self
+                                    .neighbor(local_pos, side.normal)
+                                    .is_some_and(|neighbor| {
+                                        include(neighbor)
+                                            && models.covers_side(neighbor, -side.normal)
+                                    });
+
+                            if !covered {
+                                builder.add_face(
+                                    side,
+                                    model_box,
+                                    local_pos.as_vec3(),
+                                    block.face_texture(side.normal),
+                                );
+                            }
+                        }
                     }
                 }
             }
         }
 
-        builder.to_mesh()
+        builder.into_mesh()
     }
 }
 
+/// One side of a box: the matching face of a unit cube, and the axes the texture runs along.
+#[derive(Clone, Copy)]
+pub struct Side {
+    pub normal: IVec3,
+    pub face: [[f32; 3]; 4],
+    u_axis: usize,
+    v_axis: usize,
+}
+
 /// Utility object for building the chunk meshes
 pub struct MeshBuilder {
     pub vertices: Vec<[f32; 3]>,
@@ -247,50 +218,67 @@ impl MeshBuilder {
 
     // --
 
-    pub const UV_Z_FRONT: [[f32; 2]; 4] = [
-        [0.0, 0.0], // Bottom left
-        [0.0, 1.0], // Top left
-        [1.0, 0.0], // Bottom right
-        [1.0, 1.0], // Top right
-    ];
-    pub const UV_Z_BACK: [[f32; 2]; 4] = [
-        [1.0, 0.0], // Bottom right
-        [1.0, 1.0], // Top right
-        [0.0, 0.0], // Bottom left
-        [0.0, 1.0], // Top left
-    ];
-    pub const UV_Y_FRONT: [[f32; 2]; 4] = [
-        [0.0, 1.0], // Front left
-        [0.0, 0.0], // Back left
-        [1.0, 1.0], // Front right
-        [1.0, 0.0], // Back right
+    pub const SIDES: [Side; 6] = [
+        Side {
+            normal: IVec3::Z,
+            face: Self::FACE_Z_FRONT,
+            u_axis: 0,
+            v_axis: 1,
+        },
+        Side {
+            normal: IVec3::NEG_Z,
+            face: Self::FACE_Z_BACK,
+            u_axis: 0,
+            v_axis: 1,
+        },
+        Side {
+            normal: IVec3::Y,
+            face: Self::FACE_Y_FRONT,
+            u_axis: 0,
+            v_axis: 2,
+        },
+        Side {
+            normal: IVec3::NEG_Y,
+            face: Self::FACE_Y_BACK,
+            u_axis: 0,
+            v_axis: 2,
+        },
+        Side {
+            normal: IVec3::X,
+            face: Self::FACE_X_FRONT,
+            u_axis: 2,
+            v_axis: 1,
+        },
+        Side {
+            normal: IVec3::NEG_X,
+            face: Self::FACE_X_BACK,
+            u_axis: 2,
+            v_axis: 1,
+        },
     ];
-    pub const UV_Y_BACK: [[f32; 2]; 4] = [
-        [0.0, 0.0], // Front left
-        [0.0, 1.0], // Back left
-        [1.0, 0.0], // Front right
-        [1.0, 1.0], // Back right
-    ];
-    pub const UV_X_FRONT: [[f32; 2]; 4] = [
-        [1.0, 0.0], // Front bottom
-        [1.0, 1.0], // Front top
-        [0.0, 0.0], // Back bottom
-        [0.0, 1.0], // Back top
+
+    /// The two diagonal quads of crossed models, with their texture standing upright.
+    const CROSS_QUADS: [[[f32; 3]; 4]; 2] = [
+        [
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+        ],
+        [
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ],
     ];
-    pub const UV_X_BACK: [[f32; 2]; 4] = [
-        [0.0, 0.0], // Front bottom
-        [0.0, 1.0], // Front top
-        [1.0, 0.0], // Back bottom
-        [1.0, 1.0], // Back top
+    const CROSS_UVS: [[f32; 2]; 4] = [
+        [0.0, 1.0], // Bottom left
+        [0.0, 0.0], // Top left
+        [1.0, 1.0], // Bottom right
+        [1.0, 0.0], // Top right
     ];
 
-    pub const NORMAL_Z_FRONT: [[f32; 3]; 4] = [[0.0, 0.0, 1.0]; 4];
-    pub const NORMAL_Z_BACK: [[f32; 3]; 4] = [[0.0, 0.0, -1.0]; 4];
-    pub const NORMAL_Y_FRONT: [[f32; 3]; 4] = [[0.0, 1.0, 0.0]; 4];
-    pub const NORMAL_Y_BACK: [[f32; 3]; 4] = [[0.0, -1.0, 0.0]; 4];
-    pub const NORMAL_X_FRONT: [[f32; 3]; 4] = [[1.0, 0.0, 0.0]; 4];
-    pub const NORMAL_X_BACK: [[f32; 3]; 4] = [[-1.0, 0.0, 0.0]; 4];
-
     pub fn new() -> Self {
         Self {
             vertices: Vec::new(),
@@ -311,43 +299,35 @@ impl MeshBuilder {
         ]
     }
 
+    /// Moves the UVs from 0 to 1 into the texture's part of the atlas.
     fn transform_uvs(uvs: &mut [[f32; 2]; 4], texture_config: BlockTextureConfig) {
-        let one_texel = (1.0 / ATLAS_SIZE.0 as f32, 1.0 / ATLAS_SIZE.1 as f32);
-
-        let starting_x = texture_config.starting_x;
-        let starting_y = texture_config.starting_y;
-
-        // Assumption that every texture is 16x16
-        let ending_x = starting_x + 16;
-        let ending_y = starting_y + 16;
-
-        for uv in uvs.iter_mut() {
-            if uv[0] < 0.5 {
-                uv[0] = starting_x as f32 / ATLAS_SIZE.0 as f32;
-            } else {
-                uv[0] = ending_x as f32 / ATLAS_SIZE.0 as f32;
+        // Turning the texture a quarter turn clockwise moves every corner to the next one along
+        for _ in 0..texture_config.rotation {
+            for uv in uvs.iter_mut() {
+                *uv = [uv[1], 1.0 - uv[0]];
             }
+        }
 
-            if uv[1] < 0.5 {
-                uv[1] = starting_y as f32 / ATLAS_SIZE.1 as f32;
-            } else {
-                uv[1] = ending_y as f32 / ATLAS_SIZE.1 as f32;
-            }
+        for uv in uvs.iter_mut() {
+            uv[0] = (texture_config.starting_x as f32 + uv[0] * TEXTURE_SIZE as f32)
+                / ATLAS_SIZE.0 as f32;
+            uv[1] = (texture_config.starting_y as f32 + uv[1] * TEXTURE_SIZE as f32)
+                / ATLAS_SIZE.1 as f32;
         }
     }
 
-    /// Adds both the vertices and normals of the given face, provided the offset.
-    pub fn add_face(
+    /// Adds a quad to the mesh, provided the offset.
+    fn add_quad(
         &mut self,
-        mut face: [[f32; 3]; 4],
-        normals: [[f32; 3]; 4],
+        mut quad: [[f32; 3]; 4],
+        normal: Vec3,
         mut uvs: [[f32; 2]; 4],
         offset: Vec3,
         texture_config: BlockTextureConfig,
     ) {
-        for i in 0..4 {
+        for vertex in quad.iter_mut() {
             for j in 0..3 {
-                face[i][j] += offset[j];
+                vertex[j] += offset[j];
             }
         }
 
@@ -358,15 +338,58 @@ impl MeshBuilder {
         // from index 4. This is needed to provide the correct indices for each face.
         let starting_index = self.vertices.len();
 
-        self.vertices.extend_from_slice(&face);
-        self.normals.extend_from_slice(&normals);
+        self.vertices.extend_from_slice(&quad);
+        self.normals.extend_from_slice(&[normal.to_array(); 4]);
         self.uvs.extend_from_slice(&uvs);
 
         self.indices
             .extend_from_slice(&Self::get_face_indices(starting_index as u32));
     }
 
-    pub fn to_mesh(self) -> Mesh {
+    /// Adds the side of the box, provided the offset. A box smaller than the block only shows the
+    /// part of the texture that it covers.
+    pub fn add_face(
+        &mut self,
+        side: Side,
+        model_box: ModelBox,
+        offset: Vec3,
+        texture_config: BlockTextureConfig,
+    ) {
+        let mut face = side.face;
+        let mut uvs = [[0.0; 2]; 4];
+
+        for (vertex, uv) in face.iter_mut().zip(uvs.iter_mut()) {
+            let corners = model_box
+                .min
+                .to_array()
+                .into_iter()
+                .zip(model_box.max.to_array());
+
+            for (coordinate, (min, max)) in vertex.iter_mut().zip(corners) {
+                *coordinate = if *coordinate < 0.5 { min } else { max };
+            }
+
+            *uv = [vertex[side.u_axis], vertex[side.v_axis]];
+        }
+
+        self.add_quad(face, side.normal.as_vec3(), uvs, offset, texture_config);
+    }
+
+    /// Adds a crossed model, provided the offset. Both quads are added from both sides, so
+    /// they're seen from everywhere.
+    pub fn add_cross(&mut self, offset: Vec3, texture_config: BlockTextureConfig) {
+        for quad in Self::CROSS_QUADS {
+            let [bottom_left, top_left, bottom_right, top_right] = quad;
+            let back = [bottom_right, top_right, bottom_left, top_left];
+
+            // Lit like the ground they grow on
+            for quad in [quad, back] {
+                self.add_quad(quad, Vec3::Y, Self::CROSS_UVS, offset, texture_config);
+            }
+        }
+    }
+
+    pub fn into_mesh(self) -> Mesh {
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.vertices);
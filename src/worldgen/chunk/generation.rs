@@ -1,68 +1,74 @@
-use crate::camera::PlayerCamera;
+use crate::worldgen::chunk::storage::{self, WorldSave};
 use crate::worldgen::chunk::timer::ChunkGenerationTimer;
 use crate::worldgen::chunk::{
-    Chunk, ChunkQueue, GeneratedChunks, CHUNK_SIZE, MAX_CHUNKS_PROCESSED_PER_ITER, VIEW_DISTANCE,
+    chunk_pos_containing, Chunk, ChunkGenerated, ChunkLoader, ChunkMap, ChunkQueue,
+    GeneratedChunks, NewChunks, ViewDistance, CHUNK_SIZE, MAX_CHUNKS_PROCESSED_PER_ITER,
 };
+use crate::worldgen::gen::WorldSeed;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
 use crossbeam::queue::SegQueue;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
 
-/// Fills the chunk queue with values in the current render distance.
+/// Fills the chunk queue with values in the render distance of every chunk loader.
 pub fn fill_chunk_queue(
     generated_chunks: ResMut<GeneratedChunks>,
     chunk_queue: ResMut<ChunkQueue>,
 
-    camera_query: Query<&Transform, With<PlayerCamera>>,
+    loader_query: Query<&Transform, With<ChunkLoader>>,
 
     chunk_generation_timer: Res<ChunkGenerationTimer>,
+    view_distance: Res<ViewDistance>,
 ) {
     if !chunk_generation_timer.0.just_finished() {
         return;
     }
 
-    let camera_transform = camera_query.get_single().unwrap();
-    let camera_pos = camera_transform.translation;
-
-    let camera_chunk_pos = (camera_pos / CHUNK_SIZE as f32).as_ivec3();
-
     let map = generated_chunks.map.lock().unwrap();
 
     let mut num_chunks_added = 0;
 
     // Generate chunks in a bigger radius than the view distance
     let dist_mult = 1;
+    let distance = view_distance.0 * dist_mult;
 
-    for x in (-VIEW_DISTANCE.0 * dist_mult)..=(VIEW_DISTANCE.0 * dist_mult) {
-        for y in (-VIEW_DISTANCE.1 * dist_mult)..=(VIEW_DISTANCE.1 * dist_mult) {
-            for z in (-VIEW_DISTANCE.2 * dist_mult)..=(VIEW_DISTANCE.2 * dist_mult) {
-                if num_chunks_added == MAX_CHUNKS_PROCESSED_PER_ITER {
-                    return;
-                }
+    for loader_transform in loader_query.iter() {
+        let loader_chunk_pos = chunk_pos_containing(loader_transform.translation);
 
-                let pos = IVec3::new(x, y, z) + camera_chunk_pos;
-                let pos_tuple = (pos.x, pos.y, pos.z);
+        for x in -distance.x..=distance.x {
+            for y in -distance.y..=distance.y {
+                for z in -distance.z..=distance.z {
+                    if num_chunks_added == MAX_CHUNKS_PROCESSED_PER_ITER {
+                        return;
+                    }
 
-                // A chunk was already generated, don't add it to the queue
-                if map.contains_key(&pos_tuple) {
-                    continue;
-                }
+                    let pos = IVec3::new(x, y, z) + loader_chunk_pos;
+                    let pos_tuple = (pos.x, pos.y, pos.z);
+
+                    // A chunk was already generated, don't add it to the queue
+                    if map.contains_key(&pos_tuple) {
+                        continue;
+                    }
 
-                chunk_queue.0.push(pos_tuple);
+                    chunk_queue.0.push(pos_tuple);
 
-                num_chunks_added += 1;
+                    num_chunks_added += 1;
+                }
             }
         }
     }
 }
 
 /// Helper function that runs on each thread; generating chunks in the chunk queue
-/// and storing it in the generated chunks map.
+/// and storing it in the generated chunks map. Chunks that were saved in the world folder are
+/// loaded instead of generated.
 fn generate_chunks_worker(
-    chunks: Arc<Mutex<HashMap<(i32, i32, i32), Chunk>>>,
+    chunks: Arc<Mutex<ChunkMap>>,
     queue: Arc<SegQueue<(i32, i32, i32)>>,
+    new_chunks: Arc<SegQueue<(i32, i32, i32)>>,
+    seed: WorldSeed,
+    world_dir: Option<PathBuf>,
 ) {
     while let Some(chunk_pos) = queue.pop() {
         let mut map = chunks.lock().unwrap();
@@ -72,11 +78,24 @@ fn generate_chunks_worker(
             continue;
         }
 
-        let chunk_pos_vec = IVec3::from(chunk_pos);
-        let mut chunk = Chunk::empty(chunk_pos_vec * CHUNK_SIZE as i32);
-        chunk.generate();
+        let saved = world_dir.as_ref().and_then(|dir| {
+            storage::load_chunk(dir, chunk_pos).unwrap_or_else(|err| {
+                warn!(
+                    "Failed to load chunk {:?}, generating it again: {}",
+                    chunk_pos, err
+                );
+                None
+            })
+        });
+
+        let chunk = saved.unwrap_or_else(|| {
+            let mut chunk = Chunk::empty(IVec3::from(chunk_pos) * CHUNK_SIZE as i32);
+            chunk.generate(seed);
+            chunk
+        });
 
         map.insert(chunk_pos, chunk);
+        new_chunks.push(chunk_pos);
     }
 }
 
@@ -87,6 +106,9 @@ fn generate_chunks_worker(
 pub fn generate_chunks_multithreaded(
     generated_chunks: ResMut<GeneratedChunks>,
     chunk_queue: ResMut<ChunkQueue>,
+    new_chunks: Res<NewChunks>,
+    seed: Res<WorldSeed>,
+    world_save: Option<Res<WorldSave>>,
 ) {
     let num_threads = num_cpus::get();
 
@@ -94,11 +116,21 @@ pub fn generate_chunks_multithreaded(
     for _ in 0..num_threads {
         let chunks = Arc::clone(&generated_chunks.map);
         let queue = Arc::clone(&chunk_queue.0);
+        let new_chunks = Arc::clone(&new_chunks.0);
+        let seed = *seed;
+        let world_dir = world_save.as_ref().map(|save| save.dir.clone());
 
         let handle = thread::spawn(move || {
-            generate_chunks_worker(chunks, queue);
+            generate_chunks_worker(chunks, queue, new_chunks, seed, world_dir);
         });
 
         handles.push(handle);
     }
 }
+
+/// Sends a `ChunkGenerated` event for every chunk the generation threads have finished.
+pub fn announce_new_chunks(new_chunks: Res<NewChunks>, mut generated: EventWriter<ChunkGenerated>) {
+    while let Some(chunk_pos) = new_chunks.0.pop() {
+        generated.send(ChunkGenerated(IVec3::from(chunk_pos)));
+    }
+}
@@ -1,11 +1,53 @@
-use crate::camera::PlayerCamera;
+use crate::camera::{PlayerCamera, PlayerCollision};
 use crate::worldgen::chunk::{
-    chunk_material, Chunk, ChunkedTerrain, GeneratedChunks, LoadedChunks, CHUNK_SIZE,
-    MAX_CHUNKS_PROCESSED_PER_ITER, VIEW_DISTANCE,
+    chunk_material, chunk_pos_containing, Chunk, ChunkedTerrain, DirtyChunks, GeneratedChunks,
+    LoadedChunks, NewChunks, ViewDistance, CHUNK_SIZE, MAX_CHUNKS_PROCESSED_PER_ITER,
 };
+use crate::worldgen::gen::WorldSeed;
+use crate::worldgen::model::BlockModels;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
+/// Spawns the terrain entity of a chunk. It only gets a trimesh collider if the player uses Rapier
+/// for collision, since building it is expensive.
+fn spawn_chunk_terrain(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    asset_server: &Res<AssetServer>,
+    chunk: &Chunk,
+    models: &BlockModels,
+    collision: PlayerCollision,
+) {
+    let mesh = chunk.get_mesh(models);
+
+    // Water is meshed, but shouldn't be collided with
+    let collider = match collision {
+        PlayerCollision::Rapier => Collider::from_bevy_mesh(
+            &chunk.get_collision_mesh(models),
+            &ComputedColliderShape::TriMesh,
+        ),
+        PlayerCollision::Voxel => None,
+    };
+
+    let mut terrain = commands.spawn((
+        // Geometry component
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(chunk_material(asset_server)),
+            transform: Transform::from_translation(chunk.pos.as_vec3()),
+            ..default()
+        },
+        ChunkedTerrain,
+    ));
+
+    // Physics component
+    if let Some(collider) = collider {
+        terrain.insert((RigidBody::Fixed, collider));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_initial_chunks(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -13,6 +55,10 @@ pub fn spawn_initial_chunks(
     generated_chunks: ResMut<GeneratedChunks>,
     mut loaded_chunks: ResMut<LoadedChunks>,
     asset_server: Res<AssetServer>,
+    models: Res<BlockModels>,
+    collision: Res<PlayerCollision>,
+    seed: Res<WorldSeed>,
+    new_chunks: Res<NewChunks>,
 ) {
     // Initial render distance.
     // This doesn't need to be high, because other chunks will be loaded during runtime;
@@ -27,7 +73,8 @@ pub fn spawn_initial_chunks(
                 let pos = IVec3::new(x, y, z);
 
                 let mut chunk = Chunk::empty(pos * CHUNK_SIZE as i32);
-                chunk.generate();
+                chunk.generate(*seed);
+                new_chunks.0.push((pos.x, pos.y, pos.z));
 
                 // Skip the whole mesh-making-process for empty chunks
                 if chunk.is_empty() {
@@ -37,25 +84,15 @@ pub fn spawn_initial_chunks(
                     continue;
                 }
 
-                let mesh = chunk.get_mesh();
-
-                commands
-                    .spawn((
-                        // Physics component
-                        (
-                            RigidBody::Fixed,
-                            Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh)
-                                .unwrap(),
-                        ),
-                        // Geometry component
-                        PbrBundle {
-                            mesh: meshes.add(mesh),
-                            material: materials.add(chunk_material(&asset_server)),
-                            transform: Transform::from_translation(chunk.pos.as_vec3()),
-                            ..default()
-                        },
-                    ))
-                    .insert(ChunkedTerrain);
+                spawn_chunk_terrain(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    &asset_server,
+                    &chunk,
+                    &models,
+                    *collision,
+                );
 
                 chunk_map.insert((pos.x, pos.y, pos.z), chunk);
                 loaded_chunks.chunks.insert((pos.x, pos.y, pos.z));
@@ -64,6 +101,7 @@ pub fn spawn_initial_chunks(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn load_generated_chunks(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -71,6 +109,9 @@ pub fn load_generated_chunks(
     mut loaded_chunks: ResMut<LoadedChunks>,
     generated_chunks: Res<GeneratedChunks>,
     asset_server: Res<AssetServer>,
+    models: Res<BlockModels>,
+    collision: Res<PlayerCollision>,
+    view_distance: Res<ViewDistance>,
 
     camera_query: Query<&Transform, With<PlayerCamera>>,
 ) {
@@ -78,7 +119,7 @@ pub fn load_generated_chunks(
 
     let camera_transform = camera_query.get_single().unwrap();
     let camera_pos = camera_transform.translation;
-    let camera_chunk_pos = (camera_pos / CHUNK_SIZE as f32).as_ivec3();
+    let camera_chunk_pos = chunk_pos_containing(camera_pos);
 
     let mut chunks_spawned = 0;
 
@@ -92,10 +133,7 @@ pub fn load_generated_chunks(
         // Only load the chunk if it's in the view distance
         // This is required to prevent this system from trying to spawn
         // chunks that the despawn system has just destroyed.
-        if !((-VIEW_DISTANCE.0..=VIEW_DISTANCE.0).contains(&offsetted_chunk_pos.x)
-            && (-VIEW_DISTANCE.1..=VIEW_DISTANCE.1).contains(&offsetted_chunk_pos.y)
-            && (-VIEW_DISTANCE.2..=VIEW_DISTANCE.2).contains(&offsetted_chunk_pos.z))
-        {
+        if !view_distance.contains(offsetted_chunk_pos) {
             continue;
         }
 
@@ -104,24 +142,15 @@ pub fn load_generated_chunks(
             continue;
         }
 
-        let mesh = chunk.get_mesh();
-
-        commands
-            .spawn((
-                // Physics component
-                (
-                    RigidBody::Fixed,
-                    Collider::from_bevy_mesh(&mesh, &ComputedColliderShape::TriMesh).unwrap(),
-                ),
-                // Geometry component
-                PbrBundle {
-                    mesh: meshes.add(mesh),
-                    material: materials.add(chunk_material(&asset_server)),
-                    transform: Transform::from_translation(chunk.pos.as_vec3()),
-                    ..default()
-                },
-            ))
-            .insert(ChunkedTerrain);
+        spawn_chunk_terrain(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &asset_server,
+            chunk,
+            &models,
+            *collision,
+        );
 
         loaded_chunks.chunks.insert(*chunk_pos);
 
@@ -134,10 +163,11 @@ pub fn unload_chunks(
     mut loaded_chunks: ResMut<LoadedChunks>,
     chunks_query: Query<(Entity, &Transform), With<ChunkedTerrain>>,
     camera_query: Query<&Transform, With<PlayerCamera>>,
+    view_distance: Res<ViewDistance>,
 ) {
     let camera_transform = camera_query.get_single().unwrap();
     let camera_pos = camera_transform.translation;
-    let camera_chunk_pos = (camera_pos / CHUNK_SIZE as f32).as_ivec3();
+    let camera_chunk_pos = chunk_pos_containing(camera_pos);
 
     let mut chunks_despawned = 0;
 
@@ -146,15 +176,12 @@ pub fn unload_chunks(
             return;
         }
 
-        let chunk_position = transform.translation.as_ivec3() / CHUNK_SIZE as i32;
+        let chunk_position = chunk_pos_containing(transform.translation);
 
         let offsetted_chunk_pos = chunk_position - camera_chunk_pos;
 
         // If the chunk isn't in the view distance, despawn it
-        if !((-VIEW_DISTANCE.0..=VIEW_DISTANCE.0).contains(&offsetted_chunk_pos.x)
-            && (-VIEW_DISTANCE.1..=VIEW_DISTANCE.1).contains(&offsetted_chunk_pos.y)
-            && (-VIEW_DISTANCE.2..=VIEW_DISTANCE.2).contains(&offsetted_chunk_pos.z))
-        {
+        if !view_distance.contains(offsetted_chunk_pos) {
             commands.entity(entity).despawn();
 
             loaded_chunks
@@ -165,3 +192,32 @@ pub fn unload_chunks(
         }
     }
 }
+
+/// Despawns the terrain of dirty chunks and marks them as unloaded, so that the loading system
+/// spawns them again with an up-to-date mesh.
+pub fn remesh_dirty_chunks(
+    mut commands: Commands,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
+    chunks_query: Query<(Entity, &Transform), With<ChunkedTerrain>>,
+) {
+    if dirty_chunks.chunks.is_empty() {
+        return;
+    }
+
+    for (entity, transform) in chunks_query.iter() {
+        let chunk_position = chunk_pos_containing(transform.translation);
+
+        if dirty_chunks
+            .chunks
+            .contains::<(i32, i32, i32)>(&chunk_position.into())
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    // Chunks that were empty don't have an entity, but may not be empty anymore
+    for chunk_pos in dirty_chunks.chunks.drain() {
+        loaded_chunks.chunks.remove(&chunk_pos);
+    }
+}
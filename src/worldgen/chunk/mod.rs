@@ -1,9 +1,14 @@
+/// World-space block access across chunk boundaries
+pub mod access;
 /// Contains chunk generation logic; somewhat disconnected from Bevy (still uses Bevy types)
 pub mod chunk_impl;
 pub mod generation;
 pub mod loading;
+/// Compressing chunks, and saving them to and loading them from the world folder
+pub mod storage;
 pub mod timer;
 
+use crate::machine::BlockEntity;
 use crate::worldgen::block::Block;
 use bevy::prelude::*;
 use bevy::render::render_resource::Face;
@@ -14,8 +19,9 @@ use std::sync::{Arc, Mutex};
 /// The size, in x, y, and z, of each chunk.
 pub const CHUNK_SIZE: usize = 16;
 
-// Should eventually be configurable
-pub const VIEW_DISTANCE: (i32, i32, i32) = (8, 8, 8);
+pub const DEFAULT_VIEW_DISTANCE: i32 = 8;
+/// Every chunk in the view distance gets generated, so it can't be set higher than this.
+pub const MAX_VIEW_DISTANCE: i32 = 32;
 
 pub const MAX_CHUNKS_PROCESSED_PER_ITER: usize = 32;
 pub const CHUNK_ITERATION_INTERVAL: f32 = 0.01;
@@ -24,7 +30,9 @@ pub const CHUNK_ITERATION_INTERVAL: f32 = 0.01;
 pub struct Chunk {
     pub pos: IVec3,
     pub voxels: [[[Block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
-    pub mesh: Option<Mesh>,
+    /// The state of blocks that need more than their block type, like machines, by their position
+    /// in the chunk.
+    pub block_entities: HashMap<UVec3, BlockEntity>,
 
     empty: bool,
 }
@@ -33,20 +41,86 @@ pub struct Chunk {
 #[derive(Component)]
 pub struct ChunkedTerrain;
 
+/// Marker for entities that chunks get generated around, e.g. the player, or every connected
+/// client on a server.
+#[derive(Component)]
+pub struct ChunkLoader;
+
+/// How many chunks around a chunk loader, in x, y, and z, are generated, loaded and simulated.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ViewDistance(pub IVec3);
+
+impl Default for ViewDistance {
+    fn default() -> Self {
+        Self(IVec3::splat(DEFAULT_VIEW_DISTANCE))
+    }
+}
+
+impl ViewDistance {
+    /// Whether a chunk this many chunks away from a loader is in the view distance.
+    pub fn contains(self, offset: IVec3) -> bool {
+        offset.abs().cmple(self.0).all()
+    }
+}
+
+/// The position of the chunk that a point in world space is in. Rounds down, so points at negative
+/// coordinates are in the chunk below them rather than the one towards the origin.
+pub fn chunk_pos_containing(pos: Vec3) -> IVec3 {
+    (pos / CHUNK_SIZE as f32).floor().as_ivec3()
+}
+
+/// The chunk position every chunk loader is in.
+pub fn loader_chunk_positions(loader_query: &Query<&Transform, With<ChunkLoader>>) -> Vec<IVec3> {
+    loader_query
+        .iter()
+        .map(|transform| chunk_pos_containing(transform.translation))
+        .collect()
+}
+
+/// Whether the chunk is in the view distance of any of the chunk loaders, which is where the world
+/// is simulated.
+pub fn is_in_view_distance(
+    chunk_pos: IVec3,
+    loader_chunk_positions: &[IVec3],
+    view_distance: ViewDistance,
+) -> bool {
+    loader_chunk_positions
+        .iter()
+        .any(|loader_pos| view_distance.contains(chunk_pos - *loader_pos))
+}
+
+/// Chunks by chunk position, which is their origin divided by `CHUNK_SIZE`.
+pub type ChunkMap = HashMap<(i32, i32, i32), Chunk>;
+
 #[derive(Resource)]
 pub struct GeneratedChunks {
-    pub map: Arc<Mutex<HashMap<(i32, i32, i32), Chunk>>>,
+    pub map: Arc<Mutex<ChunkMap>>,
 }
 
 #[derive(Resource)]
 pub struct ChunkQueue(pub Arc<SegQueue<(i32, i32, i32)>>);
 
+/// Chunks that were generated since this was last emptied, filled by the generation threads.
+#[derive(Resource)]
+pub struct NewChunks(pub Arc<SegQueue<(i32, i32, i32)>>);
+
+/// Sent for every chunk that's been generated, once it's in the chunk map.
+#[derive(Event)]
+pub struct ChunkGenerated(pub IVec3);
+
 /// If a chunk position is in this list, then it is loaded.
 #[derive(Resource)]
 pub struct LoadedChunks {
     pub chunks: HashSet<(i32, i32, i32)>,
 }
 
+/// Chunks whose blocks have changed since they were meshed. Their terrain entities get respawned
+/// with a new mesh.
+#[derive(Resource)]
+pub struct DirtyChunks {
+    pub chunks: HashSet<(i32, i32, i32)>,
+}
+
 /// The material to use for the chunk mesh.
 const CHUNK_MATERIAL: StandardMaterial = StandardMaterial {
     base_color: Color::WHITE,
@@ -63,8 +137,9 @@ const CHUNK_MATERIAL: StandardMaterial = StandardMaterial {
     double_sided: false,
     cull_mode: Some(Face::Back),
     unlit: false,
-    fog_enabled: false,
-    alpha_mode: AlphaMode::Opaque,
+    fog_enabled: true,
+    // Plants and bars have see-through parts
+    alpha_mode: AlphaMode::Mask(0.5),
     depth_bias: 0.0,
     depth_map: None,
     parallax_depth_scale: 0.0,
@@ -78,3 +153,21 @@ pub fn chunk_material(asset_server: &Res<AssetServer>) -> StandardMaterial {
         ..CHUNK_MATERIAL
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_positions_round_down() {
+        assert_eq!(
+            chunk_pos_containing(Vec3::new(0.5, 15.9, 16.0)),
+            IVec3::new(0, 0, 1)
+        );
+        // Truncating would put these in chunk 0 along with the positive side
+        assert_eq!(
+            chunk_pos_containing(Vec3::new(-0.5, -16.0, -16.5)),
+            IVec3::new(-1, -1, -2)
+        );
+    }
+}
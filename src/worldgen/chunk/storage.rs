@@ -0,0 +1,359 @@
+//! Chunks as bytes: compressed for sending them to clients, and saved to and loaded from the world
+//! folder so that edits and machines are kept between runs.
+//!
+//! Every chunk is saved to its own file. Only chunks that were changed, or that have machines in
+//! them, are saved; the rest are generated again from the seed.
+
+use crate::machine::BlockEntity;
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::{Chunk, ChunkMap, GeneratedChunks, CHUNK_SIZE};
+use crate::worldgen::edit::BlockChanged;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+/// Where worlds are saved if no other folder is given.
+pub const DEFAULT_WORLD_DIR: &str = "world";
+/// The folder in the world folder that chunks are saved in.
+const CHUNKS_DIR: &str = "chunks";
+const CHUNK_EXTENSION: &str = "chunk";
+/// How often changed chunks are saved, in seconds. They're also saved when the app exits.
+pub const AUTOSAVE_INTERVAL: f32 = 30.0;
+/// The most a chunk is decompressed to before it's given up on, so a small damaged file or message
+/// can't use up all the memory. A chunk full of machines is well under this.
+pub const MAX_CHUNK_DATA_SIZE: usize = 1 << 20;
+
+/// Everything stored in a chunk, in the form it's serialized as.
+#[derive(Serialize, Deserialize)]
+struct ChunkData {
+    blocks: Vec<Block>,
+    /// By position in the chunk.
+    block_entities: Vec<([u32; 3], BlockEntity)>,
+}
+
+/// Compresses the blocks and block entities of a chunk, for sending them over the network or
+/// saving them.
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let data = ChunkData {
+        blocks: chunk.voxels.iter().flatten().flatten().copied().collect(),
+        block_entities: chunk
+            .block_entities
+            .iter()
+            .map(|(pos, block_entity)| (pos.to_array(), block_entity.clone()))
+            .collect(),
+    };
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    bincode::serialize_into(&mut encoder, &data).unwrap();
+
+    encoder.finish().unwrap()
+}
+
+/// Reverse of `encode_chunk`. `pos` is the chunk position, not the world position of its origin.
+/// Data that decompresses to more than `MAX_CHUNK_DATA_SIZE`, or has block entities outside the
+/// chunk, is rejected.
+pub fn decode_chunk(pos: IVec3, data: &[u8]) -> Option<Chunk> {
+    let mut decompressed = Vec::new();
+    DeflateDecoder::new(data)
+        .take(MAX_CHUNK_DATA_SIZE as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .ok()?;
+
+    if decompressed.len() > MAX_CHUNK_DATA_SIZE {
+        return None;
+    }
+
+    let ChunkData {
+        blocks,
+        block_entities,
+    } = bincode::deserialize(&decompressed).ok()?;
+
+    if blocks.len() != CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE {
+        return None;
+    }
+
+    let size = UVec3::splat(CHUNK_SIZE as u32);
+    if block_entities
+        .iter()
+        .any(|(pos, _)| !UVec3::from_array(*pos).cmplt(size).all())
+    {
+        return None;
+    }
+
+    let mut voxels = [[[Block::Air; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+    for (i, block) in blocks.into_iter().enumerate() {
+        voxels[i / (CHUNK_SIZE * CHUNK_SIZE)][(i / CHUNK_SIZE) % CHUNK_SIZE][i % CHUNK_SIZE] =
+            block;
+    }
+
+    let mut chunk = Chunk::from_voxels(pos * CHUNK_SIZE as i32, voxels);
+    chunk.block_entities = block_entities
+        .into_iter()
+        .map(|(pos, block_entity)| (UVec3::from_array(pos), block_entity))
+        .collect();
+
+    Some(chunk)
+}
+
+/// Where the world is saved. Without it nothing is saved, and every chunk is generated fresh.
+#[derive(Resource, Clone, Debug)]
+pub struct WorldSave {
+    pub dir: PathBuf,
+}
+
+/// Chunks whose blocks changed since they were last saved.
+#[derive(Resource, Default)]
+pub struct UnsavedChunks(pub HashSet<(i32, i32, i32)>);
+
+#[derive(Resource)]
+pub struct AutosaveTimer(pub Timer);
+
+/// The file a chunk is saved in, by chunk position.
+pub fn chunk_path(world_dir: &Path, pos: (i32, i32, i32)) -> PathBuf {
+    world_dir
+        .join(CHUNKS_DIR)
+        .join(format!("{}_{}_{}", pos.0, pos.1, pos.2))
+        .with_extension(CHUNK_EXTENSION)
+}
+
+/// Saves the chunk, along with its block entities. The file is written next to the old one and
+/// then moved over it, so a crash halfway through doesn't lose the chunk.
+pub fn save_chunk(world_dir: &Path, chunk: &Chunk) -> io::Result<()> {
+    let pos = chunk.pos / CHUNK_SIZE as i32;
+    let path = chunk_path(world_dir, (pos.x, pos.y, pos.z));
+    let partial = path.with_extension("partial");
+
+    fs::create_dir_all(world_dir.join(CHUNKS_DIR))?;
+    fs::write(&partial, encode_chunk(chunk))?;
+    fs::rename(partial, path)
+}
+
+/// Loads the chunk at the chunk position, or `None` if it's never been saved.
+pub fn load_chunk(world_dir: &Path, pos: (i32, i32, i32)) -> io::Result<Option<Chunk>> {
+    let data = match fs::read(chunk_path(world_dir, pos)) {
+        Ok(data) => data,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    decode_chunk(IVec3::from(pos), &data)
+        .map(Some)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "the chunk is damaged"))
+}
+
+/// Saves every chunk that changed, and every chunk with machines in it, since their state changes
+/// without their blocks changing. Returns how many were saved.
+pub fn save_chunks(
+    world_dir: &Path,
+    map: &ChunkMap,
+    unsaved: impl IntoIterator<Item = (i32, i32, i32)>,
+) -> usize {
+    let with_machines = map
+        .iter()
+        .filter(|(_, chunk)| !chunk.block_entities.is_empty())
+        .map(|(pos, _)| *pos);
+    let positions: HashSet<_> = unsaved.into_iter().chain(with_machines).collect();
+
+    let mut saved = 0;
+
+    for pos in positions {
+        let Some(chunk) = map.get(&pos) else {
+            continue;
+        };
+
+        match save_chunk(world_dir, chunk) {
+            Ok(()) => saved += 1,
+            Err(err) => warn!("Failed to save chunk {:?}: {}", pos, err),
+        }
+    }
+
+    saved
+}
+
+pub fn mark_changed_chunks_unsaved(
+    mut changes: EventReader<BlockChanged>,
+    mut unsaved: ResMut<UnsavedChunks>,
+) {
+    for change in changes.iter() {
+        let (chunk_pos, _) = super::access::world_to_chunk_pos(change.pos);
+        unsaved.0.insert(chunk_pos);
+    }
+}
+
+/// Saves the world every `AUTOSAVE_INTERVAL`, and when the app exits.
+pub fn autosave_world(
+    mut timer: ResMut<AutosaveTimer>,
+    time: Res<Time>,
+    mut exits: EventReader<AppExit>,
+    world_save: Res<WorldSave>,
+    mut unsaved: ResMut<UnsavedChunks>,
+    generated_chunks: Res<GeneratedChunks>,
+) {
+    timer.0.tick(time.delta());
+
+    let exiting = exits.iter().count() > 0;
+    if !timer.0.just_finished() && !exiting {
+        return;
+    }
+
+    let map = generated_chunks.map.lock().unwrap();
+    let saved = save_chunks(&world_save.dir, &map, unsaved.0.drain());
+
+    if saved > 0 {
+        info!("Saved {} chunks to {}", saved, world_save.dir.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::item::{Item, ItemStack};
+    use crate::machine::furnace::Furnace;
+    use crate::worldgen::block::Facing;
+    use bevy::utils::HashMap;
+    use std::io::Write;
+
+    /// A world folder that's deleted again once the test is done.
+    struct TempWorld(PathBuf);
+
+    impl TempWorld {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "excavate-manufacturate-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempWorld {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A chunk with a few blocks in it, and a furnace partway through smelting.
+    fn chunk_with_furnace(pos: IVec3) -> Chunk {
+        let mut voxels = [[[Block::Air; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        voxels[0][0][0] = Block::Stone;
+        voxels[1][2][3] = Block::Furnace(Facing::East);
+        voxels[CHUNK_SIZE - 1][0][1] = Block::FlowingWater(4);
+
+        let mut furnace = Furnace::new();
+        furnace.insert(ItemStack::new(Item::Block(Block::IronOre), 5));
+        furnace.progress = 7;
+        let mut chunk = Chunk::from_voxels(pos * CHUNK_SIZE as i32, voxels);
+        chunk
+            .block_entities
+            .insert(UVec3::new(1, 2, 3), BlockEntity::Furnace(furnace));
+
+        chunk
+    }
+
+    fn compress(data: &ChunkData) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        bincode::serialize_into(&mut encoder, data).unwrap();
+
+        encoder.finish().unwrap()
+    }
+
+    fn assert_same_chunk(a: &Chunk, b: &Chunk) {
+        assert_eq!(a.pos, b.pos);
+        assert_eq!(a.voxels, b.voxels);
+        assert_eq!(a.block_entities, b.block_entities);
+    }
+
+    #[test]
+    fn chunks_round_trip_with_their_block_entities() {
+        let pos = IVec3::new(2, -1, 3);
+        let chunk = chunk_with_furnace(pos);
+
+        let decoded = decode_chunk(pos, &encode_chunk(&chunk)).unwrap();
+
+        assert_same_chunk(&decoded, &chunk);
+    }
+
+    #[test]
+    fn chunks_round_trip_through_the_world_folder() {
+        let world = TempWorld::new("round-trip");
+        let chunk = chunk_with_furnace(IVec3::new(-3, 0, 5));
+
+        save_chunk(&world.0, &chunk).unwrap();
+        let loaded = load_chunk(&world.0, (-3, 0, 5)).unwrap().unwrap();
+
+        assert_same_chunk(&loaded, &chunk);
+        assert!(load_chunk(&world.0, (0, 0, 0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn only_changed_chunks_and_chunks_with_machines_are_saved() {
+        let world = TempWorld::new("changed");
+        let mut map = HashMap::new();
+        map.insert((0, 0, 0), Chunk::empty(IVec3::ZERO));
+        map.insert((1, 0, 0), Chunk::empty(IVec3::new(16, 0, 0)));
+        map.insert((2, 0, 0), chunk_with_furnace(IVec3::new(2, 0, 0)));
+
+        assert_eq!(save_chunks(&world.0, &map, [(1, 0, 0), (9, 9, 9)]), 2);
+
+        assert!(load_chunk(&world.0, (0, 0, 0)).unwrap().is_none());
+        assert!(load_chunk(&world.0, (1, 0, 0)).unwrap().is_some());
+        assert!(load_chunk(&world.0, (2, 0, 0)).unwrap().is_some());
+    }
+
+    #[test]
+    fn damaged_chunk_files_are_errors() {
+        let world = TempWorld::new("damaged");
+        fs::create_dir_all(world.0.join(CHUNKS_DIR)).unwrap();
+        fs::write(chunk_path(&world.0, (0, 0, 0)), b"not a chunk").unwrap();
+
+        let err = load_chunk(&world.0, (0, 0, 0)).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn chunks_of_the_wrong_size_are_rejected() {
+        let data = ChunkData {
+            blocks: vec![Block::Stone; 10],
+            block_entities: Vec::new(),
+        };
+
+        assert!(decode_chunk(IVec3::ZERO, &compress(&data)).is_none());
+        assert!(decode_chunk(IVec3::ZERO, b"not a chunk").is_none());
+    }
+
+    #[test]
+    fn block_entities_outside_the_chunk_are_rejected() {
+        let data = ChunkData {
+            blocks: vec![Block::Air; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE],
+            block_entities: vec![(
+                [0, CHUNK_SIZE as u32, 0],
+                BlockEntity::Furnace(Furnace::new()),
+            )],
+        };
+
+        assert!(decode_chunk(IVec3::ZERO, &compress(&data)).is_none());
+    }
+
+    #[test]
+    fn chunks_that_decompress_too_far_are_rejected() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder
+            .write_all(&vec![0; MAX_CHUNK_DATA_SIZE + 1])
+            .unwrap();
+        let data = encoder.finish().unwrap();
+
+        assert!(data.len() < MAX_CHUNK_DATA_SIZE);
+        assert!(decode_chunk(IVec3::ZERO, &data).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::machine::BlockEntity;
+use crate::worldgen::block::Block;
+use crate::worldgen::chunk::access::{set_block, world_to_chunk_pos};
+use crate::worldgen::chunk::{DirtyChunks, GeneratedChunks};
+use bevy::prelude::*;
+
+/// Requests a block in the world to be changed. In singleplayer (and on a server) it's applied
+/// straight away; a client instead sends it to the server, which broadcasts the change back.
+#[derive(Event)]
+pub struct BlockEdit {
+    pub pos: IVec3,
+    pub block: Block,
+    /// Whether a block entity the edit replaces drops what's in it. World edits don't, since
+    /// undoing them puts the contents back.
+    pub drops_contents: bool,
+}
+
+/// Requests the state of a block entity, like the items in a machine, to be replaced. Sent along
+/// with the `BlockEdit` that places its block, and ignored unless the block takes that kind of
+/// block entity.
+#[derive(Event)]
+pub struct BlockEntityEdit {
+    pub pos: IVec3,
+    pub block_entity: BlockEntity,
+}
+
+/// Sent once a block in `GeneratedChunks` has actually changed.
+#[derive(Event)]
+pub struct BlockChanged {
+    pub pos: IVec3,
+    pub block: Block,
+    /// Whether a block entity the change replaced drops what's in it.
+    pub drops_contents: bool,
+}
+
+pub fn apply_block_edits(
+    mut edits: EventReader<BlockEdit>,
+    mut changes: EventWriter<BlockChanged>,
+    generated_chunks: Res<GeneratedChunks>,
+) {
+    let mut map = generated_chunks.map.lock().unwrap();
+
+    for edit in edits.iter() {
+        // Can't edit chunks that don't exist yet
+        if set_block(&mut map, edit.pos, edit.block) {
+            changes.send(BlockChanged {
+                pos: edit.pos,
+                block: edit.block,
+                drops_contents: edit.drops_contents,
+            });
+        }
+    }
+}
+
+/// Marks the chunks of changed blocks as dirty, so they get meshed again.
+pub fn mark_changed_chunks_dirty(
+    mut changes: EventReader<BlockChanged>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+) {
+    for change in changes.iter() {
+        let (chunk_pos, _) = world_to_chunk_pos(change.pos);
+        dirty_chunks.chunks.insert(chunk_pos);
+    }
+}
@@ -0,0 +1,373 @@
+//! Cellular simulation of flowing water. Sources spread flowing water downwards, and sideways over
+//! solid ground, which drains again once it's no longer fed by a source.
+
+use crate::worldgen::block::{Block, MAX_FLOW_LEVEL};
+use crate::worldgen::chunk::access::{get_block, set_block, world_to_chunk_pos};
+use crate::worldgen::chunk::{
+    is_in_view_distance, loader_chunk_positions, ChunkGenerated, ChunkLoader, GeneratedChunks,
+    ViewDistance, CHUNK_SIZE,
+};
+use crate::worldgen::edit::BlockChanged;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+/// How often fluids flow, in seconds.
+pub const FLUID_TICK_INTERVAL: f32 = 0.25;
+
+const HORIZONTAL_NEIGHBORS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+const NEIGHBORS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Resource)]
+pub struct FluidTickTimer(pub Timer);
+
+/// Positions that might have to flow or drain on the next fluid tick.
+#[derive(Resource, Default)]
+pub struct FluidUpdates {
+    pub positions: HashSet<IVec3>,
+}
+
+/// What the block at `pos` should become on the next tick, if it's water that isn't a source, or
+/// air that water could flow into. Returns `None` for anything else.
+fn next_state(pos: IVec3, get: &impl Fn(IVec3) -> Option<Block>) -> Option<Block> {
+    match get(pos)? {
+        Block::Air | Block::FlowingWater(_) => {}
+        _ => return None,
+    }
+
+    // Falling water is always at full strength
+    if get(pos + IVec3::Y).is_some_and(Block::is_liquid) {
+        return Some(Block::FlowingWater(1));
+    }
+
+    let level = HORIZONTAL_NEIGHBORS
+        .iter()
+        .filter_map(|offset| {
+            let neighbor = pos + *offset;
+            let level = get(neighbor)?.flow_level()?;
+
+            // Water only spreads sideways if it can't flow down
+            match get(neighbor - IVec3::Y)? {
+                Block::Air | Block::FlowingWater(_) => None,
+                _ => Some(level + 1),
+            }
+        })
+        .min()
+        .filter(|level| *level <= MAX_FLOW_LEVEL);
+
+    Some(level.map_or(Block::Air, Block::FlowingWater))
+}
+
+/// Works out how every one of `positions` changes during one tick. Every block is updated based on
+/// the state before the tick, so the result doesn't depend on the order of `positions`.
+pub fn step_fluids(
+    positions: impl IntoIterator<Item = IVec3>,
+    get: &impl Fn(IVec3) -> Option<Block>,
+) -> Vec<(IVec3, Block)> {
+    positions
+        .into_iter()
+        .filter_map(|pos| {
+            let next = next_state(pos, get)?;
+            (get(pos) != Some(next)).then_some((pos, next))
+        })
+        .collect()
+}
+
+/// Blocks around a change might have to start flowing or draining.
+pub fn queue_fluid_updates(
+    mut changes: EventReader<BlockChanged>,
+    mut fluid_updates: ResMut<FluidUpdates>,
+) {
+    for change in changes.iter() {
+        fluid_updates.positions.insert(change.pos);
+
+        for offset in NEIGHBORS {
+            fluid_updates.positions.insert(change.pos + offset);
+        }
+    }
+}
+
+/// Positions on either side of the faces between a chunk and its neighbors where liquid meets
+/// something else. Chunks are generated without knowing about each other, so these might have to
+/// start flowing. Faces towards chunks that haven't been generated yet are skipped; they're checked
+/// once that chunk is.
+pub fn chunk_boundary_water(chunk_pos: IVec3, get: &impl Fn(IVec3) -> Option<Block>) -> Vec<IVec3> {
+    let size = CHUNK_SIZE as i32;
+    let origin = chunk_pos * size;
+    let in_chunk =
+        |local: IVec3| local.cmpge(IVec3::ZERO).all() && local.cmplt(IVec3::splat(size)).all();
+
+    let mut positions = Vec::new();
+
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let local = IVec3::new(x, y, z);
+
+                for offset in NEIGHBORS {
+                    if in_chunk(local + offset) {
+                        continue;
+                    }
+
+                    let pos = origin + local;
+                    let (Some(block), Some(neighbor)) = (get(pos), get(pos + offset)) else {
+                        continue;
+                    };
+
+                    if block != neighbor && (block.is_liquid() || neighbor.is_liquid()) {
+                        positions.push(pos);
+                        positions.push(pos + offset);
+                    }
+                }
+            }
+        }
+    }
+
+    positions
+}
+
+/// Water at the edges of newly generated chunks might have to flow into their neighbors, or the
+/// other way around.
+pub fn queue_new_chunk_boundaries(
+    mut generated: EventReader<ChunkGenerated>,
+    generated_chunks: Res<GeneratedChunks>,
+    mut fluid_updates: ResMut<FluidUpdates>,
+) {
+    if generated.is_empty() {
+        return;
+    }
+
+    let map = generated_chunks.map.lock().unwrap();
+
+    for ChunkGenerated(chunk_pos) in generated.iter() {
+        let boundary = chunk_boundary_water(*chunk_pos, &|pos| get_block(&map, pos));
+        fluid_updates.positions.extend(boundary);
+    }
+}
+
+pub fn tick_fluid_timer(mut timer: ResMut<FluidTickTimer>, time: Res<Time>) {
+    timer.0.tick(time.delta());
+}
+
+/// Flows fluids in chunks that are in the view distance of a chunk loader. Updates in other chunks
+/// are kept until they are.
+pub fn tick_fluids(
+    mut fluid_updates: ResMut<FluidUpdates>,
+    mut changes: EventWriter<BlockChanged>,
+    generated_chunks: Res<GeneratedChunks>,
+    loader_query: Query<&Transform, With<ChunkLoader>>,
+    view_distance: Res<ViewDistance>,
+    fluid_tick_timer: Res<FluidTickTimer>,
+) {
+    if !fluid_tick_timer.0.just_finished() || fluid_updates.positions.is_empty() {
+        return;
+    }
+
+    let loader_chunk_positions = loader_chunk_positions(&loader_query);
+
+    let is_loaded = |pos: IVec3| {
+        let chunk_pos = IVec3::from(world_to_chunk_pos(pos).0);
+        is_in_view_distance(chunk_pos, &loader_chunk_positions, *view_distance)
+    };
+
+    let (loaded, unloaded): (Vec<IVec3>, Vec<IVec3>) = fluid_updates
+        .positions
+        .drain()
+        .partition(|pos| is_loaded(*pos));
+
+    fluid_updates.positions.extend(unloaded);
+
+    let mut map = generated_chunks.map.lock().unwrap();
+
+    let flowed = step_fluids(loaded, &|pos| get_block(&map, pos));
+
+    // These are picked up by `queue_fluid_updates`, which queues the next tick's updates
+    for (pos, block) in flowed {
+        set_block(&mut map, pos, block);
+        changes.send(BlockChanged {
+            pos,
+            block,
+            drops_contents: true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashMap;
+
+    /// Blocks above a stone floor at y = 0. Anything that isn't set is air.
+    struct World {
+        blocks: HashMap<IVec3, Block>,
+    }
+
+    impl World {
+        fn new(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> Self {
+            Self {
+                blocks: blocks.into_iter().collect(),
+            }
+        }
+
+        fn get(&self, pos: IVec3) -> Option<Block> {
+            if pos.y <= 0 {
+                return Some(Block::Stone);
+            }
+
+            Some(self.blocks.get(&pos).copied().unwrap_or(Block::Air))
+        }
+
+        /// Runs fluid ticks until nothing changes anymore, starting with updates around
+        /// `changed`, like `queue_fluid_updates` does. Returns how many ticks it took.
+        fn settle(&mut self, changed: impl IntoIterator<Item = IVec3>) -> usize {
+            let mut queued: HashSet<IVec3> = HashSet::new();
+            queued.extend(changed.into_iter().flat_map(|pos| {
+                std::iter::once(pos).chain(NEIGHBORS.iter().map(move |offset| pos + *offset))
+            }));
+
+            for tick in 0..100 {
+                let flowed = step_fluids(queued.drain(), &|pos| self.get(pos));
+
+                if flowed.is_empty() {
+                    return tick;
+                }
+
+                for (pos, block) in flowed {
+                    self.blocks.insert(pos, block);
+                    queued.insert(pos);
+                    queued.extend(NEIGHBORS.iter().map(|offset| pos + *offset));
+                }
+            }
+
+            panic!("fluids never settled");
+        }
+    }
+
+    #[test]
+    fn water_falls_at_full_strength() {
+        let world = World::new([(IVec3::new(0, 5, 0), Block::Water)]);
+
+        assert_eq!(
+            next_state(IVec3::new(0, 4, 0), &|pos| world.get(pos)),
+            Some(Block::FlowingWater(1))
+        );
+        // Water in the air doesn't spread sideways
+        assert_eq!(
+            next_state(IVec3::new(1, 5, 0), &|pos| world.get(pos)),
+            Some(Block::Air)
+        );
+    }
+
+    #[test]
+    fn water_spreads_sideways_over_ground() {
+        let world = World::new([
+            (IVec3::new(0, 1, 0), Block::Water),
+            (IVec3::new(2, 1, 0), Block::FlowingWater(3)),
+        ]);
+
+        // The closest source of water wins
+        assert_eq!(
+            next_state(IVec3::new(1, 1, 0), &|pos| world.get(pos)),
+            Some(Block::FlowingWater(1))
+        );
+        assert_eq!(
+            next_state(IVec3::new(3, 1, 0), &|pos| world.get(pos)),
+            Some(Block::FlowingWater(4))
+        );
+        // Solid blocks never change
+        assert_eq!(next_state(IVec3::new(0, 0, 0), &|pos| world.get(pos)), None);
+        assert_eq!(next_state(IVec3::new(0, 1, 0), &|pos| world.get(pos)), None);
+    }
+
+    #[test]
+    fn water_spreads_as_far_as_the_max_flow_level() {
+        let source = IVec3::new(0, 1, 0);
+        let mut world = World::new([(source, Block::Water)]);
+
+        world.settle([source]);
+
+        let reach = MAX_FLOW_LEVEL as i32;
+        assert_eq!(
+            world.get(IVec3::new(reach, 1, 0)),
+            Some(Block::FlowingWater(MAX_FLOW_LEVEL))
+        );
+        assert_eq!(world.get(IVec3::new(reach + 1, 1, 0)), Some(Block::Air));
+        // Spreading goes around corners, not diagonally
+        assert_eq!(
+            world.get(IVec3::new(2, 1, -3)),
+            Some(Block::FlowingWater(5))
+        );
+    }
+
+    #[test]
+    fn water_falls_down_a_ledge_and_spreads_below() {
+        let source = IVec3::new(0, 5, 0);
+        let mut world = World::new([(source, Block::Water)]);
+
+        world.settle([source]);
+
+        for y in 1..5 {
+            assert_eq!(world.get(IVec3::new(0, y, 0)), Some(Block::FlowingWater(1)));
+        }
+        assert_eq!(world.get(IVec3::new(1, 1, 0)), Some(Block::FlowingWater(2)));
+        assert_eq!(world.get(IVec3::new(1, 5, 0)), Some(Block::Air));
+    }
+
+    #[test]
+    fn water_dries_up_without_a_source() {
+        let source = IVec3::new(0, 1, 0);
+        let mut world = World::new([(source, Block::Water)]);
+        world.settle([source]);
+
+        world.blocks.insert(source, Block::Air);
+        world.settle([source]);
+
+        assert!(world.blocks.values().all(|block| *block == Block::Air));
+    }
+
+    #[test]
+    fn step_is_independent_of_order() {
+        let world = World::new([
+            (IVec3::new(0, 1, 0), Block::Water),
+            (IVec3::new(1, 1, 0), Block::FlowingWater(1)),
+        ]);
+        let positions: Vec<IVec3> = (-3..4).map(|x| IVec3::new(x, 1, 0)).collect();
+
+        let mut forwards = step_fluids(positions.clone(), &|pos| world.get(pos));
+        let mut backwards = step_fluids(positions.into_iter().rev(), &|pos| world.get(pos));
+        forwards.sort_by_key(|(pos, _)| pos.x);
+        backwards.sort_by_key(|(pos, _)| pos.x);
+
+        assert_eq!(forwards, backwards);
+    }
+
+    #[test]
+    fn chunk_boundaries_queue_water_on_both_sides() {
+        let size = CHUNK_SIZE as i32;
+        let edge = IVec3::new(size - 1, 3, 4);
+
+        // Water at the edge of chunk (0, 0, 0), with air past it in chunk (1, 0, 0). Chunk
+        // (0, -1, 0) hasn't been generated
+        let get = |pos: IVec3| match pos {
+            _ if pos.y < 0 => None,
+            _ if pos == edge => Some(Block::Water),
+            _ if pos.x < size => Some(Block::Stone),
+            _ => Some(Block::Air),
+        };
+
+        let queued = chunk_boundary_water(IVec3::ZERO, &get);
+
+        assert_eq!(queued, vec![edge, edge + IVec3::X]);
+        // The neighbor finds the same boundary when it's generated
+        assert_eq!(
+            chunk_boundary_water(IVec3::X, &get),
+            vec![edge + IVec3::X, edge]
+        );
+    }
+}
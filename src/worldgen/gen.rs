@@ -1,11 +1,76 @@
 use bevy::math::Vec3A;
 use bevy::prelude::*;
-use noisy_bevy::simplex_noise_2d;
+use noisy_bevy::{simplex_noise_2d, simplex_noise_3d};
 
 use super::block::*;
 
+/// Picks which part of the noise the world is generated from, so that every seed gives a different
+/// world.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    /// How far the world is moved through the noise. Only horizontally, since the terrain's
+    /// heights are absolute, and by whole blocks, so blocks line up with the noise the same way for
+    /// every seed.
+    fn offset(self) -> Vec3A {
+        // splitmix64, so that nearby seeds end up far apart
+        let mut hash = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        hash ^= hash >> 31;
+
+        // Small enough to keep the noise precise
+        Vec3A::new((hash as i16) as f32, 0.0, ((hash >> 16) as i16) as f32)
+    }
+}
+
+/// The noise values the terrain is generated from, at one position.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NoiseSample {
+    /// The height of the hills' surface in this column.
+    pub surface_height: f32,
+    /// Coal ore shows up where this is over 0.75.
+    pub coal: f32,
+    /// Iron ore shows up where this is over 0.8.
+    pub iron: f32,
+    /// Tall grass grows where this is over 0.6.
+    pub grass: f32,
+}
+
+impl NoiseSample {
+    pub fn at(pos: Vec3A, seed: WorldSeed) -> Self {
+        let pos = pos.floor() + seed.offset();
+
+        Self {
+            surface_height: surface_height(pos),
+            coal: coal_noise(pos),
+            iron: iron_noise(pos),
+            grass: grass_noise(pos),
+        }
+    }
+}
+
+fn surface_height(pos: Vec3A) -> f32 {
+    10.0 * simplex_noise_2d(Vec2::new(pos.x * 0.01, pos.z * 0.01))
+}
+
+fn coal_noise(pos: Vec3A) -> f32 {
+    simplex_noise_3d(Vec3::from(pos) * 0.15)
+}
+
+fn iron_noise(pos: Vec3A) -> f32 {
+    // Offset, so iron and coal don't show up in the same places
+    simplex_noise_3d(Vec3::from(pos) * 0.2 + 100.0)
+}
+
+fn grass_noise(pos: Vec3A) -> f32 {
+    // Offset, so grass doesn't only grow where there's ore below
+    simplex_noise_2d(Vec2::new(pos.x, pos.z) * 0.3 + 200.0)
+}
+
 fn hills(pos: Vec3A) -> Block {
-    let noise = 10.0 * simplex_noise_2d(Vec2::new(pos.x * 0.01, pos.z * 0.01));
+    let noise = surface_height(pos);
 
     if pos.y < noise {
         Block::Grass
@@ -25,35 +90,62 @@ fn dirt(pos: Vec3A) -> Block {
     }
 }
 
-fn water(pos: Vec3A) -> Block {
+/// How deep below the surface stone starts.
+const DIRT_DEPTH: f32 = 4.0;
+
+fn stone(pos: Vec3A) -> Block {
     let current = dirt(pos);
+    let deep = hills(pos + Vec3A::new(0.0, DIRT_DEPTH, 0.0));
 
-    if current == Block::Air && pos.y < -5.0 {
-        Block::Water
+    if deep == Block::Grass {
+        Block::Stone
     } else {
         current
     }
 }
 
-pub fn at_pos(pos: Vec3A) -> Block {
-    let pos = pos.floor();
+fn ores(pos: Vec3A) -> Block {
+    let current = stone(pos);
 
-    water(pos)
+    if current != Block::Stone {
+        return current;
+    }
+
+    if iron_noise(pos) > 0.8 {
+        Block::IronOre
+    } else if coal_noise(pos) > 0.75 {
+        Block::CoalOre
+    } else {
+        current
+    }
 }
 
-pub fn is_occluded(pos: Vec3A) -> bool {
-    let pos = pos.floor();
+fn water(pos: Vec3A) -> Block {
+    let current = ores(pos);
+
+    if current == Block::Air && pos.y < -5.0 {
+        Block::Water
+    } else {
+        current
+    }
+}
 
-    let mut occluded = true;
+fn plants(pos: Vec3A) -> Block {
+    let current = water(pos);
 
-    occluded &= at_pos(pos + Vec3A::X).is_opaque();
-    occluded &= at_pos(pos - Vec3A::X).is_opaque();
+    if current != Block::Air || water(pos - Vec3A::Y) != Block::Grass {
+        return current;
+    }
 
-    occluded &= at_pos(pos + Vec3A::Y).is_opaque();
-    occluded &= at_pos(pos - Vec3A::Y).is_opaque();
+    if grass_noise(pos) > 0.6 {
+        Block::TallGrass
+    } else {
+        current
+    }
+}
 
-    occluded &= at_pos(pos + Vec3A::Z).is_opaque();
-    occluded &= at_pos(pos - Vec3A::Z).is_opaque();
+pub fn at_pos(pos: Vec3A, seed: WorldSeed) -> Block {
+    let pos = pos.floor() + seed.offset();
 
-    occluded
+    plants(pos)
 }
@@ -1,39 +1,127 @@
 pub mod block;
 pub mod chunk;
+pub mod edit;
+pub mod fluid;
 pub mod gen;
+pub mod model;
 
 use crate::worldgen::chunk::timer::ChunkGenerationTimer;
-use crate::worldgen::chunk::{ChunkQueue, GeneratedChunks, LoadedChunks};
+use crate::worldgen::chunk::{
+    ChunkGenerated, ChunkQueue, DirtyChunks, GeneratedChunks, LoadedChunks, NewChunks, ViewDistance,
+};
 use bevy::prelude::*;
 use bevy::utils::{HashMap, HashSet};
 use crossbeam::queue::SegQueue;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-pub struct WorldgenPlugin;
+#[derive(Default)]
+pub struct WorldgenPlugin {
+    /// Skips meshing and spawning chunk entities, so that the world can be generated and simulated
+    /// without a window, renderer or asset server.
+    pub headless: bool,
+    /// Chunks are received from a server instead of being generated locally, and block edits are
+    /// left for the client to send to the server.
+    pub remote: bool,
+    /// The seed chunks are generated with. Unused if they're received from a server.
+    pub seed: u64,
+    /// The folder changed chunks are saved in and loaded from. Nothing is saved without one, and
+    /// it's unused if chunks are received from a server.
+    pub save_dir: Option<PathBuf>,
+}
 
 impl Plugin for WorldgenPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(ChunkGenerationTimer(Timer::from_seconds(
-            chunk::CHUNK_ITERATION_INTERVAL,
-            TimerMode::Repeating,
-        )))
-        .insert_resource(GeneratedChunks {
-            map: Arc::new(Mutex::new(HashMap::new())),
-        })
-        .insert_resource(ChunkQueue(Arc::new(SegQueue::new())))
-        .insert_resource(LoadedChunks {
-            chunks: HashSet::new(),
-        })
-        .add_systems(Startup, chunk::loading::spawn_initial_chunks)
-        .add_systems(
-            Update,
-            (
-                chunk::timer::tick_chunk_generation_timer,
-                chunk::generation::fill_chunk_queue,
-                chunk::generation::generate_chunks_multithreaded,
-                chunk::loading::load_generated_chunks,
-                chunk::loading::unload_chunks,
-            ),
-        );
+        app.insert_resource(model::BlockModels::load_or_default(model::MODELS_PATH))
+            .insert_resource(GeneratedChunks {
+                map: Arc::new(Mutex::new(HashMap::new())),
+            })
+            .insert_resource(LoadedChunks {
+                chunks: HashSet::new(),
+            })
+            .insert_resource(DirtyChunks {
+                chunks: HashSet::new(),
+            })
+            .init_resource::<ViewDistance>()
+            .add_event::<edit::BlockEdit>()
+            .add_event::<edit::BlockEntityEdit>()
+            .add_event::<edit::BlockChanged>()
+            .add_systems(
+                Update,
+                (
+                    edit::mark_changed_chunks_dirty,
+                    chunk::loading::remesh_dirty_chunks,
+                )
+                    .chain(),
+            );
+
+        if !self.remote {
+            info!("World seed: {}", self.seed);
+
+            app.insert_resource(gen::WorldSeed(self.seed))
+                .insert_resource(ChunkGenerationTimer(Timer::from_seconds(
+                    chunk::CHUNK_ITERATION_INTERVAL,
+                    TimerMode::Repeating,
+                )))
+                .insert_resource(ChunkQueue(Arc::new(SegQueue::new())))
+                .insert_resource(NewChunks(Arc::new(SegQueue::new())))
+                .add_event::<ChunkGenerated>()
+                .insert_resource(fluid::FluidTickTimer(Timer::from_seconds(
+                    fluid::FLUID_TICK_INTERVAL,
+                    TimerMode::Repeating,
+                )))
+                .init_resource::<fluid::FluidUpdates>()
+                .add_systems(
+                    Update,
+                    (
+                        chunk::timer::tick_chunk_generation_timer,
+                        chunk::generation::fill_chunk_queue,
+                        chunk::generation::generate_chunks_multithreaded,
+                        chunk::generation::announce_new_chunks,
+                        edit::apply_block_edits.before(edit::mark_changed_chunks_dirty),
+                        (
+                            fluid::tick_fluid_timer,
+                            fluid::queue_fluid_updates,
+                            fluid::queue_new_chunk_boundaries,
+                            fluid::tick_fluids,
+                        )
+                            .chain()
+                            .after(edit::apply_block_edits)
+                            .before(edit::mark_changed_chunks_dirty),
+                    ),
+                );
+
+            if let Some(dir) = &self.save_dir {
+                info!("Saving the world to {}", dir.display());
+
+                app.insert_resource(chunk::storage::WorldSave { dir: dir.clone() })
+                    .init_resource::<chunk::storage::UnsavedChunks>()
+                    .insert_resource(chunk::storage::AutosaveTimer(Timer::from_seconds(
+                        chunk::storage::AUTOSAVE_INTERVAL,
+                        TimerMode::Repeating,
+                    )))
+                    .add_systems(
+                        Update,
+                        chunk::storage::mark_changed_chunks_unsaved.after(edit::apply_block_edits),
+                    )
+                    // Last, so that it sees the exit event if the app is closing this frame
+                    .add_systems(Last, chunk::storage::autosave_world);
+            }
+
+            if !self.headless {
+                app.add_systems(Startup, chunk::loading::spawn_initial_chunks);
+            }
+        }
+
+        if !self.headless {
+            app.add_systems(
+                Update,
+                (
+                    chunk::loading::load_generated_chunks
+                        .after(chunk::loading::remesh_dirty_chunks),
+                    chunk::loading::unload_chunks,
+                ),
+            );
+        }
     }
 }
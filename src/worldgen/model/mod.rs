@@ -0,0 +1,161 @@
+//! Block models: the shapes blocks are meshed and collided with, loaded from the model file.
+//!
+//! Most blocks are a full cube, but a model can be any number of boxes inside the block, like
+//! slabs and stairs, or two crossed quads, like plants. Models are defined facing north and in the
+//! bottom half of the block, and turned to face the way the block does, or flipped upside down for
+//! blocks in the top half.
+
+pub mod model_file;
+
+use crate::camera::voxel_collision::Aabb;
+use crate::worldgen::block::{Block, Half};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Where the models are loaded from, relative to the working directory.
+pub const MODELS_PATH: &str = "assets/models.ron";
+
+/// A box inside a block, in block units relative to the block's corner.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ModelBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ModelBox {
+    /// The whole block.
+    pub const FULL: ModelBox = ModelBox {
+        min: Vec3::ZERO,
+        max: Vec3::ONE,
+    };
+
+    /// The box turned around the middle of the block, by quarter turns clockwise when seen from
+    /// above.
+    pub fn rotated(self, quarter_turns: u8) -> Self {
+        let mut rotated = self;
+
+        // A quarter turn takes north to east, so (x, z) goes to (1 - z, x)
+        for _ in 0..quarter_turns % 4 {
+            rotated = ModelBox {
+                min: Vec3::new(1.0 - rotated.max.z, rotated.min.y, rotated.min.x),
+                max: Vec3::new(1.0 - rotated.min.z, rotated.max.y, rotated.max.x),
+            };
+        }
+
+        rotated
+    }
+
+    /// The box mirrored from the bottom half of the block to the top, or the other way around.
+    pub fn flipped(self) -> Self {
+        ModelBox {
+            min: Vec3::new(self.min.x, 1.0 - self.max.y, self.min.z),
+            max: Vec3::new(self.max.x, 1.0 - self.min.y, self.max.z),
+        }
+    }
+
+    /// Whether the box reaches the side of the block in the direction, so its face there lies on
+    /// the block's surface.
+    pub fn touches_side(&self, normal: IVec3) -> bool {
+        let axis = axis_of(normal);
+
+        if normal[axis] > 0 {
+            self.max[axis] == 1.0
+        } else {
+            self.min[axis] == 0.0
+        }
+    }
+
+    /// Whether the box covers the whole side of the block in the direction, hiding the face of
+    /// the neighbour there.
+    pub fn covers_side(&self, normal: IVec3) -> bool {
+        let axis = axis_of(normal);
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+
+        self.touches_side(normal)
+            && [a, b]
+                .iter()
+                .all(|&other| self.min[other] == 0.0 && self.max[other] == 1.0)
+    }
+}
+
+/// The index of the axis the normal points along.
+fn axis_of(normal: IVec3) -> usize {
+    (0..3).find(|&axis| normal[axis] != 0).unwrap_or(1)
+}
+
+/// The shape of a block.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Model {
+    Boxes(Vec<ModelBox>),
+    /// Two quads crossing diagonally through the block, drawn from both sides.
+    Cross,
+}
+
+/// The model of every block, loaded from the model file at startup.
+#[derive(Resource, Debug)]
+pub struct BlockModels {
+    /// By the block in its default state, for every block that isn't a full cube.
+    blocks: HashMap<Block, Model>,
+    /// The model of every block that isn't in `blocks`.
+    cube: Model,
+}
+
+/// Every block is a full cube, for when the model file can't be loaded.
+impl Default for BlockModels {
+    fn default() -> Self {
+        Self {
+            blocks: HashMap::new(),
+            cube: Model::Boxes(vec![ModelBox::FULL]),
+        }
+    }
+}
+
+impl BlockModels {
+    pub fn get(&self, block: Block) -> &Model {
+        self.blocks
+            .get(&block.with_default_state())
+            .unwrap_or(&self.cube)
+    }
+
+    /// The boxes the block is made of, turned the way the block faces and flipped if it's in the
+    /// top half. Crossed quads aren't made of any.
+    pub fn boxes(&self, block: Block) -> impl Iterator<Item = ModelBox> + '_ {
+        let quarter_turns = block.facing().map_or(0, |facing| facing as u8);
+        let flipped = block.half() == Some(Half::Top);
+
+        let boxes = match self.get(block) {
+            Model::Boxes(boxes) => boxes.as_slice(),
+            Model::Cross => &[],
+        };
+
+        boxes.iter().map(move |model_box| {
+            let model_box = model_box.rotated(quarter_turns);
+
+            if flipped {
+                model_box.flipped()
+            } else {
+                model_box
+            }
+        })
+    }
+
+    /// Whether the block hides the face of the neighbour in the direction.
+    pub fn covers_side(&self, block: Block, normal: IVec3) -> bool {
+        self.boxes(block)
+            .any(|model_box| model_box.covers_side(normal))
+    }
+
+    /// The boxes the player and dropped items collide with, for the block at the position.
+    pub fn collision_boxes(&self, block: Block, pos: IVec3) -> Vec<Aabb> {
+        if !block.is_solid() {
+            return Vec::new();
+        }
+
+        self.boxes(block)
+            .map(|model_box| Aabb {
+                min: pos.as_vec3() + model_box.min,
+                max: pos.as_vec3() + model_box.max,
+            })
+            .collect()
+    }
+}
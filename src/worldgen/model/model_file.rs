@@ -0,0 +1,303 @@
+//! The format of the model file, and the checks a model has to pass to be loaded. Boxes are given
+//! in pixels of a block's texture, so a full block goes from 0 to 16 on every axis.
+
+use crate::inventory::item::Item;
+use crate::worldgen::model::{BlockModels, Model, ModelBox};
+use bevy::log::warn;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How many of the file's units make up one block.
+const UNITS_PER_BLOCK: u8 = 16;
+
+/// The model of every block the file doesn't map to one.
+const DEFAULT_MODEL: &str = "cube";
+
+#[derive(Deserialize)]
+struct RawModels {
+    /// Every model, by the id blocks refer to it with.
+    models: BTreeMap<String, RawShape>,
+    /// The id of each block's model, by the block's name.
+    blocks: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+enum RawShape {
+    Boxes(Vec<RawBox>),
+    Cross,
+}
+
+#[derive(Deserialize)]
+struct RawBox {
+    from: (u8, u8, u8),
+    to: (u8, u8, u8),
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    /// The model every other block uses isn't defined.
+    MissingModel(String),
+    UnknownBlock(String),
+    UnknownModel {
+        block: String,
+        model: String,
+    },
+    /// A model made of boxes doesn't have any.
+    NoBoxes(String),
+    /// A box that's empty, inside out, or sticks out of the block.
+    InvalidBox(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Io(err) => write!(f, "failed to read models: {}", err),
+            ModelError::Parse(err) => write!(f, "failed to parse models: {}", err),
+            ModelError::MissingModel(model) => write!(f, "model \"{}\" isn't defined", model),
+            ModelError::UnknownBlock(block) => write!(f, "there's no block \"{}\"", block),
+            ModelError::UnknownModel { block, model } => write!(
+                f,
+                "block \"{}\" uses model \"{}\", which isn't defined",
+                block, model
+            ),
+            ModelError::NoBoxes(model) => write!(f, "model \"{}\" needs at least one box", model),
+            ModelError::InvalidBox(model) => write!(
+                f,
+                "model \"{}\" has a box that isn't between 0 and {} on every axis",
+                model, UNITS_PER_BLOCK
+            ),
+        }
+    }
+}
+
+impl BlockModels {
+    /// Loads the models from the file. If it can't be loaded, or any model in it is invalid,
+    /// every block is a full cube, so the world can still be played in.
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        match Self::load(path) {
+            Ok(models) => models,
+            Err(err) => {
+                warn!("No models loaded from {}: {}", path.display(), err);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ModelError> {
+        let contents = fs::read_to_string(path).map_err(ModelError::Io)?;
+
+        Self::from_ron(&contents)
+    }
+
+    pub fn from_ron(contents: &str) -> Result<Self, ModelError> {
+        let raw: RawModels = ron::from_str(contents).map_err(ModelError::Parse)?;
+
+        let mut models = HashMap::new();
+
+        for (id, shape) in raw.models {
+            let model = shape.resolve(&id)?;
+            models.insert(id, model);
+        }
+
+        let cube = models
+            .get(DEFAULT_MODEL)
+            .cloned()
+            .ok_or_else(|| ModelError::MissingModel(DEFAULT_MODEL.to_string()))?;
+
+        let mut blocks = HashMap::new();
+
+        for (name, id) in raw.blocks {
+            let Some(block) = Item::from_name(&name).and_then(Item::as_block) else {
+                return Err(ModelError::UnknownBlock(name));
+            };
+
+            let Some(model) = models.get(&id) else {
+                return Err(ModelError::UnknownModel {
+                    block: name,
+                    model: id,
+                });
+            };
+
+            blocks.insert(block.with_default_state(), model.clone());
+        }
+
+        Ok(Self { blocks, cube })
+    }
+}
+
+impl RawShape {
+    fn resolve(self, model: &str) -> Result<Model, ModelError> {
+        match self {
+            RawShape::Boxes(boxes) => {
+                if boxes.is_empty() {
+                    return Err(ModelError::NoBoxes(model.to_string()));
+                }
+
+                let boxes = boxes
+                    .iter()
+                    .map(|raw_box| raw_box.resolve(model))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Model::Boxes(boxes))
+            }
+            RawShape::Cross => Ok(Model::Cross),
+        }
+    }
+}
+
+impl RawBox {
+    fn resolve(&self, model: &str) -> Result<ModelBox, ModelError> {
+        let from = [self.from.0, self.from.1, self.from.2];
+        let to = [self.to.0, self.to.1, self.to.2];
+
+        if from
+            .iter()
+            .zip(to.iter())
+            .any(|(from, to)| from >= to || *to > UNITS_PER_BLOCK)
+        {
+            return Err(ModelError::InvalidBox(model.to_string()));
+        }
+
+        let to_blocks = |units: [u8; 3]| {
+            Vec3::new(units[0] as f32, units[1] as f32, units[2] as f32) / UNITS_PER_BLOCK as f32
+        };
+
+        Ok(ModelBox {
+            min: to_blocks(from),
+            max: to_blocks(to),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worldgen::block::{Block, Facing, Half};
+
+    const CUBE: &str = r#""cube": Boxes([(from: (0, 0, 0), to: (16, 16, 16))])"#;
+
+    fn boxes(models: &BlockModels, block: Block) -> Vec<ModelBox> {
+        models.boxes(block).collect()
+    }
+
+    #[test]
+    fn the_model_file_loads() {
+        let models = BlockModels::load(crate::worldgen::model::MODELS_PATH).unwrap();
+
+        assert_eq!(*models.get(Block::TallGrass), Model::Cross);
+        assert_eq!(boxes(&models, Block::Stone), vec![ModelBox::FULL]);
+        assert_eq!(boxes(&models, Block::StoneStairs(Facing::North)).len(), 2);
+    }
+
+    #[test]
+    fn blocks_without_a_model_are_a_cube() {
+        let models = BlockModels::from_ron(&format!("(models: {{ {} }}, blocks: {{}})", CUBE));
+
+        assert_eq!(
+            boxes(&models.unwrap(), Block::StoneSlab(Half::Bottom)),
+            vec![ModelBox::FULL]
+        );
+    }
+
+    #[test]
+    fn blocks_are_mapped_to_models_by_name() {
+        let models = BlockModels::from_ron(&format!(
+            r#"(models: {{ {}, "flower": Cross }}, blocks: {{ "tall_grass": "flower" }})"#,
+            CUBE
+        ))
+        .unwrap();
+
+        assert_eq!(*models.get(Block::TallGrass), Model::Cross);
+    }
+
+    #[test]
+    fn states_of_a_block_share_its_model() {
+        let models = BlockModels::from_ron(&format!(
+            r#"(models: {{ {}, "pane": Boxes([(from: (0, 0, 7), to: (16, 16, 9))]) }},
+                blocks: {{ "iron_bars": "pane" }})"#,
+            CUBE
+        ))
+        .unwrap();
+
+        for facing in [Facing::North, Facing::East, Facing::South, Facing::West] {
+            assert_ne!(
+                boxes(&models, Block::IronBars(facing)),
+                vec![ModelBox::FULL]
+            );
+        }
+    }
+
+    #[test]
+    fn top_slabs_are_flipped_into_the_top_half() {
+        let models = BlockModels::load(crate::worldgen::model::MODELS_PATH).unwrap();
+
+        let bottom = boxes(&models, Block::StoneSlab(Half::Bottom));
+        let top = boxes(&models, Block::StoneSlab(Half::Top));
+
+        assert_eq!(bottom[0].min.y, 0.0);
+        assert_eq!(bottom[0].max.y, 0.5);
+        assert_eq!(top[0].min.y, 0.5);
+        assert_eq!(top[0].max.y, 1.0);
+    }
+
+    #[test]
+    fn models_are_turned_the_way_the_block_faces() {
+        let models = BlockModels::load(crate::worldgen::model::MODELS_PATH).unwrap();
+
+        let north = boxes(&models, Block::IronBars(Facing::North));
+        let east = boxes(&models, Block::IronBars(Facing::East));
+
+        assert_eq!(east, vec![north[0].rotated(1)]);
+    }
+
+    #[test]
+    fn unknown_blocks_are_rejected() {
+        let result = BlockModels::from_ron(&format!(
+            r#"(models: {{ {} }}, blocks: {{ "bedrock_slab": "cube" }})"#,
+            CUBE
+        ));
+
+        assert!(matches!(result, Err(ModelError::UnknownBlock(name)) if name == "bedrock_slab"));
+    }
+
+    #[test]
+    fn blocks_using_an_undefined_model_are_rejected() {
+        let result = BlockModels::from_ron(&format!(
+            r#"(models: {{ {} }}, blocks: {{ "tall_grass": "plant" }})"#,
+            CUBE
+        ));
+
+        assert!(matches!(
+            result,
+            Err(ModelError::UnknownModel { block, model }) if block == "tall_grass" && model == "plant"
+        ));
+    }
+
+    #[test]
+    fn the_cube_model_has_to_be_defined() {
+        let result = BlockModels::from_ron(r#"(models: { "plant": Cross }, blocks: {})"#);
+
+        assert!(matches!(result, Err(ModelError::MissingModel(model)) if model == DEFAULT_MODEL));
+    }
+
+    #[test]
+    fn invalid_boxes_are_rejected() {
+        let empty = BlockModels::from_ron(r#"(models: { "cube": Boxes([]) }, blocks: {})"#);
+        let too_big = BlockModels::from_ron(
+            r#"(models: { "cube": Boxes([(from: (0, 0, 0), to: (17, 16, 16))]) }, blocks: {})"#,
+        );
+
+        assert!(matches!(empty, Err(ModelError::NoBoxes(_))));
+        assert!(matches!(too_big, Err(ModelError::InvalidBox(_))));
+    }
+}